@@ -0,0 +1,224 @@
+use chrono::{prelude::*, Duration};
+use serde::{Deserialize, Serialize};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+use teg_json_store::{ Record, JsonRow };
+
+use crate::invite::Invite;
+use crate::user::User;
+use super::ApiTokenScope;
+
+/// Prefix added to every token so that leaked tokens are easy to identify (eg. by secret scanners)
+const TOKEN_PREFIX: &str = "teg_";
+
+/// How stale last_used_at can become before it is updated. Limits the number of writes made by
+/// scripts that send many requests.
+const LAST_USED_AT_RESOLUTION_SECONDS: i64 = 60;
+
+/// A personal access token used by scripts and integrations to authenticate as a user through the
+/// local HTTP server. Only a hash of the token's secret is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub id: crate::DbId,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    pub user_id: crate::DbId,
+    pub name: String,
+    pub scopes: Vec<ApiTokenScope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+
+    pub secret_hash: String,
+}
+
+impl ApiToken {
+    /// Creates an API token for the user and returns the token's secret along with its record.
+    ///
+    /// The secret is not stored and cannot be displayed again.
+    pub async fn new(
+        db: &crate::Db,
+        user_id: crate::DbId,
+        name: String,
+        scopes: Vec<ApiTokenScope>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(String, Self)> {
+        use rand_core::{RngCore, OsRng};
+
+        if scopes.is_empty() {
+            Err(eyre!("API tokens require at least one scope"))?;
+        }
+
+        // 256 Bit Secrets
+        let mut secret = [0u8; 256 / 8];
+        OsRng.fill_bytes(&mut secret);
+
+        let token = format!("{}{}", TOKEN_PREFIX, bs58::encode(secret).into_string());
+
+        let api_token = ApiToken {
+            id: nanoid!(11),
+            version: 0,
+            created_at: Utc::now(),
+            deleted_at: None,
+            user_id,
+            name,
+            scopes,
+            expires_at,
+            last_used_at: None,
+            secret_hash: Invite::hash_secret(token.as_bytes()),
+        };
+
+        api_token.insert(db).await?;
+
+        Ok((token, api_token))
+    }
+
+    /// Returns the token and its user if the token is valid, has not expired and belongs to an
+    /// authorized user.
+    pub async fn authenticate(
+        db: &crate::Db,
+        token: &str,
+    ) -> Result<(Self, User)> {
+        let secret_hash = Invite::hash_secret(token.trim().as_bytes());
+
+        let row = sqlx::query_as!(
+            JsonRow,
+            r#"
+                SELECT props FROM api_tokens
+                WHERE
+                    deleted_at IS NULL
+                    AND secret_hash = $1
+            "#,
+            secret_hash,
+        )
+            .fetch_optional(db)
+            .await?;
+
+        let mut api_token = row
+            .map(Self::from_row)
+            .transpose()?
+            .ok_or_else(|| eyre!("Invalid API token"))?;
+
+        if api_token.has_expired() {
+            Err(eyre!("API token expired"))?;
+        }
+
+        let user = User::get_optional(db, &api_token.user_id, false)
+            .await?
+            .filter(|user| user.is_authorized)
+            .ok_or_else(|| eyre!("Invalid API token"))?;
+
+        let now = Utc::now();
+        let is_stale = api_token.last_used_at
+            .map(|last_used_at| {
+                now - last_used_at > Duration::seconds(LAST_USED_AT_RESOLUTION_SECONDS)
+            })
+            .unwrap_or(true);
+
+        if is_stale {
+            api_token.last_used_at = Some(now);
+
+            // Concurrent requests may race to update last_used_at. Losing that race is harmless
+            // so the request is not failed.
+            if let Err(err) = api_token.update(db).await {
+                debug!("Unable to update API token last_used_at: {:?}", err);
+            }
+        }
+
+        Ok((api_token, user))
+    }
+
+    pub fn has_expired(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= Utc::now())
+            .unwrap_or(false)
+    }
+
+    /// Returns true if any of the token's scopes grant the required scope
+    pub fn has_scope(&self, required: ApiTokenScope) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.grants(required))
+    }
+
+    pub async fn get_by_user<'e, 'c, E>(
+        db: E,
+        user_id: &crate::DbId,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        let rows = sqlx::query_as!(
+            JsonRow,
+            r#"
+                SELECT props FROM api_tokens
+                WHERE
+                    deleted_at IS NULL
+                    AND user_id = $1
+                ORDER BY created_at
+            "#,
+            user_id,
+        )
+            .fetch_all(db)
+            .await?;
+
+        Self::from_rows(rows)
+    }
+}
+
+#[async_trait::async_trait]
+impl Record for ApiToken {
+    const TABLE: &'static str = "api_tokens";
+
+    fn id(&self) -> &crate::DbId {
+        &self.id
+    }
+
+    fn version(&self) -> teg_json_store::Version {
+        self.version
+    }
+
+    fn version_mut(&mut self) -> &mut teg_json_store::Version {
+        &mut self.version
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+
+    fn deleted_at_mut(&mut self) -> &mut Option<DateTime<Utc>> {
+        &mut self.deleted_at
+    }
+
+    async fn insert_no_rollback<'c>(
+        &self,
+        db: &mut sqlx::Transaction<'c, sqlx::Postgres>,
+    ) -> Result<()>
+    {
+        let json = serde_json::to_value(&self)?;
+        sqlx::query!(
+            r#"
+                INSERT INTO api_tokens
+                (id, version, created_at, props, user_id, secret_hash)
+                VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            self.id,
+            self.version,
+            self.created_at,
+            json,
+            self.user_id,
+            self.secret_hash,
+        )
+            .fetch_optional(db)
+            .await?;
+        Ok(())
+    }
+}
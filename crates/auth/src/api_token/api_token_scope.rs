@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// The permissions granted to scripts and integrations that authenticate with an API token.
+///
+/// Scopes are hierarchical in that the Admin scope grants every other scope and every scope grants
+/// ReadOnly access.
+#[derive(async_graphql::Enum, Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum ApiTokenScope {
+    /// Query the server without making any changes
+    #[graphql(name = "READ_ONLY")]
+    ReadOnly,
+    /// Add, remove and re-order parts and packages in print queues
+    #[graphql(name = "QUEUE_MANAGEMENT")]
+    QueueManagement,
+    /// Start, pause and stop prints and send GCodes to machines
+    #[graphql(name = "MACHINE_CONTROL")]
+    MachineControl,
+    /// Full access to the server including its users, invites and configuration. Only admins can
+    /// create tokens with this scope.
+    #[graphql(name = "ADMIN")]
+    Admin,
+}
+
+impl ApiTokenScope {
+    /// Returns true if a token with this scope is permitted to act within the required scope
+    pub fn grants(&self, required: ApiTokenScope) -> bool {
+        *self == required
+            || *self == ApiTokenScope::Admin
+            || required == ApiTokenScope::ReadOnly
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiTokenScope::*;
+
+    #[test]
+    fn admin_grants_every_scope() {
+        for scope in [ReadOnly, QueueManagement, MachineControl, Admin] {
+            assert!(Admin.grants(scope));
+        }
+    }
+
+    #[test]
+    fn every_scope_grants_read_only() {
+        for scope in [ReadOnly, QueueManagement, MachineControl, Admin] {
+            assert!(scope.grants(ReadOnly));
+        }
+    }
+
+    #[test]
+    fn scopes_do_not_grant_unrelated_scopes() {
+        assert!(!ReadOnly.grants(QueueManagement));
+        assert!(!QueueManagement.grants(MachineControl));
+        assert!(!MachineControl.grants(QueueManagement));
+        assert!(!MachineControl.grants(Admin));
+    }
+}
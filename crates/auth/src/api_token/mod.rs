@@ -0,0 +1,9 @@
+mod api_token;
+pub use api_token::{
+    ApiToken,
+};
+
+mod api_token_scope;
+pub use api_token_scope::ApiTokenScope;
+
+pub mod resolvers;
@@ -0,0 +1,132 @@
+use chrono::prelude::*;
+use async_graphql::{
+    FieldResult,
+    ID,
+    Context,
+};
+use eyre::{
+    Context as _,
+    eyre,
+    // Result
+};
+use teg_json_store::Record as _;
+
+use crate::AuthContext;
+use crate::user::UserRole;
+use crate::api_token::{
+    ApiToken,
+    ApiTokenScope,
+};
+
+// Input Types
+// ---------------------------------------------
+
+#[derive(async_graphql::InputObject)]
+pub struct CreateApiTokenInput {
+    pub name: String,
+    pub scopes: Vec<ApiTokenScope>,
+    /// The token will be rejected after this time. Tokens without an expiry date are valid until
+    /// they are deleted.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(async_graphql::SimpleObject)]
+pub struct CreateApiToken {
+    pub api_token: ApiToken,
+    /// The bearer token used to authenticate requests. Only displayed once for each API token.
+    pub token: String,
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct DeleteApiTokenInput {
+    #[graphql(name="apiTokenID")]
+    pub api_token_id: ID,
+}
+
+// Resolvers
+// ---------------------------------------------
+
+#[derive(Default)]
+pub struct ApiTokenMutation;
+
+#[async_graphql::Object]
+impl ApiTokenMutation {
+    /// Creates a personal access token for the current user
+    async fn create_api_token<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: CreateApiTokenInput,
+    ) -> FieldResult<CreateApiToken> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        async move {
+            let user = auth.require_authorized_user()?;
+
+            // Prevent leaked tokens from being used to mint longer lived tokens
+            if auth.api_token.is_some() {
+                Err(eyre!("API tokens cannot be created using an API token"))?;
+            }
+
+            let is_admin = user.config.role() == UserRole::Admin;
+
+            if input.scopes.contains(&ApiTokenScope::Admin) && !is_admin {
+                Err(eyre!("Only admins can create API tokens with the ADMIN scope"))?;
+            }
+
+            if input.expires_at.map(|expires_at| expires_at <= Utc::now()).unwrap_or(false) {
+                Err(eyre!("API token expiry must be in the future"))?;
+            }
+
+            let (token, api_token) = ApiToken::new(
+                db,
+                user.id.clone(),
+                input.name,
+                input.scopes,
+                input.expires_at,
+            ).await?;
+
+            eyre::Result::<_>::Ok(CreateApiToken {
+                api_token,
+                token,
+            })
+        }
+            // log the backtrace which is otherwise lost by FieldResult
+            .await
+            .map_err(|err| {
+                warn!("{:?}", err);
+                err.into()
+            })
+    }
+
+    /// Revokes an API token. Admins can revoke any user's tokens.
+    async fn delete_api_token<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: DeleteApiTokenInput,
+    ) -> FieldResult<Option<teg_common::Void>> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        let user = auth.require_authorized_user()?;
+
+        let mut api_token = ApiToken::get(
+            db,
+            &input.api_token_id.0,
+            true
+        ).await?;
+
+        if api_token.user_id != user.id {
+            auth.authorize_admins_only()?;
+        }
+
+        api_token.remove(
+            db,
+            false,
+        )
+            .await
+            .with_context(|| "Error deleting API token")?;
+
+        Ok(None)
+    }
+}
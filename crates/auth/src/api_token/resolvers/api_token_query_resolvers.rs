@@ -0,0 +1,33 @@
+use async_graphql::{
+    FieldResult,
+    Context,
+};
+
+use crate::{
+    AuthContext,
+};
+use crate::api_token::{
+    ApiToken,
+};
+
+#[derive(Default)]
+pub struct ApiTokenQuery;
+
+#[async_graphql::Object]
+impl ApiTokenQuery {
+    /// The current user's API tokens
+    #[instrument(skip(self, ctx))]
+    async fn api_tokens<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+    ) -> FieldResult<Vec<ApiToken>> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        let user = auth.require_authorized_user()?;
+
+        let api_tokens = ApiToken::get_by_user(db, &user.id).await?;
+
+        Ok(api_tokens)
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::prelude::*;
+use async_graphql::{
+    ID,
+};
+
+use crate::api_token::{
+    ApiToken,
+    ApiTokenScope,
+};
+
+#[async_graphql::Object]
+impl ApiToken {
+    async fn id(&self) -> ID {
+        (&self.id).into()
+    }
+
+    #[graphql(name = "userID")]
+    async fn user_id(&self) -> ID {
+        (&self.user_id).into()
+    }
+
+    async fn name(&self) -> &String {
+        &self.name
+    }
+
+    async fn scopes(&self) -> &Vec<ApiTokenScope> {
+        &self.scopes
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    async fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    async fn is_expired(&self) -> bool {
+        self.has_expired()
+    }
+
+    async fn last_used_at(&self) -> Option<DateTime<Utc>> {
+        self.last_used_at
+    }
+}
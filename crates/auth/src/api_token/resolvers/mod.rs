@@ -0,0 +1,4 @@
+pub mod api_token_query_resolvers;
+pub mod api_token_mutation_resolvers;
+
+mod api_token_resolvers;
@@ -5,13 +5,20 @@ use eyre::{
     // Context as _,
 };
 
-use crate::user::User;
+use crate::{
+    Permission,
+    api_token::{ApiToken, ApiTokenScope},
+    user::{User, UserRole},
+};
 
 static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug)]
 pub struct AuthContext {
     pub current_user: Option<User>,
+    /// The API token used to authenticate the session if any. Sessions without an API token have
+    /// every scope.
+    pub api_token: Option<ApiToken>,
     pub session_id: u64,
 }
 
@@ -21,6 +28,7 @@ impl AuthContext {
     ) -> Self {
         Self {
             current_user,
+            api_token: None,
             session_id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
         }
     }
@@ -35,11 +43,91 @@ impl AuthContext {
         Ok(auth)
     }
 
+    /// Authenticates a bearer token sent to the local HTTP server
+    pub async fn api_token_auth(
+        db: &crate::Db,
+        token: &str,
+    ) -> Result<Self> {
+        let (api_token, user) = ApiToken::authenticate(db, token).await?;
+
+        let mut auth = Self::new(Some(user));
+        auth.api_token = Some(api_token);
+
+        Ok(auth)
+    }
+
     pub fn is_admin(&self) -> bool {
         self.current_user
             .as_ref()
-            .map(|user| user.config.is_admin)
+            .map(|user| user.config.role() == UserRole::Admin)
             .unwrap_or(false)
+            && self.has_scope(ApiTokenScope::Admin)
+    }
+
+    pub fn has_scope(&self, scope: ApiTokenScope) -> bool {
+        self.api_token
+            .as_ref()
+            .map(|api_token| api_token.has_scope(scope))
+            .unwrap_or(true)
+    }
+
+    /// Requires an authorized user and, for API token sessions, a token with the given scope
+    pub fn authorize_scope(&self, scope: ApiTokenScope) -> Result<&User> {
+        let user = self.require_authorized_user()?;
+
+        if !self.has_scope(scope) {
+            Err(eyre!("Unauthorized. The API token does not have the {:?} scope.", scope))?;
+        }
+
+        Ok(user)
+    }
+
+    /// Requires an authorized user with the permission's role and, for API token sessions, a token
+    /// with the permission's scope
+    pub fn authorize(&self, permission: Permission) -> Result<&User> {
+        let user = self.authorize_scope(permission.scope())?;
+
+        if user.config.role() < permission.role() {
+            Err(eyre!("Unauthorized. The {:?} role is required.", permission.role()))?;
+        }
+
+        Ok(user)
+    }
+
+    /// Authorizes the permission for a specific machine
+    pub fn authorize_machine(&self, machine_id: &str, permission: Permission) -> Result<&User> {
+        let user = self.authorize(permission)?;
+
+        if !user.config.can_access_machine(machine_id) {
+            Err(eyre!("Unauthorized. You do not have access to this machine."))?;
+        }
+
+        Ok(user)
+    }
+
+    /// Authorizes the permission for a specific print queue
+    pub fn authorize_print_queue(
+        &self,
+        print_queue_id: &str,
+        permission: Permission,
+    ) -> Result<&User> {
+        let user = self.authorize(permission)?;
+
+        if !user.config.can_access_print_queue(print_queue_id) {
+            Err(eyre!("Unauthorized. You do not have access to this print queue."))?;
+        }
+
+        Ok(user)
+    }
+
+    /// Returns true if the user is permitted to view the machine. Used to filter queries.
+    pub fn can_view_machine(&self, machine_id: &str) -> bool {
+        self.authorize_machine(machine_id, Permission::View).is_ok()
+    }
+
+    /// Returns true if the user is permitted to view the print queue. Used to filter queries.
+    pub fn can_view_print_queue(&self, print_queue_id: &str) -> bool {
+        self.authorize_print_queue(print_queue_id, Permission::View).is_ok()
     }
 
     pub fn authorize_admins_only(&self) -> Result<()> {
@@ -10,6 +10,7 @@ use eyre::{
 use teg_json_store::Record;
 
 use crate::ServerKeys;
+use crate::user::UserRole;
 use super::InviteConfig;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,10 +32,12 @@ impl Invite {
         server_keys: &Arc<ServerKeys>,
         is_admin: bool,
     ) -> Result<Self> {
-        let config = InviteConfig {
-            is_admin,
+        let mut config = InviteConfig {
             name: Some("CLI Generated Invite".to_string()),
+            ..Default::default()
         };
+        config.set_role(if is_admin { UserRole::Admin } else { UserRole::default() });
+
         let (invite_url, invite) = Self::new(db, server_keys, config).await?;
         invite.print_welcome_text(invite_url)?;
 
@@ -195,7 +198,7 @@ impl Record for Invite {
         let (json, previous_version) = self.prep_for_update()?;
         let consumed = self.consumed_by_user_id.is_some();
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE invites
                 SET
@@ -216,9 +219,9 @@ impl Record for Invite {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
@@ -1,13 +1,80 @@
 use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 
+use crate::user::{UserConfig, UserRole};
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
 pub struct InviteConfig {
     /// # Admin Access Invite
+    /// Derived from the invite's role. Kept for backwards compatibility.
     #[serde(default)]
     pub is_admin: bool,
     /// # Invite Name (Optional)
     /// Naming invites can help keep track of what the invite was created for.
     #[serde(default)]
     pub name: Option<String>,
+    /// # Role
+    /// The role given to the user that accepts the invite
+    #[serde(default)]
+    pub role: UserRole,
+    /// # Machines (Optional)
+    /// Limits the invited user to these machine IDs. Leave unset to grant access to every machine.
+    #[serde(default)]
+    pub machine_ids: Option<Vec<crate::DbId>>,
+    /// # Print Queues (Optional)
+    /// Limits the invited user to these print queue IDs. Leave unset to grant access to every
+    /// print queue.
+    #[serde(default)]
+    pub print_queue_ids: Option<Vec<crate::DbId>>,
+}
+
+impl InviteConfig {
+    pub fn role(&self) -> UserRole {
+        if self.is_admin {
+            UserRole::Admin
+        } else {
+            self.role
+        }
+    }
+
+    /// Sets the role and keeps is_admin consistent with it
+    pub fn set_role(&mut self, role: UserRole) {
+        self.role = role;
+        self.is_admin = role == UserRole::Admin;
+    }
+
+    /// Applies the invite's role and grants to the user that accepted it. Existing users keep the
+    /// greater of their current role and the invite's role.
+    pub fn apply_to(&self, user_config: &mut UserConfig, is_new_user: bool) {
+        let role = if is_new_user {
+            self.role()
+        } else {
+            std::cmp::max(user_config.role(), self.role())
+        };
+
+        user_config.set_role(role);
+
+        if is_new_user {
+            user_config.machine_ids = self.machine_ids.clone();
+            user_config.print_queue_ids = self.print_queue_ids.clone();
+        } else {
+            merge_grants(&mut user_config.machine_ids, &self.machine_ids);
+            merge_grants(&mut user_config.print_queue_ids, &self.print_queue_ids);
+        }
+    }
+}
+
+/// Combines two sets of grants where None grants access to everything
+fn merge_grants(grants: &mut Option<Vec<crate::DbId>>, invite_grants: &Option<Vec<crate::DbId>>) {
+    match (grants.as_mut(), invite_grants) {
+        (Some(ids), Some(invite_ids)) => {
+            ids.extend(invite_ids.iter().cloned());
+            ids.sort();
+            ids.dedup();
+        }
+        (Some(_), None) => {
+            *grants = None;
+        }
+        (None, _) => (),
+    }
 }
@@ -67,10 +67,14 @@ impl InviteMutation {
 
         auth.authorize_admins_only()?;
 
+        // The role takes precedence over the legacy is_admin field
+        let mut config = input.model.0;
+        config.set_role(config.role);
+
         let (invite_url, invite) = Invite::new(
             db,
             server_keys,
-            input.model.0,
+            config,
         ).await?;
 
         Ok(CreateInvite {
@@ -98,6 +102,7 @@ impl InviteMutation {
         ).await?;
 
         invite.config = input.model.0;
+        invite.config.set_role(invite.config.role);
 
         invite.update(db).await?;
 
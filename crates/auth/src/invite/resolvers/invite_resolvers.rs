@@ -11,6 +11,7 @@ use async_graphql::{
 use teg_config_form::ConfigForm;
 
 use crate::invite::Invite;
+use crate::user::UserRole;
 
 #[async_graphql::Object]
 impl Invite {
@@ -32,7 +33,11 @@ impl Invite {
     }
 
     async fn is_admin(&self) -> bool {
-        self.config.is_admin
+        self.config.role() == UserRole::Admin
+    }
+
+    async fn role(&self) -> UserRole {
+        self.config.role()
     }
     async fn created_at(&self) -> DateTime<Utc> {
         self.created_at
@@ -7,6 +7,13 @@ pub use invite::resolvers::{
     invite_query_resolvers::InviteQuery,
 };
 
+pub mod api_token;
+pub use api_token::ApiTokenScope;
+pub use api_token::resolvers::{
+    api_token_mutation_resolvers::ApiTokenMutation,
+    api_token_query_resolvers::ApiTokenQuery,
+};
+
 pub mod user;
 pub use user::resolvers::{
     user_mutation_resolvers::UserMutation,
@@ -28,5 +35,8 @@ pub use teg_common::paths;
 mod auth_context;
 pub use auth_context::AuthContext;
 
+mod permission;
+pub use permission::Permission;
+
 pub type Db = sqlx::PgPool;
 pub type DbId = teg_json_store::DbId;
@@ -0,0 +1,42 @@
+use crate::{
+    api_token::ApiTokenScope,
+    user::UserRole,
+};
+
+/// The actions that resolvers authorize through the AuthContext. Each permission requires both a
+/// minimum user role and, for API token sessions, a token scope.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Permission {
+    /// View machines, print queues, prints and video
+    View,
+    /// Add, remove and re-order parts and packages in print queues
+    ManageQueue,
+    /// Start, pause and stop prints, e-stop machines and send GCodes
+    ControlMachine,
+    /// Change machine settings, components and materials
+    ConfigureMachine,
+    /// Add and delete machines and manage users, invites and backups
+    Administer,
+}
+
+impl Permission {
+    pub fn role(&self) -> UserRole {
+        match self {
+            Permission::View => UserRole::Viewer,
+            Permission::ManageQueue => UserRole::Operator,
+            Permission::ControlMachine => UserRole::Operator,
+            Permission::ConfigureMachine => UserRole::Maintainer,
+            Permission::Administer => UserRole::Admin,
+        }
+    }
+
+    pub fn scope(&self) -> ApiTokenScope {
+        match self {
+            Permission::View => ApiTokenScope::ReadOnly,
+            Permission::ManageQueue => ApiTokenScope::QueueManagement,
+            Permission::ControlMachine => ApiTokenScope::MachineControl,
+            Permission::ConfigureMachine => ApiTokenScope::MachineControl,
+            Permission::Administer => ApiTokenScope::Admin,
+        }
+    }
+}
@@ -130,8 +130,12 @@ impl User {
         * Update the user
         */
         if let Some(invite) = invite {
+            // Previously unauthorized users are treated as new users so that they only receive the
+            // invite's role and grants
+            let is_new_invitee = is_new_user || !user.is_authorized;
+
             user.is_authorized = true;
-            user.config.is_admin = user.config.is_admin || invite.config.is_admin;
+            invite.config.apply_to(&mut user.config, is_new_invitee);
         }
 
         user.email = email;
@@ -21,7 +21,20 @@ impl teg_config_form::Configurable<UserConfig> for User
 
 impl teg_config_form::Model for UserConfig
 {
-    fn form(all_fields: &Vec<String>) -> Vec<String> {
-        all_fields.clone()
+    // is_admin is derived from the role
+    fn static_form() -> Option<Vec<&'static str>> {
+        Some(vec![
+            "role",
+            "machine_ids",
+            "print_queue_ids",
+        ])
+    }
+
+    fn static_advanced_form() -> Option<Vec<&'static str>> {
+        Some(vec![])
+    }
+
+    fn static_developer_form() -> Option<Vec<&'static str>> {
+        Some(vec![])
     }
 }
@@ -16,6 +16,9 @@ pub use user::{
 mod user_config;
 pub use user_config::UserConfig;
 
+mod user_role;
+pub use user_role::UserRole;
+
 pub mod resolvers;
 
 #[derive(async_graphql::InputObject)]
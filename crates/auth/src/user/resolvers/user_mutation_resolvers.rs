@@ -9,6 +9,7 @@ use eyre::{
     // Result
 };
 use teg_json_store::Record as _;
+use teg_common::into_field_error;
 
 use crate::{AuthContext, user::{User, UserConfig}};
 
@@ -49,7 +50,11 @@ impl UserMutation {
 
         let mut tx = db.begin().await?;
 
-        if input.model.0.is_admin == false {
+        // The role takes precedence over the legacy is_admin field
+        let mut config = input.model.0;
+        config.set_role(config.role);
+
+        if config.is_admin == false {
             User::verify_other_admins_exist(&mut tx, &input.user_id)
                 .await
                 .with_context(|| r#"
@@ -63,11 +68,13 @@ impl UserMutation {
             &input.user_id,
             input.model_version,
             false,
-        ).await?;
+        )
+            .await
+            .map_err(into_field_error)?;
 
-        user.config = input.model.0;
+        user.config = config;
 
-        user.update(&mut tx).await?;
+        user.update(&mut tx).await.map_err(into_field_error)?;
         tx.commit().await?;
 
         Ok(user)
@@ -103,7 +110,7 @@ impl UserMutation {
             Err(eyre!("This account is required to run the server"))?;
         }
 
-        user.remove(&mut tx, false).await?;
+        user.remove(&mut tx, false).await.map_err(into_field_error)?;
         tx.commit().await?;
 
         Ok(None)
@@ -10,7 +10,7 @@ use async_graphql::{
 // };
 use teg_config_form::ConfigForm;
 
-use crate::user::User;
+use crate::user::{User, UserRole};
 
 #[async_graphql::Object]
 impl User {
@@ -37,7 +37,11 @@ impl User {
     }
 
     async fn is_admin(&self) -> bool {
-        self.config.is_admin
+        self.config.role() == UserRole::Admin
+    }
+
+    async fn role(&self) -> UserRole {
+        self.config.role()
     }
 
     #[graphql(name = "isLocalHTTPUser")]
@@ -7,7 +7,7 @@ use eyre::{
 };
 use teg_json_store::{ Record, JsonRow };
 
-use super::UserConfig;
+use super::{UserConfig, UserRole};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
@@ -54,7 +54,11 @@ impl User {
                 email: None,
                 email_verified: false,
                 last_logged_in_at: None,
-                config: Default::default(),
+                // Insecure local connections can configure machines but not administer the server
+                config: UserConfig {
+                    role: UserRole::Maintainer,
+                    ..Default::default()
+                },
                 is_authorized: true,
                 is_local_http_user: true,
             };
@@ -1,8 +1,110 @@
 use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 
+use super::UserRole;
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
 pub struct UserConfig {
     /// # Admin
+    /// Derived from the user's role. Kept for backwards compatibility.
+    #[serde(default)]
     pub is_admin: bool,
+    /// # Role
+    #[serde(default)]
+    pub role: UserRole,
+    /// # Machines (Optional)
+    /// Limits the user to these machine IDs. Leave unset to grant access to every machine.
+    #[serde(default)]
+    pub machine_ids: Option<Vec<crate::DbId>>,
+    /// # Print Queues (Optional)
+    /// Limits the user to these print queue IDs. Leave unset to grant access to every print queue.
+    #[serde(default)]
+    pub print_queue_ids: Option<Vec<crate::DbId>>,
+}
+
+impl UserConfig {
+    pub fn role(&self) -> UserRole {
+        if self.is_admin {
+            UserRole::Admin
+        } else {
+            self.role
+        }
+    }
+
+    /// Sets the role and keeps is_admin consistent with it
+    pub fn set_role(&mut self, role: UserRole) {
+        self.role = role;
+        self.is_admin = role == UserRole::Admin;
+    }
+
+    pub fn can_access_machine(&self, machine_id: &str) -> bool {
+        is_granted(self.role(), &self.machine_ids, machine_id)
+    }
+
+    pub fn can_access_print_queue(&self, print_queue_id: &str) -> bool {
+        is_granted(self.role(), &self.print_queue_ids, print_queue_id)
+    }
+}
+
+/// Admins and users without a list of grants have access to everything
+fn is_granted(role: UserRole, grants: &Option<Vec<crate::DbId>>, id: &str) -> bool {
+    role == UserRole::Admin
+        || grants
+            .as_ref()
+            .map(|ids| ids.iter().any(|granted_id| granted_id == id))
+            .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(role: UserRole, machine_ids: Option<Vec<&str>>) -> UserConfig {
+        let mut config = UserConfig {
+            machine_ids: machine_ids
+                .map(|ids| ids.into_iter().map(Into::into).collect()),
+            ..Default::default()
+        };
+        config.set_role(role);
+
+        config
+    }
+
+    #[test]
+    fn users_without_grants_can_access_every_machine() {
+        let config = config(UserRole::Operator, None);
+
+        assert!(config.can_access_machine("a"));
+        assert!(config.can_access_print_queue("a"));
+    }
+
+    #[test]
+    fn users_with_grants_can_only_access_granted_machines() {
+        let config = config(UserRole::Operator, Some(vec!["a"]));
+
+        assert!(config.can_access_machine("a"));
+        assert!(!config.can_access_machine("b"));
+    }
+
+    #[test]
+    fn an_empty_list_of_grants_denies_every_machine() {
+        let config = config(UserRole::Maintainer, Some(vec![]));
+
+        assert!(!config.can_access_machine("a"));
+    }
+
+    #[test]
+    fn admins_can_access_every_machine() {
+        let config = config(UserRole::Admin, Some(vec![]));
+
+        assert!(config.is_admin);
+        assert!(config.can_access_machine("a"));
+    }
+
+    #[test]
+    fn legacy_admins_have_the_admin_role() {
+        let config: UserConfig = serde_json::from_str(r#"{ "is_admin": true }"#).unwrap();
+
+        assert_eq!(config.role(), UserRole::Admin);
+    }
 }
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// A user's level of access to the server. Each role includes the permissions of the roles before
+/// it.
+#[derive(
+    async_graphql::Enum,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum UserRole {
+    /// Can view machines, print queues and video but cannot make changes
+    #[graphql(name = "VIEWER")]
+    #[serde(rename = "Viewer")]
+    Viewer,
+    /// Can manage print queues and start, pause and stop prints
+    #[graphql(name = "OPERATOR")]
+    #[serde(rename = "Operator")]
+    Operator,
+    /// Can also change machine settings, components and materials
+    #[graphql(name = "MAINTAINER")]
+    #[serde(rename = "Maintainer")]
+    Maintainer,
+    /// Full access to every machine as well as users, invites and backups
+    #[graphql(name = "ADMIN")]
+    #[serde(rename = "Admin")]
+    Admin,
+}
+
+impl Default for UserRole {
+    fn default() -> Self { UserRole::Operator }
+}
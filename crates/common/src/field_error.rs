@@ -0,0 +1,16 @@
+use teg_json_store::VersionConflict;
+
+/// Converts an error into a GraphQL error. Version conflicts are given a "VERSION_CONFLICT" code
+/// so that clients can reload the record and retry their change.
+pub fn into_field_error(err: eyre::Error) -> async_graphql::Error {
+    use async_graphql::ErrorExtensions as _;
+
+    let is_version_conflict = err.is::<VersionConflict>();
+    let field_error = async_graphql::Error::new(err.to_string());
+
+    if is_version_conflict {
+        field_error.extend_with(|_, e| e.set("code", "VERSION_CONFLICT"))
+    } else {
+        field_error
+    }
+}
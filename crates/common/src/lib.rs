@@ -2,6 +2,9 @@ pub struct Void;
 
 pub mod paths;
 
+mod field_error;
+pub use field_error::into_field_error;
+
 #[async_graphql::Object]
 impl Void {
     pub async fn id(&self) -> async_graphql::ID {
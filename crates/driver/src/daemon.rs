@@ -0,0 +1,71 @@
+use std::{
+    env,
+    future::Future,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+};
+use pidfile_rs::Pidfile;
+use nix::sched::{CpuSet, sched_setaffinity};
+use nix::unistd::Pid;
+
+use teg_machine::config::MachineConfig;
+
+/// Daemonizes the driver process and then runs the driver on the CPU dedicated to the drivers.
+///
+/// Expects the machine ID as the first command line argument. `start` is called with the path to
+/// the machine's config file.
+pub fn run<F, Fut>(driver_name: &str, start: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = eyre::Result<()>>,
+{
+    let mut args = env::args();
+    let machine_id = args.nth(1)
+        .unwrap_or_else(|| panic!("Expected useage: {} $MACHINE_ID", driver_name));
+
+    let pid_file = MachineConfig::pid_file_path(&machine_id);
+    let config_path = MachineConfig::config_file_path(&machine_id);
+
+    // Create and lock the pidfile
+    // See: https://yakking.branchable.com/posts/procrun-2-pidfiles/
+    let pidfile = Pidfile::new(
+        &pid_file.into(),
+        std::fs::Permissions::from_mode(0o600),
+    )?;
+
+    // Must run daemonize before starting tokio!
+    nix::unistd::daemon(true, true)
+        .unwrap_or_else(|err| panic!("Error daemonizing {} process: {:?}", driver_name, err));
+
+    // After daemonizing the process write the pid to the pidfile
+    pidfile.write()?;
+
+    // All other teg processes are prevented from running on cpu 0 so that it can be dedicated
+    // to the driver processes (eg. teg-marlin).
+    let mut cpu_set = CpuSet::new();
+    cpu_set.set(0)?;
+    sched_setaffinity(Pid::from_raw(0), &cpu_set)?;
+
+    dotenv::dotenv().ok();
+
+    tracing_subscriber::fmt::init();
+    color_eyre::install()?;
+
+    // Create single-threaded runtime that will run the driver only on the dedicated CPU
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    rt.block_on(start(config_path))?;
+
+    Ok(())
+}
+
+/// Reads the machine's config file
+pub fn load_config(config_path: &PathBuf) -> MachineConfig {
+    let config_file_content = std::fs::read_to_string(config_path)
+        .expect(&format!("Unabled to open config (file: {:?})", config_path));
+
+    toml::from_str(&config_file_content)
+        .expect(&format!("Invalid config format (file: {:?})", config_path))
+}
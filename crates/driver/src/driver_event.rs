@@ -0,0 +1,14 @@
+use teg_protobufs::ServerMessage;
+
+/// The events sent to a driver's state machine by the serial port and the protobuf socket
+pub trait DriverEvent: Send + 'static {
+    /// A response decoded from the serial port by the driver's codec
+    type Response: Send + 'static;
+
+    fn serial_port_opened() -> Self;
+    fn serial_received(response: Self::Response) -> Self;
+    fn serial_port_disconnected() -> Self;
+    fn serial_port_error(message: String) -> Self;
+    fn protobuf_client_connected() -> Self;
+    fn protobuf_received(message: ServerMessage) -> Self;
+}
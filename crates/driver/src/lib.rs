@@ -0,0 +1,14 @@
+#![crate_type = "lib"]
+#![crate_name = "teg_driver"]
+
+#[macro_use] extern crate tracing;
+
+mod daemon;
+mod driver_event;
+pub mod protobuf_server;
+mod serial_manager;
+pub mod serial_transport;
+
+pub use daemon::{run, load_config};
+pub use driver_event::DriverEvent;
+pub use serial_manager::SerialManager;
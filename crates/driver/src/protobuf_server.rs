@@ -1,12 +1,7 @@
 use std::{fs, path::PathBuf};
 
-// use futures::compat::{
-//     Stream01CompatExt,
-// };
-
 use futures::{
     future,
-    // FutureExt,
     StreamExt,
     SinkExt,
     TryStreamExt,
@@ -18,26 +13,18 @@ use tokio_util::codec::{
     Framed,
 };
 
-// use bytes::BufMut;
-
-use tokio::{
-    net::UnixListener,
-    // sync::mpsc,
-};
-
-use teg_protobufs::Message;
-use bytes::Bytes;
+use tokio::net::UnixListener;
 
-use crate::protos::{
-    // MachineMessage,
+use teg_protobufs::{
+    Message,
     ServerMessage,
-    // server_message,
 };
+use bytes::Bytes;
 
-use crate::state_machine::Event;
+use crate::DriverEvent;
 
-async fn handle_connection(
-    mut channel_sender: mpsc::Sender<Event>,
+async fn handle_connection<E: DriverEvent>(
+    mut channel_sender: mpsc::Sender<E>,
     broadcast_subscriber: bus_queue::flavors::arc_swap::Subscriber<Bytes>,
     connection: tokio::net::UnixStream,
 ) {
@@ -51,29 +38,19 @@ async fn handle_connection(
 
     let mut broadcast_subscriber = broadcast_subscriber
         .clone()
-        // .compat()
-        // .map_err(|err| {
-        //     error!("Send Error: {:?}", err);
-        //     ()
-        // })
-        // .take_while(|result| {
-        //     future::ready(result.is_ok())
-        // })
         .inspect(|_| trace!("Protobuf Sent"))
         .map(|result| Ok(Bytes::clone(&*result)));
 
-    // Read from the server. TODO: Switch to read_to_end.
-    // let mut buf = [0u8; 5];
     let mut read_stream = socket_reader
         .map_err(|e| {
             error!("Error receiving Teg Protobuf: {:?}", e);
             ()
         })
-        .and_then(move |buf| -> future::Ready<Result<Event, ()>> {
+        .and_then(move |buf| -> future::Ready<Result<E, ()>> {
             let decoded_result = ServerMessage::decode(buf);
 
             if let Ok(message) = decoded_result {
-                future::ok(Event::ProtobufRec( message ))
+                future::ok(E::protobuf_received(message))
             } else {
                 error!("Unable to decode combinator message: {:?}", decoded_result);
                 future::err(())
@@ -84,23 +61,12 @@ async fn handle_connection(
         })
         .map(|result| Ok(result.unwrap()));
 
-    // NodeJS sometimes needs a delay after opening the unix socket to prevent it from dropping the first message
-    // See: https://github.com/nodejs/help/issues/521
     tokio::spawn(async move {
-        // debug!("New connection received. Waiting 100ms.");
-
-        // tokio::time::delay_for(Duration::from_millis(100)).await;
-
         debug!("New protobuf socket connection received");
 
-        connection_event_sender.send(Event::ProtobufClientConnection).await
+        connection_event_sender.send(E::protobuf_client_connected()).await
             .expect("Unable to send connection event");
 
-        // tokio::time::delay_for(Duration::from_millis(500)).await;
-
-        // connection_event_sender.send(Event::ProtobufClientConnection).await
-        //     .expect("Unable to send connection event");
-
         info!("Socket Ready");
     });
 
@@ -112,12 +78,11 @@ async fn handle_connection(
     info!("Socket Closed");
 }
 
-pub async fn serve(
+pub async fn serve<E: DriverEvent>(
     socket_path: &PathBuf,
-    channel_sender: &mpsc::Sender<Event>,
+    channel_sender: &mpsc::Sender<E>,
     broadcast_subscriber: bus_queue::flavors::arc_swap::Subscriber<Bytes>,
 ) -> eyre::Result<()> {
-
     info!("Socket: {:?}", socket_path);
 
     // delete the previous socket if one exists
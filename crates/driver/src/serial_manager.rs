@@ -0,0 +1,198 @@
+use eyre::{
+    eyre,
+    // Context as _,
+    Result,
+};
+
+use futures::{
+    FutureExt,
+    SinkExt,
+    StreamExt,
+    TryStreamExt,
+    channel::mpsc,
+    future::{
+        self,
+        Either,
+        AbortHandle,
+        Future,
+    },
+    stream,
+};
+
+use tokio_util::codec::{
+    Decoder,
+    Encoder,
+};
+
+use crate::{
+    DriverEvent,
+    serial_transport::{
+        SerialPortAddress,
+        SerialTransport,
+    },
+};
+
+/// Streams lines encoded by the driver's codec `C` to the controller and sends the decoded
+/// responses to the driver's state machine.
+pub struct SerialManager<C, I, E> {
+    codec: C,
+    settings: tokio_serial::SerialPortBuilder,
+    address: SerialPortAddress,
+
+    event_sender: mpsc::Sender<E>,
+    line_sender: Option<mpsc::Sender<I>>,
+    abort_handle: Option<AbortHandle>,
+}
+
+impl<C, I, E> SerialManager<C, I, E>
+where
+    C: Decoder<Item = Vec<E::Response>, Error = eyre::Error>
+        + Encoder<I, Error = eyre::Error>
+        + Clone
+        + Send
+        + 'static,
+    I: Send + 'static,
+    E: DriverEvent,
+{
+    pub fn new(
+        codec: C,
+        event_sender: mpsc::Sender<E>,
+        tty_path: String,
+        default_baud_rate: u32,
+    ) -> Self {
+        info!("tty: {}", tty_path);
+
+        let address = SerialPortAddress::parse(&tty_path);
+        let settings = tokio_serial::new(tty_path, default_baud_rate);
+
+        Self {
+            codec,
+            settings,
+            address,
+
+            event_sender,
+            line_sender: None,
+            abort_handle: None,
+        }
+    }
+
+    /// Closes the previous connection and connects to the tty or network serial port
+    pub async fn connect(&mut self, baud_rate: u32) -> Result<Box<dyn SerialTransport>> {
+        self.close();
+
+        use std::{thread, time};
+
+        // the serial port needs a moment to reset when reconnecting
+        thread::sleep(time::Duration::from_millis(100));
+
+        let port: Box<dyn SerialTransport> = if self.address.is_network() {
+            self.address.connect(baud_rate).await?
+        } else {
+            let settings = self.settings
+                .clone()
+                .baud_rate(baud_rate);
+
+            let mut port = tokio_serial::SerialStream::open(&settings)?;
+
+            // #[cfg(unix)]
+            port.set_exclusive(false)?;
+
+            Box::new(port)
+        };
+
+        Ok(port)
+    }
+
+    /// Starts streaming lines to and responses from the port. The returned future runs until the
+    /// port is closed or disconnected.
+    pub async fn open(
+        &mut self,
+        port: Box<dyn SerialTransport>,
+    ) -> Result<impl Future<Output = ()>> {
+        self.close();
+
+        let (
+            port_sender,
+            port_reader,
+        ) = self.codec.clone().framed(port).split();
+
+        let (
+            line_sender,
+            line_repeater_inner,
+        ) = mpsc::channel::<I>(100);
+        self.line_sender = Some(line_sender);
+
+        let sender_future = line_repeater_inner
+            .map(|line| Ok(line))
+            .forward(port_sender);
+
+        let reader_sender = mpsc::Sender::clone(&self.event_sender)
+            .sink_map_err(|err| eyre!("Serial Read SendError: {:?}", err));
+
+        let reader_future = port_reader
+            .map_ok(|responses|
+                stream::iter(responses).map(|r| Ok(r))
+            )
+            .try_flatten()
+            .map_ok(|response| E::serial_received(response))
+            .forward(reader_sender);
+
+        let end_of_stream_event_sender = mpsc::Sender::clone(&self.event_sender);
+        let is_network = self.address.is_network();
+
+        let serial_future = future::try_select(
+            reader_future,
+            sender_future,
+        )
+            .map(move |either| {
+                match either {
+                    Ok(_) => {
+                        E::serial_port_disconnected()
+                    }
+                    // Dropped network connections are reconnected like a disconnected serial port
+                    Err(Either::Left((e, _))) | Err(Either::Right((e, _))) if is_network => {
+                        warn!("Network serial port connection lost: {:?}", e);
+                        E::serial_port_disconnected()
+                    }
+                    Err(Either::Left((e, _))) => {
+                        E::serial_port_error(format!("{:?}", e))
+                    }
+                    Err(Either::Right((e, _))) => {
+                        E::serial_port_error(format!("Serial port read error: {:?}", e))
+                    }
+                }
+            })
+            .into_stream()
+            .map(|event| Ok(event))
+            .forward(end_of_stream_event_sender)
+            .map(|_| ());
+
+        let (
+            serial_future,
+            abort_handle,
+        ) = futures::future::abortable(serial_future);
+        self.abort_handle = Some(abort_handle);
+
+        self.event_sender.send(E::serial_port_opened()).await?;
+
+        Ok(serial_future.map(|_| ()))
+    }
+
+    pub fn close(&mut self) {
+        self.abort_handle.as_ref().map(|abort_handle| abort_handle.abort());
+
+        self.abort_handle = None;
+        self.line_sender = None;
+    }
+
+    pub async fn send(&mut self, line: I) -> Result<()> {
+        if let Some(line_sender) = &mut self.line_sender {
+            line_sender
+                .send(line)
+                .await
+                .map_err(|err| eyre!("Unable to send to serial port: {:?}", err))
+        } else {
+            Err(eyre!("Unable to send. Serial port is not open."))
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+use futures::ready;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::TcpStream,
+};
+
+/// How long to wait for a network serial bridge to accept a connection
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The delay before the first attempt to reconnect to a network serial port. Each failed attempt
+/// doubles the delay up to RECONNECT_MAX_DELAY.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(500);
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Telnet commands (RFC 854)
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+// Telnet options
+const BINARY: u8 = 0;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const COM_PORT_OPTION: u8 = 44;
+
+// RFC 2217 client to server subcommands
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+
+/// A bidirectional byte stream to the controller
+pub trait SerialTransport: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> SerialTransport for T {}
+
+/// Where the controller's serial port is. Parsed from the controller's serial port ID.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SerialPortAddress {
+    /// A local tty (eg. /dev/ttyUSB0)
+    Tty(String),
+    /// A raw TCP serial bridge (eg. ser2net in raw mode or ESP3D), from `tcp://host:port`
+    Tcp(String),
+    /// A Telnet serial bridge that accepts RFC 2217 port settings, from `rfc2217://host:port`
+    Rfc2217(String),
+}
+
+impl SerialPortAddress {
+    pub fn parse(serial_port_id: &str) -> Self {
+        let host_port = |address: &str| address.trim_end_matches('/').to_string();
+
+        if let Some(address) = serial_port_id.strip_prefix("tcp://") {
+            SerialPortAddress::Tcp(host_port(address))
+        } else if let Some(address) = serial_port_id.strip_prefix("rfc2217://") {
+            SerialPortAddress::Rfc2217(host_port(address))
+        } else {
+            SerialPortAddress::Tty(serial_port_id.to_string())
+        }
+    }
+
+    /// Network serial ports are not found by device discovery so the driver reconnects to them
+    /// itself
+    pub fn is_network(&self) -> bool {
+        !matches!(self, SerialPortAddress::Tty(_))
+    }
+
+    /// True if the driver should attempt to connect on startup. Network serial ports are always
+    /// attempted.
+    pub fn is_available(&self) -> bool {
+        match self {
+            SerialPortAddress::Tty(tty_path) => std::path::Path::new(tty_path).exists(),
+            _ => true,
+        }
+    }
+
+    /// Opens a connection to a network serial port
+    pub async fn connect(&self, baud_rate: u32) -> Result<Box<dyn SerialTransport>> {
+        let transport: Box<dyn SerialTransport> = match self {
+            SerialPortAddress::Tty(tty_path) => {
+                Err(eyre!("{:?} is not a network serial port", tty_path))?
+            }
+            SerialPortAddress::Tcp(host_port) => {
+                Box::new(connect_tcp(host_port).await?)
+            }
+            SerialPortAddress::Rfc2217(host_port) => {
+                let stream = connect_tcp(host_port).await?;
+
+                Box::new(Rfc2217Stream::negotiate(stream, baud_rate).await?)
+            }
+        };
+
+        Ok(transport)
+    }
+}
+
+/// The delay before reconnecting to a network serial port after the given number of failed
+/// attempts
+pub fn reconnect_delay(reconnect_attempts: u32) -> Duration {
+    let delay = RECONNECT_MIN_DELAY * 2u32.pow(reconnect_attempts.min(6));
+
+    delay.min(RECONNECT_MAX_DELAY)
+}
+
+async fn connect_tcp(host_port: &str) -> Result<TcpStream> {
+    info!("Connecting to network serial port: {}", host_port);
+
+    let stream = tokio::time::timeout(TCP_CONNECT_TIMEOUT, TcpStream::connect(host_port))
+        .await
+        .map_err(|_| eyre!("Timed out connecting to network serial port: {}", host_port))??;
+
+    // GCodes are short and latency sensitive
+    stream.set_nodelay(true)?;
+
+    Ok(stream)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TelnetState {
+    Data,
+    Command,
+    Negotiation,
+    Subnegotiation,
+    SubnegotiationCommand,
+}
+
+/// A Telnet connection to an RFC 2217 serial bridge.
+///
+/// Telnet commands sent by the bridge are removed from the received data and IAC bytes in the
+/// sent data are escaped. Option requests from the bridge are not answered which leaves any
+/// options the driver did not ask for disabled.
+pub struct Rfc2217Stream<T> {
+    inner: T,
+    telnet_state: TelnetState,
+    /// Escaped bytes waiting to be written to the inner stream
+    write_buffer: Vec<u8>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Rfc2217Stream<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            telnet_state: TelnetState::Data,
+            write_buffer: vec![],
+        }
+    }
+
+    /// Enables the com port option and configures the bridge's serial port for 8N1 at the
+    /// given baud rate
+    pub async fn negotiate(mut inner: T, baud_rate: u32) -> io::Result<Self> {
+        let mut negotiation = vec![
+            IAC, WILL, BINARY,
+            IAC, DO, BINARY,
+            IAC, WILL, SUPPRESS_GO_AHEAD,
+            IAC, DO, SUPPRESS_GO_AHEAD,
+            IAC, WILL, COM_PORT_OPTION,
+        ];
+
+        let settings: [(u8, &[u8]); 5] = [
+            (SET_BAUDRATE, &baud_rate.to_be_bytes()),
+            (SET_DATASIZE, &[8]),
+            // No parity
+            (SET_PARITY, &[1]),
+            // One stop bit
+            (SET_STOPSIZE, &[1]),
+            // No flow control
+            (SET_CONTROL, &[1]),
+        ];
+
+        for (subcommand, value) in settings.iter() {
+            negotiation.extend_from_slice(&[IAC, SB, COM_PORT_OPTION, *subcommand]);
+            negotiation.extend(escape(value));
+            negotiation.extend_from_slice(&[IAC, SE]);
+        }
+
+        inner.write_all(&negotiation).await?;
+        inner.flush().await?;
+
+        Ok(Self::new(inner))
+    }
+
+    /// Returns the byte if it is data or None if it is part of a Telnet command
+    fn receive_byte(&mut self, byte: u8) -> Option<u8> {
+        use TelnetState::*;
+
+        let (next_state, data) = match (self.telnet_state, byte) {
+            (Data, IAC) => (Command, None),
+            (Data, _) => (Data, Some(byte)),
+            // An escaped IAC data byte
+            (Command, IAC) => (Data, Some(IAC)),
+            (Command, WILL) | (Command, WONT) | (Command, DO) | (Command, DONT) => {
+                (Negotiation, None)
+            }
+            (Command, SB) => (Subnegotiation, None),
+            // Other commands (eg. NOP) have no arguments
+            (Command, _) => (Data, None),
+            // The option being negotiated
+            (Negotiation, _) => (Data, None),
+            // Subnegotiations (eg. the bridge's port settings and line state) are ignored
+            (Subnegotiation, IAC) => (SubnegotiationCommand, None),
+            (Subnegotiation, _) => (Subnegotiation, None),
+            (SubnegotiationCommand, SE) => (Data, None),
+            (SubnegotiationCommand, _) => (Subnegotiation, None),
+        };
+
+        self.telnet_state = next_state;
+        data
+    }
+
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buffer.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.write_buffer))?;
+
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()))
+            }
+
+            self.write_buffer.drain(..n);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+
+    for byte in data {
+        escaped.push(*byte);
+
+        if *byte == IAC {
+            escaped.push(IAC);
+        }
+    }
+
+    escaped
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for Rfc2217Stream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let this = &mut *self;
+            let start = buf.filled().len();
+
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+            let end = buf.filled().len();
+
+            // End of stream
+            if end == start {
+                return Poll::Ready(Ok(()))
+            }
+
+            // Remove the Telnet commands from the received bytes
+            let mut data_end = start;
+
+            for i in start..end {
+                let byte = buf.filled()[i];
+
+                if let Some(byte) = this.receive_byte(byte) {
+                    buf.filled_mut()[data_end] = byte;
+                    data_end += 1;
+                }
+            }
+
+            buf.set_filled(data_end);
+
+            // Reading zero bytes would be mistaken for the end of the stream so keep reading
+            // until data is received
+            if data_end > start {
+                return Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for Rfc2217Stream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_write_buffer(cx))?;
+
+        self.write_buffer = escape(buf);
+
+        // Start writing immediately. Anything left over is written by the next write or flush.
+        if let Poll::Ready(Err(err)) = self.poll_write_buffer(cx) {
+            return Poll::Ready(Err(err))
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_buffer(cx))?;
+
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_buffer(cx))?;
+
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn backs_off_reconnection_attempts() {
+        assert_eq!(reconnect_delay(0), Duration::from_millis(500));
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(7), RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn parses_serial_port_ids() {
+        assert_eq!(
+            SerialPortAddress::parse("/dev/ttyUSB0"),
+            SerialPortAddress::Tty("/dev/ttyUSB0".to_string()),
+        );
+        assert_eq!(
+            SerialPortAddress::parse("tcp://192.168.0.20:2000"),
+            SerialPortAddress::Tcp("192.168.0.20:2000".to_string()),
+        );
+        assert_eq!(
+            SerialPortAddress::parse("rfc2217://printer.local:3000/"),
+            SerialPortAddress::Rfc2217("printer.local:3000".to_string()),
+        );
+    }
+
+    #[tokio::test]
+    async fn negotiates_and_filters_telnet_commands() {
+        let (client, mut bridge) = tokio::io::duplex(1024);
+
+        let mut stream = Rfc2217Stream::negotiate(client, 115_200).await.unwrap();
+
+        // The bridge receives the option negotiation followed by the port settings
+        let mut negotiation = vec![0; 15 + 10 + 4 * 7];
+        bridge.read_exact(&mut negotiation).await.unwrap();
+
+        assert_eq!(&negotiation[12..15], &[IAC, WILL, COM_PORT_OPTION]);
+        assert_eq!(
+            &negotiation[15..25],
+            &[IAC, SB, COM_PORT_OPTION, SET_BAUDRATE, 0, 1, 194, 0, IAC, SE],
+        );
+        assert_eq!(
+            &negotiation[25..32],
+            &[IAC, SB, COM_PORT_OPTION, SET_DATASIZE, 8, IAC, SE],
+        );
+
+        // Telnet commands from the bridge are removed from the data
+        bridge.write_all(&[IAC, DO, COM_PORT_OPTION]).await.unwrap();
+        bridge.write_all(&[IAC, SB, COM_PORT_OPTION, 101, 1, 0, 1, 194, 0, IAC, SE]).await.unwrap();
+        bridge.write_all(b"ok T:21.0\n").await.unwrap();
+
+        let mut line = String::new();
+        BufReader::new(&mut stream).read_line(&mut line).await.unwrap();
+
+        assert_eq!(line, "ok T:21.0\n");
+
+        // IAC bytes in the sent data are escaped
+        stream.write_all(&[b'G', IAC]).await.unwrap();
+        stream.flush().await.unwrap();
+
+        let mut sent = vec![0; 3];
+        bridge.read_exact(&mut sent).await.unwrap();
+
+        assert_eq!(sent, vec![b'G', IAC, IAC]);
+    }
+
+    #[tokio::test]
+    async fn connects_to_a_tcp_serial_bridge() {
+        // Acknowledge each line received by a local TCP bridge
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = tokio::io::split(socket);
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Some(_) = lines.next_line().await.unwrap() {
+                writer.write_all(b"ok\n").await.unwrap();
+            }
+        });
+
+        let address = SerialPortAddress::parse(&format!("tcp://{}", address));
+        let mut transport = address.connect(115_200).await.unwrap();
+
+        transport.write_all(b"M105\n").await.unwrap();
+
+        let mut lines = BufReader::new(transport).lines();
+
+        loop {
+            let line = lines.next_line().await.unwrap().expect("Unexpected end of stream");
+
+            if line.starts_with("ok") {
+                break
+            }
+        }
+    }
+}
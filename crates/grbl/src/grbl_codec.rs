@@ -0,0 +1,111 @@
+use std::str;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use bytes::{BytesMut, BufMut};
+
+use crate::response::{
+    parse_response,
+    Response,
+};
+
+/// GRBL's real-time commands. These are single bytes that GRBL acts on immediately instead of
+/// adding them to its serial buffer.
+///
+/// See: https://github.com/gnea/grbl/wiki/Grbl-v1.1-Commands#grbl-v11-realtime-commands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RealTimeCommand {
+    StatusReportQuery,
+    /// Decelerates to a stop and holds the remaining lines until the cycle is started
+    FeedHold,
+    /// Resumes from a feed hold
+    CycleStart,
+    /// Halts immediately and clears GRBL's buffers. Machine position is lost if the machine was
+    /// moving.
+    SoftReset,
+}
+
+impl RealTimeCommand {
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            RealTimeCommand::StatusReportQuery => b'?',
+            RealTimeCommand::FeedHold => b'!',
+            RealTimeCommand::CycleStart => b'~',
+            RealTimeCommand::SoftReset => 0x18,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SerialData {
+    /// A line added to GRBL's serial buffer. GRBL acknowledges each line with an ok or an error.
+    Line(String),
+    RealTime(RealTimeCommand),
+}
+
+#[derive(Clone)]
+pub struct GrblCodec;
+
+impl Decoder for GrblCodec {
+    type Item = Vec<(String, Response)>;
+    type Error = eyre::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let mut responses = vec![];
+
+        while let Some(n) = src.iter().position(|b| *b == b'\n') {
+            let line = src.split_to(n + 1);
+
+            // Invalid UTF8 lines are treated as unknown responses
+            let line = str::from_utf8(&line)
+                .map(|line| line.trim().to_string())
+                .unwrap_or_default();
+
+            if line.is_empty() {
+                continue
+            }
+
+            let response = parse_response(&line);
+
+            if let Response::Status(_) = response {
+                trace!("RX {:?}", line);
+            } else {
+                debug!("RX {:?}", line);
+            }
+
+            responses.push((line, response));
+        }
+
+        if responses.len() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(responses))
+        }
+    }
+}
+
+impl Encoder<SerialData> for GrblCodec {
+    type Error = eyre::Error;
+
+    fn encode(&mut self, item: SerialData, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            SerialData::Line(line) => {
+                debug!("TX {:?}", line);
+
+                dst.reserve(line.len() + 1);
+                dst.put(line.as_bytes());
+                dst.put_u8(b'\n');
+            }
+            SerialData::RealTime(command) => {
+                if command != RealTimeCommand::StatusReportQuery {
+                    debug!("TX {:?}", command);
+                }
+
+                dst.reserve(1);
+                dst.put_u8(command.to_byte());
+            }
+        }
+
+        Ok(())
+    }
+}
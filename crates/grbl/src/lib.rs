@@ -0,0 +1,143 @@
+#![crate_type = "lib"]
+#![crate_name = "teg_grbl"]
+
+#[macro_use] extern crate tracing;
+#[macro_use] extern crate lazy_static;
+
+pub mod codes;
+pub mod grbl_codec;
+pub mod response;
+
+pub mod state_machine;
+
+pub mod protos {
+    pub use teg_protobufs::*;
+}
+
+pub use teg_machine::config::MachineConfig;
+
+use std::{collections::HashMap, path::PathBuf};
+use chrono::prelude::*;
+use futures::{
+    StreamExt,
+    future::AbortHandle,
+    channel::mpsc,
+    sink::SinkExt,
+};
+use bytes::Bytes;
+
+use state_machine::{Loop, State, Event, Context};
+use grbl_codec::{GrblCodec, SerialData};
+use teg_driver::{
+    protobuf_server,
+    serial_transport::SerialPortAddress,
+};
+
+pub type DbId = teg_json_store::DbId;
+
+pub type SerialManager = teg_driver::SerialManager<GrblCodec, SerialData, Event>;
+
+lazy_static! {
+    pub static ref PROCESS_STARTED_AT: DateTime<Utc> = Utc::now();
+}
+
+pub struct StateMachineReactor {
+    pub event_sender: mpsc::Sender<Event>,
+    pub protobuf_broadcast: bus_queue::flavors::arc_swap::Publisher<Bytes>,
+    pub serial_manager: SerialManager,
+    pub delays: HashMap<String, AbortHandle>,
+    pub context: Context,
+}
+
+async fn tick_state_machine(
+    state: State,
+    event: Event,
+    reactor: &mut StateMachineReactor,
+) -> State {
+    trace!("Received Event:  {:#?}", event);
+
+    let Loop{ next_state, effects } = state.consume(event, &mut reactor.context);
+
+    trace!("Next State: {:#?}", next_state);
+    trace!("Effects: {:#?}", effects);
+
+    for effect in effects.into_iter() {
+        effect.exec(reactor).await;
+    };
+
+    next_state
+}
+
+pub async fn start(
+    config_path: PathBuf,
+) -> eyre::Result<()> {
+    lazy_static::initialize(&PROCESS_STARTED_AT);
+
+    // Config
+    // ----------------------------------------------------
+    let config = teg_driver::load_config(&config_path);
+
+    // Channels
+    // ----------------------------------------------------
+    let (mut event_sender, event_reader) = mpsc::channel::<Event>(100);
+
+    // Serial Port
+    // ----------------------------------------------------
+    let serial_manager = SerialManager::new(
+        GrblCodec,
+        event_sender.clone(),
+        config.tty_path().clone(),
+        115_200,
+    );
+
+    // attempt to connect to serial on startup if the port is available. Network serial ports
+    // (eg. tcp://host:port or rfc2217://host:port) are always attempted.
+    let serial_port_available = SerialPortAddress::parse(config.tty_path()).is_available();
+    event_sender.send(Event::Init { serial_port_available })
+        .await
+        .expect("Unable to send init event");
+
+    // Protobuf Server
+    // ----------------------------------------------------
+    let (protobuf_broadcast, protobuf_recv) = bus_queue::flavors::arc_swap::bounded(10);
+
+    let protobuf_sender = mpsc::Sender::clone(&event_sender);
+
+    let socket_path = config.socket_path();
+    protobuf_server::serve(&socket_path, &protobuf_sender, protobuf_recv)
+        .await
+        .expect("Error starting teg protobuf server error");
+
+    // Reactor
+    // ----------------------------------------------------
+    let reactor = StateMachineReactor {
+        protobuf_broadcast,
+        event_sender,
+        serial_manager,
+        delays: HashMap::new(),
+        context: Context::new(config),
+    };
+
+    // Glue Code
+    // ----------------------------------------------------
+    let initial_acc = (
+        State::Disconnected,
+        reactor,
+    );
+
+    event_reader
+        .fold(initial_acc, move |acc, message| async {
+            let (state, mut reactor) = acc;
+
+            let next_state = tick_state_machine(
+                state,
+                message,
+                &mut reactor,
+            ).await;
+
+            (next_state, reactor)
+        })
+        .await;
+
+    Ok(())
+}
@@ -0,0 +1,7 @@
+extern crate teg_grbl;
+
+pub use teg_machine::paths;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    teg_driver::run("teg-grbl", teg_grbl::start)
+}
@@ -0,0 +1,304 @@
+use nom::{
+    IResult,
+    character::complete::*,
+    bytes::complete::*,
+    number::complete::float,
+};
+use nom::branch::*;
+use nom::combinator::*;
+use nom::multi::*;
+use nom::sequence::*;
+
+/// A line received from a GRBL 1.1 controller.
+///
+/// See: https://github.com/gnea/grbl/wiki/Grbl-v1.1-Interface
+#[derive(Clone, Debug, PartialEq)]
+pub enum Response {
+    /// The line was received and executed (or added to the planner buffer)
+    Ok,
+    /// The line was received but could not be executed
+    Error(u32),
+    /// The controller has halted (eg. a limit switch was triggered). Alarms are sent unsolicited.
+    Alarm(u32),
+    /// A reply to the "?" real-time status report query
+    Status(StatusReport),
+    /// Sent on startup and after each soft reset. Contains the GRBL version (eg. "1.1h").
+    Greeting(String),
+    /// A line of the "$$" settings report (eg. "$30=1000")
+    Setting { id: u32, value: String },
+    /// Bracketed feedback messages (eg. "[MSG:'$H'|'$X' to unlock]")
+    Message(String),
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MachineState {
+    Idle,
+    Run,
+    /// Feed hold. The sub-state is 0 once the hold is complete and 1 while decelerating.
+    Hold(u32),
+    Jog,
+    Alarm,
+    /// Safety door. The sub-state describes whether the door is open and the parking motion.
+    Door(u32),
+    Check,
+    Home,
+    Sleep,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusReport {
+    pub state: MachineState,
+    /// Machine position in mm (or inches if $13=1). Reported instead of the work position
+    /// depending on $10.
+    pub machine_position: Option<Vec<f32>>,
+    pub work_position: Option<Vec<f32>>,
+    /// The work coordinate offset. Only included in some of the status reports.
+    pub work_coordinate_offset: Option<Vec<f32>>,
+    pub feed_rate: Option<f32>,
+    /// The programmed spindle speed in RPM
+    pub spindle_speed: Option<f32>,
+    /// The free planner blocks and serial RX buffer bytes. Only reported if enabled in $10.
+    pub buffer: Option<(u32, u32)>,
+    /// The enabled accessories (eg. "S" for the spindle running clockwise, "C" for counter
+    /// clockwise and "F" or "M" for coolant). Omitted from the report when all accessories are off.
+    pub accessories: Option<String>,
+}
+
+#[derive(Clone)]
+enum StatusField {
+    MachinePosition(Vec<f32>),
+    WorkPosition(Vec<f32>),
+    WorkCoordinateOffset(Vec<f32>),
+    FeedRate(f32),
+    FeedRateAndSpindleSpeed(f32, f32),
+    Buffer(u32, u32),
+    Accessories(String),
+    Other,
+}
+
+/// Parses a single line received from the controller. Lines that are not recognized are parsed
+/// as Response::Unknown.
+pub fn parse_response(line: &str) -> Response {
+    match response(line.trim()) {
+        Ok((_, response)) => response,
+        Err(_) => Response::Unknown,
+    }
+}
+
+fn response<'r>(input: &'r str) -> IResult<&'r str, Response> {
+    alt((
+        value(Response::Ok, all_consuming(tag("ok"))),
+        map(preceded(tag("error:"), integer), |code| Response::Error(code)),
+        map(preceded(tag("ALARM:"), integer), |code| Response::Alarm(code)),
+        map(status_report, |report| Response::Status(report)),
+        greeting,
+        setting,
+        map(
+            delimited(char('['), is_not("]"), char(']')),
+            |message: &str| Response::Message(message.to_string()),
+        ),
+    ))(input)
+}
+
+fn integer<'r>(input: &'r str) -> IResult<&'r str, u32> {
+    map_res(digit1, |digits: &str| digits.parse())(input)
+}
+
+fn greeting<'r>(input: &'r str) -> IResult<&'r str, Response> {
+    map(
+        preceded(
+            pair(tag("Grbl"), space1),
+            is_not(" \t"),
+        ),
+        |version: &str| Response::Greeting(version.to_string()),
+    )(input)
+}
+
+fn setting<'r>(input: &'r str) -> IResult<&'r str, Response> {
+    map(
+        pair(
+            preceded(char('$'), integer),
+            preceded(char('='), rest),
+        ),
+        |(id, value): (u32, &str)| Response::Setting {
+            id,
+            value: value.trim().to_string(),
+        },
+    )(input)
+}
+
+fn machine_state<'r>(input: &'r str) -> IResult<&'r str, MachineState> {
+    map_opt(
+        pair(
+            alpha1,
+            opt(preceded(char(':'), integer)),
+        ),
+        |(name, sub_state): (&str, Option<u32>)| {
+            let state = match name {
+                "Idle" => MachineState::Idle,
+                "Run" => MachineState::Run,
+                "Hold" => MachineState::Hold(sub_state.unwrap_or(0)),
+                "Jog" => MachineState::Jog,
+                "Alarm" => MachineState::Alarm,
+                "Door" => MachineState::Door(sub_state.unwrap_or(0)),
+                "Check" => MachineState::Check,
+                "Home" => MachineState::Home,
+                "Sleep" => MachineState::Sleep,
+                _ => return None,
+            };
+
+            Some(state)
+        },
+    )(input)
+}
+
+fn coordinates<'r>(input: &'r str) -> IResult<&'r str, Vec<f32>> {
+    separated_list1(char(','), float)(input)
+}
+
+fn status_field<'r>(input: &'r str) -> IResult<&'r str, StatusField> {
+    alt((
+        map(
+            preceded(tag("MPos:"), coordinates),
+            |position| StatusField::MachinePosition(position),
+        ),
+        map(
+            preceded(tag("WPos:"), coordinates),
+            |position| StatusField::WorkPosition(position),
+        ),
+        map(
+            preceded(tag("WCO:"), coordinates),
+            |offset| StatusField::WorkCoordinateOffset(offset),
+        ),
+        map(
+            preceded(tag("FS:"), separated_pair(float, char(','), float)),
+            |(feed_rate, spindle_speed)| {
+                StatusField::FeedRateAndSpindleSpeed(feed_rate, spindle_speed)
+            },
+        ),
+        map(
+            preceded(tag("F:"), float),
+            |feed_rate| StatusField::FeedRate(feed_rate),
+        ),
+        map(
+            preceded(tag("Bf:"), separated_pair(integer, char(','), integer)),
+            |(planner_blocks, rx_bytes)| StatusField::Buffer(planner_blocks, rx_bytes),
+        ),
+        map(
+            preceded(tag("A:"), alpha1),
+            |accessories: &str| StatusField::Accessories(accessories.to_string()),
+        ),
+        // Ignore the fields that are not used by the driver (eg. pins and overrides)
+        value(StatusField::Other, is_not("|>")),
+    ))(input)
+}
+
+fn status_report<'r>(input: &'r str) -> IResult<&'r str, StatusReport> {
+    map(
+        delimited(
+            char('<'),
+            pair(
+                machine_state,
+                many0(preceded(char('|'), status_field)),
+            ),
+            char('>'),
+        ),
+        |(state, fields)| {
+            let mut report = StatusReport {
+                state,
+                machine_position: None,
+                work_position: None,
+                work_coordinate_offset: None,
+                feed_rate: None,
+                spindle_speed: None,
+                buffer: None,
+                accessories: None,
+            };
+
+            for field in fields {
+                match field {
+                    StatusField::MachinePosition(p) => report.machine_position = Some(p),
+                    StatusField::WorkPosition(p) => report.work_position = Some(p),
+                    StatusField::WorkCoordinateOffset(p) => {
+                        report.work_coordinate_offset = Some(p)
+                    }
+                    StatusField::FeedRate(feed_rate) => report.feed_rate = Some(feed_rate),
+                    StatusField::FeedRateAndSpindleSpeed(feed_rate, spindle_speed) => {
+                        report.feed_rate = Some(feed_rate);
+                        report.spindle_speed = Some(spindle_speed);
+                    }
+                    StatusField::Buffer(planner_blocks, rx_bytes) => {
+                        report.buffer = Some((planner_blocks, rx_bytes))
+                    }
+                    StatusField::Accessories(a) => report.accessories = Some(a),
+                    StatusField::Other => (),
+                }
+            }
+
+            report
+        },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_oks_errors_and_alarms() {
+        assert_eq!(parse_response("ok\r"), Response::Ok);
+        assert_eq!(parse_response("error:20"), Response::Error(20));
+        assert_eq!(parse_response("ALARM:1"), Response::Alarm(1));
+        assert_eq!(parse_response("okay"), Response::Unknown);
+    }
+
+    #[test]
+    fn parses_the_greeting_and_settings() {
+        assert_eq!(
+            parse_response("Grbl 1.1h ['$' for help]"),
+            Response::Greeting("1.1h".to_string()),
+        );
+        assert_eq!(
+            parse_response("$30=1000.000"),
+            Response::Setting { id: 30, value: "1000.000".to_string() },
+        );
+        assert_eq!(
+            parse_response("[MSG:'$H'|'$X' to unlock]"),
+            Response::Message("MSG:'$H'|'$X' to unlock".to_string()),
+        );
+    }
+
+    #[test]
+    fn parses_status_reports() {
+        let response = parse_response(
+            "<Run|MPos:10.000,-2.500,0.000|Bf:15,128|FS:500,12000|Ov:100,100,100|A:SF>"
+        );
+
+        assert_eq!(response, Response::Status(StatusReport {
+            state: MachineState::Run,
+            machine_position: Some(vec![10.0, -2.5, 0.0]),
+            work_position: None,
+            work_coordinate_offset: None,
+            feed_rate: Some(500.0),
+            spindle_speed: Some(12000.0),
+            buffer: Some((15, 128)),
+            accessories: Some("SF".to_string()),
+        }));
+    }
+
+    #[test]
+    fn parses_hold_states_and_work_coordinates() {
+        let response = parse_response("<Hold:1|WPos:1.000,2.000,3.000|F:0|WCO:0.000,0.000,-5.000>");
+
+        if let Response::Status(report) = response {
+            assert_eq!(report.state, MachineState::Hold(1));
+            assert_eq!(report.work_position, Some(vec![1.0, 2.0, 3.0]));
+            assert_eq!(report.work_coordinate_offset, Some(vec![0.0, 0.0, -5.0]));
+            assert_eq!(report.feed_rate, Some(0.0));
+            assert_eq!(report.spindle_speed, None);
+        } else {
+            panic!("Expected a status report, got: {:?}", response);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::vec_deque::VecDeque;
+use teg_protobufs::MachineFlags;
+use teg_machine::{
+    config::MachineConfig,
+    components::Controller,
+};
+
+use crate::protos::machine_message::{
+    self,
+    TaskStatus,
+};
+use crate::response::StatusReport;
+use crate::state_machine;
+use super::Task;
+
+/// The order of the axes in GRBL's status report positions
+const AXIS_ADDRESSES: [&str; 6] = ["x", "y", "z", "a", "b", "c"];
+
+const MM_PER_INCH: f32 = 25.4;
+
+#[derive(Clone, Debug)]
+pub struct Context {
+    pub baud_rate: u32,
+    pub machine_flags: MachineFlags,
+
+    pub config: MachineConfig,
+    pub controller: Controller,
+
+    pub reset_when_idle: bool,
+
+    pub feedback: machine_message::Feedback,
+    gcode_history_buffer: VecDeque<machine_message::GCodeHistoryEntry>,
+
+    /// GRBL only includes the work coordinate offset in some of its status reports so the most
+    /// recently reported offset is used to calculate the work position.
+    work_coordinate_offset: Vec<f32>,
+    /// The maximum spindle speed in RPM ($30). Used to convert the spindle speed to a percentage.
+    max_spindle_speed: f32,
+    /// True if GRBL reports positions in inches ($13)
+    report_inches: bool,
+
+    /// The number of reconnection attempts to a network serial port since it was last connected
+    pub reconnect_attempts: u32,
+}
+
+impl Context {
+    pub fn new(config: MachineConfig) -> Self {
+        let status = machine_message::Status::Disconnected as i32;
+        let controller = config.get_controller().clone();
+        let feedback = Self::reset_feedback(status, &config);
+        let gcode_history_buffer = VecDeque::with_capacity(
+            controller.model.gcode_history_buffer_size
+        );
+
+        Self {
+            baud_rate: 115_200,
+            machine_flags: MachineFlags::default(),
+            reset_when_idle: false,
+            feedback,
+            config,
+            controller,
+            gcode_history_buffer,
+            work_coordinate_offset: vec![],
+            // GRBL's default $30 setting
+            max_spindle_speed: 1000.0,
+            report_inches: false,
+            reconnect_attempts: 0,
+        }
+    }
+
+    fn reset_feedback(status: i32, config: &MachineConfig) -> machine_message::Feedback {
+        machine_message::Feedback {
+            status,
+            axes: config.feedrates().into_iter().map(|f| {
+                machine_message::Axis {
+                    address: f.address,
+                    ..machine_message::Axis::default()
+                }
+            }).collect(),
+            speed_controllers: config.speed_controllers.iter().map(|sc| {
+                machine_message::SpeedController {
+                    address: sc.model.address.clone(),
+                    ..machine_message::SpeedController::default()
+                }
+            }).collect(),
+
+            ..machine_message::Feedback::default()
+        }
+    }
+
+    pub fn add_gcode_history_to_feedback(&mut self) -> () {
+        self.feedback.gcode_history = self.gcode_history_buffer.drain(..).collect();
+    }
+
+    pub fn handle_state_change(&mut self, state: &state_machine::State) {
+        use state_machine::State::*;
+
+        let status = match state {
+            Disconnected => machine_message::Status::Disconnected as i32,
+            Connecting => machine_message::Status::Connecting as i32,
+            Ready( .. ) => machine_message::Status::Ready as i32,
+            Errored { .. } => machine_message::Status::Errored as i32,
+            EStopped => machine_message::Status::Estopped as i32,
+        };
+
+        // reset everything except the new status and then move over the events from the previous struct
+        let next_feedback = Self::reset_feedback(status, &self.config);
+
+        let previous_feedback = std::mem::replace(&mut self.feedback, next_feedback);
+
+        self.feedback.task_progress = previous_feedback.task_progress;
+
+        if let Errored { message } = state  {
+            let error = machine_message::Error {
+                message: message.clone(),
+            };
+
+            self.feedback.error = Some(error);
+        };
+    }
+
+    pub fn delete_task_history(&mut self, task_ids: &Vec<crate::DbId>) {
+        self.feedback.task_progress.retain(|p| {
+            !task_ids.contains(&p.task_id)
+        });
+    }
+
+    pub fn push_start_task(&mut self, task: &Task) {
+        self.push_task_progress(task, TaskStatus::TaskStarted);
+    }
+
+    pub fn push_cancel_task(&mut self, task: &Task) {
+        self.push_task_progress(task, TaskStatus::TaskCancelled);
+    }
+
+    pub fn push_pause_task(&mut self, task: &Task) {
+        self.machine_flags.set(MachineFlags::PAUSED_STATE, true);
+        self.push_task_progress(task, TaskStatus::TaskPaused);
+    }
+
+    pub fn push_finish_task(&mut self, task: &Task) {
+        self.push_task_progress(task, TaskStatus::TaskFinished);
+    }
+
+    pub fn push_error(&mut self, task: &Task) {
+        self.push_task_progress(task, TaskStatus::TaskErrored);
+    }
+
+    fn push_task_progress(
+        &mut self,
+        task: &Task,
+        status: TaskStatus,
+    ) {
+        if task.client_id == "INTERNAL" {
+            return
+        };
+
+        let despooled_line_number = task.despooled_line_number
+            .unwrap_or(0);
+
+        let progress = self.feedback.task_progress
+            .iter_mut()
+            .find(|p| p.task_id == task.id);
+
+        if let Some(mut progress) = progress {
+            progress.despooled_line_number = despooled_line_number;
+            progress.status = status as i32;
+        } else {
+            let new_progress = machine_message::TaskProgress {
+                task_id: task.id.clone(),
+                despooled_line_number,
+                status: status as i32,
+            };
+
+            self.feedback.task_progress.push(new_progress);
+        }
+    }
+
+    /// Records a setting from GRBL's "$$" settings report
+    pub fn set_setting(&mut self, id: u32, value: &str) {
+        match id {
+            13 => self.report_inches = value == "1",
+            30 => {
+                match value.parse::<f32>() {
+                    Ok(max_spindle_speed) if max_spindle_speed > 0.0 => {
+                        self.max_spindle_speed = max_spindle_speed;
+                    }
+                    _ => warn!("Warning: Invalid max spindle speed ($30): {:?}", value),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Updates the axes and spindle from a status report
+    pub fn update_status(&mut self, report: &StatusReport) {
+        if let Some(offset) = &report.work_coordinate_offset {
+            self.work_coordinate_offset = offset.clone();
+        }
+
+        // Positions are displayed in work coordinates
+        let work_position = report.work_position.clone().or_else(|| {
+            report.machine_position.as_ref().map(|machine_position| {
+                machine_position
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| p - self.work_coordinate_offset.get(i).unwrap_or(&0.0))
+                    .collect()
+            })
+        });
+
+        if let Some(work_position) = work_position {
+            let units = if self.report_inches { MM_PER_INCH } else { 1.0 };
+
+            for (address, position) in AXIS_ADDRESSES.iter().zip(work_position) {
+                let axis = self.feedback.axes
+                    .iter_mut()
+                    .find(|axis| axis.address == *address);
+
+                if let Some(axis) = axis {
+                    axis.actual_position = position * units;
+                }
+            }
+        }
+
+        // The spindle is mapped to the machine's first speed controller
+        if let Some(spindle) = self.feedback.speed_controllers.first_mut() {
+            if let Some(spindle_speed) = report.spindle_speed {
+                let speed = 100.0 * spindle_speed / self.max_spindle_speed;
+
+                spindle.target_speed = speed;
+                spindle.actual_speed = speed;
+            }
+
+            spindle.enabled = report.accessories
+                .as_ref()
+                .map(|a| a.contains('S') || a.contains('C'))
+                .unwrap_or(false);
+        }
+    }
+
+    pub fn push_gcode_rx(&mut self, raw_src: String, is_polling: bool) {
+        // Placeholder: Some day we might allow a toggle to display polling gcodes
+        if is_polling {
+            return
+        }
+
+        let direction = machine_message::GCodeHistoryDirection::Rx as i32;
+        self.push_gcode_history_entry(raw_src, direction)
+    }
+
+    pub fn push_gcode_tx(&mut self, raw_src: String) {
+        let direction = machine_message::GCodeHistoryDirection::Tx as i32;
+        self.push_gcode_history_entry(raw_src, direction)
+    }
+
+    fn push_gcode_history_entry(&mut self, content: String, direction: i32) {
+        let entry = machine_message::GCodeHistoryEntry {
+            content,
+            direction,
+        };
+
+        if self.gcode_history_buffer.len() >= self.controller.model.gcode_history_buffer_size {
+            let _ = self.gcode_history_buffer.pop_front();
+        }
+        self.gcode_history_buffer.push_back(entry)
+    }
+}
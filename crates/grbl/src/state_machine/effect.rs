@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use futures::{
+    SinkExt,
+    future::{
+        Abortable,
+        AbortHandle,
+        Aborted,
+        Future,
+    },
+    channel::mpsc,
+};
+
+use teg_protobufs::{MachineFlags, Message};
+use teg_machine::task::GCodeFile;
+use bytes::Bytes;
+
+use super::{
+    Event,
+    Task,
+    GCodeLines,
+};
+
+use crate::{
+    grbl_codec::SerialData,
+    StateMachineReactor,
+};
+
+#[derive(Clone, Debug)]
+pub enum Effect {
+    Delay { key: String, duration: Duration, event: Event },
+    CancelDelay { key: String },
+    CancelAllDelays,
+    SendSerial ( SerialData ),
+    OpenSerialPort { baud_rate: u32 },
+    SendInitProtobuf,
+    SendFeedbackProtobuf,
+    LoadGCode {
+        file_path: String,
+        task_id: crate::DbId,
+        client_id: crate::DbId,
+        machine_override: bool,
+        despooled_line_number: Option<u32>,
+    },
+    CloseSerialPort,
+    ExitProcess,
+    ExitProcessAfterDelay,
+}
+
+impl Effect {
+    pub async fn exec(
+        self,
+        reactor: &mut StateMachineReactor,
+    ) {
+        match self {
+            Effect::Delay { key, event, duration, .. } => {
+                let mut task_tx = mpsc::Sender::clone(&reactor.event_sender);
+                // create a handle for cancelling the delay
+                let (abort_handle, abort_registration) = AbortHandle::new_pair();
+
+                // cancel the previous delay of this key and replace it with a new cancel Sender for this delay
+                reactor.delays.remove(&key).map(|previous| { previous.abort() });
+                reactor.delays.insert(key, abort_handle);
+
+                tokio::spawn(async move {
+                    let abortable_delay = Abortable::new(
+                        tokio::time::sleep(duration),
+                        abort_registration,
+                    );
+
+                    if let Err(Aborted) = abortable_delay.await {
+                        return
+                    }
+
+                    task_tx.send(event)
+                        .await
+                        .expect("Unable to send to machine event channel");
+                });
+            }
+            Effect::CancelAllDelays => {
+                for (_k, abort_handle) in reactor.delays.drain() {
+                    abort_handle.abort()
+                }
+            }
+            Effect::CancelDelay { key } => {
+                if let Some(abort_handle) = reactor.delays.remove(&key) {
+                    abort_handle.abort()
+                }
+            }
+            Effect::OpenSerialPort { baud_rate } => {
+                let result = open_serial_port(reactor, baud_rate).await;
+
+                match result {
+                    Ok(serial_future) => {
+                        tokio::spawn(serial_future);
+                    }
+                    Err(err) => {
+                        warn!("Unable to open serial port: {:?}", err);
+
+                        reactor.event_sender.send(Event::SerialPortDisconnected).await
+                            .expect("failed to send serial connection failure event");
+                    }
+                }
+            }
+            Effect::SendSerial ( data ) => {
+                // Real-time commands (eg. the soft reset sent on estop) may be sent after the
+                // serial port has closed
+                if let Err(err) = reactor.serial_manager.send(data).await {
+                    warn!("{:?}", err);
+                }
+            }
+            Effect::SendInitProtobuf => {
+                use teg_protobufs::{
+                    MachineMessage,
+                    machine_message::{ Payload, Init },
+                };
+
+                // Create a protobuf init message
+                let message = MachineMessage {
+                    payload: Some(Payload::Init (Init {
+                        process_started_at_nanos: crate::PROCESS_STARTED_AT.timestamp_nanos(),
+                    })),
+                };
+
+                let mut buf = Vec::with_capacity(message.encoded_len());
+                message.encode(&mut buf).expect("machine message encoding failed");
+
+                reactor.protobuf_broadcast
+                    .send(Bytes::from(buf))
+                    .await
+                    .expect("machine message send failed");
+            }
+            Effect::SendFeedbackProtobuf => {
+                use teg_protobufs::{
+                    MachineMessage,
+                    machine_message::{ Feedback, Payload },
+                };
+
+                // Update the feedback
+                reactor.context.add_gcode_history_to_feedback();
+
+                // take the feedback from reactor.context
+                let mut feedback = std::mem::replace(
+                    &mut reactor.context.feedback,
+                    Feedback::default(),
+                );
+
+                feedback.machine_flags = reactor.context.machine_flags.bits();
+
+                // Create a protobuf message around the feedback
+                let message = MachineMessage {
+                    payload: Some(Payload::Feedback (
+                        feedback,
+                    )),
+                };
+
+                let mut buf = Vec::with_capacity(message.encoded_len());
+                message.encode(&mut buf).expect("machine message encoding failed");
+
+                // re-store the feedback in reactor.context
+                if let Some(Payload::Feedback(feedback)) = message.payload {
+                    reactor.context.feedback = feedback;
+                } else {
+                    panic!("Feedback protobuf did not contain the expected payload");
+                }
+
+                reactor.protobuf_broadcast
+                    .send(Bytes::from(buf))
+                    .await
+                    .expect("machine message send failed");
+
+                // Reset the PAUSED_STATE flag after the protobuf has been sent
+                reactor.context.machine_flags.set(MachineFlags::PAUSED_STATE, false);
+            }
+            Effect::LoadGCode {
+                file_path,
+                task_id,
+                client_id,
+                machine_override,
+                despooled_line_number,
+            } => {
+                let mut tx = mpsc::Sender::clone(&reactor.event_sender);
+
+                let file_path = reactor.context.config.transform_gcode_file_path(file_path);
+
+                // Open the file after the despooled lines if a job is being resumed
+                let next_line_number = despooled_line_number
+                    .map(|line_number| line_number + 1)
+                    .unwrap_or(0);
+
+                let gcode_file = GCodeFile::open_at_line(&file_path, next_line_number as u64);
+
+                let event = if let Ok(gcode_file) = gcode_file {
+                    Event::GCodeLoaded(
+                        Task {
+                            id: task_id,
+                            client_id,
+                            gcode_lines: GCodeLines::File(gcode_file),
+                            next_line_number,
+                            machine_override,
+                            started: false,
+                            despooled_line_number,
+                        }
+                    )
+                } else {
+                    Event::GCodeLoadFailed { task_id, file_path }
+                };
+
+                tx.send(event)
+                    .await
+                    .expect("machine event channel send failed");
+            }
+            Effect::CloseSerialPort => {
+                reactor.serial_manager.close();
+            }
+            Effect::ExitProcess => {
+                reactor.serial_manager.close();
+                std::process::exit(0);
+            }
+            Effect::ExitProcessAfterDelay => {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+
+                reactor.serial_manager.close();
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+/// Opens the controller's tty or network serial port
+async fn open_serial_port(
+    reactor: &mut StateMachineReactor,
+    baud_rate: u32,
+) -> eyre::Result<impl Future<Output = ()>> {
+    if reactor.context.controller.model.simulate {
+        Err(eyre::eyre!("The GRBL driver does not support simulated controllers"))?;
+    }
+
+    let port = reactor.serial_manager.connect(baud_rate).await?;
+
+    reactor.serial_manager.open(port).await
+}
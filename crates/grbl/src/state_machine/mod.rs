@@ -0,0 +1,469 @@
+use std::time::Duration;
+
+use teg_driver::serial_transport::{
+    reconnect_delay,
+    SerialPortAddress,
+};
+
+use crate::{
+    grbl_codec::{RealTimeCommand, SerialData},
+    protos::{
+        server_message,
+        ServerMessage,
+    },
+    response::Response,
+};
+
+mod ready_state;
+mod context;
+mod effect;
+
+mod task;
+pub use task::{
+    Task,
+    GCodeLines,
+};
+
+pub use context::Context;
+pub use effect::Effect;
+
+use ready_state::ReadyState;
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    Init { serial_port_available: bool },
+    ConnectionTimeout,
+    SerialPortOpened,
+    SerialRec ((String, Response)),
+    ProtobufClientConnection,
+    ProtobufRec ( ServerMessage ),
+    PollFeedback,
+    SerialPortDisconnected,
+    SerialPortError{ message: String },
+    GCodeLoaded(Task),
+    GCodeLoadFailed{ task_id: crate::DbId, file_path: String },
+    /// Reconnect to a network serial port after the connection was lost
+    Reconnect,
+}
+
+#[derive(Clone, Debug)]
+pub enum State {
+    Disconnected,
+    /// Waiting for GRBL's greeting after a soft reset
+    Connecting,
+    Ready ( ReadyState ),
+    Errored { message: String },
+    EStopped,
+}
+
+pub struct Loop {
+    pub next_state: State,
+    pub effects: Vec<Effect>,
+}
+
+impl Loop {
+    fn new(next_state: State, effects: Vec<Effect>) -> Self {
+        Self {
+            next_state,
+            effects,
+        }
+    }
+}
+
+use State::*;
+use Event::*;
+
+impl teg_driver::DriverEvent for Event {
+    type Response = (String, Response);
+
+    fn serial_port_opened() -> Self {
+        SerialPortOpened
+    }
+
+    fn serial_received(response: Self::Response) -> Self {
+        SerialRec(response)
+    }
+
+    fn serial_port_disconnected() -> Self {
+        SerialPortDisconnected
+    }
+
+    fn serial_port_error(message: String) -> Self {
+        SerialPortError { message }
+    }
+
+    fn protobuf_client_connected() -> Self {
+        ProtobufClientConnection
+    }
+
+    fn protobuf_received(message: ServerMessage) -> Self {
+        ProtobufRec(message)
+    }
+}
+
+pub fn cancel_all_tasks(state: &mut State, context: &mut Context) {
+    if let Ready( ready_state ) = state {
+        ready_state.tasks
+            .iter()
+            .for_each(|task| context.push_cancel_task(&task));
+
+        ready_state.tasks.truncate(0);
+    };
+}
+
+fn push_task_errors(state: &State, context: &mut Context) {
+    if let Ready( ReadyState { tasks, .. }) = state {
+        tasks
+            .iter()
+            .for_each(|task| {
+                context.push_error(&task);
+            });
+    };
+}
+
+fn errored(message: String, state: &State, context: &mut Context) -> Loop {
+    error!("Error State: {:?}", message);
+
+    push_task_errors(state, context);
+
+    let next_state = Errored { message };
+    context.handle_state_change(&next_state);
+
+    let effects = vec![
+        Effect::CancelAllDelays,
+        Effect::SendFeedbackProtobuf,
+    ];
+
+    Loop::new(next_state, effects)
+}
+
+pub fn disconnect(state: &State, context: &mut Context) -> Loop {
+    info!("Disconnected");
+
+    push_task_errors(state, context);
+
+    let mut effects = vec![
+        Effect::CancelAllDelays,
+        Effect::CloseSerialPort,
+        Effect::SendFeedbackProtobuf,
+    ];
+
+    // Network serial ports are not found by device discovery so they are reconnected with an
+    // exponential backoff
+    if SerialPortAddress::parse(context.config.tty_path()).is_network() {
+        let delay = reconnect_delay(context.reconnect_attempts);
+
+        info!("Reconnecting to network serial port in {:?}", delay);
+        context.reconnect_attempts += 1;
+
+        effects.push(Effect::Delay {
+            key: "reconnect".to_string(),
+            duration: delay,
+            event: Reconnect,
+        });
+    }
+
+    context.handle_state_change(&Disconnected);
+
+    Loop::new(
+        Disconnected,
+        effects,
+    )
+}
+
+impl State {
+    fn and_no_effects(self) -> Loop {
+        Loop {
+            next_state: self,
+            effects: vec![],
+        }
+    }
+
+    fn invalid_transition_warning(self, event: &Event) -> Loop {
+        warn!("Warning: received invalid event: {:?} in state: {:?}", event, self);
+
+        self.and_no_effects()
+    }
+
+    fn invalid_transition_error(self, event: &Event, context: &mut Context) -> Loop {
+        let message = format!("Invalid transition. State: {:?} Event: {:?}", self, event);
+
+        errored(message, &self, context)
+    }
+
+    pub fn consume(mut self, event: Event, context: &mut Context) -> Loop {
+        if let ProtobufClientConnection = &event {
+            return Loop::new(
+                self,
+                vec![Effect::SendInitProtobuf, Effect::SendFeedbackProtobuf],
+            )
+        }
+
+        if let GCodeLoadFailed { file_path, ..} = &event {
+            let message = format!("Failed to load GCode: {:}", file_path);
+            return errored(message, &self, context)
+        }
+
+        if let ProtobufRec( ServerMessage { payload } ) = &event {
+            use server_message::*;
+
+            match payload {
+                Some(Payload::DeviceDiscovered(_)) => {
+                    info!("Device Discovered");
+                    // Due to the async nature of discovery the new port could be discovered before disconnecting from the old one.
+                    return if let Disconnected = self {
+                        self.connect(context)
+                    } else {
+                        self.and_no_effects()
+                    }
+                }
+                Some(Payload::DeviceDisconnected(_)) => {
+                    return if let Disconnected = self {
+                        self.and_no_effects()
+                    } else {
+                        disconnect(&self, context)
+                    }
+                }
+                Some(Payload::DeleteTaskHistory( DeleteTaskHistory { task_ids })) => {
+                    context.delete_task_history(task_ids);
+                    return self.and_no_effects()
+                }
+                Some(Payload::Estop(_)) => {
+                    info!("ESTOP");
+
+                    cancel_all_tasks(&mut self, context);
+
+                    context.handle_state_change(&State::EStopped);
+
+                    // A soft reset stops the machine immediately and clears GRBL's buffers
+                    return Loop::new(
+                        State::EStopped,
+                        vec![
+                            Effect::SendSerial(SerialData::RealTime(RealTimeCommand::SoftReset)),
+                            Effect::CancelAllDelays,
+                            Effect::SendFeedbackProtobuf,
+                        ],
+                    )
+                }
+                Some(Payload::Reset(_)) => {
+                    info!("RESET: restarting service");
+
+                    return Loop::new(
+                        self,
+                        vec![Effect::ExitProcess],
+                    )
+                }
+                Some(Payload::ResetWhenIdle(_)) => {
+                    let busy = if let Ready ( ready ) = &self {
+                        ready.tasks.len() > 0
+                    } else  {
+                        false
+                    };
+
+                    return if busy {
+                        context.reset_when_idle = true;
+
+                        self.and_no_effects()
+                    } else {
+                        info!("RESET: restarting service");
+
+                        Loop::new(
+                            self,
+                            vec![Effect::ExitProcess],
+                        )
+                    }
+                }
+                _ => ()
+            }
+        };
+
+        match &event {
+            SerialPortDisconnected => {
+                return disconnect(&self, context)
+            }
+            SerialPortError { message } => {
+                error!("Disconnected due to serial port error: {:?}", message);
+                return errored(message.to_string(), &self, context)
+            }
+            _ => (),
+        }
+
+        if let Ready ( ready ) = self {
+            ready.consume(event, context)
+        } else {
+            match event {
+                Init { serial_port_available } => {
+                    if serial_port_available {
+                        info!("Teg GRBL: Started (Serial port found)");
+                        self.connect(context)
+                    } else {
+                        info!("Teg GRBL: Started (No device found)");
+                        self.and_no_effects()
+                    }
+                }
+                SerialPortOpened => {
+                    if let Connecting = self {
+                        // Reset GRBL so that it sends a greeting once it is ready
+                        Loop::new(
+                            self,
+                            vec![Effect::SendSerial(
+                                SerialData::RealTime(RealTimeCommand::SoftReset),
+                            )],
+                        )
+                    } else {
+                        self.invalid_transition_warning(&event)
+                    }
+                }
+                SerialRec((src, response)) => {
+                    context.push_gcode_rx(src.clone(), false);
+
+                    match (self, response) {
+                        (Connecting, Response::Greeting(version)) => {
+                            Self::receive_greeting(version, context)
+                        }
+                        (Connecting, response @ Response::Error(_)) => {
+                            let event = SerialRec(( src, response ));
+                            Connecting.invalid_transition_error(&event, context)
+                        }
+                        /* No ops */
+                        (state, _) => state.and_no_effects()
+                    }
+                }
+                ConnectionTimeout => {
+                    if let Connecting = self {
+                        let message = "Timed out waiting for GRBL's greeting".to_string();
+                        errored(message, &self, context)
+                    } else {
+                        self.invalid_transition_error(&event, context)
+                    }
+                }
+                Reconnect => {
+                    if let Disconnected = self {
+                        self.connect(context)
+                    } else {
+                        self.invalid_transition_warning(&event)
+                    }
+                }
+                /* Warnings */
+                PollFeedback |
+                GCodeLoaded(..) |
+                GCodeLoadFailed{..} |
+                SerialPortDisconnected |
+                SerialPortError {..} |
+                ProtobufRec(_) |
+                ProtobufClientConnection => {
+                    self.invalid_transition_warning(&event)
+                }
+            }
+        }
+    }
+
+    fn connect(self, context: &mut Context) -> Loop {
+        let baud_rate = context.controller.model.baud_rate.to_u32();
+        let connection_timeout_ms = context.controller.model.serial_connection_timeout;
+
+        info!("Connecting to GRBL with baud rate: {:?}", baud_rate);
+        context.baud_rate = baud_rate;
+
+        let next_state = Connecting;
+        context.handle_state_change(&next_state);
+
+        Loop::new(
+            next_state,
+            vec![
+                Effect::CancelAllDelays,
+                Effect::OpenSerialPort { baud_rate },
+                Effect::Delay {
+                    key: "connection_timeout".to_string(),
+                    duration: Duration::from_millis(connection_timeout_ms),
+                    event: ConnectionTimeout,
+                },
+                Effect::SendFeedbackProtobuf,
+            ],
+        )
+    }
+
+    fn receive_greeting(version: String, context: &mut Context) -> Loop {
+        info!("Greeting Received: {}", version);
+
+        context.reconnect_attempts = 0;
+
+        let mut effects = vec![
+            Effect::CancelAllDelays,
+        ];
+
+        let next_state = Ready( ReadyState::default() );
+        context.handle_state_change(&next_state);
+
+        // Start polling for status reports
+        let mut next_loop = next_state.consume(PollFeedback, context);
+
+        effects.append(&mut next_loop.effects);
+        next_loop.effects = effects;
+
+        next_loop
+    }
+}
+
+#[cfg(test)]
+fn test_context() -> Context {
+    let config: crate::MachineConfig = toml::from_str(
+        include_str!("../../../../machine.default.toml"),
+    )
+        .expect("Invalid default machine config");
+
+    Context::new(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connects_after_the_greeting() {
+        let mut context = test_context();
+
+        let Loop { next_state, effects } = Connecting.consume(
+            SerialRec((
+                "Grbl 1.1h ['$' for help]".to_string(),
+                Response::Greeting("1.1h".to_string()),
+            )),
+            &mut context,
+        );
+
+        let ready = if let Ready(ready) = next_state {
+            ready
+        } else {
+            panic!("Expected Ready, got: {:?}", next_state)
+        };
+
+        // The settings are requested and the status report polling is started
+        assert_eq!(ready.tasks.len(), 0);
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::SendSerial(SerialData::Line(line)) if line == "$$",
+        )));
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::Delay { key, .. } if key == "polling_delay",
+        )));
+    }
+
+    #[test]
+    fn reconnects_to_network_serial_ports() {
+        let mut context = test_context();
+        context.config.get_controller_mut().model.serial_port_id = "tcp://127.0.0.1:2000".to_string();
+
+        let Loop { next_state, effects } = disconnect(&Connecting, &mut context);
+
+        assert!(matches!(next_state, Disconnected));
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::Delay { event: Reconnect, .. },
+        )));
+
+        let Loop { next_state, .. } = next_state.consume(Reconnect, &mut context);
+
+        assert!(matches!(next_state, Connecting));
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::*;
+use crate::{
+    codes::{alarm_description, error_description},
+    grbl_codec::{RealTimeCommand, SerialData},
+    protos::{
+        ServerMessage,
+        server_message,
+    },
+    response::Response,
+};
+
+/// The size of GRBL's serial receive buffer in bytes
+const RX_BUFFER_SIZE: usize = 128;
+
+#[derive(serde::Deserialize, Debug)]
+enum HostGCode {
+    /// Decelerates to a stop without losing position. Lines sent afterwards are held in GRBL's
+    /// buffer until the cycle is started.
+    #[serde(rename = "feedHold")]
+    FeedHold {},
+    #[serde(rename = "cycleStart")]
+    CycleStart {},
+}
+
+#[derive(Clone, Debug)]
+pub struct ReadyState {
+    /// Lines sent to GRBL that have not yet been acknowledged, oldest first.
+    ///
+    /// Lines are streamed by character counting: new lines are sent as long as the unacknowledged
+    /// lines fit in GRBL's serial buffer. This keeps GRBL's planner full without waiting for an ok
+    /// after each line.
+    sent_lines: VecDeque<String>,
+    /// The next line of the current task and its line number. Held until there is room for it in
+    /// GRBL's serial buffer.
+    next_line: Option<(u32, String)>,
+    loading_gcode: bool,
+    pub tasks: VecDeque<Task>,
+}
+
+impl Default for ReadyState {
+    fn default() -> Self {
+        let mut tasks = VecDeque::new();
+        // Read the settings needed to interpret the status reports (eg. the max spindle speed)
+        tasks.push_front(Task {
+            id: "GRBL_SETTINGS".into(),
+            client_id: "INTERNAL".into(),
+            gcode_lines: vec!["$$".to_string()].into(),
+            next_line_number: 0,
+            despooled_line_number: None,
+            machine_override: true,
+            started: false,
+        });
+
+        Self {
+            sent_lines: VecDeque::new(),
+            next_line: None,
+            loading_gcode: false,
+            tasks,
+        }
+    }
+}
+
+impl ReadyState {
+    fn and_no_effects(self) -> Loop {
+        Ready( self ).and_no_effects()
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.sent_lines
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum()
+    }
+
+    pub fn consume(mut self, event: Event, context: &mut Context) -> Loop {
+        match event {
+            ProtobufRec( msg@ServerMessage { payload: None } ) => {
+                warn!("Warning: ServerMessage received without a payload. Ignoring: {:?}", msg);
+                self.and_no_effects()
+            }
+            ProtobufRec( ServerMessage { payload: Some(message) } ) => {
+                match message {
+                    server_message::Payload::SpoolTask(spool_task) => {
+                        use server_message::{
+                            spool_task::Content,
+                            InlineContent,
+                        };
+
+                        self.loading_gcode = true;
+
+                        let server_message::SpoolTask {
+                            task_id,
+                            client_id,
+                            start_at_line_number,
+                            content,
+                            machine_override,
+                            line_actions,
+                            skipped_lines,
+                        } = spool_task;
+
+                        if !line_actions.is_empty() {
+                            warn!("Warning: Scheduled line actions are not supported by GRBL. Ignoring.");
+                        }
+
+                        if !skipped_lines.is_empty() {
+                            warn!("Warning: Skipped lines are not supported by GRBL. Ignoring.");
+                        }
+
+                        let despooled_line_number = if start_at_line_number == 0 {
+                            None
+                        } else {
+                            Some((start_at_line_number - 1) as u32)
+                        };
+
+                        match content {
+                            Some(Content::Inline ( InlineContent { commands })) => {
+                                let task = Task {
+                                    id: task_id,
+                                    client_id,
+                                    gcode_lines: commands.into(),
+                                    next_line_number: 0,
+                                    machine_override,
+                                    started: false,
+                                    despooled_line_number,
+                                };
+                                self.consume(GCodeLoaded(task), context)
+                            }
+                            Some(Content::FilePath( file_path )) => {
+                                let effects = vec![
+                                    Effect::LoadGCode {
+                                        file_path: file_path.clone(),
+                                        task_id,
+                                        client_id,
+                                        machine_override,
+                                        despooled_line_number,
+                                    },
+                                ];
+                                Loop::new(Ready(self), effects)
+                            }
+                            None => {
+                                warn!("Warning: spool_task received without content. Ignoring.");
+                                self.and_no_effects()
+                            }
+                        }
+                    }
+                    server_message::Payload::PauseTask(server_message::PauseTask { task_id }) => {
+                        let index = self.tasks
+                            .iter()
+                            .position(|task| task.id == task_id);
+
+                        // The held back line has not been sent so it is discarded with its task
+                        if index == Some(0) {
+                            self.next_line = None;
+                        }
+
+                        match index.and_then(|index| self.tasks.remove(index)) {
+                            Some(task) => {
+                                context.push_pause_task(&task);
+
+                                let mut effects = vec![Effect::SendFeedbackProtobuf];
+
+                                if context.reset_when_idle && self.tasks.is_empty() {
+                                    effects.push(Effect::ExitProcessAfterDelay);
+                                }
+
+                                Loop::new(Ready(self), effects)
+                            }
+                            None => {
+                                self.and_no_effects()
+                            }
+                        }
+                    }
+                    | server_message::Payload::ListSdCardFiles(_)
+                    | server_message::Payload::DeleteSdCardFile(_)
+                    | server_message::Payload::StartSdCardPrint(_)
+                    | server_message::Payload::PauseSdCardPrint(_)
+                    | server_message::Payload::RespondToPrompt(_)
+                    | server_message::Payload::SetLineActions(_)
+                    | server_message::Payload::SetSkippedLines(_) => {
+                        warn!("Warning: {:?} is not supported by GRBL. Ignoring.", message);
+                        self.and_no_effects()
+                    }
+                    _ => {
+                        self.and_no_effects()
+                    }
+                }
+            }
+            GCodeLoaded ( mut task ) => {
+                self.loading_gcode = false;
+
+                // Skip through the despooled lines if a job is being resumed
+                if let Err(err) = task.skip_despooled_lines() {
+                    return errored(err.to_string(), &Ready(self), context)
+                }
+
+                if task.machine_override {
+                    // The held back line belongs to the current task so override tasks are
+                    // inserted after it
+                    let first_non_override = self.tasks.iter()
+                        .enumerate()
+                        .position(|(i, t)| {
+                            !t.machine_override && !(i == 0 && self.next_line.is_some())
+                        })
+                        .unwrap_or(self.tasks.len());
+                    // insert the overide task before the first non-override task
+                    self.tasks.insert(first_non_override, task)
+                } else {
+                    // append non-override tasks to the end of the queue
+                    self.tasks.push_back(task)
+                };
+
+                self.despool_and_loop(vec![], context)
+            }
+            SerialRec((src, response)) => {
+                let is_polling = matches!(response, Response::Status(_));
+                context.push_gcode_rx(src, is_polling);
+
+                self.receive_response(response, context)
+            }
+            PollFeedback => {
+                let effects = vec![
+                    Effect::SendSerial(SerialData::RealTime(RealTimeCommand::StatusReportQuery)),
+                    Effect::SendFeedbackProtobuf,
+                    Effect::Delay {
+                        key: "polling_delay".to_string(),
+                        duration: Duration::from_millis(context.controller.model.polling_interval),
+                        event: PollFeedback,
+                    },
+                ];
+
+                self.despool_and_loop(effects, context)
+            }
+            _ => {
+                Ready(self).invalid_transition_warning(&event)
+            }
+        }
+    }
+
+    fn receive_response(mut self, response: Response, context: &mut Context) -> Loop {
+        match response {
+            Response::Ok => {
+                if self.sent_lines.pop_front().is_none() {
+                    warn!("Warning: Received an ok without any lines awaiting acknowledgement");
+                }
+
+                self.despool_and_loop(vec![], context)
+            }
+            Response::Error(code) => {
+                let line = self.sent_lines.pop_front().unwrap_or_default();
+
+                let message = format!(
+                    "GRBL error:{} in {:?}: {}",
+                    code,
+                    line,
+                    error_description(code),
+                );
+
+                errored(message, &Ready(self), context)
+            }
+            Response::Alarm(code) => {
+                let message = format!("GRBL ALARM:{}: {}", code, alarm_description(code));
+
+                errored(message, &Ready(self), context)
+            }
+            Response::Status(report) => {
+                context.update_status(&report);
+                self.and_no_effects()
+            }
+            Response::Setting { id, value } => {
+                context.set_setting(id, &value);
+                self.and_no_effects()
+            }
+            Response::Greeting(_) => {
+                let message = format!(
+                    "Unexpected GRBL restart. State: {:?}",
+                    self,
+                );
+
+                errored(message, &Ready(self), context)
+            }
+            Response::Message(message) => {
+                info!("GRBL: {}", message);
+                self.and_no_effects()
+            }
+            Response::Unknown => {
+                self.and_no_effects()
+            }
+        }
+    }
+
+    fn despool_and_loop(mut self, mut effects: Vec<Effect>, context: &mut Context) -> Loop {
+        if let Err(err) = self.despool(&mut effects, context) {
+            return errored(err.to_string(), &Ready(self), context)
+        }
+
+        Loop::new(Ready(self), effects)
+    }
+
+    fn despool(&mut self, effects: &mut Vec<Effect>, context: &mut Context) -> eyre::Result<()> {
+        loop {
+            if self.next_line.is_none() {
+                self.next_line = self.next_task_line(effects, context)?;
+            }
+
+            let (line_number, gcode) = if let Some(next_line) = self.next_line.take() {
+                next_line
+            } else {
+                break
+            };
+
+            if gcode.len() + 1 > RX_BUFFER_SIZE {
+                Err(eyre::eyre!("GCode line is too long for GRBL: {:?}", gcode))?;
+            }
+
+            // Wait for GRBL to acknowledge enough lines to make room for the next one
+            if self.buffered_bytes() + gcode.len() + 1 > RX_BUFFER_SIZE {
+                self.next_line = Some((line_number, gcode));
+                break
+            }
+
+            if let Some(task) = self.tasks.front_mut() {
+                task.despooled_line_number = Some(line_number);
+                context.push_start_task(&task);
+            }
+
+            context.push_gcode_tx(gcode.clone());
+            effects.push(Effect::SendSerial(SerialData::Line(gcode.clone())));
+
+            self.sent_lines.push_back(gcode);
+        }
+
+        if
+            context.reset_when_idle
+            && self.sent_lines.is_empty()
+            && self.tasks.is_empty()
+            && !self.loading_gcode
+        {
+            effects.push(Effect::ExitProcessAfterDelay)
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next line of the current task. Completed tasks are removed and driver macros
+    /// are executed along the way.
+    fn next_task_line(
+        &mut self,
+        effects: &mut Vec<Effect>,
+        context: &mut Context,
+    ) -> eyre::Result<Option<(u32, String)>> {
+        while let Some(task) = self.tasks.front_mut() {
+            if !task.started {
+                trace!("Despool: Starting Task #{}", task.id);
+                task.started = true;
+
+                effects.push(Effect::SendFeedbackProtobuf);
+            };
+
+            match task.next_gcode()? {
+                Some((line_number, gcode)) if gcode.starts_with('!') => {
+                    task.despooled_line_number = Some(line_number);
+
+                    Self::execute_host_gcode(effects, &gcode)?;
+                }
+                Some(line) => {
+                    return Ok(Some(line))
+                }
+                None => {
+                    trace!("Despool: Completed Task #{}", task.id);
+
+                    // record a task completion event
+                    context.push_finish_task(&task);
+
+                    effects.push(Effect::SendFeedbackProtobuf);
+
+                    let _ = self.tasks.pop_front();
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Runs a driver macro. Real-time commands act immediately so they take effect before the
+    /// lines already in GRBL's buffer have been executed.
+    fn execute_host_gcode(effects: &mut Vec<Effect>, gcode: &str) -> eyre::Result<()> {
+        let gcode: HostGCode = serde_json::from_str(&gcode[1..])?;
+
+        debug!("Macro: {:?}", gcode);
+
+        let command = match gcode {
+            HostGCode::FeedHold {} => RealTimeCommand::FeedHold,
+            HostGCode::CycleStart {} => RealTimeCommand::CycleStart,
+        };
+
+        effects.push(Effect::SendSerial(SerialData::RealTime(command)));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready_state_with_task(gcodes: Vec<String>) -> ReadyState {
+        let mut ready = ReadyState::default();
+
+        ready.tasks = vec![Task {
+            id: "TASK".into(),
+            client_id: "INTERNAL".into(),
+            gcode_lines: gcodes.into(),
+            next_line_number: 0,
+            despooled_line_number: None,
+            machine_override: false,
+            started: false,
+        }].into();
+
+        ready
+    }
+
+    fn sent_lines(effects: &Vec<Effect>) -> Vec<String> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::SendSerial(SerialData::Line(line)) => Some(line.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streams_lines_until_the_serial_buffer_is_full() {
+        // 10 lines of 20 bytes each (including the newline)
+        let gcode = "G1 X100.000 Y100.00".to_string();
+        let mut ready = ready_state_with_task(vec![gcode.clone(); 10]);
+        let mut context = test_context();
+
+        let mut effects = vec![];
+        ready.despool(&mut effects, &mut context).unwrap();
+
+        // Only 6 lines (120 bytes) fit in GRBL's 128 byte buffer
+        assert_eq!(sent_lines(&effects).len(), 6);
+        assert_eq!(ready.buffered_bytes(), 120);
+        assert_eq!(ready.next_line, Some((6, gcode)));
+
+        // Each ok makes room for another line
+        let Loop { next_state, effects } = ready.receive_response(Response::Ok, &mut context);
+
+        assert_eq!(sent_lines(&effects).len(), 1);
+
+        if let Ready(ready) = next_state {
+            assert_eq!(ready.buffered_bytes(), 120);
+            assert_eq!(ready.tasks[0].despooled_line_number, Some(6));
+        } else {
+            panic!("Expected Ready, got: {:?}", next_state);
+        }
+    }
+
+    #[test]
+    fn strips_comments_and_runs_real_time_macros() {
+        let mut ready = ready_state_with_task(vec![
+            "(Contour 1)".to_string(),
+            "G0 Z5 ; Retract".to_string(),
+            "!{\"feedHold\":{}}".to_string(),
+        ]);
+        let mut context = test_context();
+
+        let mut effects = vec![];
+        ready.despool(&mut effects, &mut context).unwrap();
+
+        assert_eq!(sent_lines(&effects), vec!["G0 Z5".to_string()]);
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::SendSerial(SerialData::RealTime(RealTimeCommand::FeedHold)),
+        )));
+        assert!(ready.tasks.is_empty());
+    }
+}
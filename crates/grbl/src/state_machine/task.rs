@@ -0,0 +1,94 @@
+use std::io;
+use teg_machine::task::GCodeFile;
+
+/// The lines of a task's GCode. Task files are read from disk as they are despooled so that
+/// the driver's memory use does not grow with the size of the job.
+#[derive(Clone, Debug)]
+pub enum GCodeLines {
+    Inline(std::vec::IntoIter<String>),
+    File(GCodeFile),
+}
+
+impl From<Vec<String>> for GCodeLines {
+    fn from(gcodes: Vec<String>) -> Self {
+        GCodeLines::Inline(gcodes.into_iter())
+    }
+}
+
+impl Iterator for GCodeLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            GCodeLines::Inline(gcodes) => gcodes.next().map(Ok),
+            GCodeLines::File(gcode_file) => gcode_file.next(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Task
+{
+    pub id: crate::DbId,
+    pub client_id: crate::DbId,
+    pub gcode_lines: GCodeLines,
+    /// The file line number of the next line in gcode_lines
+    pub next_line_number: u32,
+    pub despooled_line_number: Option<u32>,
+    pub machine_override: bool,
+    pub started: bool,
+}
+
+impl Task {
+    /// Skips through the lines that were despooled before the job was paused. Task files are
+    /// opened after the despooled lines so this only skips inline GCodes.
+    pub fn skip_despooled_lines(&mut self) -> io::Result<()> {
+        if let Some(despooled_line_number) = self.despooled_line_number {
+            while self.next_line_number <= despooled_line_number {
+                if self.gcode_lines.next().transpose()?.is_none() {
+                    break
+                }
+                self.next_line_number += 1;
+            }
+            self.next_line_number = despooled_line_number + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Get the next gcode and its file line number skipping any empty lines or comments. Every
+    /// byte sent takes up space in GRBL's serial buffer so comments and whitespace are removed.
+    pub fn next_gcode(&mut self) -> io::Result<Option<(u32, String)>> {
+        while let Some(gcode) = self.gcode_lines.next().transpose()? {
+            let line_number = self.next_line_number;
+            self.next_line_number += 1;
+
+            // return driver macros
+            if gcode.starts_with('!') {
+                return Ok(Some((line_number, gcode)));
+            };
+
+            let gcode = gcode
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim();
+
+            // Skip empty lines, comments and program start and end markers
+            if gcode.is_empty() || gcode == "%" || is_comment(gcode) {
+                continue
+            }
+
+            return Ok(Some((line_number, gcode.to_string())))
+        }
+
+        Ok(None)
+    }
+}
+
+/// True if the whole line is a parenthesized comment (eg. "(Contour 1)")
+fn is_comment(gcode: &str) -> bool {
+    gcode.starts_with('(')
+        && gcode.ends_with(')')
+        && gcode.matches('(').count() == 1
+}
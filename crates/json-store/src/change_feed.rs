@@ -0,0 +1,226 @@
+use std::sync::{Arc, Mutex};
+use std::future::Future;
+use std::time::Duration;
+use async_std::channel;
+use futures::stream::{
+    self,
+    Stream,
+    StreamExt,
+};
+use serde::Deserialize;
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::Record;
+
+/// The Postgres NOTIFY channel that the record change triggers publish to.
+///
+/// See the `notify_record_change` trigger in the server migrations.
+pub const RECORD_CHANGES_CHANNEL: &str = "record_changes";
+
+/// The number of changes queued for each subscriber before further changes are dropped
+const SUBSCRIBER_BUFFER_SIZE: usize = 256;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOperation {
+    #[serde(rename = "INSERT")]
+    Insert,
+    #[serde(rename = "UPDATE")]
+    Update,
+    #[serde(rename = "DELETE")]
+    Delete,
+}
+
+/// A notification that a row was inserted, updated or deleted.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecordChange {
+    pub table: String,
+    pub operation: ChangeOperation,
+    pub id: crate::DbId,
+}
+
+/// A record change with the latest copy of the record. The record is None if it has been hard
+/// deleted.
+#[derive(Debug, Clone)]
+pub struct Change<R: Record> {
+    pub operation: ChangeOperation,
+    pub id: crate::DbId,
+    pub record: Option<R>,
+}
+
+/// Listens for record change notifications from Postgres and fans them out to any number of
+/// subscriber streams.
+#[derive(Clone, Default)]
+pub struct ChangeFeed {
+    subscribers: Arc<Mutex<Vec<channel::Sender<RecordChange>>>>,
+}
+
+impl ChangeFeed {
+    pub async fn start(db: &crate::Db) -> Result<Self> {
+        let mut listener = sqlx::postgres::PgListener::connect_with(db).await?;
+        listener.listen(RECORD_CHANGES_CHANNEL).await?;
+
+        let change_feed = Self::default();
+        let change_feed_clone = change_feed.clone();
+
+        async_std::task::spawn(async move {
+            loop {
+                let result = async {
+                    let notification = listener.recv().await?;
+                    let change: RecordChange = serde_json::from_str(notification.payload())?;
+
+                    Result::<_>::Ok(change)
+                }.await;
+
+                match result {
+                    Ok(change) => change_feed_clone.publish(change),
+                    Err(err) => {
+                        // The listener reconnects on the next call to recv so back off briefly to
+                        // avoid spinning while the database is unavailable.
+                        warn!("Error receiving record change notification: {:?}", err);
+                        async_std::task::sleep(Duration::from_millis(500)).await;
+                    }
+                }
+            }
+        });
+
+        Ok(change_feed)
+    }
+
+    /// Sends the change to every subscriber, dropping any subscribers whose streams have
+    /// been dropped.
+    ///
+    /// Changes are dropped for subscribers that have fallen behind (eg. a stalled websocket) so
+    /// that they do not queue up without limit. Live queries re-run for the changes that are
+    /// already queued so they still catch up to the latest state.
+    pub fn publish(&self, change: RecordChange) {
+        let mut subscribers = match self.subscribers.lock() {
+            Ok(subscribers) => subscribers,
+            Err(err) => {
+                error!("Change feed lock poisoned: {:?}", err);
+                return
+            }
+        };
+
+        subscribers.retain(|sender| {
+            match sender.try_send(change.clone()) {
+                Err(channel::TrySendError::Closed(_)) => false,
+                Err(channel::TrySendError::Full(_)) => {
+                    trace!("Change feed subscriber is full. Dropping change: {:?}", change);
+                    true
+                }
+                Ok(_) => true,
+            }
+        });
+    }
+
+    /// A stream of every record change
+    pub fn subscribe(&self) -> channel::Receiver<RecordChange> {
+        let (sender, receiver) = channel::bounded(SUBSCRIBER_BUFFER_SIZE);
+
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+
+        receiver
+    }
+
+    /// A stream of changes to the given tables
+    pub fn watch_tables(
+        &self,
+        tables: &'static [&'static str],
+    ) -> impl Stream<Item = RecordChange> {
+        self.subscribe()
+            .filter(move |change| {
+                futures::future::ready(tables.contains(&&change.table[..]))
+            })
+    }
+
+    /// A stream of changes to records of type R along with the latest copy of each record.
+    ///
+    /// Subscribers that fall behind miss changes (see `publish`).
+    pub fn watch<R: Record>(
+        &self,
+        db: &crate::Db,
+    ) -> impl Stream<Item = Result<Change<R>>> {
+        let db = db.clone();
+
+        self.subscribe()
+            .filter(|change| futures::future::ready(change.table == R::TABLE))
+            .then(move |change| {
+                let db = db.clone();
+
+                async move {
+                    let record = R::get_optional(&db, &change.id, true).await?;
+
+                    Ok(Change {
+                        operation: change.operation,
+                        id: change.id,
+                        record,
+                    })
+                }
+            })
+    }
+
+    /// Runs the query once immediately and then again each time a record in one of the tables
+    /// changes. Changes that arrive while a query is running are batched into a single re-run.
+    pub fn live_query<'a, T, F, Fut>(
+        &self,
+        tables: &'static [&'static str],
+        mut query: F,
+    ) -> impl Stream<Item = T> + 'a
+    where
+        T: 'a,
+        F: FnMut() -> Fut + Send + 'a,
+        Fut: Future<Output = T> + Send + 'a,
+    {
+        let changes = self.watch_tables(tables)
+            .ready_chunks(1024)
+            .map(|_| ());
+
+        stream::once(futures::future::ready(()))
+            .chain(changes)
+            .then(move |_| query())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(id: usize) -> RecordChange {
+        RecordChange {
+            table: "tasks".to_string(),
+            operation: ChangeOperation::Update,
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn drops_changes_for_subscribers_that_fall_behind() {
+        let change_feed = ChangeFeed::default();
+        let receiver = change_feed.subscribe();
+
+        for id in 0..SUBSCRIBER_BUFFER_SIZE + 10 {
+            change_feed.publish(change(id));
+        }
+
+        assert_eq!(receiver.len(), SUBSCRIBER_BUFFER_SIZE);
+
+        // The subscriber is kept and receives new changes once it catches up
+        while receiver.try_recv().is_ok() {}
+        change_feed.publish(change(0));
+
+        assert_eq!(receiver.len(), 1);
+        assert_eq!(change_feed.subscribers.lock().unwrap().len(), 1);
+
+        // Dropped subscribers are removed
+        drop(receiver);
+        change_feed.publish(change(0));
+
+        assert!(change_feed.subscribers.lock().unwrap().is_empty());
+    }
+}
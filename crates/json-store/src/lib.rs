@@ -1,4 +1,4 @@
-// #[macro_use] extern crate tracing;
+#[macro_use] extern crate tracing;
 
 pub type Db = sqlx::PgPool;
 pub type DbId = String;
@@ -9,3 +9,15 @@ pub use record::{
     Record,
     JsonRow,
 };
+
+mod version_conflict;
+pub use version_conflict::VersionConflict;
+
+mod change_feed;
+pub use change_feed::{
+    Change,
+    ChangeFeed,
+    ChangeOperation,
+    RecordChange,
+    RECORD_CHANGES_CHANNEL,
+};
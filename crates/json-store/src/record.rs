@@ -6,6 +6,8 @@ use eyre::{
     Context as _,
 };
 
+use crate::VersionConflict;
+
 #[derive(sqlx::FromRow, Debug)]
 pub struct JsonRow {
     pub props: serde_json::Value,
@@ -117,6 +119,8 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
         Ok(entry)
     }
 
+    /// Returns a VersionConflict error if the record is not at the given version (eg. it was
+    /// changed by someone else since the client loaded it).
     async fn get_with_version<'e, 'c, E>(
         db: E,
         id: &crate::DbId,
@@ -126,25 +130,16 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
     where
         E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
     {
-        let deletion_filter = if include_deleted {
-            ""
-        } else {
-            "AND deleted_at IS NULL"
-        };
-
-        let sql = format!(
-            "SELECT props FROM {} WHERE id = $1 {} AND version = $2",
-            Self::TABLE,
-            deletion_filter,
-        );
-
-        let row: JsonRow = sqlx::query_as(&sql)
-            .bind(id)
-            .bind(version)
-            .fetch_one(db)
-            .await?;
+        let entry = Self::get(db, id, include_deleted).await?;
+
+        if entry.version() != version {
+            Err(VersionConflict {
+                table: Self::TABLE,
+                id: id.clone(),
+                version,
+            })?;
+        }
 
-        let entry: Self = Self::from_row(row)?;
         Ok(entry)
     }
 
@@ -235,6 +230,27 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
         Ok((json, previous_version))
     }
 
+    /// Returns a VersionConflict error if an update did not affect any rows (ie. the record was
+    /// changed in the database since it was loaded). The version is reset to the previous
+    /// version so that the record still matches what was last read from the database.
+    fn check_version_conflict(
+        &mut self,
+        rows_affected: u64,
+        previous_version: crate::Version,
+    ) -> Result<()> {
+        if rows_affected == 0 {
+            *self.version_mut() = previous_version;
+
+            Err(VersionConflict {
+                table: Self::TABLE,
+                id: self.id().clone(),
+                version: previous_version,
+            })?;
+        }
+
+        Ok(())
+    }
+
     async fn update<'e, 'c, E>(
         &mut self,
         db: E,
@@ -244,7 +260,7 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
     {
         let (json, previous_version) = self.prep_for_update()?;
 
-        sqlx::query(&format!(
+        let result = sqlx::query(&format!(
             r#"
                 UPDATE {}
                 SET
@@ -264,10 +280,53 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
             // where
             .bind(self.id())
             .bind(previous_version)
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
+    }
+
+    /// Reloads the record and re-applies the changes until it is updated without a version
+    /// conflict, giving up after max_attempts. `apply_changes` returns false if the record should
+    /// not be updated (eg. because the caller saves it some other way).
+    ///
+    /// Returns None if the record does not exist.
+    async fn update_with_retry<F>(
+        db: &crate::Db,
+        id: &crate::DbId,
+        include_deleted: bool,
+        max_attempts: usize,
+        mut apply_changes: F,
+    ) -> Result<Option<Self>>
+    where
+        F: FnMut(&mut Self) -> Result<bool> + Send,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let mut record = if let
+                Some(record) = Self::get_optional(db, id, include_deleted).await?
+            {
+                record
+            } else {
+                return Ok(None)
+            };
+
+            if !apply_changes(&mut record)? {
+                return Ok(Some(record))
+            }
+
+            match record.update(db).await {
+                Err(err) if attempt < max_attempts && err.is::<VersionConflict>() => {
+                    debug!("{} (id: {}) changed while updating, retrying", Self::TABLE, id);
+                    attempt += 1;
+                }
+                result => {
+                    result?;
+                    return Ok(Some(record))
+                }
+            }
+        }
     }
 
     async fn remove<'e, 'c, E>(
@@ -289,8 +348,7 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
                 .fetch_optional(db)
                 .await?;
         } else {
-            *self.deleted_at_mut() = Some(Utc::now());
-            self.update(db).await?;
+            soft_delete(self, db).await?;
         }
 
         Ok(())
@@ -305,7 +363,7 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
         E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
     {
         if hard_delete {
-            sqlx::query(&format!(
+            let result = sqlx::query(&format!(
                 r#"
                     DELETE FROM {} WHERE id=$1 AND version=$2
                 "#,
@@ -313,13 +371,35 @@ pub trait Record: Sync + Send + Serialize + DeserializeOwned + 'static {
             ))
                 .bind(self.id())
                 .bind(self.version())
-                .fetch_optional(db)
+                .execute(db)
                 .await?;
+
+            let version = self.version();
+            self.check_version_conflict(result.rows_affected(), version)?;
         } else {
-            *self.deleted_at_mut() = Some(Utc::now());
-            self.update(db).await?;
+            soft_delete(self, db).await?;
         }
 
         Ok(())
     }
 }
+
+/// Marks the record as deleted. The deletion is reverted in memory if the update fails.
+async fn soft_delete<'e, 'c, R, E>(
+    record: &mut R,
+    db: E,
+) -> Result<()>
+where
+    R: Record,
+    E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    let previous_deleted_at = record.deleted_at();
+    *record.deleted_at_mut() = Some(Utc::now());
+
+    if let Err(err) = record.update(db).await {
+        *record.deleted_at_mut() = previous_deleted_at;
+        return Err(err)
+    }
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+/// Returned when a record is updated or deleted after it was changed by someone else. The
+/// record should be reloaded from the database before retrying the change.
+#[derive(Debug, Clone)]
+pub struct VersionConflict {
+    pub table: &'static str,
+    pub id: crate::DbId,
+    /// The version of the record that was expected to be in the database
+    pub version: crate::Version,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (id: {}) was changed by someone else (expected version: {}). Please reload and try again.",
+            self.table,
+            self.id,
+            self.version,
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
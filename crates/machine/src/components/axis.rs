@@ -32,6 +32,19 @@ pub struct AxisConfig {
     #[validate(range(min = 0, message = "Feedrate must be greater then or equal to 0"))]
     pub feedrate: f32,
 
+    /// # Acceleration (mm/s²)
+    /// Used to estimate print times. Defaults to the firmware's typical acceleration if not set.
+    #[serde(default)]
+    #[validate(range(min = 0, message = "Acceleration must be greater then or equal to 0"))]
+    pub acceleration: Option<f32>,
+
+    /// # Length (mm)
+    /// The axis' range of travel. Parts that move beyond it are not sent to this machine by the
+    /// farm scheduler.
+    #[serde(default)]
+    #[validate(range(min = 0, message = "Length must be greater then or equal to 0"))]
+    pub length: Option<f32>,
+
     /// # Reverse direction for move buttons and macros
     #[serde(default)]
     pub reverse_direction: bool,
@@ -27,6 +27,12 @@ pub struct BuildPlatformConfig {
     /// # Heated Build Platform
     #[serde(default)]
     pub heater: bool,
+
+    /// # Max Temperature (°C)
+    /// The machine is stopped if the build platform exceeds this temperature. Defaults to 130°C.
+    #[serde(default)]
+    #[validate(range(min = 0, message = "Max temperature cannot be less than 0"))]
+    pub max_temperature: Option<f32>,
 }
 
 impl teg_config_form::Model for BuildPlatformConfig {
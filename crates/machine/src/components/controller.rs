@@ -27,7 +27,15 @@ pub struct ControllerConfig {
     #[validate(length(min = 1, message = "Name cannot be blank"))]
     pub name: String,
 
+    /// # Firmware
+    /// The firmware running on the controller. Selects the driver used to communicate with it.
+    #[serde(default)]
+    pub firmware: Firmware,
+
     /// # Serial Port
+    /// A local serial port (eg. /dev/ttyUSB0) or a networked serial bridge (eg.
+    /// tcp://192.168.0.20:2000 for a raw TCP bridge or rfc2217://192.168.0.20:2000 for an
+    /// RFC 2217 Telnet bridge).
     #[validate(length(min = 1, message = "Serial port id cannot be blank"))]
     #[serde(rename = "serialPortID")]
     pub serial_port_id: String,
@@ -65,12 +73,145 @@ pub struct ControllerConfig {
     /// # Send checksums with response timeout tickle attempts
     #[serde(default)]
     pub checksum_tickles: bool,
+    /// # Send window size
+    /// The maximum number of GCodes to send before waiting for the firmware to acknowledge them.
+    /// Sending multiple GCodes keeps the firmware's planner buffer full on dense curved segments.
+    /// Firmwares with ADVANCED_OK enabled further limit this to the free space in their command
+    /// buffer.
+    #[serde(default)]
+    pub send_window_size: u32,
+    /// # No motion before homing
+    /// Enable if the firmware rejects moves before the axes are homed (eg. Marlin's
+    /// NO_MOTION_BEFORE_HOMING). Prints cannot be recovered on these machines because Z is not
+    /// re-homed during recovery.
+    #[serde(default)]
+    pub no_motion_before_homing: bool,
+    /// # Simulator personality
+    /// The firmware imitated by the simulator. Only used when the controller is simulated.
+    #[serde(default)]
+    pub simulator_personality: SimulatorPersonality,
+    /// # Simulator physics
+    /// The thermal and motion properties of the simulated printer. Only used when the controller
+    /// is simulated.
+    #[serde(default)]
+    pub simulator_physics: SimulatorPhysics,
+    /// # Simulator fault injection
+    /// Faults to inject into the simulated serial connection to exercise the driver's error
+    /// recovery. Only used when the controller is simulated.
+    #[serde(default)]
+    pub simulator_faults: SimulatorFaults,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Copy, Clone, PartialEq)]
+pub enum Firmware {
+    /// Marlin, RepRapFirmware, Klipper and other RepRap GCode firmwares
+    #[serde(rename = "RepRap")]
+    RepRap,
+    #[serde(rename = "GRBL")]
+    Grbl,
+}
+
+impl Default for Firmware {
+    fn default() -> Self { Firmware::RepRap }
+}
+
+impl Firmware {
+    /// The name of the driver crate and binary (prefixed with "teg-") for the firmware
+    pub fn driver_name(&self) -> &'static str {
+        match self {
+            Firmware::RepRap => "marlin",
+            Firmware::Grbl => "grbl",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Copy, Clone, PartialEq)]
+pub enum SimulatorPersonality {
+    #[serde(rename = "Marlin")]
+    Marlin,
+    #[serde(rename = "RepRapFirmware")]
+    RepRapFirmware,
+    #[serde(rename = "Klipper")]
+    Klipper,
+}
+
+impl Default for SimulatorPersonality {
+    fn default() -> Self { SimulatorPersonality::Marlin }
+}
+
+/// The physical properties of the simulated printer
+#[derive(Serialize, Deserialize, JsonSchema, SmartDefault, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SimulatorPhysics {
+    /// # Ambient temperature (°C)
+    #[default(22.0)]
+    pub ambient_temperature: f32,
+    /// # Extruder
+    #[default(SimulatedHeater {
+        thermal_mass: 12.0,
+        heater_power: 40.0,
+        heat_loss: 0.05,
+    })]
+    pub extruder: SimulatedHeater,
+    /// # Bed
+    #[default(SimulatedHeater {
+        thermal_mass: 600.0,
+        heater_power: 220.0,
+        heat_loss: 1.2,
+    })]
+    pub bed: SimulatedHeater,
+    /// # Acceleration (mm/s²)
+    #[default(1_000.0)]
+    pub acceleration: f32,
+    /// # Default feedrate (mm/min)
+    /// Used for homing and for moves sent before a feedrate is set.
+    #[default(3_000.0)]
+    pub default_feedrate: f32,
+}
+
+/// A simulated heater with first-order thermal dynamics
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedHeater {
+    /// # Thermal mass (J/°C)
+    /// The energy needed to raise the heater's temperature by one degree.
+    pub thermal_mass: f32,
+    /// # Heater power (W)
+    pub heater_power: f32,
+    /// # Heat loss (W/°C)
+    /// The power lost to the surroundings per degree above the ambient temperature.
+    pub heat_loss: f32,
+}
+
+/// The probability (from 0 to 1) of each fault being injected into a line sent to the simulator.
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SimulatorFaults {
+    /// # Dropped lines
+    /// Lines lost in transit. The simulator does not respond to them.
+    pub dropped_lines: f64,
+    /// # Corrupted bytes
+    /// Lines with a corrupted byte that fail their checksum.
+    pub corrupted_bytes: f64,
+    /// # Resend requests
+    /// Valid lines that the simulator requests be resent.
+    pub resend_requests: f64,
+    /// # Missing oks
+    /// Lines executed without sending an ok.
+    pub missing_oks: f64,
+    /// # Busy responses
+    /// Spurious `busy: processing` responses sent before the ok.
+    pub busy_responses: f64,
+    /// # Random seed
+    /// Seed for reproducing a sequence of faults
+    pub seed: Option<u64>,
 }
 
 impl teg_config_form::Model for ControllerConfig {
     fn static_form() -> Option<Vec<&'static str>> {
         Some(vec![
             "name",
+            "firmware",
             "serialPortID",
             "automaticBaudRateDetection",
             "baudRate",
@@ -79,6 +220,7 @@ impl teg_config_form::Model for ControllerConfig {
 
     fn static_advanced_form() -> Option<Vec<&'static str>> {
         Some(vec![
+            "noMotionBeforeHoming",
         ])
     }
 }
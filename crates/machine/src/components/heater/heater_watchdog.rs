@@ -0,0 +1,245 @@
+use chrono::{ prelude::*, Duration };
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+/// Readings below this temperature are assumed to be a disconnected or shorted thermistor
+const MIN_TEMPERATURE: f32 = 0.0;
+
+/// Changes in temperature smaller than this are allowed between any two readings to account for
+/// noise and the resolution of the firmware's reports
+const READING_NOISE: f32 = 5.0;
+
+/// The thermal protection thresholds of a heater. Modeled on Marlin's thermal protection.
+#[derive(Debug, Clone)]
+pub struct HeaterWatchdogLimits {
+    pub max_temperature: f32,
+    /// While heating the temperature must rise by watch_increase every watch_period
+    pub watch_period: Duration,
+    pub watch_increase: f32,
+    /// Once the target is reached the temperature may not stay more than runaway_hysteresis
+    /// below the target for longer than the runaway_period
+    pub runaway_period: Duration,
+    pub runaway_hysteresis: f32,
+    /// The fastest rate of change (in °C/s) expected from a working thermistor
+    pub max_rate: f32,
+}
+
+impl HeaterWatchdogLimits {
+    pub fn toolhead(max_temperature: Option<f32>) -> Self {
+        Self {
+            max_temperature: max_temperature.unwrap_or(300.0),
+            watch_period: Duration::seconds(20),
+            watch_increase: 2.0,
+            runaway_period: Duration::seconds(40),
+            runaway_hysteresis: 4.0,
+            max_rate: 20.0,
+        }
+    }
+
+    pub fn build_platform(max_temperature: Option<f32>) -> Self {
+        Self {
+            max_temperature: max_temperature.unwrap_or(130.0),
+            watch_period: Duration::seconds(60),
+            watch_increase: 2.0,
+            runaway_period: Duration::seconds(20),
+            runaway_hysteresis: 2.0,
+            max_rate: 5.0,
+        }
+    }
+}
+
+/// Detects heaters failing to heat, thermal runaway, thermistor faults and over temperature
+/// heaters from their feedback.
+#[derive(Debug, Clone, Default)]
+pub struct HeaterWatchdog {
+    target: f32,
+    reached_target: bool,
+    /// The time and temperature at the start of the current watch period while heating
+    watch: Option<(DateTime<Utc>, f32)>,
+    /// When the temperature fell below the runaway hysteresis after reaching the target
+    fell_below_target_at: Option<DateTime<Utc>>,
+    last_reading: Option<(DateTime<Utc>, f32)>,
+}
+
+impl HeaterWatchdog {
+    /// Checks a heater's feedback. Returns an error describing the fault if the heater is
+    /// unsafe.
+    pub fn check(
+        &mut self,
+        name: &str,
+        target: f32,
+        actual: f32,
+        now: DateTime<Utc>,
+        limits: &HeaterWatchdogLimits,
+    ) -> Result<()> {
+        let last_reading = self.last_reading.replace((now, actual));
+
+        // Thermistor faults
+        if !actual.is_finite() || actual < MIN_TEMPERATURE {
+            Err(eyre!(
+                "Thermistor fault: {} read an impossible temperature of {:.1}°C. \
+                Check that the thermistor is connected.",
+                name,
+                actual,
+            ))?;
+        }
+
+        if let Some((read_at, previous)) = last_reading {
+            let seconds = (now - read_at).num_milliseconds() as f32 / 1000.0;
+
+            if (actual - previous).abs() > limits.max_rate * seconds + READING_NOISE {
+                Err(eyre!(
+                    "Thermistor fault: {} jumped from {:.1}°C to {:.1}°C in {:.1} seconds. \
+                    Check the thermistor's wiring.",
+                    name,
+                    previous,
+                    actual,
+                    seconds,
+                ))?;
+            }
+        }
+
+        if actual > limits.max_temperature {
+            Err(eyre!(
+                "Max temperature exceeded: {} reached {:.1}°C (max {:.1}°C)",
+                name,
+                actual,
+                limits.max_temperature,
+            ))?;
+        }
+
+        // Restart the watch whenever the target changes
+        if target != self.target {
+            self.target = target;
+            self.reached_target = false;
+            self.watch = Some((now, actual));
+            self.fell_below_target_at = None;
+        }
+
+        // Heater off
+        if target <= 0.0 {
+            self.watch = None;
+            return Ok(())
+        }
+
+        if !self.reached_target {
+            if actual >= target - limits.runaway_hysteresis {
+                self.reached_target = true;
+                self.watch = None;
+            } else if let Some((watch_started_at, watch_temperature)) = self.watch {
+                if now - watch_started_at >= limits.watch_period {
+                    if actual < watch_temperature + limits.watch_increase {
+                        Err(eyre!(
+                            "Heating failed: {} only rose from {:.1}°C to {:.1}°C in {} seconds \
+                            while heating to {:.1}°C. Check the heater and thermistor.",
+                            name,
+                            watch_temperature,
+                            actual,
+                            (now - watch_started_at).num_seconds(),
+                            target,
+                        ))?;
+                    }
+
+                    self.watch = Some((now, actual));
+                }
+            }
+        } else if actual < target - limits.runaway_hysteresis {
+            let fell_at = *self.fell_below_target_at.get_or_insert(now);
+
+            if now - fell_at >= limits.runaway_period {
+                Err(eyre!(
+                    "Thermal runaway: {} fell to {:.1}°C for {} seconds while holding \
+                    {:.1}°C. Check the heater and thermistor.",
+                    name,
+                    actual,
+                    (now - fell_at).num_seconds(),
+                    target,
+                ))?;
+            }
+        } else {
+            self.fell_below_target_at = None;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks a reading taken `seconds` after the first reading
+    fn check(
+        watchdog: &mut HeaterWatchdog,
+        seconds: i64,
+        target: f32,
+        actual: f32,
+    ) -> Result<()> {
+        let now = DateTime::<Utc>::from(std::time::UNIX_EPOCH) + Duration::seconds(seconds);
+        let limits = HeaterWatchdogLimits::toolhead(None);
+
+        watchdog.check("Extruder 1", target, actual, now, &limits)
+    }
+
+    #[test]
+    fn allows_a_heater_to_heat_and_hold_its_target() {
+        let mut watchdog = HeaterWatchdog::default();
+
+        for (seconds, actual) in (0..60).step_by(5).zip((25..200).step_by(15)) {
+            check(&mut watchdog, seconds, 200.0, actual as f32).unwrap();
+        }
+
+        for seconds in (60..600).step_by(5) {
+            check(&mut watchdog, seconds, 200.0, 199.0).unwrap();
+        }
+    }
+
+    #[test]
+    fn errors_when_heating_fails() {
+        let mut watchdog = HeaterWatchdog::default();
+
+        check(&mut watchdog, 0, 200.0, 25.0).unwrap();
+        check(&mut watchdog, 10, 200.0, 25.5).unwrap();
+
+        let err = check(&mut watchdog, 20, 200.0, 26.0).unwrap_err();
+        assert!(err.to_string().starts_with("Heating failed"));
+    }
+
+    #[test]
+    fn errors_on_thermal_runaway() {
+        let mut watchdog = HeaterWatchdog::default();
+
+        check(&mut watchdog, 0, 200.0, 200.0).unwrap();
+        check(&mut watchdog, 5, 200.0, 194.0).unwrap();
+        check(&mut watchdog, 30, 200.0, 190.0).unwrap();
+
+        let err = check(&mut watchdog, 45, 200.0, 185.0).unwrap_err();
+        assert!(err.to_string().starts_with("Thermal runaway"));
+    }
+
+    #[test]
+    fn errors_on_thermistor_faults() {
+        let mut watchdog = HeaterWatchdog::default();
+
+        check(&mut watchdog, 0, 200.0, 200.0).unwrap();
+
+        let err = check(&mut watchdog, 1, 200.0, 25.0).unwrap_err();
+        assert!(err.to_string().contains("jumped from 200.0°C to 25.0°C"));
+
+        let err = check(&mut watchdog, 2, 200.0, -14.0).unwrap_err();
+        assert!(err.to_string().contains("impossible temperature of -14.0°C"));
+    }
+
+    #[test]
+    fn errors_above_the_max_temperature() {
+        let mut watchdog = HeaterWatchdog::default();
+
+        check(&mut watchdog, 0, 290.0, 295.0).unwrap();
+
+        let err = check(&mut watchdog, 5, 290.0, 301.0).unwrap_err();
+        assert!(err.to_string().starts_with("Max temperature exceeded"));
+    }
+}
@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 use chrono::prelude::*;
 use nanoid::nanoid;
 
+mod heater_watchdog;
+pub use heater_watchdog::*;
+
 #[derive(async_graphql::SimpleObject, Debug, Clone, SmartDefault)]
 pub struct HeaterEphemeral {
     #[default(nanoid!(11).into())]
@@ -29,6 +32,8 @@ pub struct HeaterEphemeral {
     /// preventing any more gcodes from executing until it does.
     pub blocking: bool,
     pub history: VecDeque<TemperatureHistoryEntry>,
+    #[graphql(skip)]
+    pub watchdog: HeaterWatchdog,
 }
 
 
@@ -10,6 +10,7 @@ use eyre::{
 };
 use teg_auth::{
     AuthContext,
+    Permission,
 };
 // use teg_json_store::Record as _;
 
@@ -56,7 +57,7 @@ impl ComponentMutation {
         // let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        auth.authorize_admins_only()?;
+        auth.authorize_machine(&input.machine_id, Permission::ConfigureMachine)?;
 
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -86,7 +87,7 @@ impl ComponentMutation {
         let machines = machines.load();
 
         async move {
-            auth.authorize_admins_only()?;
+            auth.authorize_machine(&input.machine_id, Permission::ConfigureMachine)?;
 
             let machine = machines.get(&input.machine_id)
                 .ok_or_else(|| eyre!("Machine ID not found"))?;
@@ -119,7 +120,7 @@ impl ComponentMutation {
         // let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        auth.authorize_admins_only()?;
+        auth.authorize_machine(&input.machine_id, Permission::ConfigureMachine)?;
 
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -43,6 +43,12 @@ pub struct ToolheadConfig {
     #[serde(default)]
     pub heater: bool,
 
+    /// # Max Temperature (°C)
+    /// The machine is stopped if the extruder exceeds this temperature. Defaults to 300°C.
+    #[serde(default)]
+    #[validate(range(min = 0, message = "Max temperature cannot be less than 0"))]
+    pub max_temperature: Option<f32>,
+
     /// # Feedrate (mm/s)
     /// The extrude speed for the maintenance panel as well for filament swaps.
     #[validate(range(min = 0, message = "Feedrate cannot be less then 0"))]
@@ -125,6 +131,7 @@ impl teg_config_form::Model for ToolheadConfig {
 
     fn static_advanced_form() -> Option<Vec<&'static str>> {
         Some(vec![
+            "maxTemperature",
             "pauseRetractionDistance",
             "filamentSwapExtrudeDistance",
             "filamentSwapFastMoveEnabled",
@@ -13,6 +13,7 @@ use crate::components::{
     Component,
     Controller,
     HeaterEphemeral,
+    HeaterWatchdogLimits,
     SpeedController,
     Toolhead,
     Video,
@@ -131,6 +132,29 @@ impl MachineConfig {
         }
     }
 
+    /// The name and thermal protection limits of the heater at the given address
+    pub fn get_heater_limits(&self, address: &String) -> Option<(String, HeaterWatchdogLimits)> {
+        if let Some(toolhead) = self.toolheads
+            .iter()
+            .find(|c| &c.model.address == address)
+        {
+            Some((
+                toolhead.model.name.clone(),
+                HeaterWatchdogLimits::toolhead(toolhead.model.max_temperature),
+            ))
+        } else if let Some(build_platform) = self.build_platforms
+            .iter()
+            .find(|c| &c.model.address == address)
+        {
+            Some((
+                build_platform.model.name.clone(),
+                HeaterWatchdogLimits::build_platform(build_platform.model.max_temperature),
+            ))
+        } else {
+            None
+        }
+    }
+
     pub fn feedrates(&self) -> Vec<Feedrate> {
         let axe_feedrates = self.axes.iter()
             .map(|Axis { model: axis, .. }| {
@@ -9,9 +9,10 @@ use eyre::{
     // Context as _,
 };
 use messages::set_materials::SetMaterialsInput;
-// use teg_auth::{
-//     AuthContext,
-// };
+use teg_auth::{
+    AuthContext,
+    Permission,
+};
 // use teg_json_store::Record as _;
 
 use crate::{
@@ -31,6 +32,9 @@ impl ConfigMutation {
     ) -> FieldResult<Option<teg_common::Void>> {
         // use cgt::ConfigCollection::*;
         // let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
 
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
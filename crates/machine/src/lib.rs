@@ -18,6 +18,7 @@ pub use config::resolvers::mutation_resolvers::ConfigMutation;
 pub mod machine;
 pub use machine::resolvers::machines_query_resolvers::MachineQuery;
 pub use machine::resolvers::mutation_resolvers::MachineMutation;
+pub use machine::resolvers::machine_subscription_resolvers::MachineSubscription;
 
 pub mod plugins;
 pub mod signalling_updater;
@@ -0,0 +1,7 @@
+/// Published when a machine becomes ready to start a print (eg. after finishing a print or when
+/// it's bed is confirmed clear)
+#[xactor::message(result = "()")]
+#[derive(Clone, Debug)]
+pub struct MachineReady {
+    pub machine_id: crate::DbId,
+}
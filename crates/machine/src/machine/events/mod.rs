@@ -1,2 +1,5 @@
+mod machine_ready;
+pub use machine_ready::MachineReady;
+
 mod task_settled;
 pub use task_settled::TaskSettled;
@@ -0,0 +1,19 @@
+use teg_protobufs::machine_message;
+
+/// A prompt sent by the machine's firmware (eg. during an M600 filament change) that is waiting
+/// on the user to press one of its buttons.
+#[derive(async_graphql::SimpleObject, Debug, Clone)]
+pub struct FirmwarePrompt {
+    pub message: String,
+    /// The labels of the prompt's buttons in the order they are indexed by respondToPrompt
+    pub buttons: Vec<String>,
+}
+
+impl From<&machine_message::Prompt> for FirmwarePrompt {
+    fn from(prompt: &machine_message::Prompt) -> Self {
+        Self {
+            message: prompt.message.clone(),
+            buttons: prompt.buttons.clone(),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use chrono::prelude::*;
 use xactor::Actor;
 // use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::path::PathBuf;
 // use std::sync::Arc;
 use async_std::{
     fs,
@@ -14,14 +15,25 @@ use eyre::{
     // Context as _,
 };
 
+use teg_json_store::{
+    ChangeFeed,
+    ChangeOperation,
+    RecordChange,
+};
+
 use super::{MachineStatus, messages::{AddDevice, ConnectToSocket, ResetMaterialTargets}, streams::receive_stream::codec::MachineCodec};
 use super::GCodeHistoryEntry;
 use crate::config::MachineConfig;
 use crate::components::Toolhead;
 
+/// Machine data is held in memory by each machine's actor rather than in the database so the
+/// actors publish their changes to the change feed under this table name.
+pub const MACHINE_DATA_CHANGES: &str = "machine_data";
+
 pub struct Machine {
     pub db: crate::Db,
     pub hooks: crate::MachineHooksList,
+    pub change_feed: ChangeFeed,
     pub id: crate::DbId,
     pub write_stream: Option<Framed<UnixStream, MachineCodec>>,
     pub unix_socket: Option<UnixStream>,
@@ -48,6 +60,19 @@ pub struct MachineData {
     pub blocked_at: Option<DateTime<Utc>>,
     #[new(default)]
     pub gcode_history: VecDeque<GCodeHistoryEntry>,
+    /// The files on the machine's SD card. None until the files have been listed.
+    #[new(default)]
+    pub sd_card_files: Option<Vec<super::SDCardFile>>,
+    /// The firmware prompt currently waiting on a response from the user, if any
+    #[new(default)]
+    pub firmware_prompt: Option<super::FirmwarePrompt>,
+    /// True once the user (or an automatic printing machine's bed clearing hardware) has
+    /// confirmed that the previous print has been removed from the build platform.
+    ///
+    /// Saved to disk by `Machine::set_bed_clear` so that it survives driver reconnects and server
+    /// restarts. Machines that have never been confirmed clear default to false.
+    #[new(default)]
+    pub bed_clear: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -108,6 +133,7 @@ impl Machine {
     pub async fn start(
         db: crate::Db,
         hooks: crate::MachineHooksList,
+        change_feed: ChangeFeed,
         machine_id: &crate::DbId,
     ) -> Result<xactor::Addr<Machine>> {
         let machine_id = machine_id.clone();
@@ -115,6 +141,7 @@ impl Machine {
             Machine {
                 db: db.clone(),
                 hooks: hooks.clone(),
+                change_feed: change_feed.clone(),
                 id: machine_id.clone(),
                 write_stream: None,
                 unix_socket: None,
@@ -161,13 +188,64 @@ impl Machine {
         let config = fs::read_to_string(config_path).await?;
         let config: MachineConfig = toml::from_str(&config)?;
 
-        self.data = Some(MachineData::new(config));
+        let mut data = MachineData::new(config);
+
+        data.bed_clear = self.load_bed_clear()
+            .await
+            .unwrap_or_else(|err| {
+                warn!("Unable to load bed clear state for Machine #{}: {:?}", self.id, err);
+                false
+            });
+
+        self.data = Some(data);
 
         self.reset_material_targets(
             ResetMaterialTargets { material_id_filter: None },
         )
             .await?;
 
+        self.publish_data_change();
+
         Ok(())
     }
+
+    /// The path the machine's bed clear state is saved to
+    fn bed_clear_path(&self) -> PathBuf {
+        crate::paths::var()
+            .join("bed-clear")
+            .join(format!("machine-{}.json", self.id))
+    }
+
+    async fn load_bed_clear(&self) -> Result<bool> {
+        let json = match fs::read_to_string(self.bed_clear_path()).await {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => Err(err)?,
+        };
+
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Sets and saves whether the machine's build platform is clear for the next print
+    pub async fn set_bed_clear(&mut self, bed_clear: bool) -> Result<()> {
+        self.get_data()?.bed_clear = bed_clear;
+
+        let path = self.bed_clear_path();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::write(path, serde_json::to_string(&bed_clear)?).await?;
+
+        Ok(())
+    }
+
+    /// Notifies live machine subscriptions that the machine's data has changed
+    pub fn publish_data_change(&self) {
+        self.change_feed.publish(RecordChange {
+            table: MACHINE_DATA_CHANGES.to_string(),
+            operation: ChangeOperation::Update,
+            id: self.id.clone(),
+        });
+    }
 }
@@ -6,6 +6,7 @@ use eyre::{
     // Context as _,
 };
 use futures::Future;
+use teg_protobufs::machine_message::HostAction;
 
 use crate::{MachineHooksList, config::MachineConfig, plugins::Plugin, task::Task};
 
@@ -44,4 +45,15 @@ pub trait MachineHooks {
         machine_id: &crate::DbId,
         plugin: &Plugin,
     ) -> Result<()>;
+
+    /// Called when the machine's firmware requests that the host pause, resume or cancel the
+    /// print (eg. on filament runout). This runs inside the machine actor so implementations
+    /// must not wait on messages sent to the machine.
+    async fn after_host_action(
+        &self,
+        machine_hooks: &MachineHooksList,
+        machine_data: &MachineData,
+        machine_addr: xactor::Addr<Machine>,
+        action: HostAction,
+    ) -> Result<()>;
 }
@@ -102,7 +102,7 @@ impl Record for MachineViewer {
     {
         let (json, previous_version) = self.prep_for_update()?;
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE machine_viewers
                 SET
@@ -123,9 +123,9 @@ impl Record for MachineViewer {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
@@ -0,0 +1,81 @@
+use teg_json_store::Record as _;
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::Machine;
+use crate::task::Task;
+
+/// Cancels an object on a print's build plate. The rest of the plate continues printing.
+#[xactor::message(result = "Result<Task>")]
+pub struct CancelObject {
+    pub task_id: crate::DbId,
+    pub object: u32,
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<CancelObject> for Machine {
+    async fn handle(
+        &mut self,
+        ctx: &mut xactor::Context<Self>,
+        msg: CancelObject,
+    ) -> Result<Task> {
+        let mut tx = self.db.begin().await?;
+        // Re-fetch the task within the transaction
+        let mut task = Task::get(&mut tx, &msg.task_id, false).await?;
+
+        if task.status.is_settled() {
+            return Err(eyre!("Cannot cancel an object of a task that is not running"));
+        }
+
+        let objects = task.objects();
+
+        if !objects.contains_key(&msg.object) {
+            return Err(eyre!("Object #{} not found in task #{}", msg.object, task.id));
+        }
+
+        if task.cancelled_objects.contains(&msg.object) {
+            // handle redundant calls as a no-op to cancel idempotently
+            return Ok(task);
+        }
+
+        if task.cancelled_objects.len() + 1 >= objects.len() {
+            return Err(eyre!("Cannot cancel the last object. Cancel the print instead."));
+        }
+
+        task.cancelled_objects.push(msg.object);
+
+        task.update(&mut tx).await?;
+        tx.commit().await?;
+
+        // Paused tasks and tasks that have not yet been sent to the driver receive their
+        // skipped lines when they are spooled
+        if task.status.is_in_driver() {
+            let protobuf_msg = ServerMessage {
+                payload: Some(
+                    server_message::Payload::SetSkippedLines(
+                        server_message::SetSkippedLines {
+                            task_id: task.id.clone(),
+                            skipped_lines: task.skipped_lines(),
+                        }
+                    )
+                ),
+            };
+
+            if let Err(err) = self.send_message(protobuf_msg).await {
+                error!("Error cancelling object on machine #{}: {:?}", self.id, err);
+                ctx.stop(Some(err));
+            };
+        }
+
+        info!("Cancelled object #{} of Print #{}", msg.object, task.id);
+
+        Ok(task)
+    }
+}
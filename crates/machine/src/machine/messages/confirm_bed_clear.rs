@@ -0,0 +1,39 @@
+use xactor::Service as _;
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::{
+    Machine,
+    MachineStatus,
+    events::MachineReady,
+};
+
+/// Marks the machine's build platform as clear so that it can start the next queued print
+#[xactor::message(result = "Result<()>")]
+pub struct ConfirmBedClear;
+
+#[async_trait::async_trait]
+impl xactor::Handler<ConfirmBedClear> for Machine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut xactor::Context<Self>,
+        _msg: ConfirmBedClear,
+    ) -> Result<()> {
+        self.set_bed_clear(true).await?;
+
+        let is_ready = self.get_data()?.status == MachineStatus::Ready;
+        self.publish_data_change();
+
+        if is_ready {
+            let mut broker = xactor::Broker::from_registry().await?;
+            broker.publish(MachineReady {
+                machine_id: self.id.clone(),
+            })?;
+        }
+
+        Ok(())
+    }
+}
@@ -46,14 +46,24 @@ async fn spawn_driver(machine: &Machine) -> Result<()> {
         .map(|v| &v == "development")
         .unwrap_or(true);
 
+    // Each firmware has its own driver (eg. teg-marlin for RepRap firmwares)
+    let driver = machine.data
+        .as_ref()
+        .ok_or_else(|| eyre!("Attempted to spawn a driver before the machine config was loaded"))?
+        .config
+        .get_controller()
+        .model
+        .firmware
+        .driver_name();
+
     let cmd = if is_dev {
-        let mut marlin = env::current_exe()?;
-        marlin.pop();
-        marlin.pop();
-        marlin.pop();
-        marlin.push("crates/marlin");
+        let mut driver_dir = env::current_exe()?;
+        driver_dir.pop();
+        driver_dir.pop();
+        driver_dir.pop();
+        driver_dir.push(format!("crates/{}", driver));
 
-        let marlin = marlin.to_str()
+        let driver_dir = driver_dir.to_str()
             .ok_or_else(|| eyre!("Error loading file path to drivers"))?;
 
         let release_flag = if
@@ -66,14 +76,14 @@ async fn spawn_driver(machine: &Machine) -> Result<()> {
             ""
         };
 
-        // format!("cd {} && cargo watch -s \"cargo run -- {}\"", marlin, config_file)
-        format!("RUST_BACKTRACE=1 cd {} && cargo run{} -- {}", marlin, release_flag, machine.id)
+        // format!("cd {} && cargo watch -s \"cargo run -- {}\"", driver_dir, config_file)
+        format!("RUST_BACKTRACE=1 cd {} && cargo run{} -- {}", driver_dir, release_flag, machine.id)
     } else {
-        let marlin = crate::paths::etc().join("teg-marlin");
-        let marlin = marlin.to_str()
+        let driver_bin = crate::paths::etc().join(format!("teg-{}", driver));
+        let driver_bin = driver_bin.to_str()
             .ok_or_else(|| eyre!("Error loading file path to drivers"))?;
 
-        format!("{} {}", marlin, machine.id)
+        format!("{} {}", driver_bin, machine.id)
     };
 
     info!("Spawning driver for {:?}: {}", config_file, cmd);
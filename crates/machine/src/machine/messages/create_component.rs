@@ -60,6 +60,7 @@ impl xactor::Handler<CreateComponent> for Machine {
         };
 
         data.config.save_config().await?;
+        self.publish_data_change();
         ctx.address().send(ResetWhenIdle)?;
 
         Ok(())
@@ -0,0 +1,50 @@
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::{Machine, MachineStatus};
+
+#[xactor::message(result = "Result<()>")]
+pub struct DeleteSDCardFile {
+    pub filename: String,
+}
+
+impl From<DeleteSDCardFile> for ServerMessage {
+    fn from(msg: DeleteSDCardFile) -> ServerMessage {
+        ServerMessage {
+            payload: Some(
+                server_message::Payload::DeleteSdCardFile(
+                    server_message::DeleteSdCardFile {
+                        filename: msg.filename,
+                    }
+                )
+            ),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<DeleteSDCardFile> for Machine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut xactor::Context<Self>,
+        msg: DeleteSDCardFile,
+    ) -> Result<()> {
+        let status = &self.get_data()?.status;
+
+        // Files cannot be deleted while they are being printed
+        if !matches!(status, MachineStatus::Ready) {
+            Err(eyre!("Cannot delete SD card files while machine is: {:?}", status))?;
+        }
+
+        self.send_message(msg.into()).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::Machine;
+
+/// Requests the list of files on the machine's SD card. The files are sent back in the driver's
+/// feedback.
+#[xactor::message(result = "Result<()>")]
+pub struct ListSDCardFiles;
+
+impl From<ListSDCardFiles> for ServerMessage {
+    fn from(_msg: ListSDCardFiles) -> ServerMessage {
+        ServerMessage {
+            payload: Some(
+                server_message::Payload::ListSdCardFiles(
+                    server_message::ListSdCardFiles {}
+                )
+            ),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<ListSDCardFiles> for Machine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut xactor::Context<Self>,
+        msg: ListSDCardFiles,
+    ) -> Result<()> {
+        if !self.get_data()?.status.is_driver_ready() {
+            Err(eyre!("Cannot list SD card files while machine is not ready"))?;
+        }
+
+        self.send_message(msg.into()).await?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,12 @@
 mod add_device;
 pub use add_device::AddDevice;
 
+mod cancel_object;
+pub use cancel_object::CancelObject;
+
+mod confirm_bed_clear;
+pub use confirm_bed_clear::ConfirmBedClear;
+
 mod connect_to_socket;
 pub use connect_to_socket::ConnectToSocket;
 
@@ -10,20 +16,35 @@ pub use create_component::CreateComponent;
 mod delete_machine;
 pub use delete_machine::DeleteMachine;
 
+mod delete_sd_card_file;
+pub use delete_sd_card_file::DeleteSDCardFile;
+
 mod delete_task_history;
 pub use delete_task_history::DeleteTaskHistory;
 
 mod get_data;
 pub use get_data::GetData;
 
+mod list_sd_card_files;
+pub use list_sd_card_files::ListSDCardFiles;
+
 pub mod set_materials;
 
+mod pause_sd_card_print;
+pub use pause_sd_card_print::PauseSDCardPrint;
+
 mod pause_task;
 pub use pause_task::PauseTask;
 
+mod recover_task;
+pub use recover_task::RecoverTask;
+
 mod remove_component;
 pub use remove_component::RemoveComponent;
 
+mod set_scheduled_actions;
+pub use set_scheduled_actions::SetScheduledActions;
+
 mod spool_task;
 pub use spool_task::SpoolTask;
 
@@ -36,9 +57,15 @@ pub use reset_when_idle::ResetWhenIdle;
 mod reset_material_targets;
 pub use reset_material_targets::ResetMaterialTargets;
 
+mod respond_to_prompt;
+pub use respond_to_prompt::RespondToPrompt;
+
 mod resume_task;
 pub use resume_task::ResumeTask;
 
+mod start_sd_card_print;
+pub use start_sd_card_print::StartSDCardPrint;
+
 mod stop_machine;
 pub use stop_machine::StopMachine;
 
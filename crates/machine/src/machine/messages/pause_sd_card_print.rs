@@ -0,0 +1,77 @@
+use chrono::prelude::*;
+use teg_json_store::Record as _;
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::{Machine, MachineStatus, Printing};
+use crate::task::{Paused, Task, TaskStatus};
+
+/// Pauses an SD card print. Unlike other prints the pause is handled by the machine's firmware so
+/// the pause hook is not run.
+#[xactor::message(result = "Result<Task>")]
+pub struct PauseSDCardPrint {
+    pub task_id: crate::DbId,
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<PauseSDCardPrint> for Machine {
+    async fn handle(
+        &mut self,
+        ctx: &mut xactor::Context<Self>,
+        msg: PauseSDCardPrint,
+    ) -> Result<Task> {
+        let mut tx = self.db.begin().await?;
+        // Re-fetch the task within the transaction
+        let mut task = Task::get(&mut tx, &msg.task_id, false).await?;
+
+        if task.status.is_settled() {
+            return Err(eyre!("Cannot pause a task that is not running"));
+        }
+
+        if !task.is_sd_card_print() {
+            return Err(eyre!("Task #{} is not an SD card print", task.id));
+        }
+
+        if task.status.is_paused() {
+            // handle redundant calls as a no-op to pause idempotently
+            return Ok(task);
+        }
+
+        task.status = TaskStatus::Paused(Paused {
+            paused_at: Utc::now(),
+        });
+
+        task.update(&mut tx).await?;
+        tx.commit().await?;
+
+        self.get_data()?.status = MachineStatus::Printing(Printing {
+            task_id: msg.task_id.clone(),
+            paused: true,
+            paused_state: None,
+        });
+
+        let protobuf_msg = ServerMessage {
+            payload: Some(
+                server_message::Payload::PauseSdCardPrint(
+                    server_message::PauseSdCardPrint { task_id: msg.task_id.clone() }
+                )
+            ),
+        };
+
+        if let Err(err) = self.send_message(protobuf_msg).await {
+            error!("Error pausing SD card print on machine #{}: {:?}", self.id, err);
+            ctx.stop(Some(err));
+        };
+
+        info!("Paused SD Card Print #{}", msg.task_id);
+
+        Ok(task)
+    }
+}
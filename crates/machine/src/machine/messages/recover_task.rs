@@ -0,0 +1,63 @@
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::{
+    machine::{Machine, MachineStatus, Printing},
+    task::Task,
+};
+
+#[xactor::message(result = "Result<Task>")]
+pub struct RecoverTask {
+    pub task: Task,
+    /// Restores the machine to the state it was in when the print stopped
+    pub recovery_hook: Task,
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<RecoverTask> for Machine {
+    async fn handle(&mut self, ctx: &mut xactor::Context<Self>, msg: RecoverTask) -> Result<Task> {
+        let machine_id = self.id.clone();
+
+        let status = &self.get_data()?.status;
+        if !matches!(status, MachineStatus::Ready) {
+            Err(eyre!("Cannot recover print while machine is: {:?}", status))?;
+        }
+
+        async move {
+            let RecoverTask {
+                task,
+                recovery_hook,
+            } = msg;
+
+            let (self, _) = self.spool_task(
+                recovery_hook,
+            ).await?;
+
+            // Begin printing the task again from the point it stopped at
+            let (self, task) = self.spool_task(
+                task,
+            ).await?;
+
+            // Update the machine status
+            self.get_data()?.status = MachineStatus::Printing(Printing {
+                task_id: task.id.clone(),
+                paused: false,
+                paused_state: None,
+            });
+
+            info!("Recovered Print #{}", task.id);
+
+            Result::<_>::Ok(task)
+        }
+            .await
+            .map_err(|err| {
+                error!("Error recovering task on machine #{}: {:?}", machine_id, err);
+                ctx.stop(Some(err));
+
+                eyre!("Unable to recover print due to internal error")
+            })
+    }
+}
@@ -34,6 +34,7 @@ impl xactor::Handler<RemoveComponent> for Machine {
         remove_component(&id, &mut data.config.videos);
 
         data.config.save_config().await?;
+        self.publish_data_change();
         ctx.address().send(ResetWhenIdle)?;
 
         Ok(())
@@ -0,0 +1,55 @@
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::Machine;
+
+/// Answers the firmware's prompt by pressing the button at button_index (M876)
+#[xactor::message(result = "Result<()>")]
+pub struct RespondToPrompt {
+    pub button_index: u32,
+}
+
+impl From<RespondToPrompt> for ServerMessage {
+    fn from(msg: RespondToPrompt) -> ServerMessage {
+        ServerMessage {
+            payload: Some(
+                server_message::Payload::RespondToPrompt(
+                    server_message::RespondToPrompt {
+                        button_index: msg.button_index,
+                    }
+                )
+            ),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<RespondToPrompt> for Machine {
+    async fn handle(
+        &mut self,
+        _ctx: &mut xactor::Context<Self>,
+        msg: RespondToPrompt,
+    ) -> Result<()> {
+        let prompt = self.get_data()?.firmware_prompt
+            .as_ref()
+            .ok_or_else(|| eyre!("The machine is not waiting on a prompt"))?;
+
+        // Prompts without buttons can only be dismissed
+        let button_count = std::cmp::max(prompt.buttons.len(), 1);
+
+        if msg.button_index as usize >= button_count {
+            Err(eyre!("Invalid prompt button index: {}", msg.button_index))?;
+        }
+
+        self.send_message(msg.into()).await?;
+
+        Ok(())
+    }
+}
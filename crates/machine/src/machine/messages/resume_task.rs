@@ -123,9 +123,13 @@ impl xactor::Handler<ResumeTask> for Machine {
                     annotations: vec![],
                     estimated_filament_meters: None,
                     estimated_print_time: None,
+                    print_time_table: Default::default(),
+                    material_ids: vec![],
                     time_blocked: Default::default(),
                     time_paused: Default::default(),
                     status: Default::default(),
+                    scheduled_actions: vec![],
+                    cancelled_objects: vec![],
                 };
 
                 task.insert(&self.db).await?;
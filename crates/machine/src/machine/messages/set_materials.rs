@@ -56,6 +56,7 @@ impl xactor::Handler<SetMaterial> for Machine {
         // Persist the config changes
         let data = self.get_data()?;
         data.config.save_config().await?;
+        self.publish_data_change();
 
         Ok(())
     }
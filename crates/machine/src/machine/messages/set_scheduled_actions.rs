@@ -0,0 +1,65 @@
+use teg_json_store::Record as _;
+use teg_protobufs::{
+    ServerMessage,
+    server_message,
+};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::Machine;
+use crate::task::{ScheduledAction, Task};
+
+/// Replaces the pauses and GCodes scheduled to run at layers or heights of a task
+#[xactor::message(result = "Result<Task>")]
+pub struct SetScheduledActions {
+    pub task_id: crate::DbId,
+    pub scheduled_actions: Vec<ScheduledAction>,
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<SetScheduledActions> for Machine {
+    async fn handle(
+        &mut self,
+        ctx: &mut xactor::Context<Self>,
+        msg: SetScheduledActions,
+    ) -> Result<Task> {
+        let mut tx = self.db.begin().await?;
+        // Re-fetch the task within the transaction
+        let mut task = Task::get(&mut tx, &msg.task_id, false).await?;
+
+        if task.status.is_settled() {
+            return Err(eyre!("Cannot schedule actions on a task that is not running"));
+        }
+
+        task.scheduled_actions = msg.scheduled_actions;
+        task.scheduled_actions.sort_by_key(|action| action.line_number);
+
+        task.update(&mut tx).await?;
+        tx.commit().await?;
+
+        // Paused tasks and tasks that have not yet been sent to the driver receive their
+        // actions when they are spooled
+        if task.status.is_in_driver() {
+            let protobuf_msg = ServerMessage {
+                payload: Some(
+                    server_message::Payload::SetLineActions(
+                        server_message::SetLineActions {
+                            task_id: task.id.clone(),
+                            line_actions: task.line_actions(),
+                        }
+                    )
+                ),
+            };
+
+            if let Err(err) = self.send_message(protobuf_msg).await {
+                error!("Error scheduling actions on machine #{}: {:?}", self.id, err);
+                ctx.stop(Some(err));
+            };
+        }
+
+        Ok(task)
+    }
+}
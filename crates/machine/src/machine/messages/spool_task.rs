@@ -61,37 +61,8 @@ impl Machine {
 
         info!("spooling task");
 
-        let content = match &task.content {
-            TaskContent::FilePath(file_path) => {
-                server_message::spool_task::Content::FilePath(
-                    file_path.clone().into_os_string().into_string().unwrap(),
-                )
-            }
-            TaskContent::GCodes(gcodes) => {
-                server_message::spool_task::Content::Inline(
-                    server_message::InlineContent {
-                        commands: gcodes.clone(),
-                    },
-                )
-            }
-        };
-
-        let start_at_line_number = task.despooled_line_number
-            .map(|n| n + 1)
-            .unwrap_or(0);
-
         let message = ServerMessage {
-            payload: Some(
-                server_message::Payload::SpoolTask(
-                    server_message::SpoolTask {
-                        task_id: task.id.clone(),
-                        client_id,
-                        start_at_line_number,
-                        machine_override: task.machine_override,
-                        content: Some(content),
-                    }
-                )
-            ),
+            payload: Some(spool_task_payload(&task, client_id)),
         };
 
         let this = self.send_message(message).await?;
@@ -99,6 +70,114 @@ impl Machine {
         task.status = TaskStatus::Created(Created { sent_to_driver: true });
         task.update(&this.db).await?;
 
+        if task.is_print() {
+            this.set_bed_clear(false).await?;
+        }
+
         Ok((this, task))
     }
 }
+
+/// The message that starts the task on the driver. Paused and recovered tasks start after their
+/// despooled line and their scheduled actions and cancelled objects are sent each time they are
+/// spooled.
+fn spool_task_payload(task: &Task, client_id: String) -> server_message::Payload {
+    let start_at_line_number = task.despooled_line_number
+        .map(|n| n + 1)
+        .unwrap_or(0);
+
+    let spool_task = |content| {
+        server_message::Payload::SpoolTask(
+            server_message::SpoolTask {
+                task_id: task.id.clone(),
+                client_id,
+                start_at_line_number,
+                machine_override: task.machine_override,
+                content: Some(content),
+                line_actions: task.line_actions(),
+                skipped_lines: task.skipped_lines(),
+            }
+        )
+    };
+
+    match &task.content {
+        TaskContent::FilePath(file_path) => {
+            spool_task(server_message::spool_task::Content::FilePath(
+                file_path.clone().into_os_string().into_string().unwrap(),
+            ))
+        }
+        TaskContent::GCodes(gcodes) => {
+            spool_task(server_message::spool_task::Content::Inline(
+                server_message::InlineContent {
+                    commands: gcodes.clone(),
+                },
+            ))
+        }
+        // SD card prints are run by the machine's firmware
+        TaskContent::SDCardFile(filename) => {
+            server_message::Payload::StartSdCardPrint(
+                server_message::StartSdCardPrint {
+                    task_id: task.id.clone(),
+                    filename: filename.clone(),
+                }
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use crate::task::GCodeAnnotation;
+    use super::*;
+
+    #[test]
+    fn resumes_paused_tasks_without_their_cancelled_objects() {
+        let object = |object: u32, end_line_number: u64| GCodeAnnotation::Object {
+            object,
+            name: format!("object_{}", object),
+            end_line_number,
+        };
+
+        let mut task = Task {
+            id: "task".to_string(),
+            version: 0,
+            created_at: Utc::now(),
+            deleted_at: None,
+            machine_id: "machine".to_string(),
+            part_id: Some("part".to_string()),
+            content: TaskContent::GCodes(vec!["G1 X1 E1".to_string(); 8]),
+            annotations: vec![
+                (2, object(0, 4)),
+                (4, object(1, 6)),
+                (6, object(0, 8)),
+            ],
+            total_lines: 8,
+            despooled_line_number: None,
+            machine_override: false,
+            estimated_print_time: None,
+            print_time_table: Default::default(),
+            time_blocked: Default::default(),
+            time_paused: Default::default(),
+            estimated_filament_meters: None,
+            material_ids: vec![],
+            status: Default::default(),
+            scheduled_actions: vec![],
+            cancelled_objects: vec![0],
+        };
+
+        // The task is paused after its first cancelled range and then resumed
+        task.despooled_line_number = Some(4);
+
+        let spool_task = match spool_task_payload(&task, "client".to_string()) {
+            server_message::Payload::SpoolTask(spool_task) => spool_task,
+            payload => panic!("Expected SpoolTask, got: {:?}", payload),
+        };
+
+        assert_eq!(spool_task.start_at_line_number, 5);
+        assert_eq!(spool_task.skipped_lines, vec![
+            server_message::LineRange { start: 2, end: 4 },
+            server_message::LineRange { start: 6, end: 8 },
+        ]);
+    }
+}
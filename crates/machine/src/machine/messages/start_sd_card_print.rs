@@ -0,0 +1,72 @@
+use chrono::prelude::*;
+use teg_json_store::Record as _;
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use crate::machine::{Machine, MachineStatus, Printing};
+use crate::task::{Task, TaskStatus};
+
+/// Starts printing a file from the machine's SD card or resumes a paused SD card print.
+#[xactor::message(result = "Result<Task>")]
+pub struct StartSDCardPrint {
+    pub task_id: crate::DbId,
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<StartSDCardPrint> for Machine {
+    async fn handle(
+        &mut self,
+        ctx: &mut xactor::Context<Self>,
+        msg: StartSDCardPrint,
+    ) -> Result<Task> {
+        let mut tx = self.db.begin().await?;
+        // Re-fetch the task within the transaction
+        let mut task = Task::get(&mut tx, &msg.task_id, false).await?;
+
+        if !task.is_sd_card_print() {
+            Err(eyre!("Task #{} is not an SD card print", task.id))?;
+        }
+
+        if task.status.is_settled() {
+            Err(eyre!("Cannot start a task that is {}", task.status.to_db_str()))?;
+        }
+
+        self.get_data()?.status.verify_can_start(&task, false)?;
+
+        if let TaskStatus::Paused(paused_status) = &task.status {
+            // Update the amount of time the task has been paused
+            task.time_paused += (Utc::now() - paused_status.paused_at).to_std()?;
+            task.status = TaskStatus::Created(Default::default());
+        }
+
+        task.update(&mut tx).await?;
+        tx.commit().await?;
+
+        let machine_id = self.id.clone();
+
+        async move {
+            let (self, task) = self.spool_task(task).await?;
+
+            // Update the machine status
+            self.get_data()?.status = MachineStatus::Printing(Printing {
+                task_id: task.id.clone(),
+                paused: false,
+                paused_state: None,
+            });
+
+            info!("Started SD Card Print #{}", task.id);
+
+            Result::<_>::Ok(task)
+        }
+            .await
+            .map_err(|err| {
+                error!("Error starting SD card print on machine #{}: {:?}", machine_id, err);
+                ctx.stop(Some(err));
+
+                eyre!("Unable to start SD card print due to internal error")
+            })
+    }
+}
@@ -116,6 +116,7 @@ impl xactor::Handler<UpdateComponent> for Machine {
 
         let data = self.get_data()?;
         data.config.save_config().await?;
+        self.publish_data_change();
         ctx.address().send(ResetWhenIdle)?;
 
         Ok(())
@@ -38,6 +38,7 @@ impl xactor::Handler<UpdatePlugin> for Machine {
         plugin.model_version += 1;
 
         data.config.save_config().await?;
+        self.publish_data_change();
         ctx.address().send(ResetWhenIdle)?;
 
         // Need an immutable reference to the containing Plugin enum to send to the machine hooks
@@ -8,6 +8,7 @@ pub use machine::{
     Machine,
     MachineData,
     PositioningUnits,
+    MACHINE_DATA_CHANGES,
 };
 
 pub mod machine_hooks;
@@ -31,4 +32,10 @@ pub use gcode_history_entry::{
     GCodeHistoryDirection,
 };
 
+mod sd_card_file;
+pub use sd_card_file::SDCardFile;
+
+mod firmware_prompt;
+pub use firmware_prompt::FirmwarePrompt;
+
 mod send_message;
@@ -22,7 +22,7 @@ use crate::components::{
     Component
 };
 use crate::plugins::Plugin;
-use crate::machine::GCodeHistoryEntry;
+use crate::machine::{FirmwarePrompt, GCodeHistoryEntry, SDCardFile};
 use super::machine_error_resolvers::MachineError;
 
 #[derive(async_graphql::InputObject, Debug, Default)]
@@ -146,6 +146,16 @@ impl MachineData {
 
     async fn motors_enabled(&self) -> bool { self.motors_enabled }
 
+    /// True if the previous print has been removed from the build platform. The farm scheduler
+    /// only starts queued prints on automatic printing machines with a clear bed.
+    async fn bed_clear(&self) -> bool { self.bed_clear }
+
+    /// The tags used to match queued parts to this machine
+    async fn tags(&self) -> FieldResult<&Vec<String>> {
+        let tags = &self.config.core_plugin()?.model.tags;
+        Ok(tags)
+    }
+
     async fn error(&self) -> Option<MachineError> {
         if let MachineStatus::Errored(error) = &self.status {
             Some(MachineError {
@@ -178,4 +188,17 @@ impl MachineData {
             .take(limit)
             .collect()
     }
+
+    /// The files on the machine's SD card. Null until the files have been listed with the
+    /// listSDCardFiles mutation.
+    #[graphql(name = "sdCardFiles")]
+    async fn sd_card_files(&self) -> Option<&Vec<SDCardFile>> {
+        self.sd_card_files.as_ref()
+    }
+
+    /// The prompt the machine's firmware is waiting on the user to answer (eg. to confirm that
+    /// filament has been loaded during a filament change)
+    async fn firmware_prompt(&self) -> Option<&FirmwarePrompt> {
+        self.firmware_prompt.as_ref()
+    }
 }
@@ -0,0 +1,112 @@
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use futures::{
+    future,
+    stream::{
+        self,
+        Stream,
+        StreamExt,
+    },
+};
+use teg_auth::{
+    AuthContext,
+    Permission,
+};
+use teg_json_store::ChangeFeed;
+
+use crate::machine::{
+    MachineData,
+    MACHINE_DATA_CHANGES,
+};
+use crate::task::Task;
+use super::machines_query_resolvers::{
+    MachineQuery,
+    MachinesInput,
+};
+
+#[derive(async_graphql::InputObject, Debug, Default)]
+struct TasksInput {
+    /// Optional filter: Return only the tasks that are associated with the given machine id
+    #[graphql(name="machineID")]
+    machine_id: Option<ID>,
+}
+
+#[derive(Default)]
+pub struct MachineSubscription;
+
+#[async_graphql::Subscription]
+impl MachineSubscription {
+    /// Live machines. Sends the current machines and then again whenever the machines change.
+    async fn machines<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: MachinesInput,
+    ) -> FieldResult<impl Stream<Item = FieldResult<Vec<MachineData>>> + 'ctx> {
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        // Machine actors publish their in-memory changes (eg. temperatures and positions) to the
+        // change feed alongside the database's task changes.
+        let machine_id = input.machine_id.as_ref().map(|id| id.to_string());
+
+        let changes = change_feed.watch_tables(&["tasks", MACHINE_DATA_CHANGES])
+            .filter(move |change| {
+                // Skip changes to other machines when subscribed to a single machine
+                let is_other_machine = change.table == MACHINE_DATA_CHANGES
+                    && machine_id.as_ref().map(|id| id != &change.id).unwrap_or(false);
+
+                future::ready(!is_other_machine)
+            })
+            .ready_chunks(1024);
+
+        let stream = stream::once(future::ready(vec![]))
+            .chain(changes)
+            .then(move |_| {
+                (&MachineQuery).machines(ctx, input.clone())
+            });
+
+        Ok(stream)
+    }
+
+    /// Sends each task as it is created or updated
+    async fn tasks<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: TasksInput,
+    ) -> FieldResult<impl Stream<Item = Task> + 'ctx> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        auth.authorize(Permission::View)?;
+
+        let machine_id = input.machine_id.map(|id| id.to_string());
+
+        let stream = change_feed.watch::<Task>(db)
+            .filter_map(move |change| {
+                let task = match change {
+                    Ok(change) => change.record,
+                    Err(err) => {
+                        warn!("Error loading changed task: {:?}", err);
+                        None
+                    }
+                };
+
+                let task = task.filter(|task| {
+                    machine_id
+                        .as_ref()
+                        .map(|machine_id| &task.machine_id == machine_id)
+                        .unwrap_or(true)
+                        && auth.can_view_machine(&task.machine_id)
+                });
+
+                future::ready(task)
+            });
+
+        Ok(stream)
+    }
+}
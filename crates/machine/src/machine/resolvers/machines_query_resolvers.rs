@@ -6,6 +6,10 @@ use async_graphql::{
     Context,
 };
 use xactor::Addr;
+use teg_auth::{
+    AuthContext,
+    Permission,
+};
 use eyre::{
     eyre,
     Result,
@@ -21,11 +25,11 @@ use crate::machine::{
     // TaskStatus,
 };
 
-#[derive(async_graphql::InputObject, Debug, Default)]
-struct MachinesInput {
+#[derive(async_graphql::InputObject, Debug, Default, Clone)]
+pub(crate) struct MachinesInput {
     /// Optional filter: Return the machine by id
     #[graphql(name="machineID")]
-    machine_id: Option<async_graphql::ID>,
+    pub(crate) machine_id: Option<async_graphql::ID>,
 }
 
 #[derive(Default)]
@@ -46,22 +50,30 @@ impl MachineQuery {
     }
 
     #[instrument(skip(self, ctx))]
-    async fn machines<'ctx>(
+    pub(crate) async fn machines<'ctx>(
         &self,
         ctx: &'ctx Context<'_>,
         #[graphql(default)]
         input: MachinesInput,
     ) -> FieldResult<Vec<MachineData>> {
+        let auth: &AuthContext = ctx.data()?;
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
 
+        auth.authorize(Permission::View)?;
+
         let machines: Vec<&Addr<Machine>> = if let Some(id) = input.machine_id {
             let addr = machines.get(&id)
+                .filter(|_| auth.can_view_machine(&id))
                 .ok_or_else(|| eyre!("Machine not found ({:?})", id))?;
 
             vec![addr]
         } else {
-            machines.values().collect()
+            machines
+                .iter()
+                .filter(|(id, _)| auth.can_view_machine(id))
+                .map(|(_, addr)| addr)
+                .collect()
         };
 
         let machines = machines
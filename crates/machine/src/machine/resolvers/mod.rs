@@ -1,4 +1,5 @@
 pub mod machine_resolvers;
 pub mod machines_query_resolvers;
+pub mod machine_subscription_resolvers;
 pub mod mutation_resolvers;
 pub mod machine_error_resolvers;
@@ -11,9 +11,16 @@ use eyre::{
 };
 use teg_auth::{
     AuthContext,
+    Permission,
 };
 use teg_common::Void;
-use teg_json_store::{JsonRow, Record as _};
+use teg_json_store::{
+    ChangeFeed,
+    ChangeOperation,
+    JsonRow,
+    Record as _,
+    RecordChange,
+};
 
 use crate::machine::{
     MachineViewer,
@@ -27,7 +34,7 @@ use crate::{
         CombinedConfigView,
         MachineConfig,
     },
-    machine::{Machine, MachineData},
+    machine::{Machine, MachineData, MACHINE_DATA_CHANGES},
 };
 
 #[derive(async_graphql::InputObject, Debug)]
@@ -55,6 +62,9 @@ impl MachineMutation {
         #[graphql(name = "machineID")]
         machine_id: ID,
     ) -> FieldResult<MachineData> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&machine_id, Permission::ControlMachine)?;
+
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
 
@@ -75,6 +85,9 @@ impl MachineMutation {
         #[graphql(name = "machineID")]
         machine_id: ID,
     ) -> FieldResult<MachineData> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&machine_id, Permission::ControlMachine)?;
+
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
 
@@ -87,6 +100,55 @@ impl MachineMutation {
         Ok(machine_data)
     }
 
+    /// Answers the machine's firmware prompt by pressing the button at buttonIndex
+    #[instrument(skip(self, ctx))]
+    async fn respond_to_prompt<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(name = "machineID")]
+        machine_id: ID,
+        button_index: u32,
+    ) -> FieldResult<MachineData> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&machine_id, Permission::ControlMachine)?;
+
+        let machines: &crate::MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        let machine = machines.get(&machine_id)
+            .ok_or_else(|| eyre!("Machine #{:?} not found", machine_id))?;
+
+        machine.call(messages::RespondToPrompt { button_index }).await??;
+        let machine_data = machine.call(messages::GetData).await??;
+
+        Ok(machine_data)
+    }
+
+    /// Confirms that the previous print has been removed from the machine's build platform so
+    /// that the farm scheduler can start the next queued print on it if automatic printing is
+    /// enabled
+    #[instrument(skip(self, ctx))]
+    async fn confirm_bed_clear<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(name = "machineID")]
+        machine_id: ID,
+    ) -> FieldResult<MachineData> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&machine_id, Permission::ControlMachine)?;
+
+        let machines: &crate::MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        let machine = machines.get(&machine_id)
+            .ok_or_else(|| eyre!("Machine #{:?} not found", machine_id))?;
+
+        machine.call(messages::ConfirmBedClear).await??;
+        let machine_data = machine.call(messages::GetData).await??;
+
+        Ok(machine_data)
+    }
+
     #[instrument(skip(self, ctx))]
     async fn continue_viewing_machine<'ctx>(
         &self,
@@ -97,7 +159,7 @@ impl MachineMutation {
         let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        let user = auth.require_authorized_user()?;
+        let user = auth.authorize_machine(&machine_id, Permission::View)?;
 
         let machine_id = machine_id.parse::<crate::DbId>()
             .with_context(|| format!("Invalid machine id: {:?}", machine_id))?;
@@ -143,6 +205,7 @@ impl MachineMutation {
         let auth: &AuthContext = ctx.data()?;
         let machines_store: &crate::MachineMap = ctx.data()?;
         let machine_hooks: &crate::MachineHooksList = ctx.data()?;
+        let change_feed: &ChangeFeed = ctx.data()?;
 
         async move {
             // dbg!(&input.model);
@@ -212,6 +275,7 @@ impl MachineMutation {
             let machine = Machine::start(
                 db_clone,
                 machine_hooks.clone(),
+                change_feed.clone(),
                 &machine_id
             )
                 .await?;
@@ -223,6 +287,13 @@ impl MachineMutation {
                 machines
             });
 
+            // Add the machine to live machine subscriptions
+            change_feed.publish(RecordChange {
+                table: MACHINE_DATA_CHANGES.to_string(),
+                operation: ChangeOperation::Insert,
+                id: machine_id.clone(),
+            });
+
             for hooks_provider in machine_hooks.iter() {
                 hooks_provider.after_create(
                     &machine_id,
@@ -249,7 +320,7 @@ impl MachineMutation {
     ) -> FieldResult<MachineData> {
         let auth: &AuthContext = ctx.data()?;
 
-        auth.require_authorized_user()?;
+        auth.authorize_machine(&input.machine_id, Permission::ConfigureMachine)?;
 
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -286,9 +357,10 @@ impl MachineMutation {
     ) -> FieldResult<Option<Void>> {
         let auth: &AuthContext = ctx.data()?;
 
-        auth.require_authorized_user()?;
+        auth.authorize(Permission::Administer)?;
 
         let machines_store: &crate::MachineMap = ctx.data()?;
+        let change_feed: &ChangeFeed = ctx.data()?;
         let machines = machines_store.load();
 
         async move {
@@ -303,6 +375,13 @@ impl MachineMutation {
                 machines
             });
 
+            // Remove the machine from live machine subscriptions
+            change_feed.publish(RecordChange {
+                table: MACHINE_DATA_CHANGES.to_string(),
+                operation: ChangeOperation::Delete,
+                id: machine_id.to_string(),
+            });
+
             eyre::Result::<_>::Ok(None)
         }
             // log the backtrace which is otherwise lost by FieldResult
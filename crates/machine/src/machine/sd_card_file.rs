@@ -0,0 +1,18 @@
+use teg_protobufs::machine_message;
+
+/// A file on the machine's SD card
+#[derive(async_graphql::SimpleObject, Debug, Clone)]
+pub struct SDCardFile {
+    pub filename: String,
+    /// The size of the file in bytes if the firmware reports file sizes
+    pub size: Option<u64>,
+}
+
+impl From<&machine_message::SdCardFile> for SDCardFile {
+    fn from(file: &machine_message::SdCardFile) -> Self {
+        Self {
+            filename: file.filename.clone(),
+            size: Some(file.size).filter(|size| *size > 0),
+        }
+    }
+}
@@ -59,7 +59,10 @@ impl StreamHandler<RxResult> for Machine
         if let Err(err) = result {
             error!("Restarting machine #{} due to rx error: {:?}", self.id, err);
             ctx.stop(Some(err));
+            return
         };
+
+        self.publish_data_change();
     }
 
     async fn started(&mut self, _ctx: &mut XContext<Self>) {
@@ -103,6 +106,8 @@ impl StreamHandler<RxResult> for Machine
             self.data
                 .as_mut()
                 .map(|data| data.status = MachineStatus::Stopped);
+
+            self.publish_data_change();
         }
 
         if let Err(err) = ctx.address().send(ConnectToSocket) {
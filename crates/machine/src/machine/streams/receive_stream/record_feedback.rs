@@ -1,5 +1,8 @@
 use chrono::{ prelude::*, Duration };
-use machine::messages::DeleteTaskHistory;
+use machine::messages::{
+    DeleteTaskHistory,
+    StopMachine,
+};
 use xactor::{Service as _};
 use eyre::{
     eyre,
@@ -8,9 +11,9 @@ use eyre::{
 };
 use teg_protobufs::{
     MachineFlags,
-    machine_message::{self, Status},
+    machine_message::{self, HostAction, Status, TaskProgress},
 };
-use teg_json_store::{ Record as _ };
+use teg_json_store::Record as _;
 
 use machine_message::Feedback;
 use crate::{machine::{
@@ -23,7 +26,7 @@ use crate::{machine::{
     MachineData,
     MachineStatus,
     Printing,
-    events::TaskSettled,
+    events::{MachineReady, TaskSettled},
 }, task::Created};
 use crate::task::{
     Task,
@@ -37,6 +40,9 @@ use crate::components::{
     // SpeedController,
 };
 
+/// The number of times a task is reloaded after a version conflict before giving up
+const MAX_UPDATE_ATTEMPTS: usize = 3;
+
 pub async fn record_feedback(
     machine: &mut Machine,
     feedback: Feedback,
@@ -45,16 +51,37 @@ pub async fn record_feedback(
     let db = machine.db.clone();
     let now = Utc::now();
 
+    let was_ready = machine.data
+        .as_ref()
+        .map(|data| data.status == MachineStatus::Ready)
+        .unwrap_or(false);
+
     update_tasks(machine, &db, &feedback, &now, ctx).await?;
 
     let machine_data = machine.get_data()?;
 
-    update_heaters(machine_data, &feedback, &now).await?;
+    let heater_fault = update_heaters(machine_data, &feedback, &now).await?;
     update_axes(machine_data, &feedback).await?;
     update_speed_controllers(machine_data, &feedback).await?;
+    update_sd_card(machine_data, &feedback).await?;
+    update_firmware_prompt(machine_data, &feedback).await?;
 
     update_machine(&db, machine, &feedback, &now, ctx).await?;
 
+    if let Some(message) = heater_fault {
+        stop_for_heater_fault(machine, &db, message, &now, ctx).await?;
+    }
+
+    run_host_actions(machine, &feedback, ctx).await?;
+
+    // Notify the farm scheduler that the machine can start the next print
+    if !was_ready && machine.get_data()?.status == MachineStatus::Ready {
+        let mut broker = xactor::Broker::from_registry().await?;
+        broker.publish(MachineReady {
+            machine_id: machine.id.clone(),
+        })?;
+    }
+
     machine.has_received_feedback = true;
     Ok(())
 }
@@ -85,11 +112,15 @@ pub async fn update_tasks(
                     .iter()
                     .any(|p| p.task_id == task.id)
             {
-                let error_message = format!(
+                let mut error_message = format!(
                     "Task (#{}) missing from driver, possibly due to driver crash.",
                     task.id
                 );
 
+                if task.is_print() && !task.is_sd_card_print() {
+                    error_message.push_str(" The print can be recovered from where it stopped.");
+                }
+
                 warn!("{}", error_message);
 
                 task.status = TaskStatus::Errored(task::Errored {
@@ -126,10 +157,25 @@ pub async fn update_tasks(
 
     for progress in feedback.task_progress.iter() {
         let status = TaskStatus::from_task_progress(&progress, &feedback.error)?;
+        let mut status_changed = false;
 
-        let mut task = if let
-            Ok(task) = Task::get(db, &progress.task_id, true).await
-        {
+        // The feedback can race a mutation that changes the same task (eg. pausing the print). If
+        // the task was changed since it was loaded it is reloaded and the feedback re-applied.
+        let task = Task::update_with_retry(
+            db,
+            &progress.task_id,
+            true,
+            MAX_UPDATE_ATTEMPTS,
+            |task| {
+                trace!("Task #{} status: {:?}", task.id, status);
+                status_changed = apply_task_progress(task, &status, progress, feedback);
+
+                // Settled tasks are updated by settle_task
+                Ok(!(status_changed && task.status.is_settled()))
+            },
+        ).await?;
+
+        let mut task = if let Some(task) = task {
             task
         } else {
             warn!(
@@ -139,21 +185,6 @@ pub async fn update_tasks(
             continue
         };
 
-        trace!("Task #{} status: {:?}", task.id, status);
-        task.despooled_line_number = Some(progress.despooled_line_number as u64);
-
-        let status_changed = if
-            !task.status.is_settled()
-            // Prevent re-setting paused_at
-            && !(task.status.is_paused() && status.is_paused())
-        {
-            // Update the task status if it has changed and was not previously settled
-            task.status = status;
-            true
-        } else {
-            false
-        };
-
         // When a task is settled
         if status_changed && task.status.is_settled() {
             // Update the task and delete any temporary files
@@ -175,6 +206,13 @@ pub async fn update_tasks(
                     ..
                 }) if task_id == &task.id => {
                     info!("Print #{} Completed!", task.id);
+
+                    // Automatic printing machines clear their own beds after each print
+                    let bed_clear = task.status.was_successful()
+                        && machine.data_ref()?.config.core_plugin()?.model.automatic_printing;
+
+                    machine.set_bed_clear(bed_clear).await?;
+
                     // Check if a new task was started (eg. by automatic printing)
                     let next_task = Task::tasks_running_on_machine(
                         db,
@@ -197,8 +235,6 @@ pub async fn update_tasks(
                 }
                 _ => ()
             };
-        } else {
-            task.update(db).await?;
         }
 
         // Notify listeners
@@ -253,21 +289,73 @@ pub async fn update_tasks(
     Ok(())
 }
 
+/// Copies the driver's progress onto the task. Returns true if the task's status changed.
+fn apply_task_progress(
+    task: &mut Task,
+    status: &TaskStatus,
+    progress: &TaskProgress,
+    feedback: &Feedback,
+) -> bool {
+    task.despooled_line_number = Some(progress.despooled_line_number as u64);
+
+    // SD card print progress is reported in bytes
+    if let Some(sd_card_print) = feedback.sd_card
+        .as_ref()
+        .and_then(|sd_card| sd_card.print.as_ref())
+        .filter(|print| print.task_id == task.id)
+    {
+        task.total_lines = sd_card_print.total_bytes;
+    }
+
+    if
+        !task.status.is_settled()
+        // Prevent re-setting paused_at
+        && !(task.status.is_paused() && status.is_paused())
+    {
+        // Update the task status if it has changed and was not previously settled
+        task.status = status.clone();
+        true
+    } else {
+        false
+    }
+}
+
+/// Updates the heaters and checks them for thermal runaway and other faults. Returns a
+/// description of the first fault found.
 pub async fn update_heaters(
     machine: &mut MachineData,
     feedback: &Feedback,
     now: &DateTime<Utc>,
-) -> Result<()> {
+) -> Result<Option<String>> {
+    let is_driver_ready = machine.status.is_driver_ready();
+    let mut fault = None;
+
     for h in feedback.heaters.iter() {
+        let limits = machine.config.get_heater_limits(&h.address);
         let heater = machine.config.get_heater_mut(&h.address);
 
-        let heater = if let Some(heater) = heater {
-            heater
+        let (heater, (name, limits)) = if let (Some(heater), Some(limits)) = (heater, limits) {
+            (heater, limits)
         } else {
             warn!("Heater not found: {}", h.address);
             continue
         };
 
+        // Only check the heaters while the machine is running so that a fault stops it once
+        if is_driver_ready && fault.is_none() {
+            let result = heater.watchdog.check(
+                &name,
+                h.target_temperature,
+                h.actual_temperature,
+                *now,
+                &limits,
+            );
+
+            if let Err(err) = result {
+                fault = Some(err.to_string());
+            }
+        }
+
         let history = &mut heater.history;
 
         // record a data point once every half second
@@ -299,6 +387,44 @@ pub async fn update_heaters(
         heater.blocking = h.blocking;
     }
 
+    Ok(fault)
+}
+
+/// E-stops the machine and errors its tasks after the heater watchdog detects a fault
+pub async fn stop_for_heater_fault(
+    machine: &mut Machine,
+    db: &crate::Db,
+    message: String,
+    now: &DateTime<Utc>,
+    ctx: &mut xactor::Context<Machine>,
+) -> Result<()> {
+    error!("Stopping machine #{}: {}", machine.id, message);
+
+    machine.send_message(StopMachine.into()).await?;
+
+    let err = Errored {
+        errored_at: now.clone(),
+        message,
+    };
+
+    let tasks = Task::tasks_running_on_machine(db, &machine.id).await?;
+
+    for mut task in tasks {
+        task.status = TaskStatus::Errored(err.clone());
+
+        let tx = db.begin().await?;
+        let machine_hooks = machine.hooks.clone();
+
+        task.settle_task(
+            tx,
+            &machine_hooks,
+            machine.data_ref()?,
+            &ctx.address(),
+        ).await?;
+    }
+
+    machine.get_data()?.status = MachineStatus::Errored(err);
+
     Ok(())
 }
 
@@ -356,6 +482,58 @@ pub async fn update_speed_controllers(machine: &mut MachineData, feedback: &Feed
     Ok(())
 }
 
+pub async fn update_sd_card(
+    machine: &mut MachineData,
+    feedback: &Feedback,
+) -> Result<()> {
+    // The SD card's files are only included in the feedback after they have been listed
+    if let Some(sd_card) = feedback.sd_card.as_ref().filter(|sd_card| sd_card.files_listed) {
+        machine.sd_card_files = Some(
+            sd_card.files
+                .iter()
+                .map(Into::into)
+                .collect()
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn update_firmware_prompt(
+    machine: &mut MachineData,
+    feedback: &Feedback,
+) -> Result<()> {
+    machine.firmware_prompt = feedback.prompt.as_ref().map(Into::into);
+
+    Ok(())
+}
+
+/// Passes the pause, resume and cancel requests sent by the firmware (eg. on filament runout) to
+/// the machine hooks
+pub async fn run_host_actions(
+    machine: &mut Machine,
+    feedback: &Feedback,
+    ctx: &mut xactor::Context<Machine>,
+) -> Result<()> {
+    for action in feedback.host_actions.iter() {
+        let action = HostAction::from_i32(*action)
+            .ok_or_else(|| eyre!("Invalid host action: {:?}", action))?;
+
+        let machine_hooks = machine.hooks.clone();
+
+        for machine_hook in machine_hooks.iter() {
+            machine_hook.after_host_action(
+                &machine_hooks,
+                machine.data_ref()?,
+                ctx.address(),
+                action,
+            ).await?;
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn update_machine(
     db: &crate::Db,
     machine: &mut Machine,
@@ -421,10 +599,14 @@ pub async fn update_machine(
 
     let machine_data = machine.get_data()?;
 
-    // Do not reset the machine status to ready while it is printing
+    let is_errored = matches!(machine_data.status, MachineStatus::Errored(_));
+
+    // Do not reset the machine status to ready while it is printing and do not replace the
+    // error of a machine stopped by the heater watchdog
     if
         machine_data.status != next_status &&
-        !(machine_data.status.is_printing() && next_status == MachineStatus::Ready)
+        !(machine_data.status.is_printing() && next_status == MachineStatus::Ready) &&
+        !(is_errored && next_status == MachineStatus::Stopped)
     {
         info!("Printer status changed from {:?} to {:?}", machine_data.status, next_status);
         machine_data.status = next_status;
@@ -51,6 +51,24 @@ pub struct CorePluginConfig {
     /// # Before Resume (GCode)
     pub resume_hook: String,
 
+    /// # Slicer
+    /// The slicer used to slice 3D models for this printer.
+    #[serde(default)]
+    pub slicer: SlicerEngine,
+
+    /// # Slicing Profile
+    /// The default slicing profile for this printer. Materials with their own slicing profile
+    /// take precedence over this profile when they are loaded.
+    #[serde(default)]
+    #[serde(rename = "slicingProfileID")]
+    pub slicing_profile_id: Option<crate::DbId>,
+
+    /// # Tags
+    /// Parts that require tags (eg. "nozzle-0.6mm" or "enclosed") are only printed on machines
+    /// with all of those tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
     /// # Developer Mode
     /// Show settings & debugging tools intended for developers.
     #[serde(default)]
@@ -75,7 +93,28 @@ impl teg_config_form::Model for CorePluginConfig {
             "afterPrintHook",
             "pauseHook",
             "resumeHook",
+            "slicer",
+            "slicingProfileID",
+            "tags",
             "developerMode",
         ])
     }
 }
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Copy, Clone, PartialEq)]
+pub enum SlicerEngine {
+    #[serde(rename = "CuraEngine")]
+    CuraEngine,
+    #[serde(rename = "PrusaSlicer")]
+    PrusaSlicer,
+    #[serde(rename = "SuperSlicer")]
+    SuperSlicer,
+    #[serde(rename = "belt-engine")]
+    BeltEngine,
+}
+
+/// belt-engine was the only slicer before slicers were configurable so it remains the default for
+/// existing machines.
+impl Default for SlicerEngine {
+    fn default() -> Self { SlicerEngine::BeltEngine }
+}
@@ -1,6 +1,7 @@
 use std::{pin::Pin, sync::Arc};
 use futures::Future;
 use xactor::Actor;
+use teg_protobufs::machine_message::HostAction;
 use eyre::{
     // eyre,
     Result,
@@ -125,4 +126,14 @@ impl MachineHooks for SignallingUpdaterMachineHooks {
     ) -> Result<Option<Pin<Box<dyn Future<Output = ()> + Send>>>> {
         Ok(None)
     }
+
+    async fn after_host_action(
+        &self,
+        _machine_hooks: &MachineHooksList,
+        _machine_data: &MachineData,
+        _machine_addr: xactor::Addr<Machine>,
+        _action: HostAction,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
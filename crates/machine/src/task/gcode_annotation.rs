@@ -2,5 +2,12 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum GCodeAnnotation {
-    SetToolheadMaterials()
+    SetToolheadMaterials(),
+    /// The start of a layer. Layers are numbered from 1.
+    ///
+    /// z is the height of the layer or None if it could not be determined.
+    Layer { layer: u32, z: Option<f32> },
+    /// The start of a range of lines printing an object. Objects are numbered from 0 in the
+    /// order they are first printed and most objects are printed in a range per layer.
+    Object { object: u32, name: String, end_line_number: u64 },
 }
@@ -0,0 +1,259 @@
+use std::{
+    fs::File,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        BufWriter,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// The byte offset of every INDEX_INTERVAL'th line is recorded in a GCode file's index
+pub const INDEX_INTERVAL: u64 = 1024;
+
+const OFFSET_BYTES: u64 = std::mem::size_of::<u64>() as u64;
+
+/// The path of the line offset index of a GCode file
+pub fn index_path<P: AsRef<Path>>(gcode_file_path: P) -> PathBuf {
+    let mut path = gcode_file_path.as_ref().as_os_str().to_owned();
+    path.push(".index");
+
+    path.into()
+}
+
+/// Writes the line offset index of a GCode file as it is written. The index is a list of little
+/// endian u64 byte offsets for lines 0, INDEX_INTERVAL, 2 * INDEX_INTERVAL, etc.
+pub struct GCodeIndexWriter {
+    writer: BufWriter<File>,
+    line_number: u64,
+    byte_offset: u64,
+}
+
+impl GCodeIndexWriter {
+    pub fn create<P: AsRef<Path>>(gcode_file_path: P) -> io::Result<Self> {
+        let file = File::create(index_path(gcode_file_path))?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            line_number: 0,
+            byte_offset: 0,
+        })
+    }
+
+    /// Records the next line of the GCode file. `len` is the length of the line in bytes
+    /// including its newline.
+    pub fn push_line(&mut self, len: u64) -> io::Result<()> {
+        if self.line_number % INDEX_INTERVAL == 0 {
+            self.writer.write_all(&self.byte_offset.to_le_bytes())?;
+        }
+
+        self.line_number += 1;
+        self.byte_offset += len;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A buffered iterator over the lines of a GCode file. Only the buffer is held in memory so
+/// GCode files of any size can be read.
+///
+/// Clones reopen the file at the clone's position when they are first read.
+#[derive(Debug)]
+pub struct GCodeFile {
+    path: PathBuf,
+    byte_offset: u64,
+    reader: Option<BufReader<File>>,
+}
+
+impl Clone for GCodeFile {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            byte_offset: self.byte_offset,
+            reader: None,
+        }
+    }
+}
+
+impl GCodeFile {
+    /// Opens the GCode file at a (zero-indexed) line number. If the file has an index it is used
+    /// to seek to the nearest indexed line, otherwise the lines before line_number are read and
+    /// discarded.
+    pub fn open_at_line<P: Into<PathBuf>>(path: P, line_number: u64) -> io::Result<Self> {
+        let path = path.into();
+        let (indexed_line_number, byte_offset) = Self::indexed_offset(&path, line_number)?;
+
+        let mut gcode_file = Self {
+            path,
+            byte_offset,
+            reader: None,
+        };
+
+        for _ in indexed_line_number..line_number {
+            if gcode_file.read_line()?.is_none() {
+                break
+            }
+        }
+
+        Ok(gcode_file)
+    }
+
+    /// Returns the line number and byte offset of the last indexed line at or before line_number
+    fn indexed_offset(path: &Path, line_number: u64) -> io::Result<(u64, u64)> {
+        let mut index = match File::open(index_path(path)) {
+            Ok(index) => index,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(err) => return Err(err),
+        };
+
+        let entries = index.metadata()?.len() / OFFSET_BYTES;
+
+        if entries == 0 {
+            return Ok((0, 0))
+        }
+
+        let entry = std::cmp::min(line_number / INDEX_INTERVAL, entries - 1);
+
+        let mut bytes = [0u8; OFFSET_BYTES as usize];
+        index.seek(SeekFrom::Start(entry * OFFSET_BYTES))?;
+        index.read_exact(&mut bytes)?;
+
+        Ok((entry * INDEX_INTERVAL, u64::from_le_bytes(bytes)))
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => {
+                let mut file = File::open(&self.path)?;
+                file.seek(SeekFrom::Start(self.byte_offset))?;
+
+                self.reader.get_or_insert(BufReader::new(file))
+            }
+        };
+
+        let mut line = String::new();
+        let len = reader.read_line(&mut line)?;
+
+        if len == 0 {
+            return Ok(None)
+        }
+
+        self.byte_offset += len as u64;
+
+        // Strip the newline the same way as BufRead::lines
+        if line.ends_with('\n') {
+            line.pop();
+
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+impl Iterator for GCodeFile {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_line().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_gcode_file(name: &str, total_lines: u64, indexed: bool) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "teg-gcode-index-{}-{}.gcode",
+            std::process::id(),
+            name,
+        ));
+
+        let mut writer = BufWriter::new(File::create(&path).unwrap());
+        let mut index = GCodeIndexWriter::create(&path).unwrap();
+
+        for line_number in 0..total_lines {
+            let line = format!("G1 X{}\n", line_number);
+
+            writer.write_all(line.as_bytes()).unwrap();
+            index.push_line(line.len() as u64).unwrap();
+        }
+
+        writer.flush().unwrap();
+        index.finish().unwrap();
+
+        if !indexed {
+            std::fs::remove_file(index_path(&path)).unwrap();
+        }
+
+        path
+    }
+
+    fn remove_gcode_file(path: PathBuf) {
+        let _ = std::fs::remove_file(index_path(&path));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn opens_indexed_files_at_any_line() {
+        let path = write_gcode_file("indexed", 3 * INDEX_INTERVAL + 10, true);
+
+        for line_number in [0, 1, INDEX_INTERVAL - 1, INDEX_INTERVAL, 3 * INDEX_INTERVAL + 9] {
+            let mut gcode_file = GCodeFile::open_at_line(&path, line_number).unwrap();
+
+            assert_eq!(
+                gcode_file.next().unwrap().unwrap(),
+                format!("G1 X{}", line_number),
+            );
+        }
+
+        let mut gcode_file = GCodeFile::open_at_line(&path, 3 * INDEX_INTERVAL + 10).unwrap();
+        assert!(gcode_file.next().is_none());
+
+        remove_gcode_file(path);
+    }
+
+    #[test]
+    fn opens_files_without_an_index() {
+        let path = write_gcode_file("unindexed", 2 * INDEX_INTERVAL, false);
+
+        let mut gcode_file = GCodeFile::open_at_line(&path, INDEX_INTERVAL + 1).unwrap();
+        assert_eq!(
+            gcode_file.next().unwrap().unwrap(),
+            format!("G1 X{}", INDEX_INTERVAL + 1),
+        );
+
+        remove_gcode_file(path);
+    }
+
+    #[test]
+    fn clones_continue_from_the_same_line() {
+        let path = write_gcode_file("clone", 10, true);
+
+        let mut gcode_file = GCodeFile::open_at_line(&path, 2).unwrap();
+        gcode_file.next();
+
+        let lines = gcode_file.clone().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "G1 X3");
+        assert_eq!(gcode_file.next().unwrap().unwrap(), "G1 X3");
+
+        remove_gcode_file(path);
+    }
+}
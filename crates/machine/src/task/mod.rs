@@ -9,8 +9,31 @@ pub use task_status::{
     Paused,
     Cancelled,
     Errored,
+    TaskStatusGQL,
 };
 
+mod gcode_index;
+pub use gcode_index::{
+    GCodeFile,
+    GCodeIndexWriter,
+    index_path as gcode_index_path,
+};
+
+mod scheduled_action;
+pub use scheduled_action::{
+    ActionTrigger,
+    ScheduledAction,
+};
+
+mod print_object;
+pub use print_object::PrintObject;
+
+mod print_time_table;
+pub use print_time_table::PrintTimeTable;
+
+mod print_recovery;
+pub use print_recovery::PrintRecoveryState;
+
 mod task;
 pub use task::*;
 
@@ -0,0 +1,8 @@
+/// An object on a print's build plate that can be cancelled individually
+#[derive(async_graphql::SimpleObject, Debug, Clone)]
+pub struct PrintObject {
+    pub id: async_graphql::ID,
+    /// The object's name from the slicer's object labels
+    pub name: String,
+    pub cancelled: bool,
+}
@@ -0,0 +1,503 @@
+use std::{
+    collections::BTreeMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use eyre::{
+    // eyre,
+    Result,
+    Context as _,
+};
+use teg_protobufs::MachineFlags;
+
+/// The distance (in mm) to raise the toolhead off of the print before re-homing X and Y
+const Z_HOP: f32 = 2.0;
+
+/// Feedrate (in mm/min) for the moves back to the recovered position
+const RETURN_FEEDRATE: f32 = 3_000.0;
+
+/// The addresses and GCode arguments of the X, Y and Z axes
+const AXES: [(&str, char); 3] = [("x", 'X'), ("y", 'Y'), ("z", 'Z')];
+
+/// The machine state needed to resume a print after the driver or the host crashes (or the
+/// machine loses power). Saved to disk by the driver while printing and reconstructed from the
+/// task's GCode by the server if the saved state is missing or out of date.
+///
+/// Positions are tracked in millimeters relative to the print's coordinate system (ie. after any
+/// G92 offsets).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintRecoveryState {
+    pub task_id: crate::DbId,
+    /// The last line of the task that was acknowledged by the machine
+    pub despooled_line_number: Option<u64>,
+    pub saved_at: DateTime<Utc>,
+    /// Heater target temperatures by address (eg. `e0` or `b`)
+    pub heater_targets: BTreeMap<String, f32>,
+    /// Fan speeds (0-255) by address (eg. `f0`)
+    pub fan_speeds: BTreeMap<String, f32>,
+    /// X, Y and Z target positions by address
+    pub positions: BTreeMap<String, f32>,
+    pub extruder_position: f32,
+    /// The last feedrate set by the print in mm/min
+    pub feedrate: Option<f32>,
+    pub absolute_extrusion: bool,
+    pub current_hotend_index: u32,
+    /// `MachineFlags` bits (motors enabled, absolute positioning and millimeters)
+    pub machine_flags: u64,
+}
+
+impl PrintRecoveryState {
+    pub fn new(task_id: crate::DbId) -> Self {
+        Self {
+            task_id,
+            despooled_line_number: None,
+            saved_at: Utc::now(),
+            heater_targets: BTreeMap::new(),
+            fan_speeds: BTreeMap::new(),
+            positions: BTreeMap::new(),
+            extruder_position: 0.0,
+            feedrate: None,
+            absolute_extrusion: true,
+            current_hotend_index: 0,
+            machine_flags: MachineFlags::default().bits(),
+        }
+    }
+
+    /// The path the driver saves the machine's print recovery state to
+    pub fn path(machine_id: &crate::DbId) -> PathBuf {
+        teg_common::paths::var()
+            .join("print-recovery")
+            .join(format!("machine-{}.json", machine_id))
+    }
+
+    /// Reconstructs the state of a print by replaying the lines of its GCode up to and including
+    /// the despooled line number
+    pub fn from_gcode_prefix<I>(
+        task_id: crate::DbId,
+        gcode_lines: I,
+        despooled_line_number: u64,
+    ) -> Result<Self>
+    where
+        I: Iterator<Item = std::io::Result<String>>,
+    {
+        let mut state = Self::new(task_id);
+        state.replay(gcode_lines, despooled_line_number)?;
+
+        Ok(state)
+    }
+
+    /// Advances the state by replaying the lines after it's despooled line number up to and
+    /// including the given despooled line number. `gcode_lines` starts at the line after the
+    /// state's despooled line number.
+    pub fn replay<I>(&mut self, gcode_lines: I, despooled_line_number: u64) -> Result<()>
+    where
+        I: Iterator<Item = std::io::Result<String>>,
+    {
+        let first_line_number = self.next_line_number();
+
+        for (line_number, gcode) in (first_line_number..).zip(gcode_lines) {
+            if line_number > despooled_line_number {
+                break
+            }
+
+            self.apply_gcode(&gcode?);
+            self.despooled_line_number = Some(line_number);
+        }
+
+        Ok(())
+    }
+
+    /// The file line number of the first line that has not been despooled
+    pub fn next_line_number(&self) -> u64 {
+        self.despooled_line_number
+            .map(|line_number| line_number + 1)
+            .unwrap_or(0)
+    }
+
+    /// Loads a saved print recovery state. Returns None if no state has been saved.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => Err(err)?,
+        };
+
+        let state = serde_json::from_str(&json)
+            .wrap_err_with(|| format!("Invalid print recovery state: {:?}", path))?;
+
+        Ok(Some(state))
+    }
+
+    /// Saves the state. The state is written to a temporary file first so that a crash while
+    /// saving cannot corrupt the previously saved state.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.saved_at = Utc::now();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(&self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    fn flag(&self, flag: MachineFlags) -> bool {
+        MachineFlags::from_bits_truncate(self.machine_flags).contains(flag)
+    }
+
+    fn set_flag(&mut self, flag: MachineFlags, value: bool) {
+        let mut flags = MachineFlags::from_bits_truncate(self.machine_flags);
+        flags.set(flag, value);
+
+        self.machine_flags = flags.bits();
+    }
+
+    /// Updates the state with a GCode sent to the machine
+    pub fn apply_gcode(&mut self, gcode: &str) {
+        let gcode = gcode.split(';').next().unwrap_or("");
+        let mut words = gcode.split_whitespace();
+
+        let command = match words.next() {
+            Some(command) => command.to_ascii_uppercase(),
+            None => return,
+        };
+
+        let args = words
+            .filter_map(|word| {
+                let mut chars = word.chars();
+                let key = chars.next()?.to_ascii_uppercase();
+
+                Some((key, chars.as_str().parse::<f32>().ok()))
+            })
+            .collect::<Vec<_>>();
+
+        let arg = |key: char| {
+            args.iter().find(|(k, _)| *k == key).and_then(|(_, v)| *v)
+        };
+
+        let is_metric = self.flag(MachineFlags::MILLIMETERS);
+        let millimeters = |value: f32| {
+            if is_metric {
+                value
+            } else {
+                value * 25.4
+            }
+        };
+
+        match &command[..] {
+            "G0" | "G1" => {
+                self.set_flag(MachineFlags::MOTORS_ENABLED, true);
+
+                let absolute = self.flag(MachineFlags::ABSOLUTE_POSITIONING);
+
+                for (address, key) in AXES.iter() {
+                    if let Some(value) = arg(*key) {
+                        let value = millimeters(value);
+                        let position = self.positions.entry(address.to_string()).or_insert(0.0);
+
+                        if absolute {
+                            *position = value;
+                        } else {
+                            *position += value;
+                        }
+                    }
+                }
+
+                if let Some(value) = arg('E') {
+                    let value = millimeters(value);
+
+                    if absolute && self.absolute_extrusion {
+                        self.extruder_position = value;
+                    } else {
+                        self.extruder_position += value;
+                    }
+                }
+
+                if let Some(feedrate) = arg('F') {
+                    self.feedrate = Some(millimeters(feedrate));
+                }
+            }
+            "G20" => self.set_flag(MachineFlags::MILLIMETERS, false),
+            "G21" => self.set_flag(MachineFlags::MILLIMETERS, true),
+            "G28" => {
+                self.set_flag(MachineFlags::MOTORS_ENABLED, true);
+
+                let home_all = !args.iter().any(|(k, _)| ['X', 'Y', 'Z'].contains(k));
+
+                for (address, key) in AXES.iter() {
+                    if home_all || args.iter().any(|(k, _)| k == key) {
+                        self.positions.insert(address.to_string(), 0.0);
+                    }
+                }
+            }
+            "G90" => self.set_flag(MachineFlags::ABSOLUTE_POSITIONING, true),
+            "G91" => self.set_flag(MachineFlags::ABSOLUTE_POSITIONING, false),
+            "G92" => {
+                for (address, key) in AXES.iter() {
+                    if let Some(value) = arg(*key) {
+                        self.positions.insert(address.to_string(), millimeters(value));
+                    }
+                }
+
+                if let Some(value) = arg('E') {
+                    self.extruder_position = millimeters(value);
+                }
+            }
+            "M17" => self.set_flag(MachineFlags::MOTORS_ENABLED, true),
+            "M18" | "M84" => self.set_flag(MachineFlags::MOTORS_ENABLED, false),
+            "M82" => self.absolute_extrusion = true,
+            "M83" => self.absolute_extrusion = false,
+            "M104" | "M109" => {
+                let index = arg('T')
+                    .map(|t| t as u32)
+                    .unwrap_or(self.current_hotend_index);
+
+                if let Some(target) = arg('S').or_else(|| arg('R')) {
+                    self.heater_targets.insert(format!("e{}", index), target);
+                }
+            }
+            "M140" | "M190" => {
+                if let Some(target) = arg('S').or_else(|| arg('R')) {
+                    self.heater_targets.insert("b".to_string(), target);
+                }
+            }
+            "M141" | "M191" => {
+                if let Some(target) = arg('S').or_else(|| arg('R')) {
+                    self.heater_targets.insert("c".to_string(), target);
+                }
+            }
+            "M106" => {
+                let index = arg('P').map(|p| p as u32).unwrap_or(0);
+                let speed = arg('S').unwrap_or(255.0);
+
+                self.fan_speeds.insert(format!("f{}", index), speed);
+            }
+            "M107" => {
+                let index = arg('P').map(|p| p as u32).unwrap_or(0);
+
+                self.fan_speeds.insert(format!("f{}", index), 0.0);
+            }
+            tool if tool.starts_with('T') => {
+                if let Ok(index) = tool[1..].parse() {
+                    self.current_hotend_index = index;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// GCodes that restore the machine to this state so that the print can continue from the
+    /// line after the despooled line number.
+    ///
+    /// The Z axis cannot be homed without hitting the print so the last Z position is set with
+    /// G92 and assumed to be correct. The toolhead is raised off of the print before the heaters
+    /// are waited on so that the hot nozzle does not melt into the print, and before X and Y are
+    /// re-homed.
+    ///
+    /// The Z moves are made on an axis that has not been homed. Firmware built with
+    /// NO_MOTION_BEFORE_HOMING (eg. Marlin) rejects these moves so prints are not recovered on
+    /// machines configured with `no_motion_before_homing`.
+    pub fn recovery_gcodes(&self) -> Vec<String> {
+        let position = |address: &str| {
+            self.positions.get(address).copied().unwrap_or(0.0)
+        };
+        let z = position("z");
+
+        let mut gcodes = vec![
+            format!("T{}", self.current_hotend_index),
+            "G21".to_string(),
+            "G90".to_string(),
+            // Set the current Z position and lift the toolhead off of the print
+            format!("G92 Z{} E{}", z, self.extruder_position),
+            format!("G1 Z{} F{}", z + Z_HOP, RETURN_FEEDRATE),
+        ];
+
+        let heaters = self.heater_targets
+            .iter()
+            .filter(|(_, target)| **target > 0.0);
+
+        // Start every heater before waiting on any of them
+        for (address, target) in heaters.clone() {
+            match address.as_str() {
+                "b" => gcodes.push(format!("M140 S{}", target)),
+                "c" => gcodes.push(format!("M141 S{}", target)),
+                _ => {
+                    if let Ok(index) = address[1..].parse::<u32>() {
+                        gcodes.push(format!("M104 S{} T{}", target, index));
+                    }
+                }
+            }
+        }
+
+        for (address, target) in heaters {
+            match address.as_str() {
+                "b" => gcodes.push(format!("M190 S{}", target)),
+                "c" => gcodes.push(format!("M191 S{}", target)),
+                _ => {
+                    if let Ok(index) = address[1..].parse::<u32>() {
+                        gcodes.push(format!("M109 S{} T{}", target, index));
+                    }
+                }
+            }
+        }
+
+        gcodes.extend(vec![
+            "G28 X Y".to_string(),
+            format!(
+                "G1 X{} Y{} F{}",
+                position("x"),
+                position("y"),
+                RETURN_FEEDRATE,
+            ),
+            format!("G1 Z{} F{}", z, RETURN_FEEDRATE),
+        ]);
+
+        for (address, speed) in self.fan_speeds.iter() {
+            if let Ok(index) = address[1..].parse::<u32>() {
+                if *speed > 0.0 {
+                    gcodes.push(format!("M106 S{} P{}", speed, index));
+                } else {
+                    gcodes.push(format!("M107 P{}", index));
+                }
+            }
+        }
+
+        // Restore the print's modes
+        gcodes.push(
+            if self.absolute_extrusion { "M82" } else { "M83" }.to_string()
+        );
+
+        if let Some(feedrate) = self.feedrate {
+            gcodes.push(format!("G1 F{}", feedrate));
+        }
+
+        if !self.flag(MachineFlags::ABSOLUTE_POSITIONING) {
+            gcodes.push("G91".to_string());
+        }
+
+        if !self.flag(MachineFlags::MILLIMETERS) {
+            gcodes.push("G20".to_string());
+        }
+
+        gcodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(gcodes: &[&str]) -> PrintRecoveryState {
+        let lines = gcodes.iter().map(|gcode| Ok(gcode.to_string()));
+
+        PrintRecoveryState::from_gcode_prefix(
+            "task".to_string(),
+            lines,
+            gcodes.len() as u64 - 1,
+        ).unwrap()
+    }
+
+    #[test]
+    fn replays_the_lines_after_a_saved_state() {
+        let mut state = replay(&["M104 S200", "G1 X10 Y20 Z0.2 E1"]);
+
+        let lines = ["G1 X20 E2", "M106 S255", "G1 X30 E3"]
+            .iter()
+            .map(|gcode| Ok(gcode.to_string()));
+
+        // Lines after the despooled line number are not replayed
+        state.replay(lines, 3).unwrap();
+
+        assert_eq!(state.despooled_line_number, Some(3));
+        assert_eq!(state.next_line_number(), 4);
+        assert_eq!(state.heater_targets.get("e0"), Some(&200.0));
+        assert_eq!(state.fan_speeds.get("f0"), Some(&255.0));
+        assert_eq!(state.positions.get("x"), Some(&20.0));
+        assert_eq!(state.extruder_position, 2.0);
+    }
+
+    #[test]
+    fn reconstructs_the_state_from_the_gcode_prefix() {
+        let state = replay(&[
+            "M140 S60",
+            "M104 S200 ; comment",
+            "M190 S60",
+            "G28",
+            "M83",
+            "G1 X10 Y20 Z0.2 F1200",
+            "G1 X20 E1.5",
+            "G92 E0",
+            "G1 X30 E0.5",
+            "M106 S128",
+            "G91",
+            "G1 Z0.2",
+            "M104 S0",
+            "G1 X40",
+        ]);
+
+        assert_eq!(state.despooled_line_number, Some(13));
+        assert_eq!(state.heater_targets.get("b"), Some(&60.0));
+        assert_eq!(state.heater_targets.get("e0"), Some(&0.0));
+        assert_eq!(state.fan_speeds.get("f0"), Some(&128.0));
+        assert_eq!(state.positions.get("x"), Some(&70.0));
+        assert_eq!(state.positions.get("y"), Some(&20.0));
+        assert!((state.positions["z"] - 0.4).abs() < 0.001);
+        assert_eq!(state.extruder_position, 0.5);
+        assert_eq!(state.feedrate, Some(1200.0));
+        assert!(!state.absolute_extrusion);
+        assert!(!state.flag(MachineFlags::ABSOLUTE_POSITIONING));
+        assert!(state.flag(MachineFlags::MOTORS_ENABLED));
+    }
+
+    #[test]
+    fn only_replays_the_despooled_lines() {
+        let lines = ["G1 X10", "G1 X20", "G1 X30"]
+            .iter()
+            .map(|gcode| Ok(gcode.to_string()));
+
+        let state = PrintRecoveryState::from_gcode_prefix("task".to_string(), lines, 1).unwrap();
+
+        assert_eq!(state.despooled_line_number, Some(1));
+        assert_eq!(state.positions.get("x"), Some(&20.0));
+    }
+
+    #[test]
+    fn lifts_the_toolhead_before_heating_and_rehoming_x_and_y() {
+        let state = replay(&[
+            "M140 S60",
+            "M104 S210",
+            "G28",
+            "G1 X10 Y20 Z5 E3 F1800",
+            "M106 S255",
+        ]);
+
+        assert_eq!(
+            state.recovery_gcodes(),
+            vec![
+                "T0",
+                "G21",
+                "G90",
+                "G92 Z5 E3",
+                "G1 Z7 F3000",
+                "M140 S60",
+                "M104 S210 T0",
+                "M190 S60",
+                "M109 S210 T0",
+                "G28 X Y",
+                "G1 X10 Y20 F3000",
+                "G1 Z5 F3000",
+                "M106 S255 P0",
+                "M82",
+                "G1 F1800",
+            ],
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// The estimated print time at lines of a task's GCode.
+///
+/// Each entry is a line number and the estimated number of seconds from the start of the task to
+/// the end of that line. Entries are sorted by line number. Lines between entries are
+/// interpolated so only a sample of the task's lines are stored.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PrintTimeTable(pub Vec<(u64, f32)>);
+
+impl PrintTimeTable {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The estimated time to run the whole task
+    pub fn total(&self) -> Option<Duration> {
+        self.0.last().map(|(_, seconds)| Duration::from_secs_f32(*seconds))
+    }
+
+    /// The estimated time from the start of the task to the end of a line
+    pub fn time_at(&self, line_number: u64) -> Option<Duration> {
+        let table = &self.0;
+
+        if table.is_empty() {
+            return None
+        }
+
+        // The index of the first entry at or after the line
+        let index = match table.binary_search_by_key(&line_number, |(line, _)| *line) {
+            Ok(index) => index,
+            Err(index) => index,
+        };
+
+        let seconds = if let Some((next_line, next_seconds)) = table.get(index) {
+            let (previous_line, previous_seconds) = index
+                .checked_sub(1)
+                .map(|previous| table[previous])
+                .unwrap_or((0, 0.0));
+
+            if *next_line <= previous_line {
+                *next_seconds
+            } else {
+                let progress = (line_number - previous_line) as f32
+                    / (next_line - previous_line) as f32;
+
+                previous_seconds + progress * (next_seconds - previous_seconds)
+            }
+        } else {
+            // Lines after the last entry do not add any time
+            table[table.len() - 1].1
+        };
+
+        Some(Duration::from_secs_f32(seconds.max(0.0)))
+    }
+
+    /// The estimated time remaining after a line
+    pub fn time_remaining_after(&self, line_number: Option<u64>) -> Option<Duration> {
+        let total = self.total()?;
+
+        let elapsed = line_number
+            .and_then(|line_number| self.time_at(line_number))
+            .unwrap_or_default();
+
+        Some(total.checked_sub(elapsed).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_between_entries() {
+        let table = PrintTimeTable(vec![(10, 10.0), (20, 30.0)]);
+
+        assert_eq!(table.total(), Some(Duration::from_secs(30)));
+        assert_eq!(table.time_at(5), Some(Duration::from_secs(5)));
+        assert_eq!(table.time_at(15), Some(Duration::from_secs(20)));
+        assert_eq!(table.time_at(25), Some(Duration::from_secs(30)));
+        assert_eq!(table.time_remaining_after(Some(15)), Some(Duration::from_secs(10)));
+        assert_eq!(table.time_remaining_after(None), Some(Duration::from_secs(30)));
+        assert_eq!(PrintTimeTable::default().time_at(15), None);
+    }
+}
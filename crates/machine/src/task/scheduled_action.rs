@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use async_graphql::ID;
+use teg_protobufs::server_message;
+
+/// Where in a print a scheduled action runs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ActionTrigger {
+    /// The start of a layer. Layers are numbered from 1.
+    Layer(u32),
+    /// The start of the first layer at or above a Z height (in mm)
+    Height(f32),
+}
+
+/// A pause and/or GCodes to run as the driver reaches a line of a print (eg. a colour change at
+/// a layer)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledAction {
+    pub id: crate::DbId,
+    pub trigger: ActionTrigger,
+    /// The task file line number that the action runs before
+    pub line_number: u64,
+    pub pause: bool,
+    pub gcodes: Vec<String>,
+}
+
+impl From<&ScheduledAction> for server_message::LineAction {
+    fn from(action: &ScheduledAction) -> Self {
+        server_message::LineAction {
+            line_number: action.line_number,
+            pause: action.pause,
+            gcodes: action.gcodes.clone(),
+        }
+    }
+}
+
+#[async_graphql::Object]
+impl ScheduledAction {
+    async fn id(&self) -> ID { (&self.id).into() }
+
+    /// The layer the action runs at or null if the action is scheduled by height
+    async fn layer(&self) -> Option<u32> {
+        match self.trigger {
+            ActionTrigger::Layer(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    /// The Z height the action runs at or null if the action is scheduled by layer
+    async fn height(&self) -> Option<f32> {
+        match self.trigger {
+            ActionTrigger::Height(height) => Some(height),
+            _ => None,
+        }
+    }
+
+    async fn line_number(&self) -> u64 { self.line_number }
+
+    /// True if the print is paused before the GCodes are run
+    async fn pause(&self) -> bool { self.pause }
+
+    async fn gcodes(&self) -> &Vec<String> { &self.gcodes }
+}
@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+};
 
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -11,8 +14,13 @@ use teg_json_store::{ Record, JsonRow };
 
 use crate::{MachineHooksList, machine::Machine, machine::MachineData};
 
+use teg_protobufs::server_message;
+
 use super::{
+    ActionTrigger,
     GCodeAnnotation,
+    PrintTimeTable,
+    ScheduledAction,
     TaskStatus,
 };
 
@@ -33,6 +41,10 @@ pub struct Task {
     pub despooled_line_number: Option<u64>,
     pub machine_override: bool,
     pub estimated_print_time: Option<std::time::Duration>,
+    /// The estimated print time at lines of the task from simulating it's moves, dwells and
+    /// heating
+    #[serde(default)]
+    pub print_time_table: PrintTimeTable,
 
     #[serde(default)]
     /// The amount of time the task spent waiting on blocking GCodes eg. heating up the extruder.
@@ -43,21 +55,146 @@ pub struct Task {
     pub time_paused: std::time::Duration,
 
     pub estimated_filament_meters: Option<f64>,
+    /// The materials loaded in the machine's toolheads when the print was spooled
+    #[serde(default)]
+    pub material_ids: Vec<crate::DbId>,
     // #[new(default)]
     // pub sent_to_machine: bool,
     #[serde(default)]
     pub status: TaskStatus,
+    /// Pauses and GCodes to run at layers or heights of the print
+    #[serde(default)]
+    pub scheduled_actions: Vec<ScheduledAction>,
+    /// The objects that were cancelled mid-print. The driver skips the extrusions of their lines.
+    #[serde(default)]
+    pub cancelled_objects: Vec<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TaskContent {
     FilePath(PathBuf),
     GCodes(Vec<String>),
+    /// A file stored on the machine's SD card and printed by the machine's firmware. The progress
+    /// of SD card prints is tracked in bytes rather than lines.
+    SDCardFile(String),
 }
 
 impl Task {
     pub fn is_print(&self) -> bool {
-        self.part_id.is_some()
+        self.part_id.is_some() || self.is_sd_card_print()
+    }
+
+    pub fn is_sd_card_print(&self) -> bool {
+        matches!(self.content, TaskContent::SDCardFile(_))
+    }
+
+    /// The line number, layer and Z height of each layer change in the task's GCode
+    pub fn layers(&self) -> impl Iterator<Item = (u64, u32, Option<f32>)> + '_ {
+        self.annotations
+            .iter()
+            .filter_map(|(line_number, annotation)| {
+                if let GCodeAnnotation::Layer { layer, z } = annotation {
+                    Some((*line_number, *layer, *z))
+                } else {
+                    None
+                }
+            })
+    }
+
+    pub fn total_layers(&self) -> Option<u32> {
+        self.layers().map(|(_, layer, _)| layer).max()
+    }
+
+    /// The layer and Z height of the most recently despooled layer change
+    pub fn current_layer(&self) -> Option<(u32, Option<f32>)> {
+        let despooled_line_number = self.despooled_line_number?;
+
+        self.layers()
+            .take_while(|(line_number, ..)| *line_number <= despooled_line_number)
+            .last()
+            .map(|(_, layer, z)| (layer, z))
+    }
+
+    /// The line number that an action triggered at a layer or height would run before
+    pub fn trigger_line_number(&self, trigger: &ActionTrigger) -> Option<u64> {
+        self.layers()
+            .find(|(_, layer, z)| match trigger {
+                ActionTrigger::Layer(target) => layer == target,
+                // Allow for rounding errors in the layer heights
+                ActionTrigger::Height(target) => z
+                    .map(|z| z >= target - 0.001)
+                    .unwrap_or(false),
+            })
+            .map(|(line_number, ..)| line_number)
+    }
+
+    /// The estimated print time up to the despooled line
+    pub fn estimated_elapsed_time(&self) -> Option<std::time::Duration> {
+        let total = self.estimated_total_time()?;
+        let remaining = self.estimated_remaining_time()?;
+
+        Some(total.checked_sub(remaining).unwrap_or_default())
+    }
+
+    /// The estimated print time after the despooled line. Tasks without a print time table
+    /// fallback to the slicer's estimate scaled by the lines remaining.
+    pub fn estimated_remaining_time(&self) -> Option<std::time::Duration> {
+        if !self.print_time_table.is_empty() {
+            return self.print_time_table.time_remaining_after(self.despooled_line_number)
+        }
+
+        let print_time = self.estimated_print_time?;
+        let printed_lines = self.despooled_line_number
+            .map(|n| n + 1)
+            .unwrap_or(0);
+        let total_lines = std::cmp::max(self.total_lines, 1);
+        let remaining_lines = total_lines.saturating_sub(printed_lines);
+
+        Some(print_time.mul_f64(remaining_lines as f64 / total_lines as f64))
+    }
+
+    fn estimated_total_time(&self) -> Option<std::time::Duration> {
+        self.print_time_table.total().or(self.estimated_print_time)
+    }
+
+    /// The number and name of each object on the print's build plate
+    pub fn objects(&self) -> BTreeMap<u32, &str> {
+        self.annotations
+            .iter()
+            .filter_map(|(_, annotation)| {
+                if let GCodeAnnotation::Object { object, name, .. } = annotation {
+                    Some((*object, name.as_str()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The line ranges of the cancelled objects
+    pub fn skipped_lines(&self) -> Vec<server_message::LineRange> {
+        self.annotations
+            .iter()
+            .filter_map(|(line_number, annotation)| match annotation {
+                GCodeAnnotation::Object { object, end_line_number, .. }
+                    if self.cancelled_objects.contains(object) =>
+                {
+                    Some(server_message::LineRange {
+                        start: *line_number,
+                        end: *end_line_number,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The scheduled actions that the driver runs as it despools the task
+    pub fn line_actions(&self) -> Vec<server_message::LineAction> {
+        self.scheduled_actions
+            .iter()
+            .map(Into::into)
+            .collect()
     }
 
     pub async fn tasks_running_on_machine<'e, 'c, E>(
@@ -84,6 +221,47 @@ impl Task {
         Ok(tasks)
     }
 
+    /// Clears the GCode files kept for recovering the machine's previously errored prints and
+    /// returns their paths.
+    async fn discard_recoverable_gcode_files<'c>(
+        &self,
+        tx: &mut sqlx::Transaction<'c, sqlx::Postgres>,
+    ) -> Result<Vec<PathBuf>> {
+        let tasks = sqlx::query_as!(
+            JsonRow,
+            r#"
+                SELECT props FROM tasks
+                WHERE
+                    tasks.machine_id = $1
+                    AND tasks.id != $2
+                    AND tasks.part_id IS NOT NULL
+                    AND tasks.status = 'errored'
+                    AND tasks.props->'content' ? 'FilePath'
+            "#,
+            self.machine_id,
+            self.id,
+        )
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut file_paths = vec![];
+
+        for mut task in Task::from_rows(tasks)? {
+            let content = std::mem::replace(
+                &mut task.content,
+                TaskContent::GCodes(vec![]),
+            );
+
+            if let TaskContent::FilePath(file_path) = content {
+                file_paths.push(file_path);
+            }
+
+            task.update(&mut *tx).await?;
+        }
+
+        Ok(file_paths)
+    }
+
     pub async fn settle_task<'c>(
         &mut self,
         mut tx: sqlx::Transaction<'c, sqlx::Postgres>,
@@ -99,11 +277,24 @@ impl Task {
             self.despooled_line_number = Some(total_lines - 1);
         }
 
-        // Replace the completed GCodes with an empty vec to save space
-        let content = std::mem::replace(
-            &mut self.content,
-            TaskContent::GCodes(vec![]),
-        );
+        // Keep the GCode file of the machine's latest errored print so that it can be recovered.
+        // The files kept for the machine's previous prints are no longer recoverable.
+        let is_recoverable = self.is_print() && matches!(self.status, TaskStatus::Errored(_));
+
+        let mut unrecoverable_files = self.discard_recoverable_gcode_files(&mut tx).await?;
+
+        // Replace the completed GCodes with an empty vec to save space. SD card file names are
+        // kept for the print history.
+        if !is_recoverable && !self.is_sd_card_print() {
+            let content = std::mem::replace(
+                &mut self.content,
+                TaskContent::GCodes(vec![]),
+            );
+
+            if let TaskContent::FilePath(file_path) = content {
+                unrecoverable_files.push(file_path);
+            }
+        }
 
         let mut after_task_settle_cbs = vec![];
         for machine_hook in machine_hooks.iter() {
@@ -120,15 +311,18 @@ impl Task {
             }
         }
 
-        // Delete the completed GCode file in a seperate task to prevent blocking the database on
+        // Delete the completed GCode files in a seperate task to prevent blocking the database on
         // disk IO
-        if let TaskContent::FilePath(file_path) = content {
+        for file_path in unrecoverable_files {
             let _ = async_std::task::spawn(async move {
                 use async_std::fs::remove_file;
 
                 if let Err(err) = remove_file(&file_path).await {
                     warn!("Unable to remove completed GCode file ({:?}): {:?}", file_path, err);
                 }
+
+                // Task files compiled before GCode indexes were added do not have one
+                let _ = remove_file(super::gcode_index_path(&file_path)).await;
             });
         }
 
@@ -208,7 +402,7 @@ impl Record for Task {
         let (json, previous_version) = self.prep_for_update()?;
         let status = self.status.to_db_str();
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE tasks
                 SET
@@ -227,9 +421,9 @@ impl Record for Task {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
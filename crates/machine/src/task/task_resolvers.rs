@@ -6,7 +6,7 @@ use eyre::{
     // Context as _,
 };
 
-use super::{Task, TaskStatus, task_status::TaskStatusGQL};
+use super::{PrintObject, ScheduledAction, Task, TaskStatus, TaskStatusGQL};
 
 use crate::{MachineMap, machine::{
     MachineData,
@@ -80,6 +80,43 @@ impl Task {
         }
     }
 
+    /// The layer currently being printed. Null if the task's layers could not be detected or
+    /// the first layer has not yet been reached.
+    #[graphql(name = "currentLayer")]
+    async fn _current_layer(&self) -> Option<u32> {
+        self.current_layer().map(|(layer, _)| layer)
+    }
+
+    /// The Z height of the layer currently being printed (in mm)
+    async fn current_layer_z(&self) -> Option<f32> {
+        self.current_layer().and_then(|(_, z)| z)
+    }
+
+    /// The number of layers in the task or null if the task's layers could not be detected
+    #[graphql(name = "totalLayers")]
+    async fn _total_layers(&self) -> Option<u32> {
+        self.total_layers()
+    }
+
+    /// Pauses and GCodes scheduled to run at layers or heights of the print
+    async fn scheduled_actions(&self) -> &Vec<ScheduledAction> {
+        &self.scheduled_actions
+    }
+
+    /// The objects on the print's build plate. Empty if the slicer did not label the objects or
+    /// the plate has a single object.
+    #[graphql(name = "objects")]
+    async fn _objects(&self) -> Vec<PrintObject> {
+        self.objects()
+            .into_iter()
+            .map(|(object, name)| PrintObject {
+                id: object.to_string().into(),
+                name: name.to_string(),
+                cancelled: self.cancelled_objects.contains(&object),
+            })
+            .collect()
+    }
+
     async fn estimated_print_time_millis(&self) -> Option<u64> {
         self.estimated_print_time.map(|print_time| {
             let millis = print_time.as_millis();
@@ -88,6 +125,21 @@ impl Task {
         })
     }
 
+    /// The estimated time spent printing the lines that have been sent to the machine. Excludes
+    /// time spent paused.
+    async fn estimated_elapsed_time_millis(&self) -> Option<u64> {
+        self.estimated_elapsed_time().map(|duration| {
+            std::cmp::min(duration.as_millis(), std::u64::MAX as u128) as u64
+        })
+    }
+
+    /// The estimated time to print the lines that have not yet been sent to the machine
+    async fn estimated_remaining_time_millis(&self) -> Option<u64> {
+        self.estimated_remaining_time().map(|duration| {
+            std::cmp::min(duration.as_millis(), std::u64::MAX as u128) as u64
+        })
+    }
+
     async fn eta<'ctx>(&self, ctx: &'ctx Context<'_>) -> FieldResult<Option<DateTime<Utc>>> {
         let machines: &MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -98,6 +150,14 @@ impl Task {
 
         let machine_data = addr.call(GetData).await??;
 
+        // Estimate running tasks' ETAs from their remaining lines if they have a print time
+        // table. Paused tasks are estimated as if they were resumed now.
+        if self.status.is_pending() && !self.print_time_table.is_empty() {
+            let remaining = self.estimated_remaining_time().unwrap_or_default();
+
+            return Ok(Some(Utc::now() + ::chrono::Duration::from_std(remaining)?))
+        }
+
         let print_time = if let Some(print_time) = self.estimated_print_time {
             print_time
         } else {
@@ -122,6 +182,12 @@ impl Task {
         &self.estimated_filament_meters
     }
 
+    /// The materials loaded in the machine's toolheads when the print was spooled
+    #[graphql(name="materialIDs")]
+    async fn material_ids(&self) -> Vec<ID> {
+        self.material_ids.iter().map(Into::into).collect()
+    }
+
     async fn started_at(&self) -> &DateTime<Utc> { &self.created_at }
 
     async fn stopped_at(&self) -> Option<&DateTime<Utc>> {
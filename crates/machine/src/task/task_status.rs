@@ -96,6 +96,21 @@ impl From<&TaskStatus> for TaskStatusGQL {
     }
 }
 
+impl TaskStatusGQL {
+    /// The value of the tasks table's status column for tasks with this status
+    pub fn to_db_str(&self) -> &'static str {
+        use TaskStatusGQL::*;
+        match self {
+            Spooled => "spooled",
+            Started => "started",
+            Finished => "finished",
+            Paused => "paused",
+            Cancelled => "cancelled",
+            Errored => "errored",
+        }
+    }
+}
+
 impl Default for TaskStatus {
     fn default() -> Self { TaskStatus::Created(Default::default()) }
 }
@@ -160,6 +175,16 @@ impl TaskStatus {
           }
     }
 
+    /// True if the task is queued or running in the driver. Paused tasks are not in the driver
+    /// until they are resumed.
+    pub fn is_in_driver(&self) -> bool {
+        match self {
+            TaskStatus::Created(created) => created.sent_to_driver,
+            TaskStatus::Started => true,
+            _ => false,
+        }
+    }
+
     pub fn is_pending(&self) -> bool {
         !self.is_settled()
     }
@@ -15,6 +15,7 @@ use serde::{
 };
 use teg_auth::{
     AuthContext,
+    Permission,
 };
 // use teg_json_store::Record;
 
@@ -81,7 +82,7 @@ impl VideoMutation {
     ) -> FieldResult<VideoSession> {
         let auth: &AuthContext = ctx.data()?;
 
-        let user = auth.require_authorized_user()?;
+        let user = auth.authorize_machine(&input.machine_id, Permission::View)?;
 
         let machines: &crate::MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -11,6 +11,7 @@ use eyre::{
 };
 use teg_auth::{
     AuthContext,
+    Permission,
 };
 
 use super::{
@@ -37,7 +38,7 @@ impl VideoQuery {
     ) -> FieldResult<Vec<VideoSource>> {
         let auth: &AuthContext = ctx.data()?;
 
-        let _ = auth.require_authorized_user()?;
+        auth.authorize(Permission::View)?;
 
         async move {
             let req = surf::post(&format!("{}/getMediaList", WEBRTC_STREAMER_API))
@@ -80,7 +81,7 @@ impl VideoQuery {
     ) -> FieldResult<Vec<IceCandidate>> {
         let auth: &AuthContext = ctx.data()?;
 
-        let user = auth.require_authorized_user()?;
+        let user = auth.authorize(Permission::View)?;
 
         if !video_session_id.starts_with(&format!("{}.", user.id)) {
             Err(eyre!("Invalid Video Session ID"))?;
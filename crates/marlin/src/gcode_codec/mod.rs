@@ -15,6 +15,7 @@ use nom_reprap_response::{
     Feedback,
 };
 
+#[derive(Clone)]
 pub struct GCodeCodec;
 
 impl Decoder for GCodeCodec {
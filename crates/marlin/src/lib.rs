@@ -3,12 +3,9 @@
 #![type_length_limit="1073741824"]
 
 #[macro_use] extern crate tracing;
-#[macro_use] extern crate error_chain;
 #[macro_use] extern crate lazy_static;
 
-mod protobuf_server;
 pub mod gcode_codec;
-mod serial_manager;
 mod serial_simulator;
 
 pub mod state_machine;
@@ -18,7 +15,6 @@ pub mod protos {
     pub use teg_protobufs::*;
 }
 
-pub use serial_manager::SerialManager;
 pub use teg_machine::config::MachineConfig;
 
 use std::{collections::HashMap, path::PathBuf};
@@ -54,8 +50,13 @@ use bytes::Bytes;
 
 use {
     state_machine::{Loop, State, Event, Context},
+    gcode_codec::{GCodeCodec, GCodeLine},
     // protos::MachineMessage,
 };
+use teg_driver::{
+    protobuf_server,
+    serial_transport::SerialPortAddress,
+};
 
 
 // use futures::compat::{
@@ -77,13 +78,12 @@ use {
 
 pub type DbId = teg_json_store::DbId;
 
-// Create the Error, ErrorKind, ResultExt, and Result types
-error_chain! {}
-
 // type SerialSender = Arc<Mutex<SplitSink<tokio::codec::Framed<tokio_serial::Serial, GCodeCodec>, GCodeLine>>>;
 // type SerialSender = mpsc::Sender<GCodeLine>;
 
 
+pub type SerialManager = teg_driver::SerialManager<GCodeCodec, GCodeLine, Event>;
+
 lazy_static! {
     pub static ref PROCESS_STARTED_AT: DateTime<Utc> = Utc::now();
 }
@@ -119,18 +119,10 @@ pub async fn start(
     config_path: PathBuf,
 ) -> eyre::Result<()> {
     lazy_static::initialize(&PROCESS_STARTED_AT);
-    dotenv::dotenv().ok();
-
-    tracing_subscriber::fmt::init();
-    color_eyre::install()?;
 
     // Config
     // ----------------------------------------------------
-    let config_file_content = std::fs::read_to_string(config_path.clone())
-        .expect(&format!("Unabled to open config (file: {:?})", config_path));
-
-    let config: MachineConfig = toml::from_str(&config_file_content)
-        .expect(&format!("Invalid config format (file: {:?})", config_path));
+    let config = teg_driver::load_config(&config_path);
 
     // Channels
     // ----------------------------------------------------
@@ -138,10 +130,16 @@ pub async fn start(
 
     // Serial Port
     // ----------------------------------------------------
-    let serial_manager = SerialManager::new(event_sender.clone(), config.tty_path().clone());
+    let serial_manager = SerialManager::new(
+        GCodeCodec,
+        event_sender.clone(),
+        config.tty_path().clone(),
+        9600,
+    );
 
-    // attempt to connect to serial on startup if the port is available
-    let serial_port_available = std::path::Path::new(config.tty_path()).exists();
+    // attempt to connect to serial on startup if the port is available. Network serial ports
+    // are always attempted.
+    let serial_port_available = SerialPortAddress::parse(config.tty_path()).is_available();
     event_sender.send(Event::Init { serial_port_available })
         .await
         .expect("Unable to send init event");
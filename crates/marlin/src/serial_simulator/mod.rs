@@ -1,2 +1,15 @@
 mod serial_simulator;
 pub use serial_simulator::SerialSimulator;
+
+mod serial_link;
+pub use serial_link::{
+    SerialLink,
+    Fault,
+    FaultScript,
+};
+
+mod personality;
+pub use personality::Personality;
+
+mod physics;
+pub use physics::Physics;
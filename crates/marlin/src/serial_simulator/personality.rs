@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use eyre::{
+    // eyre,
+    Context as _,
+    Result,
+};
+use teg_machine::components::SimulatorPersonality;
+
+use super::physics::{
+    Axes,
+    Heater,
+};
+
+/// The scripted responses of a firmware imitated by the simulator. Personalities are loaded from
+/// the TOML scripts in the personalities directory.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Personality {
+    pub name: String,
+    /// Sent when the serial port is opened
+    #[serde(default)]
+    pub greeting: String,
+    /// Response to M115 (followed by an ok)
+    pub firmware_info: String,
+    /// Temperature template. Sent after the ok to M105 and while heating.
+    pub temperature_report: String,
+    /// Position template. Response to M114 (followed by an ok).
+    pub position_report: String,
+    /// Keep alive sent while a GCode is being processed
+    pub busy: Option<String>,
+    /// Template sent when a line can not be parsed as a GCode (followed by an ok)
+    pub unknown_command: String,
+    /// If false line numbers and checksums are stripped from lines without validating them
+    #[serde(default)]
+    pub validates_lines: bool,
+    /// Templates sent when a line is rejected, followed by the resend request
+    #[serde(default)]
+    pub line_error: Vec<String>,
+    /// Templates for requesting that a line be resent
+    #[serde(default)]
+    pub resend: Vec<String>,
+    #[serde(default)]
+    pub errors: LineErrors,
+}
+
+/// The reasons given for rejecting a line
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LineErrors {
+    pub line_number_mismatch: String,
+    pub checksum_mismatch: String,
+    pub no_checksum: String,
+}
+
+impl Personality {
+    pub fn load(personality: SimulatorPersonality) -> Result<Self> {
+        let script = match personality {
+            SimulatorPersonality::Marlin => {
+                include_str!("personalities/marlin.toml")
+            }
+            SimulatorPersonality::RepRapFirmware => {
+                include_str!("personalities/reprap_firmware.toml")
+            }
+            SimulatorPersonality::Klipper => {
+                include_str!("personalities/klipper.toml")
+            }
+        };
+
+        let personality = toml::from_str(script)
+            .wrap_err_with(|| format!("Invalid simulator personality: {:?}", personality))?;
+
+        Ok(personality)
+    }
+
+    pub fn temperature_report(&self, extruder: &Heater, bed: &Heater) -> String {
+        render(&self.temperature_report, &[
+            ("extruder", format!("{:.2}", extruder.temperature)),
+            ("extruderTarget", format!("{:.2}", extruder.target)),
+            ("extruderPower", extruder.pwm().to_string()),
+            ("bed", format!("{:.2}", bed.temperature)),
+            ("bedTarget", format!("{:.2}", bed.target)),
+            ("bedPower", bed.pwm().to_string()),
+        ])
+    }
+
+    /// Renders the position report. {x}, {y}, {z} and {e} are the current position of the
+    /// toolhead and {targetX}, {targetY}, {targetZ} and {targetE} are the end of the last planned
+    /// move.
+    pub fn position_report(&self, target: &Axes, position: &Axes) -> String {
+        render(&self.position_report, &[
+            ("targetX", format!("{:.2}", target[0])),
+            ("targetY", format!("{:.2}", target[1])),
+            ("targetZ", format!("{:.2}", target[2])),
+            ("targetE", format!("{:.2}", target[3])),
+            ("x", format!("{:.2}", position[0])),
+            ("y", format!("{:.2}", position[1])),
+            ("z", format!("{:.2}", position[2])),
+            ("e", format!("{:.2}", position[3])),
+        ])
+    }
+}
+
+impl Personality {
+    pub fn unknown_command(&self, line: &str) -> String {
+        render(&self.unknown_command, &[
+            ("line", line.to_string()),
+        ])
+    }
+}
+
+/// Replaces each {name} in the template with its value
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{}}}", name), value)
+        })
+}
@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use teg_machine::components::{
+    SimulatedHeater,
+    SimulatorPhysics,
+};
+
+/// X, Y, Z and E positions in mm
+pub type Axes = [f32; 4];
+
+pub const AXES: [char; 4] = ['X', 'Y', 'Z', 'E'];
+
+/// The number of moves the planner buffers before further moves block (Marlin's
+/// BLOCK_BUFFER_SIZE)
+pub const PLANNER_BUFFER_SIZE: usize = 16;
+
+/// Heaters are considered to have reached their target once they are within this many degrees of
+/// it (Marlin's TEMP_WINDOW)
+const TEMPERATURE_WINDOW: f32 = 1.0;
+
+/// Heater temperatures are integrated in steps of at most this many seconds
+const HEATER_TIME_STEP: f32 = 0.05;
+
+/// The heater controllers apply full power when the heater is this many degrees below its target
+const PROPORTIONAL_BAND: f32 = 2.0;
+
+/// A heater with first-order thermal dynamics driven by a proportional controller
+#[derive(Debug, Clone)]
+pub struct Heater {
+    config: SimulatedHeater,
+    ambient: f32,
+    pub temperature: f32,
+    pub target: f32,
+}
+
+impl Heater {
+    fn new(config: SimulatedHeater, ambient: f32) -> Self {
+        Self {
+            config,
+            ambient,
+            temperature: ambient,
+            target: 0.0,
+        }
+    }
+
+    /// The heater's power output in watts
+    pub fn power(&self) -> f32 {
+        if self.target <= self.ambient {
+            return 0.0
+        }
+
+        // Feed forward the heat loss at the target so that the heater settles on it
+        let feed_forward = self.config.heat_loss * (self.target - self.ambient);
+        let error = (self.target - self.temperature) / PROPORTIONAL_BAND;
+
+        (feed_forward + error * self.config.heater_power)
+            .max(0.0)
+            .min(self.config.heater_power)
+    }
+
+    /// The heater's power as a 0-127 PWM value (as reported by Marlin's M105)
+    pub fn pwm(&self) -> u8 {
+        if self.config.heater_power <= 0.0 {
+            return 0
+        }
+
+        (self.power() / self.config.heater_power * 127.0).round() as u8
+    }
+
+    /// Returns true once the heater has reached its target. Heaters are only waited on while
+    /// heating unless wait_for_cooling is set (eg. M109 R).
+    pub fn reached_target(&self, wait_for_cooling: bool) -> bool {
+        // Heaters cannot cool below the ambient temperature
+        let target = self.target.max(self.ambient);
+
+        if wait_for_cooling {
+            (self.temperature - target).abs() <= TEMPERATURE_WINDOW
+        } else {
+            self.temperature >= target - TEMPERATURE_WINDOW
+        }
+    }
+
+    fn step(&mut self, seconds: f32) {
+        let heat_loss = self.config.heat_loss * (self.temperature - self.ambient);
+
+        self.temperature += (self.power() - heat_loss) * seconds / self.config.thermal_mass;
+    }
+}
+
+/// A linear move with a trapezoidal velocity profile. Each move accelerates from and decelerates
+/// to a stop.
+#[derive(Debug, Clone)]
+struct Move {
+    start: Axes,
+    end: Axes,
+    distance: f32,
+    /// Peak speed in mm/s
+    speed: f32,
+    acceleration: f32,
+    /// Total duration in seconds
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Move {
+    fn new(start: Axes, end: Axes, feedrate: f32, acceleration: f32) -> Self {
+        let delta = |i: usize| end[i] - start[i];
+        let travel = (delta(0).powi(2) + delta(1).powi(2) + delta(2).powi(2)).sqrt();
+
+        // Extrude-only moves are timed by the extruder's travel
+        let distance = if travel > 0.0 {
+            travel
+        } else {
+            delta(3).abs()
+        };
+
+        let speed = feedrate / 60.0;
+
+        // Short moves do not reach the feedrate before they need to decelerate
+        let speed = if distance >= speed.powi(2) / acceleration {
+            speed
+        } else {
+            (distance * acceleration).sqrt()
+        };
+
+        let duration = if distance > 0.0 && speed > 0.0 {
+            distance / speed + speed / acceleration
+        } else {
+            0.0
+        };
+
+        Self {
+            start,
+            end,
+            distance,
+            speed,
+            acceleration,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// The distance travelled after `t` seconds
+    fn travelled(&self, t: f32) -> f32 {
+        let acceleration_time = self.speed / self.acceleration;
+        let a = self.acceleration;
+
+        if t >= self.duration {
+            self.distance
+        } else if t < acceleration_time {
+            0.5 * a * t.powi(2)
+        } else if t < self.duration - acceleration_time {
+            0.5 * a * acceleration_time.powi(2) + self.speed * (t - acceleration_time)
+        } else {
+            self.distance - 0.5 * a * (self.duration - t).powi(2)
+        }
+    }
+
+    fn position(&self) -> Axes {
+        let progress = if self.distance > 0.0 {
+            self.travelled(self.elapsed) / self.distance
+        } else {
+            1.0
+        };
+
+        let mut position = self.start;
+        for i in 0..position.len() {
+            position[i] += (self.end[i] - self.start[i]) * progress;
+        }
+
+        position
+    }
+}
+
+/// A time-stepped model of the simulated printer's heaters and motion
+#[derive(Debug, Clone)]
+pub struct Physics {
+    config: SimulatorPhysics,
+    pub extruder: Heater,
+    pub bed: Heater,
+    /// The position at the end of the last planned move
+    pub target: Axes,
+    /// The position at the end of the last completed move
+    completed: Axes,
+    moves: VecDeque<Move>,
+    /// Feedrate in mm/min
+    feedrate: f32,
+    relative_moves: bool,
+    relative_extrusion: bool,
+}
+
+impl Physics {
+    pub fn new(config: SimulatorPhysics) -> Self {
+        Self {
+            extruder: Heater::new(config.extruder.clone(), config.ambient_temperature),
+            bed: Heater::new(config.bed.clone(), config.ambient_temperature),
+            target: Axes::default(),
+            completed: Axes::default(),
+            moves: VecDeque::new(),
+            feedrate: config.default_feedrate,
+            relative_moves: false,
+            relative_extrusion: false,
+            config,
+        }
+    }
+
+    /// Advances the simulation
+    pub fn step(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f32();
+
+        let mut remaining = seconds;
+        while remaining > 0.0 {
+            let time_step = remaining.min(HEATER_TIME_STEP);
+
+            self.extruder.step(time_step);
+            self.bed.step(time_step);
+
+            remaining -= time_step;
+        }
+
+        let mut remaining = seconds;
+        while let Some(next_move) = self.moves.front_mut() {
+            let time_step = (next_move.duration - next_move.elapsed).min(remaining);
+
+            next_move.elapsed += time_step;
+            remaining -= time_step;
+
+            if next_move.elapsed < next_move.duration {
+                break
+            }
+
+            self.completed = next_move.end;
+            self.moves.pop_front();
+        }
+    }
+
+    /// The current position of the toolhead
+    pub fn position(&self) -> Axes {
+        self.moves
+            .front()
+            .map(|current_move| current_move.position())
+            .unwrap_or(self.completed)
+    }
+
+    /// Returns true once all planned moves have completed
+    pub fn is_idle(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn planner_full(&self) -> bool {
+        self.moves.len() >= PLANNER_BUFFER_SIZE
+    }
+
+    /// Plans a G0/G1 move. The F argument sets the feedrate for this and subsequent moves.
+    pub fn plan_move(&mut self, args: &[(char, f32)]) {
+        let mut end = self.target;
+
+        for (key, value) in args {
+            if *key == 'F' {
+                self.feedrate = *value;
+            }
+
+            if let Some(i) = AXES.iter().position(|axis| axis == key) {
+                let relative = if *key == 'E' {
+                    self.relative_moves || self.relative_extrusion
+                } else {
+                    self.relative_moves
+                };
+
+                end[i] = if relative {
+                    end[i] + value
+                } else {
+                    *value
+                };
+            }
+        }
+
+        self.plan_move_to(end, self.feedrate);
+    }
+
+    /// Plans a G28 move to zero for the given axes (or all of X, Y and Z if none are given)
+    pub fn plan_home(&mut self, axes: &[char]) {
+        let mut end = self.target;
+
+        for (i, axis) in AXES[..3].iter().enumerate() {
+            if axes.is_empty() || axes.contains(axis) {
+                end[i] = 0.0;
+            }
+        }
+
+        self.plan_move_to(end, self.config.default_feedrate);
+    }
+
+    /// Sets the position of the last planned move without moving (G92). Planned moves are offset
+    /// so that the printer does not need to wait for them to complete.
+    pub fn set_position(&mut self, args: &[(char, f32)]) {
+        for (key, value) in args {
+            if let Some(i) = AXES.iter().position(|axis| axis == key) {
+                let offset = value - self.target[i];
+
+                self.target[i] += offset;
+                self.completed[i] += offset;
+
+                for planned_move in self.moves.iter_mut() {
+                    planned_move.start[i] += offset;
+                    planned_move.end[i] += offset;
+                }
+            }
+        }
+    }
+
+    /// G90 / G91
+    pub fn set_relative_moves(&mut self, relative: bool) {
+        self.relative_moves = relative;
+    }
+
+    /// M82 / M83
+    pub fn set_relative_extrusion(&mut self, relative: bool) {
+        self.relative_extrusion = relative;
+    }
+
+    fn plan_move_to(&mut self, end: Axes, feedrate: f32) {
+        let planned_move = Move::new(self.target, end, feedrate, self.config.acceleration);
+
+        self.target = end;
+        self.moves.push_back(planned_move);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn physics() -> Physics {
+        Physics::new(SimulatorPhysics::default())
+    }
+
+    #[test]
+    fn heats_to_the_target_and_holds_it() {
+        let mut physics = physics();
+        physics.extruder.target = 200.0;
+
+        physics.step(Duration::from_secs(5));
+        assert!(!physics.extruder.reached_target(false));
+
+        physics.step(Duration::from_secs(300));
+        assert!(physics.extruder.reached_target(false));
+        assert!((physics.extruder.temperature - 200.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn only_waits_for_cooling_when_requested() {
+        let mut physics = physics();
+        physics.extruder.temperature = 200.0;
+        physics.extruder.target = 100.0;
+
+        assert!(physics.extruder.reached_target(false));
+        assert!(!physics.extruder.reached_target(true));
+
+        physics.step(Duration::from_secs(600));
+        assert!(physics.extruder.reached_target(true));
+    }
+
+    #[test]
+    fn plans_trapezoidal_moves() {
+        let mut physics = physics();
+
+        // 100mm at 50mm/s with 1000mm/s² acceleration: 0.05s to accelerate, 1.95s at speed and
+        // 0.05s to decelerate
+        physics.plan_move(&[('X', 100.0), ('F', 3_000.0)]);
+
+        physics.step(Duration::from_millis(50));
+        assert!((physics.position()[0] - 1.25).abs() < 0.01);
+
+        physics.step(Duration::from_millis(1_000));
+        assert!((physics.position()[0] - 51.25).abs() < 0.01);
+        assert_eq!(physics.target[0], 100.0);
+
+        physics.step(Duration::from_millis(900));
+        assert!(!physics.is_idle());
+
+        physics.step(Duration::from_millis(100));
+        assert!(physics.is_idle());
+        assert_eq!(physics.position(), [100.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn plans_relative_extrusion() {
+        let mut physics = physics();
+
+        physics.set_relative_extrusion(true);
+        physics.plan_move(&[('X', 10.0), ('E', 1.0)]);
+        physics.plan_move(&[('X', 20.0), ('E', 1.0)]);
+
+        assert_eq!(physics.target, [20.0, 0.0, 0.0, 2.0]);
+
+        physics.step(Duration::from_secs(10));
+        physics.set_position(&[('E', 0.0)]);
+
+        assert_eq!(physics.position(), [20.0, 0.0, 0.0, 0.0]);
+    }
+}
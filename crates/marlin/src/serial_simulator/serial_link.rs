@@ -0,0 +1,368 @@
+use std::collections::{
+    HashMap,
+    VecDeque,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use rand::{
+    Rng,
+    SeedableRng,
+    rngs::StdRng,
+};
+use teg_machine::components::SimulatorFaults;
+
+use super::{
+    Personality,
+    personality::render,
+};
+
+/// A fault injected into a single line sent to the simulator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The line is lost in transit and never reaches the firmware
+    DropLine,
+    /// A byte of the GCode is corrupted in transit so that the line fails its checksum
+    CorruptByte,
+    /// The firmware requests that the line be resent instead of executing it
+    RequestResend,
+    /// The firmware executes the line but does not send an ok
+    MissingOk,
+    /// The firmware sends a `busy: processing` before the ok
+    Busy,
+}
+
+/// Faults to inject into the next lines carrying each GCode and a log of the GCodes the
+/// simulator executed. Shared with the running simulator so that tests can script faults and
+/// check what was printed.
+#[derive(Clone, Default)]
+pub struct FaultScript {
+    faults: Arc<Mutex<HashMap<String, VecDeque<Fault>>>>,
+    executed: Arc<Mutex<Vec<String>>>,
+}
+
+impl FaultScript {
+    /// Injects the fault into the next line sent for the GCode
+    pub fn inject(&self, gcode: &str, fault: Fault) {
+        self.faults
+            .lock()
+            .unwrap()
+            .entry(gcode.to_string())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// The GCodes executed by the simulator in the order they were executed
+    pub fn executed(&self) -> Vec<String> {
+        self.executed.lock().unwrap().clone()
+    }
+
+    fn next_fault(&self, gcode: &str) -> Option<Fault> {
+        self.faults
+            .lock()
+            .unwrap()
+            .get_mut(gcode)
+            .and_then(|faults| faults.pop_front())
+    }
+}
+
+/// The firmware's end of the simulated serial connection. Validates the line numbers and
+/// checksums of the lines it receives the way the personality's firmware does and injects faults
+/// into them.
+pub struct SerialLink {
+    personality: Personality,
+    last_line_number: u32,
+    faults: SimulatorFaults,
+    rng: StdRng,
+    script: Option<FaultScript>,
+}
+
+impl SerialLink {
+    pub fn new(personality: Personality, faults: SimulatorFaults) -> Self {
+        let rng = faults.seed
+            .map(StdRng::seed_from_u64)
+            .unwrap_or_else(StdRng::from_entropy);
+
+        Self {
+            personality,
+            last_line_number: 0,
+            faults,
+            rng,
+            script: None,
+        }
+    }
+
+    /// Injects the script's faults ahead of the random faults and logs the executed GCodes to it
+    pub fn with_script(mut self, script: FaultScript) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    /// Picks the fault for the next line, if any
+    pub fn next_fault(&mut self, line: &str) -> Option<Fault> {
+        let scripted_fault = self.script
+            .as_ref()
+            .and_then(|script| script.next_fault(strip_line(line)));
+
+        scripted_fault.or_else(|| self.random_fault())
+    }
+
+    /// Picks a fault for the next line at random using the configured probabilities
+    pub fn random_fault(&mut self) -> Option<Fault> {
+        let SimulatorFaults {
+            dropped_lines,
+            corrupted_bytes,
+            resend_requests,
+            missing_oks,
+            busy_responses,
+            ..
+        } = self.faults;
+
+        let faults = [
+            (Fault::DropLine, dropped_lines),
+            (Fault::CorruptByte, corrupted_bytes),
+            (Fault::RequestResend, resend_requests),
+            (Fault::MissingOk, missing_oks),
+            (Fault::Busy, busy_responses),
+        ];
+
+        faults
+            .iter()
+            .find(|(_, probability)| {
+                probability > &0f64 && self.rng.gen_bool(probability.min(1f64))
+            })
+            .map(|(fault, _)| *fault)
+    }
+
+    /// Validates a line received from the host. Returns the GCode to execute or, if the line was
+    /// rejected, the responses to send in its place.
+    pub fn receive(&mut self, line: &str, fault: Option<Fault>) -> Result<String, Vec<String>> {
+        let gcode = self.validate(line, fault)?;
+
+        if let Some(script) = &self.script {
+            script.executed.lock().unwrap().push(gcode.clone());
+        }
+
+        Ok(gcode)
+    }
+
+    fn validate(&mut self, line: &str, fault: Option<Fault>) -> Result<String, Vec<String>> {
+        let line = match fault {
+            Some(Fault::DropLine) => return Err(vec![]),
+            Some(Fault::CorruptByte) => self.corrupt(line),
+            _ => line.to_string(),
+        };
+
+        let (content, checksum) = if let Some(asterisk) = line.rfind('*') {
+            (&line[..asterisk], Some(line[asterisk + 1..].trim()))
+        } else {
+            (&line[..], None)
+        };
+
+        let (line_number, gcode) = if content.starts_with('N') {
+            let end = content.find(' ').unwrap_or(content.len());
+            let line_number = content[1..end].parse::<u32>().unwrap_or(0);
+
+            (Some(line_number), content[end..].trim())
+        } else {
+            (None, content.trim())
+        };
+
+        // Like Marlin, M110 sets the line number to its N argument instead of the line's
+        let is_m110 = gcode.starts_with("M110");
+
+        let line_number = if is_m110 {
+            gcode
+                .split_whitespace()
+                .find_map(|word| word.strip_prefix('N'))
+                .and_then(|n| n.parse::<u32>().ok())
+                .or(line_number)
+        } else {
+            line_number
+        };
+
+        if !self.personality.validates_lines {
+            return Ok(gcode.to_string())
+        }
+
+        // Lines without line numbers are executed without validation
+        if let Some(line_number) = line_number {
+            let errors = &self.personality.errors;
+
+            if !is_m110 && line_number != self.last_line_number + 1 {
+                return Err(self.line_error(&errors.line_number_mismatch))
+            }
+
+            let sum = content.bytes().fold(0u8, |sum, byte| sum ^ byte);
+
+            match checksum {
+                Some(checksum) if checksum.parse::<u8>() == Ok(sum) => (),
+                Some(_) => return Err(self.line_error(&errors.checksum_mismatch)),
+                None => return Err(self.line_error(&errors.no_checksum)),
+            };
+
+            if let Some(Fault::RequestResend) = fault {
+                return Err(self.resend_request())
+            }
+
+            self.last_line_number = line_number;
+        }
+
+        Ok(gcode.to_string())
+    }
+
+    /// Applies any fault to the lines of the firmware's response to a GCode
+    pub fn respond(&self, response: String, fault: Option<Fault>) -> Vec<String> {
+        let mut responses = response
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+
+        match fault {
+            Some(Fault::MissingOk) => {
+                // Keep any feedback sent on the same line as the ok
+                responses = responses
+                    .into_iter()
+                    .filter(|line| line != "ok")
+                    .map(|line| line.trim_start_matches("ok ").to_string())
+                    .collect();
+            }
+            Some(Fault::Busy) => {
+                if let Some(busy) = &self.personality.busy {
+                    responses.insert(0, busy.clone());
+                }
+            }
+            _ => (),
+        };
+
+        responses
+    }
+
+    /// Flips a bit in one of the bytes between the line number and the checksum
+    fn corrupt(&mut self, line: &str) -> String {
+        let start = if line.starts_with('N') {
+            line.find(' ').map(|i| i + 1).unwrap_or(0)
+        } else {
+            0
+        };
+        let end = line.rfind('*').unwrap_or(line.len());
+
+        let mut bytes = line.as_bytes().to_vec();
+
+        if start < end {
+            let index = self.rng.gen_range(start..end);
+            bytes[index] ^= 0x01;
+        }
+
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+
+    fn line_error(&self, error: &str) -> Vec<String> {
+        let values = [
+            ("error", error.to_string()),
+            ("lastLineNumber", self.last_line_number.to_string()),
+        ];
+
+        let mut responses = self.personality.line_error
+            .iter()
+            .map(|template| render(template, &values))
+            .collect::<Vec<_>>();
+
+        responses.append(&mut self.resend_request());
+
+        responses
+    }
+
+    fn resend_request(&self) -> Vec<String> {
+        let values = [
+            ("lineNumber", (self.last_line_number + 1).to_string()),
+        ];
+
+        self.personality.resend
+            .iter()
+            .map(|template| render(template, &values))
+            .collect()
+    }
+}
+
+/// The GCode of a line without its line number and checksum
+fn strip_line(line: &str) -> &str {
+    let content = line.rfind('*')
+        .map(|asterisk| &line[..asterisk])
+        .unwrap_or(line);
+
+    if content.starts_with('N') {
+        content.find(' ')
+            .map(|space| content[space..].trim())
+            .unwrap_or("")
+    } else {
+        content.trim()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teg_machine::components::SimulatorPersonality;
+
+    fn with_checksum(line: &str) -> String {
+        let sum = line.bytes().fold(0u8, |sum, byte| sum ^ byte);
+        format!("{}*{}", line, sum)
+    }
+
+    fn connected_link() -> SerialLink {
+        let personality = Personality::load(SimulatorPersonality::Marlin).unwrap();
+        let mut link = SerialLink::new(personality, SimulatorFaults::default());
+        assert_eq!(link.receive(&with_checksum("M110 N0"), None), Ok("M110 N0".to_string()));
+
+        link
+    }
+
+    #[test]
+    fn accepts_sequential_lines() {
+        let mut link = connected_link();
+
+        assert_eq!(link.receive(&with_checksum("N1 M105"), None), Ok("M105".to_string()));
+        assert_eq!(link.receive(&with_checksum("N2 G1 X10"), None), Ok("G1 X10".to_string()));
+        // Tickles are sent without line numbers
+        assert_eq!(link.receive("M105", None), Ok("M105".to_string()));
+    }
+
+    #[test]
+    fn requests_a_resend_for_out_of_order_lines() {
+        let mut link = connected_link();
+
+        assert_eq!(
+            link.receive(&with_checksum("N2 M105"), None),
+            Err(vec![
+                "Error:Line Number is not Last Line Number+1, Last Line: 0".to_string(),
+                "Resend: 1".to_string(),
+                "ok".to_string(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn requests_a_resend_for_checksum_mismatches() {
+        let mut link = connected_link();
+
+        assert_eq!(
+            link.receive(&with_checksum("N1 M105"), Some(Fault::CorruptByte)),
+            Err(vec![
+                "Error:checksum mismatch, Last Line: 0".to_string(),
+                "Resend: 1".to_string(),
+                "ok".to_string(),
+            ]),
+        );
+        assert_eq!(
+            link.receive("N1 M105", None),
+            Err(vec![
+                "Error:No Checksum with line number, Last Line: 0".to_string(),
+                "Resend: 1".to_string(),
+                "ok".to_string(),
+            ]),
+        );
+        assert_eq!(link.receive(&with_checksum("N1 M105"), None), Ok("M105".to_string()));
+    }
+}
@@ -3,11 +3,17 @@ use eyre::{
     Context as _,
     Result,
 };
-use futures::{SinkExt, StreamExt};
-use std::{io, str, cmp};
+use futures::{Sink, SinkExt, StreamExt};
+use std::{io, str};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::codec::{Decoder, Encoder};
 use bytes::{BufMut, BytesMut};
-use rand::Rng;
+use teg_machine::components::{
+    SimulatorFaults,
+    SimulatorPersonality,
+    SimulatorPhysics,
+};
 use nom_gcode::{
     GCodeLine,
     Mnemonic::{
@@ -17,181 +23,326 @@ use nom_gcode::{
     },
 };
 
+use super::{
+    SerialLink,
+    Personality,
+    Physics,
+    physics::AXES,
+};
+
+/// How often the simulation is advanced while a GCode blocks
+const TIME_STEP: Duration = Duration::from_millis(50);
+
+/// How often temperatures are reported while waiting on a heater
+const TEMPERATURE_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often busy keepalives are sent while waiting on motion (Marlin's
+/// DEFAULT_KEEPALIVE_INTERVAL)
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
+
 pub struct SerialSimulator;
 
-#[derive(Default)]
-struct Position {
-    pub actual: f32,
-    pub target: f32,
+/// What the firmware sends while a GCode blocks
+enum Keepalive {
+    Silent,
+    Temperatures,
+    Busy,
+}
+
+/// The physics model advanced in real time
+struct Simulation {
+    personality: Personality,
+    physics: Physics,
+    updated_at: Instant,
+}
+
+impl Simulation {
+    fn update(&mut self) {
+        let now = Instant::now();
+
+        self.physics.step(now - self.updated_at);
+        self.updated_at = now;
+    }
+
+    fn temperature_report(&self) -> String {
+        self.personality.temperature_report(&self.physics.extruder, &self.physics.bed)
+    }
+
+    /// Blocks until `done` returns true, sending keepalives while it waits
+    async fn wait_until<S, F>(
+        &mut self,
+        sender: &mut S,
+        keepalive: Keepalive,
+        mut done: F,
+    ) -> Result<()>
+    where
+        S: Sink<String, Error = io::Error> + Unpin,
+        F: FnMut(&Physics) -> bool,
+    {
+        let mut reported_at = Instant::now();
+
+        loop {
+            self.update();
+
+            if done(&self.physics) {
+                return Ok(())
+            }
+
+            let report = match keepalive {
+                Keepalive::Temperatures
+                    if reported_at.elapsed() >= TEMPERATURE_REPORT_INTERVAL
+                => {
+                    Some(self.temperature_report())
+                }
+                Keepalive::Busy if reported_at.elapsed() >= KEEPALIVE_INTERVAL => {
+                    self.personality.busy.clone()
+                }
+                _ => None,
+            };
+
+            if let Some(report) = report {
+                sender.send(report).await?;
+                reported_at = Instant::now();
+            }
+
+            tokio::time::sleep(TIME_STEP).await;
+        }
+    }
 }
 
 impl SerialSimulator {
-    pub async fn run(serial: tokio_serial::SerialStream) -> Result<()> {
+    pub async fn run<S>(
+        serial: S,
+        personality: SimulatorPersonality,
+        physics: SimulatorPhysics,
+        faults: SimulatorFaults,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let personality = Personality::load(personality)?;
+        let link = SerialLink::new(personality.clone(), faults);
+
+        Self::run_with_link(serial, personality, physics, link).await
+    }
+
+    /// Runs the simulator with a pre-configured serial link (eg. with scripted faults)
+    pub async fn run_with_link<S>(
+        serial: S,
+        personality: Personality,
+        physics: SimulatorPhysics,
+        mut link: SerialLink,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         let (
             mut sender,
             mut reader,
         ) = LineCodec.framed(serial).split();
 
-        let greeting = include_str!("greeting.txt").trim().to_string();
-        sender.send(greeting).await?;
-
-        let mut extruder = 22f32;
-        let mut extruder_target = 0f32;
-        let mut bed = 22f32;
-        let mut bed_target = 22f32;
-
-        let mut positions = std::collections::HashMap::new();
-        positions.insert('X', Position::default());
-        positions.insert('Y', Position::default());
-        positions.insert('Z', Position::default());
-        positions.insert('E', Position::default());
+        for line in personality.greeting.lines() {
+            sender.send(line.to_string()).await?;
+        }
 
-        let feedrate = 50f32;
+        let mut simulation = Simulation {
+            personality,
+            physics: Physics::new(physics),
+            updated_at: Instant::now(),
+        };
 
         while let Some(line_result) = reader.next().await {
-            // let mut rng = rand::thread_rng();
-
             let line = line_result
-                .wrap_err("Failed to read serial simulator")?
-                .replace("*", " *");
+                .wrap_err("Failed to read serial simulator")?;
 
             if line.is_empty() {
                 continue;
             }
 
-            let (_, gcode) = nom_gcode::parse_gcode(&line)?;
+            let fault = link.next_fault(&line);
 
-            let gcode = if let Some(GCodeLine::GCode(gcode)) = gcode {
-                gcode
-            } else {
-                continue;
+            if let Some(fault) = fault {
+                debug!("Simulator injecting {:?} into {:?}", fault, line);
+            }
+
+            // Validate the line number and checksum and strip them from the GCode
+            let line = match link.receive(&line, fault) {
+                Ok(line) => line,
+                Err(responses) => {
+                    for response in responses {
+                        sender.send(response).await?;
+                    }
+                    continue;
+                }
             };
 
-            let response = match (&gcode.mnemonic, &gcode.major) {
-                // Set Hotend
-                (M, 104) => {
-                    if let Some((_, Some(target))) = gcode.arguments()
-                        .find(|(key, _)| key == &'S')
-                    {
-                        extruder_target = *target;
+            // Lines are not validated by every firmware (eg. Klipper) so corrupted lines can reach
+            // the parser
+            let gcode = match nom_gcode::parse_gcode(&line) {
+                Ok((_, Some(GCodeLine::GCode(gcode)))) => gcode,
+                Ok(_) => continue,
+                Err(err) => {
+                    warn!("Simulator unable to parse {:?}: {:?}", line, err);
+
+                    let response = format!(
+                        "{}\nok",
+                        simulation.personality.unknown_command(&line),
+                    );
+
+                    for response in link.respond(response, fault) {
+                        sender.send(response).await?;
                     }
+                    continue;
+                }
+            };
 
-                    while extruder < extruder_target {
-                        tokio::time::sleep(
-                            std::time::Duration::from_millis(500)
-                        )
-                            .await;
-
-                        extruder += rand::thread_rng().gen_range(20f32..30f32);
-                        bed += rand::thread_rng().gen_range(-2f32..2f32);
-
-                        if (bed as i64) < 0i64 {
-                            bed = 0f32
-                        }
-
-                        let feedback = format!(
-                            "T:{extruder} /0.0 B:{bed} /0.0 B@:0 @:0",
-                            extruder = extruder,
-                            bed = bed,
-                        );
-                        sender.send(feedback.to_string()).await?;
-                    };
+            let args = gcode.arguments()
+                .filter_map(|(key, value)| value.map(|value| (*key, value)))
+                .collect::<Vec<_>>();
 
-                    "ok".to_string()
-                },
-                // Set Bed
-                (M, 140) => {
-                    if let Some((_, Some(target))) = gcode.arguments()
-                        .find(|(key, _)| key == &'S')
-                    {
-                        bed_target = *target;
-                    }
+            let find_arg = |key: char| {
+                args.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, value)| *value)
+            };
 
-                    while bed < bed_target {
-                        tokio::time::sleep(
-                            std::time::Duration::from_millis(500)
-                        )
-                            .await;
-
-                        extruder += rand::thread_rng().gen_range(-2f32..2f32);
-                        bed += rand::thread_rng().gen_range(5f32..15f32);
-
-                        if (extruder as i64) < 0i64 {
-                            extruder = 0f32
-                        }
-
-                        let feedback = format!(
-                            "T:{extruder} /0.0 B:{bed} /0.0 B@:0 @:0",
-                            extruder = extruder,
-                            bed = bed,
-                        );
-                        sender.send(feedback.to_string()).await?;
+            simulation.update();
+
+            let response = match (&gcode.mnemonic, &gcode.major) {
+                // Set Hotend / Set Bed
+                | (M, 104)
+                | (M, 140) => {
+                    let heater = if gcode.major == 104 {
+                        &mut simulation.physics.extruder
+                    } else {
+                        &mut simulation.physics.bed
                     };
 
+                    if let Some(target) = find_arg('S') {
+                        heater.target = target;
+                    }
+
                     "ok".to_string()
                 },
-                (M, 105) => {
-                    extruder +=
-                        (cmp::max(extruder_target as i64, 22i64) as f32 - extruder).signum()
-                        * rand::thread_rng().gen_range(0f32..5f32);
+                // Wait for Hotend / Wait for Bed. S only waits while heating, R also waits
+                // while cooling.
+                | (M, 109)
+                | (M, 190) => {
+                    let is_extruder = gcode.major == 109;
+
+                    let (target, wait_for_cooling) = match (find_arg('S'), find_arg('R')) {
+                        (_, Some(target)) => (Some(target), true),
+                        (target, None) => (target, false),
+                    };
 
-                    bed +=
-                        (cmp::max(bed_target as i64, 22i64) as f32 - bed).signum()
-                        * rand::thread_rng().gen_range(0f32..5f32);
+                    if let Some(target) = target {
+                        let physics = &mut simulation.physics;
+                        let heater = if is_extruder {
+                            &mut physics.extruder
+                        } else {
+                            &mut physics.bed
+                        };
 
-                    if (extruder as i64) <= 0i64 {
-                        extruder = 0f32
+                        heater.target = target;
                     }
 
-                    if (bed as i64) <= 0i64 {
-                        bed = 0f32
-                    }
+                    simulation.wait_until(&mut sender, Keepalive::Temperatures, |physics| {
+                        let heater = if is_extruder {
+                            &physics.extruder
+                        } else {
+                            &physics.bed
+                        };
 
-                    format!(
-                        "ok T:{extruder} /0.0 B:{bed} /0.0 B@:0 @:0",
-                        extruder = extruder,
-                        bed = bed,
-                    )
+                        heater.reached_target(wait_for_cooling)
+                    }).await?;
+
+                    "ok".to_string()
+                },
+                // Get Temperatures
+                (M, 105) => {
+                    format!("ok {}", simulation.temperature_report())
+                },
+                // Firmware Info
+                (M, 115) => {
+                    format!("{}\nok", simulation.personality.firmware_info.trim())
                 },
                 // Move
                 | (G, 0)
                 | (G, 1) => {
-                    for arg in gcode.arguments() {
-                        let value = if let Some(value) = arg.1 {
-                            value
-                        } else {
-                            continue
-                        };
-                        if let Some(p) = positions.get_mut(&arg.0) {
-                            p.target = value
-                        };
-                    }
+                    // Moves block while the planner's buffer is full
+                    simulation.wait_until(&mut sender, Keepalive::Silent, |physics| {
+                        !physics.planner_full()
+                    }).await?;
+
+                    simulation.physics.plan_move(&args);
+
+                    "ok".to_string()
+                }
+                // Home
+                (G, 28) => {
+                    let axes = gcode.arguments()
+                        .map(|(key, _)| *key)
+                        .filter(|key| AXES.contains(key))
+                        .collect::<Vec<_>>();
+
+                    simulation.wait_until(&mut sender, Keepalive::Silent, |physics| {
+                        !physics.planner_full()
+                    }).await?;
+
+                    simulation.physics.plan_home(&axes);
+
+                    simulation.wait_until(&mut sender, Keepalive::Busy, |physics| {
+                        physics.is_idle()
+                    }).await?;
+
+                    "ok".to_string()
+                }
+                // Absolute / Relative Positioning
+                | (G, 90)
+                | (G, 91) => {
+                    simulation.physics.set_relative_moves(gcode.major == 91);
+
+                    "ok".to_string()
+                }
+                // Absolute / Relative Extrusion
+                | (M, 82)
+                | (M, 83) => {
+                    simulation.physics.set_relative_extrusion(gcode.major == 83);
+
+                    "ok".to_string()
+                }
+                // Set Position
+                (G, 92) => {
+                    simulation.physics.set_position(&args);
+
+                    "ok".to_string()
+                }
+                // Finish Moves
+                (M, 400) => {
+                    simulation.wait_until(&mut sender, Keepalive::Busy, |physics| {
+                        physics.is_idle()
+                    }).await?;
+
                     "ok".to_string()
                 }
                 // Get Position
                 (M, 114) => {
-                    for (_, p) in positions.iter_mut() {
-                        let direction_signum = (p.target - p.actual).signum();
-                        p.actual += direction_signum * feedrate;
-
-                        // Handle overshoots
-                        if (p.target - p.actual).signum() != direction_signum {
-                            p.actual = p.target
-                        }
-                    }
-                    format!(
-                        "X:{x} Y:{y} Z:{z} E:{e} Count X: 0.00Y:0.00Z:0.00\nok",
-                        x = positions.get(&'X').unwrap().actual,
-                        y = positions.get(&'Y').unwrap().actual,
-                        z = positions.get(&'Z').unwrap().actual,
-                        e = positions.get(&'E').unwrap().actual,
-                    )
+                    let physics = &simulation.physics;
+                    let report = simulation.personality.position_report(
+                        &physics.target,
+                        &physics.position(),
+                    );
+
+                    format!("{}\nok", report)
                 }
                 _ => "ok".to_string()
             };
 
             trace!("Simulator responding to {:?} with {:?}", gcode, response);
-            sender.send(response).await?;
+
+            for response in link.respond(response, fault) {
+                sender.send(response).await?;
+            }
         }
         Ok(())
     }
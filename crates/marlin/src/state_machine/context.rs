@@ -1,4 +1,5 @@
-use machine_message::TaskStatus;
+use machine_message::{HostAction, TaskStatus};
+use nom_reprap_response::{SDFile, SDPrintProgress};
 use super::Task;
 use std::collections::vec_deque::VecDeque;
 use teg_protobufs::MachineFlags;
@@ -12,8 +13,29 @@ use crate::state_machine;
 use teg_machine::{
     config::MachineConfig,
     components::Controller,
+    task::PrintRecoveryState,
 };
 
+/// The task line that a GCode was despooled from
+#[derive(Clone, Debug)]
+pub struct PrintRecoveryLine {
+    pub task_id: crate::DbId,
+    pub client_id: crate::DbId,
+    pub machine_override: bool,
+    pub line_number: u32,
+}
+
+impl PrintRecoveryLine {
+    pub fn new(task: &Task, line_number: u32) -> Self {
+        Self {
+            task_id: task.id.clone(),
+            client_id: task.client_id.clone(),
+            machine_override: task.machine_override,
+            line_number,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Context {
     pub baud_rate: u32,
@@ -27,6 +49,14 @@ pub struct Context {
 
     pub feedback: machine_message::Feedback,
     gcode_history_buffer: VecDeque<machine_message::GCodeHistoryEntry>,
+    host_actions_buffer: Vec<i32>,
+
+    /// The state needed to recover the current print if the driver or host crashes
+    pub print_recovery: Option<PrintRecoveryState>,
+    print_recovery_save_scheduled: bool,
+
+    /// The number of reconnection attempts to a network serial port since it was last connected
+    pub reconnect_attempts: u32,
 }
 
 impl Context {
@@ -47,6 +77,10 @@ impl Context {
             config,
             controller,
             gcode_history_buffer,
+            host_actions_buffer: vec![],
+            print_recovery: None,
+            print_recovery_save_scheduled: false,
+            reconnect_attempts: 0,
         }
     }
 
@@ -81,6 +115,10 @@ impl Context {
         self.feedback.gcode_history = self.gcode_history_buffer.drain(..).collect();
     }
 
+    pub fn add_host_actions_to_feedback(&mut self) -> () {
+        self.feedback.host_actions = self.host_actions_buffer.drain(..).collect();
+    }
+
     pub fn handle_state_change(&mut self, state: &state_machine::State) {
         use state_machine::State::*;
 
@@ -99,6 +137,8 @@ impl Context {
 
         self.feedback.task_progress = previous_feedback.task_progress;
         self.current_hotend_index = 0;
+        // State changes cancel all delays including any scheduled save
+        self.print_recovery_save_scheduled = false;
 
         if let Errored { message } = state  {
             let error = machine_message::Error {
@@ -149,9 +189,30 @@ impl Context {
         let despooled_line_number = task.despooled_line_number
             .unwrap_or(0);
 
+        self.upsert_task_progress(&task.id, despooled_line_number, status);
+    }
+
+    /// Updates the task's progress once one of it's lines is acknowledged without changing it's
+    /// status (eg. a paused task's lines are still acknowledged after it is paused).
+    pub fn push_task_line_acknowledged(&mut self, task_id: &crate::DbId, line_number: u32) {
+        let progress = self.feedback.task_progress
+            .iter_mut()
+            .find(|p| p.task_id == *task_id);
+
+        if let Some(progress) = progress {
+            progress.despooled_line_number = progress.despooled_line_number.max(line_number);
+        }
+    }
+
+    fn upsert_task_progress(
+        &mut self,
+        task_id: &crate::DbId,
+        despooled_line_number: u32,
+        status: TaskStatus,
+    ) {
         let progress = self.feedback.task_progress
             .iter_mut()
-            .find(|p| p.task_id == task.id);
+            .find(|p| p.task_id == *task_id);
 
         if let Some(mut progress) = progress {
             // Optimized by re-using existing progress structs if they exist
@@ -160,7 +221,7 @@ impl Context {
         } else {
             // If a progress struct doesn't exist for this task we push a new one
             let new_progress = machine_message::TaskProgress {
-                task_id: task.id.clone(),
+                task_id: task_id.clone(),
                 despooled_line_number,
                 status: status as i32,
             };
@@ -169,6 +230,169 @@ impl Context {
         }
     }
 
+    fn sd_card_mut(&mut self) -> &mut machine_message::SdCard {
+        self.feedback.sd_card.get_or_insert_with(Default::default)
+    }
+
+    pub fn set_sd_card_files(&mut self, files: &Vec<SDFile>) {
+        let sd_card = self.sd_card_mut();
+
+        sd_card.files_listed = true;
+        sd_card.files = files
+            .iter()
+            .map(|file| machine_message::SdCardFile {
+                filename: file.filename.clone(),
+                size: file.size.unwrap_or(0),
+            })
+            .collect();
+    }
+
+    pub fn sd_card_print(&self) -> Option<&machine_message::SdCardPrint> {
+        self.feedback.sd_card
+            .as_ref()
+            .and_then(|sd_card| sd_card.print.as_ref())
+    }
+
+    pub fn is_sd_card_print_paused(&self) -> bool {
+        let task_id = if let Some(print) = self.sd_card_print() {
+            &print.task_id
+        } else {
+            return false
+        };
+
+        self.feedback.task_progress
+            .iter()
+            .any(|p| {
+                p.task_id == *task_id && p.status == TaskStatus::TaskPaused as i32
+            })
+    }
+
+    pub fn start_sd_card_print(&mut self, task_id: crate::DbId, filename: String) {
+        self.upsert_task_progress(&task_id, 0, TaskStatus::TaskStarted);
+
+        self.sd_card_mut().print = Some(machine_message::SdCardPrint {
+            task_id,
+            filename,
+            ..Default::default()
+        });
+    }
+
+    /// SD card prints report the number of bytes printed in place of their despooled line number
+    fn push_sd_card_print_progress(&mut self, status: TaskStatus) {
+        let (task_id, bytes_printed) = if let Some(print) = self.sd_card_print() {
+            (print.task_id.clone(), print.bytes_printed)
+        } else {
+            return
+        };
+
+        let despooled_line_number = std::cmp::min(bytes_printed, u32::MAX as u64) as u32;
+
+        self.upsert_task_progress(&task_id, despooled_line_number, status);
+    }
+
+    pub fn pause_sd_card_print(&mut self) {
+        self.push_sd_card_print_progress(TaskStatus::TaskPaused);
+    }
+
+    pub fn resume_sd_card_print(&mut self) {
+        self.push_sd_card_print_progress(TaskStatus::TaskStarted);
+    }
+
+    pub fn update_sd_card_print_progress(&mut self, progress: &SDPrintProgress) {
+        let print = self.feedback.sd_card
+            .as_mut()
+            .and_then(|sd_card| sd_card.print.as_mut());
+
+        if let Some(print) = print {
+            print.bytes_printed = progress.bytes_printed;
+            print.total_bytes = progress.total_bytes;
+        } else {
+            return
+        }
+
+        let status = if self.is_sd_card_print_paused() {
+            TaskStatus::TaskPaused
+        } else {
+            TaskStatus::TaskStarted
+        };
+
+        self.push_sd_card_print_progress(status);
+    }
+
+    /// Records the end of the SD card print (if one is running) with the given status
+    pub fn settle_sd_card_print(&mut self, status: TaskStatus) {
+        self.push_sd_card_print_progress(status);
+
+        if let Some(sd_card) = self.feedback.sd_card.as_mut() {
+            sd_card.print = None;
+        }
+    }
+
+    /// Records a task GCode acknowledged by the firmware in the print recovery state. Override
+    /// tasks (eg. temperature changes made during a print) update the state of the current print.
+    ///
+    /// Returns true if a save of the print recovery state should be scheduled.
+    pub fn record_print_recovery(&mut self, line: &PrintRecoveryLine, gcode: &str) -> bool {
+        if line.client_id == "INTERNAL" {
+            return false
+        }
+
+        if !line.machine_override {
+            // State carries over from the tasks before the print (eg. the resume hook)
+            let state = self.print_recovery.get_or_insert_with(|| {
+                PrintRecoveryState::new(line.task_id.clone())
+            });
+
+            if state.task_id != line.task_id {
+                state.task_id = line.task_id.clone();
+                state.despooled_line_number = None;
+            }
+        }
+
+        let state = if let Some(state) = &mut self.print_recovery {
+            state
+        } else {
+            return false
+        };
+
+        if state.task_id == line.task_id {
+            state.despooled_line_number = Some(line.line_number as u64);
+        }
+        state.apply_gcode(gcode);
+
+        let schedule_save = !self.print_recovery_save_scheduled;
+        self.print_recovery_save_scheduled = true;
+
+        schedule_save
+    }
+
+    pub fn print_recovery_saved(&mut self) {
+        self.print_recovery_save_scheduled = false;
+    }
+
+    pub fn push_host_action(&mut self, action: HostAction) {
+        self.host_actions_buffer.push(action as i32);
+    }
+
+    pub fn begin_prompt(&mut self, message: String) {
+        self.feedback.prompt = Some(machine_message::Prompt {
+            message,
+            buttons: vec![],
+        });
+    }
+
+    pub fn add_prompt_button(&mut self, label: String) {
+        if let Some(prompt) = self.feedback.prompt.as_mut() {
+            prompt.buttons.push(label);
+        } else {
+            warn!("Warning: Prompt button received without a prompt. Ignoring: {:?}", label);
+        }
+    }
+
+    pub fn end_prompt(&mut self) {
+        self.feedback.prompt = None;
+    }
+
     pub fn push_gcode_rx(&mut self, raw_src: String, is_polling: bool) {
         // Placeholder: Some day we might allow a toggle to display polling gcodes
         if is_polling {
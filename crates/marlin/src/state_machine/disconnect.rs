@@ -1,9 +1,16 @@
+use crate::protos::machine_message::TaskStatus;
+use teg_driver::serial_transport::{
+    reconnect_delay,
+    SerialPortAddress,
+};
+
 use super::{
     State,
     Loop,
     State::*,
     Effect,
     Context,
+    Event,
     ReadyState,
 };
 
@@ -18,14 +25,32 @@ pub fn disconnect(state: &State, context: &mut Context) -> Loop {
             });
     };
 
-    let effects = vec![
+    context.settle_sd_card_print(TaskStatus::TaskErrored);
+
+    let mut effects = vec![
         Effect::CancelAllDelays,
+        Effect::SavePrintRecoveryState,
         Effect::CloseSerialPort,
         Effect::SendFeedbackProtobuf,
         // TODO: try to re-open the serial port immediately in case a new port is already available
         // Effect::DetectSerialPort,
     ];
 
+    // Network serial ports are not found by device discovery so they are reconnected with an
+    // exponential backoff
+    if SerialPortAddress::parse(context.config.tty_path()).is_network() {
+        let delay = reconnect_delay(context.reconnect_attempts);
+
+        info!("Reconnecting to network serial port in {:?}", delay);
+        context.reconnect_attempts += 1;
+
+        effects.push(Effect::Delay {
+            key: "reconnect".to_string(),
+            duration: delay,
+            event: Event::Reconnect,
+        });
+    }
+
     context.handle_state_change(&Disconnected);
 
     Loop::new(
@@ -33,3 +58,39 @@ pub fn disconnect(state: &State, context: &mut Context) -> Loop {
         effects,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MachineConfig;
+    use std::time::Duration;
+    use teg_driver::serial_transport::RECONNECT_MAX_DELAY;
+
+    fn scheduled_reconnect_delay(effects: &Vec<Effect>) -> Option<Duration> {
+        effects.iter().find_map(|effect| match effect {
+            Effect::Delay { event: Event::Reconnect, duration, .. } => Some(*duration),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn reconnects_to_network_serial_ports_with_backoff() {
+        let mut config: MachineConfig = toml::from_str(
+            include_str!("../../../../machine.default.toml"),
+        )
+            .expect("Invalid default machine config");
+
+        config.get_controller_mut().model.serial_port_id = "tcp://127.0.0.1:2000".to_string();
+
+        let mut context = Context::new(config);
+
+        let delays = (0..8)
+            .map(|_| scheduled_reconnect_delay(&disconnect(&Disconnected, &mut context).effects))
+            .collect::<Vec<_>>();
+
+        assert_eq!(delays[0], Some(Duration::from_millis(500)));
+        assert_eq!(delays[1], Some(Duration::from_secs(1)));
+        assert_eq!(delays[2], Some(Duration::from_secs(2)));
+        assert_eq!(delays[7], Some(RECONNECT_MAX_DELAY));
+    }
+}
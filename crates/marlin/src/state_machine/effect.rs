@@ -1,8 +1,4 @@
 use std::{
-    io::{
-        BufReader,
-        BufRead,
-    },
     time::{
         Duration,
         // Instant,
@@ -14,13 +10,16 @@ use futures::{
     // StreamExt,
     SinkExt,
     future::{
+        self,
         Abortable,
         AbortHandle,
         Aborted,
-        // FutureExt,
+        Future,
+        FutureExt,
     },
     channel::mpsc,
 };
+use tokio::io::AsyncWriteExt;
 
 // use tokio::{
 //     // prelude::*,
@@ -28,16 +27,20 @@ use futures::{
 //     // sync::oneshot,
 // };
 
-use fallible_iterator::{
-    FallibleIterator,
-};
-
 use teg_protobufs::{MachineFlags, Message};
+use teg_machine::task::{
+    GCodeFile,
+    PrintRecoveryState,
+};
 use bytes::Bytes;
+use teg_driver::serial_transport::SerialTransport;
 
 use super::{
     Event,
     Task,
+    GCodeLines,
+    LineActions,
+    SkippedLines,
 };
 
 use crate::{
@@ -45,12 +48,13 @@ use crate::{
         GCodeLine,
         // ResponsePayload::Response,
     },
-    // protos::{
-    //     // machine_message,
-    //     // MachineMessage,
-    //     server_message,
-    //     // ServerMessage,
-    // },
+    protos::{
+        // machine_message,
+        // MachineMessage,
+        server_message,
+        // ServerMessage,
+    },
+    serial_simulator::SerialSimulator,
     StateMachineReactor,
 };
 
@@ -64,12 +68,15 @@ pub enum Effect {
     // ResetSerial
     SendInitProtobuf,
     SendFeedbackProtobuf,
+    SavePrintRecoveryState,
     LoadGCode {
         file_path: String,
         task_id: crate::DbId,
         client_id: crate::DbId,
         machine_override: bool,
         despooled_line_number: Option<u32>,
+        line_actions: Vec<server_message::LineAction>,
+        skipped_lines: Vec<server_message::LineRange>,
     },
     CloseSerialPort,
     ExitProcess,
@@ -123,10 +130,7 @@ impl Effect {
             //     }
             // }
             Effect::OpenSerialPort { baud_rate } => {
-                if let Ok(serial_future) = reactor.serial_manager.open(
-                    baud_rate,
-                    reactor.context.controller.model.simulate,
-                ).await {
+                if let Ok(serial_future) = open_serial_port(reactor, baud_rate).await {
                     tokio::spawn(serial_future);
                 } else {
                     reactor.event_sender.send(Event::SerialPortDisconnected).await
@@ -168,6 +172,7 @@ impl Effect {
 
                 // Update the feedback
                 reactor.context.add_gcode_history_to_feedback();
+                reactor.context.add_host_actions_to_feedback();
 
                 // take the feedback from reactor.context
                 let mut feedback = std::mem::replace(
@@ -205,35 +210,47 @@ impl Effect {
                 // Reset the PAUSED_STATE flag after the protobuf has been sent
                 reactor.context.machine_flags.set(MachineFlags::PAUSED_STATE, false);
             }
+            Effect::SavePrintRecoveryState => {
+                let path = PrintRecoveryState::path(&reactor.context.config.id);
+
+                if let Some(state) = &mut reactor.context.print_recovery {
+                    if let Err(err) = state.save(&path) {
+                        warn!("Unable to save print recovery state ({:?}): {:?}", path, err);
+                    }
+                }
+            }
             Effect::LoadGCode {
                 file_path,
                 task_id,
                 client_id,
                 machine_override,
                 despooled_line_number,
+                line_actions,
+                skipped_lines,
             } => {
                 let mut tx = mpsc::Sender::clone(&reactor.event_sender);
 
                 let file_path = reactor.context.config.transform_gcode_file_path(file_path);
 
-                let gcode_lines = std::fs::File::open(&file_path)
-                    .map(|file| BufReader::new(file).lines())
-                    .map(|iter| fallible_iterator::convert(iter))
-                    .and_then(|iter| {
-                        iter
-                            .collect::<Vec<_>>()
-                            .map(|v| v.into_iter())
-                    });
+                // Open the file after the despooled lines if a print is being resumed
+                let next_line_number = despooled_line_number
+                    .map(|line_number| line_number + 1)
+                    .unwrap_or(0);
+
+                let gcode_file = GCodeFile::open_at_line(&file_path, next_line_number as u64);
 
-                let event = if let Ok(gcode_lines) = gcode_lines {
+                let event = if let Ok(gcode_file) = gcode_file {
                     Event::GCodeLoaded(
                         Task {
                             id: task_id,
                             client_id,
-                            gcode_lines,
+                            gcode_lines: GCodeLines::File(gcode_file),
+                            next_line_number,
                             machine_override,
                             started: false,
                             despooled_line_number,
+                            line_actions: LineActions::new(line_actions),
+                            skipped_lines: SkippedLines::new(skipped_lines),
                         }
                     )
                 } else {
@@ -262,3 +279,48 @@ impl Effect {
         }
     }
 }
+
+/// Opens the controller's serial port. Simulated controllers are connected to a serial simulator
+/// running in the driver process.
+async fn open_serial_port(
+    reactor: &mut StateMachineReactor,
+    baud_rate: u32,
+) -> eyre::Result<impl Future<Output = ()>> {
+    let controller = &reactor.context.controller.model;
+
+    let mut port: Box<dyn SerialTransport> = if controller.simulate {
+        reactor.serial_manager.close();
+
+        let (
+            mut host_port,
+            simulator_port,
+        ) = tokio_serial::SerialStream::pair()?;
+
+        // #[cfg(unix)]
+        host_port.set_exclusive(false)?;
+
+        // Spawn the simulator
+        let simulator = SerialSimulator::run(
+            simulator_port,
+            controller.simulator_personality,
+            controller.simulator_physics.clone(),
+            controller.simulator_faults.clone(),
+        )
+            .then(|result| {
+                if let Err(err) = result {
+                    error!("Simulator Error: {}", err);
+                }
+                future::ready(())
+            });
+
+        tokio::spawn(simulator);
+
+        Box::new(host_port)
+    } else {
+        reactor.serial_manager.connect(baud_rate).await?
+    };
+
+    port.write_all(&[b'\n']).await?;
+
+    reactor.serial_manager.open(port).await
+}
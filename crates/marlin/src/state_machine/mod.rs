@@ -9,7 +9,7 @@ use nom_reprap_response::{
 };
 
 use crate::protos::{
-    // machine_message,
+    machine_message::TaskStatus,
     // MachineMessage,
     server_message,
     ServerMessage,
@@ -20,10 +20,24 @@ mod context;
 mod effect;
 mod send_serial;
 
+mod skipped_lines;
+pub use skipped_lines::{
+    SkippedLines,
+    SkipResult,
+};
+
 mod task;
-pub use task::Task;
+pub use task::{
+    Task,
+    TaskLine,
+    GCodeLines,
+    LineActions,
+};
 
-pub use context::Context;
+pub use context::{
+    Context,
+    PrintRecoveryLine,
+};
 pub use effect::Effect;
 pub use send_serial::send_serial;
 
@@ -32,6 +46,9 @@ pub use disconnect::disconnect;
 
 use ready_state::ReadyState;
 
+#[cfg(test)]
+mod simulator_tests;
+
 #[derive(Clone, Debug)]
 pub enum Event {
     Init { serial_port_available: bool },
@@ -48,6 +65,9 @@ pub enum Event {
     SerialPortError{ message: String },
     GCodeLoaded(Task),
     GCodeLoadFailed{ task_id: crate::DbId, file_path: String },
+    SavePrintRecoveryState,
+    /// Attempt to reconnect to a network serial port after the connection was lost
+    Reconnect,
 }
 
 #[derive(Clone, Debug)]
@@ -81,6 +101,34 @@ impl Loop {
 use State::*;
 use Event::*;
 
+impl teg_driver::DriverEvent for Event {
+    type Response = (String, Response);
+
+    fn serial_port_opened() -> Self {
+        SerialPortOpened
+    }
+
+    fn serial_received(response: Self::Response) -> Self {
+        SerialRec(response)
+    }
+
+    fn serial_port_disconnected() -> Self {
+        SerialPortDisconnected
+    }
+
+    fn serial_port_error(message: String) -> Self {
+        SerialPortError { message }
+    }
+
+    fn protobuf_client_connected() -> Self {
+        ProtobufClientConnection
+    }
+
+    fn protobuf_received(message: ServerMessage) -> Self {
+        ProtobufRec(message)
+    }
+}
+
 pub fn cancel_all_tasks(state: &mut State, context: &mut Context) {
     if let Ready( ready_state ) = state {
         ready_state.tasks
@@ -89,6 +137,8 @@ pub fn cancel_all_tasks(state: &mut State, context: &mut Context) {
 
         ready_state.tasks.truncate(0);
     };
+
+    context.settle_sd_card_print(TaskStatus::TaskCancelled);
 }
 
 fn errored(message: String, state: &State, context: &mut Context) -> Loop {
@@ -102,11 +152,14 @@ fn errored(message: String, state: &State, context: &mut Context) -> Loop {
             });
     };
 
+    context.settle_sd_card_print(TaskStatus::TaskErrored);
+
     let next_state = Errored { message };
     context.handle_state_change(&next_state);
 
     let effects = vec![
         Effect::CancelAllDelays,
+        Effect::SavePrintRecoveryState,
         Effect::SendFeedbackProtobuf,
     ];
 
@@ -173,6 +226,15 @@ impl State {
             )
         }
 
+        if let SavePrintRecoveryState = &event {
+            context.print_recovery_saved();
+
+            return Loop::new(
+                self,
+                vec![Effect::SavePrintRecoveryState],
+            )
+        }
+
         if let GCodeLoadFailed { file_path, ..} = &event {
             let message = format!("Failed to load GCode: {:}", file_path);
             return errored(message, &self, context)
@@ -328,6 +390,13 @@ impl State {
                 ConnectionTimeout => {
                     self.connection_timeout(event, context)
                 }
+                Reconnect => {
+                    if let Disconnected = self {
+                        self.reconnect_with_next_baud(context)
+                    } else {
+                        self.invalid_transition_warning(&event)
+                    }
+                }
                 /* Awaiting Greeting Timer: After Delay */
                 RetryResetLineNumber => {
                     if let Connecting(conn) = self {
@@ -341,6 +410,7 @@ impl State {
                 TickleSerialPort |
                 GCodeLoaded(..) |
                 GCodeLoadFailed{..} |
+                SavePrintRecoveryState |
                 ProtobufRec(_) |
                 ProtobufClientConnection => {
                     self.invalid_transition_warning(&event)
@@ -429,6 +499,8 @@ impl State {
     fn receive_ok(event: Event, context: &mut Context) -> Loop {
         info!("Greeting Received");
 
+        context.reconnect_attempts = 0;
+
         let mut effects = vec![
             Effect::CancelAllDelays,
         ];
@@ -6,6 +6,9 @@ use std::convert::identity;
 use nom_reprap_response::{
     Response,
     Feedback,
+    Action,
+    AdvancedOk,
+    Busy,
 };
 
 use crate::gcode_codec::{
@@ -18,21 +21,33 @@ use super::{
     Event::{self, *},
     Effect,
     Task,
+    TaskLine,
+    LineActions,
+    SkippedLines,
     errored,
     send_serial,
     Context,
+    PrintRecoveryLine,
     disconnect,
 };
 
 use crate::protos::{
     ServerMessage,
     server_message,
+    machine_message::{HostAction, TaskStatus},
 };
 
+/// The number of acknowledged lines to keep for resending
+const RESEND_HISTORY_SIZE: usize = 32;
+
+/// How often the print recovery state is saved while printing
+const PRINT_RECOVERY_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Polling {
     PollTemperature,
     PollPosition,
+    PollSDCardPrint,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -76,18 +91,49 @@ enum Mark {
     WaitingToSendFallbackGCode,
 }
 
+#[derive(Clone, Debug)]
+struct SentLine {
+    line_number: u32,
+    gcode: String,
+    is_polling: bool,
+    /// Long running and blocking GCodes are sent on their own so that their response timeouts
+    /// are not overwritten by the GCodes sent after them.
+    is_long_running: bool,
+    /// The task line that the GCode was despooled from. Once the line is acknowledged it is
+    /// counted as despooled and recorded in the print recovery state. Taken when it is
+    /// acknowledged so that resent lines are only recorded once.
+    task_line: Option<PrintRecoveryLine>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ReadyState {
     mark: Option<Mark>,
     poll_for: Option<Polling>,
     awaiting_polling_delay: bool,
-    awaiting_polling_feedback: bool,
     tickles_attempted: u32,
-    pub last_gcode_sent: Option<String>,
     on_ok: OnOK,
     next_serial_line_number: u32,
+    // send window
+    /// Lines sent to the firmware that have not yet been acknowledged, oldest first.
+    sent_lines: VecDeque<SentLine>,
+    /// Recently acknowledged lines, oldest first. These can still be resent if the firmware never
+    /// received them, eg. if a line was lost and the OK to a tickle was taken as its
+    /// acknowledgement.
+    acknowledged_lines: VecDeque<SentLine>,
+    /// Lines rewound by a resend request. These are resent with their original line numbers
+    /// before any new lines are despooled. Task lines are counted as despooled when they are
+    /// acknowledged so resending them does not change the despooled_line_number.
+    resend_lines: VecDeque<SentLine>,
+    /// The line number the send window was last rewound to and the number of repeated resend
+    /// requests for it that may still be received.
+    ignored_resends: Option<(u32, usize)>,
+    /// Free command buffer slots reported by the firmware's most recent ADVANCED_OK
+    command_buffer_available: Option<u32>,
     // spool
     loading_gcode: bool,
+    /// True between sending an M28 and an M29. Every line sent in between is written to the SD
+    /// card file instead of being executed so polling is paused.
+    writing_to_sd_card: bool,
     pub tasks: VecDeque<Task>,
     pub actual_positions_received: BTreeSet<String>,
     pub capabilities: BTreeSet<String>,
@@ -99,23 +145,31 @@ impl Default for ReadyState {
         tasks.push_front(Task {
             id: "FIRMWARE_INFO".into(),
             client_id: "INTERNAL".into(),
-            gcode_lines: vec!["M115".to_string()].into_iter(),
+            gcode_lines: vec!["M115".to_string()].into(),
+            next_line_number: 0,
             despooled_line_number: None,
             machine_override: true,
             started: false,
+            line_actions: Default::default(),
+            skipped_lines: Default::default(),
         });
 
         Self {
             mark: None,
             on_ok: OnOK::TransitionToReady,
-            last_gcode_sent: None,
             poll_for: Some(Polling::PollTemperature),
             awaiting_polling_delay: false,
-            awaiting_polling_feedback: false,
             tickles_attempted: 0,
             next_serial_line_number: 1,
+            // send window
+            sent_lines: VecDeque::new(),
+            acknowledged_lines: VecDeque::new(),
+            resend_lines: VecDeque::new(),
+            ignored_resends: None,
+            command_buffer_available: None,
             // spool
             loading_gcode: false,
+            writing_to_sd_card: false,
             tasks,
             actual_positions_received: Default::default(),
             capabilities: Default::default(),
@@ -128,6 +182,30 @@ impl ReadyState {
         Ready( self ).and_no_effects()
     }
 
+    /// Spools GCodes generated by the driver. Internal tasks do not send task progress to the
+    /// server.
+    fn spool_internal_task(
+        self,
+        id: &str,
+        gcodes: Vec<String>,
+        machine_override: bool,
+        context: &mut Context,
+    ) -> Loop {
+        let task = Task {
+            id: id.into(),
+            client_id: "INTERNAL".into(),
+            gcode_lines: gcodes.into(),
+            next_line_number: 0,
+            despooled_line_number: None,
+            machine_override,
+            started: false,
+            line_actions: Default::default(),
+            skipped_lines: Default::default(),
+        };
+
+        self.consume(GCodeLoaded(task), context)
+    }
+
     pub fn consume(mut self, event: Event, context: &mut Context) -> Loop {
         match event {
             ProtobufRec( msg@ServerMessage { payload: None } ) => {
@@ -151,6 +229,8 @@ impl ReadyState {
                             start_at_line_number,
                             content,
                             machine_override,
+                            line_actions,
+                            skipped_lines,
                         } = spool_task;
 
                         let despooled_line_number = if start_at_line_number == 0 {
@@ -164,10 +244,13 @@ impl ReadyState {
                                 let task = Task {
                                     id: task_id,
                                     client_id,
-                                    gcode_lines: commands.into_iter(),
+                                    gcode_lines: commands.into(),
+                                    next_line_number: 0,
                                     machine_override,
                                     started: false,
                                     despooled_line_number,
+                                    line_actions: LineActions::new(line_actions),
+                                    skipped_lines: SkippedLines::new(skipped_lines),
                                 };
                                 self.consume(GCodeLoaded(task), context)
                             }
@@ -179,6 +262,8 @@ impl ReadyState {
                                         client_id,
                                         machine_override,
                                         despooled_line_number,
+                                        line_actions,
+                                        skipped_lines,
                                     },
                                 ];
                                 Loop::new(Ready(self), effects)
@@ -196,7 +281,9 @@ impl ReadyState {
                             .and_then(|index| self.tasks.remove(index));
 
                         match task {
-                            Some(task) => {
+                            Some(mut task) => {
+                                self.pause_in_flight_lines(&mut task);
+
                                 context.push_pause_task(&task);
 
                                 if context.reset_when_idle && self.tasks.is_empty() {
@@ -219,6 +306,124 @@ impl ReadyState {
                             }
                         }
                     }
+                    server_message::Payload::SetLineActions(
+                        server_message::SetLineActions { task_id, line_actions },
+                    ) => {
+                        let task = self.tasks
+                            .iter_mut()
+                            .find(|task| task.id == task_id);
+
+                        if let Some(task) = task {
+                            task.line_actions.set(line_actions);
+                        } else {
+                            warn!("Unable to set line actions, task #{} not found", task_id);
+                        }
+
+                        self.and_no_effects()
+                    }
+                    server_message::Payload::SetSkippedLines(
+                        server_message::SetSkippedLines { task_id, skipped_lines },
+                    ) => {
+                        let task = self.tasks
+                            .iter_mut()
+                            .find(|task| task.id == task_id);
+
+                        if let Some(task) = task {
+                            task.skipped_lines.set(skipped_lines);
+                        } else {
+                            warn!("Unable to set skipped lines, task #{} not found", task_id);
+                        }
+
+                        self.and_no_effects()
+                    }
+                    server_message::Payload::ListSdCardFiles(_) => {
+                        self.spool_internal_task(
+                            "LIST_SD_CARD_FILES",
+                            vec!["M20".to_string()],
+                            true,
+                            context,
+                        )
+                    }
+                    server_message::Payload::DeleteSdCardFile(
+                        server_message::DeleteSdCardFile { filename }
+                    ) => {
+                        // Re-list the files afterwards so that the deleted file is removed
+                        self.spool_internal_task(
+                            "DELETE_SD_CARD_FILE",
+                            vec![format!("M30 {}", filename), "M20".to_string()],
+                            true,
+                            context,
+                        )
+                    }
+                    server_message::Payload::StartSdCardPrint(
+                        server_message::StartSdCardPrint { task_id, filename }
+                    ) => {
+                        let is_paused = context.sd_card_print()
+                            .map(|print| print.task_id == task_id)
+                            .unwrap_or(false)
+                            && context.is_sd_card_print_paused();
+
+                        let gcodes = if is_paused {
+                            context.resume_sd_card_print();
+
+                            vec!["M24".to_string()]
+                        } else {
+                            context.start_sd_card_print(task_id, filename.clone());
+
+                            vec![format!("M23 {}", filename), "M24".to_string()]
+                        };
+
+                        self.spool_internal_task("START_SD_CARD_PRINT", gcodes, false, context)
+                    }
+                    server_message::Payload::PauseSdCardPrint(
+                        server_message::PauseSdCardPrint { task_id }
+                    ) => {
+                        let is_printing = context.sd_card_print()
+                            .map(|print| print.task_id == task_id)
+                            .unwrap_or(false);
+
+                        if is_printing {
+                            context.pause_sd_card_print();
+
+                            self.spool_internal_task(
+                                "PAUSE_SD_CARD_PRINT",
+                                vec!["M25".to_string()],
+                                true,
+                                context,
+                            )
+                        } else {
+                            warn!("Warning: SD card print #{} not found. Ignoring pause.", task_id);
+                            self.and_no_effects()
+                        }
+                    }
+                    server_message::Payload::RespondToPrompt(
+                        server_message::RespondToPrompt { button_index }
+                    ) => {
+                        if context.feedback.prompt.is_none() {
+                            warn!("Warning: No prompt to respond to. Ignoring: {:?}", button_index);
+                            return self.and_no_effects()
+                        }
+
+                        context.end_prompt();
+
+                        // Prompts are shown while the firmware is waiting for the user (eg. during
+                        // an M600) so the response is sent without waiting for space in the send
+                        // window. Marlin reads M876 with its emergency parser.
+                        let mut effects = vec![Effect::SendFeedbackProtobuf];
+
+                        self.send_line(
+                            &mut effects,
+                            context,
+                            format!("M876 S{}", button_index),
+                            false,
+                        );
+
+                        if self.on_ok == OnOK::NotAwaitingOk {
+                            self.on_ok = OnOK::Despool;
+                        }
+
+                        Loop::new(Ready(self), effects)
+                    }
                     _ => {
                         self.and_no_effects()
                     }
@@ -229,10 +434,8 @@ impl ReadyState {
             }
             GCodeLoaded ( mut task ) => {
                 // Skip to through the despooled lines in a print is being resumed
-                if let Some(despooled_line_number) = task.despooled_line_number {
-                    for _ in 0..despooled_line_number {
-                        task.gcode_lines.next();
-                    }
+                if let Err(err) = task.skip_despooled_lines() {
+                    return errored(err.to_string(), &Ready(self), context)
                 }
 
                 if task.machine_override {
@@ -246,11 +449,11 @@ impl ReadyState {
                     self.tasks.push_back(task)
                 };
 
-                // despool the first line of the task if ready to do so
-                if let OnOK::NotAwaitingOk = self.on_ok {
+                // despool the first lines of the task if ready to do so
+                if self.can_despool() {
                     let mut effects = vec![];
 
-                    let result = self.despool_task(&mut effects, context);
+                    let result = self.despool(&mut effects, context);
 
                     match result {
                         Err(err) => {
@@ -270,7 +473,10 @@ impl ReadyState {
 
                 let is_polling_feedback = match response {
                     | Response::Ok(_)
-                    | Response::Feedback(_) => self.awaiting_polling_feedback,
+                    | Response::Feedback(_) => self.sent_lines
+                        .front()
+                        .map(|line| line.is_polling)
+                        .unwrap_or(false),
                     _ => false
                 };
 
@@ -307,10 +513,13 @@ impl ReadyState {
                                 client_id: "INTERNAL".into(),
                                 gcode_lines: vec![
                                     "M155 S1".to_string(),
-                                ].into_iter(),
+                                ].into(),
+                                next_line_number: 0,
                                 despooled_line_number: None,
                                 machine_override: true,
                                 started: false,
+                                line_actions: Default::default(),
+                                skipped_lines: Default::default(),
                             });
                         }
 
@@ -365,21 +574,28 @@ impl ReadyState {
                     Response::Resend(resend) => {
                         self.receive_resend_request(resend.line_number, context)
                     }
+                    Response::Action(action) => {
+                        self.receive_action(action, context)
+                    }
                 }
             }
             PollFeedback => {
                 self.poll_for = Some(Polling::PollTemperature);
 
                 // eprintln!("POLL FEEDBACK!!!!! {:?}", self);
-                if let OnOK::NotAwaitingOk = self.on_ok {
+                if self.can_despool() {
                     let mut effects = vec![];
 
-                    self.poll_feedback(&mut effects, context, Polling::PollTemperature);
+                    let result = self.despool(&mut effects, context);
 
-                    Loop::new(
-                        Ready(self),
-                        effects,
-                    )
+                    match result {
+                        Err(err) => {
+                            errored(err.to_string(), &Ready(self), context)
+                        }
+                        Ok(_) => {
+                            Loop::new(Ready(self), effects)
+                        }
+                    }
                 } else {
                     self.and_no_effects()
                 }
@@ -397,11 +613,45 @@ impl ReadyState {
         context: &mut Context
     ) -> eyre::Result<Vec<Effect>> {
         match feedback {
+            Feedback::Busy(Busy::Processing) => {
+                // Processing messages are keep alives sent before the OK of a slow GCode
+                Ok(vec![])
+            }
             Feedback::Busy(_) => {
                 // Marlin sends an extra OK after filament swaps so make sure to ignore those
                 self.on_ok = OnOK::IgnoreOK;
                 Ok(vec![])
             }
+            Feedback::AdvancedOk(AdvancedOk {
+                line_number,
+                planner_buffer_available,
+                command_buffer_available,
+            }) => {
+                trace!(
+                    "Firmware buffers: {} planner / {} command slots available",
+                    planner_buffer_available,
+                    command_buffer_available,
+                );
+
+                self.command_buffer_available = Some(*command_buffer_available);
+
+                let mut effects = vec![];
+
+                // If the firmware acknowledged a later line than expected then the oks for the
+                // lines sent before it were lost.
+                if let (Some(line_number), OnOK::Despool) = (line_number, self.on_ok) {
+                    while let Some(line) = self.sent_lines.front() {
+                        if line.line_number >= *line_number {
+                            break;
+                        }
+
+                        warn!("Warning: OK not received for line {}", line.line_number);
+                        self.acknowledge_line(&mut effects, context);
+                    }
+                }
+
+                Ok(effects)
+            }
             Feedback::ActualTemperatures(temperatures) => {
                 // set actual_temperatures
                 temperatures.iter().for_each(|(address, val)| {
@@ -517,17 +767,78 @@ impl ReadyState {
                         debug!("Macro: Wait to Reach Mark [COMPLETE]");
                         self.mark = None;
                         let mut effects = vec![];
-                        self.despool(&mut effects, context)?;
+                        if self.can_despool() {
+                            self.despool(&mut effects, context)?;
+                        }
                         return Ok(effects);
                     };
                 };
 
                 Ok(vec![])
             },
+            Feedback::SDFileList(files) => {
+                context.set_sd_card_files(files);
+
+                Ok(vec![Effect::SendFeedbackProtobuf])
+            }
+            Feedback::SDPrintProgress(progress) => {
+                context.update_sd_card_print_progress(progress);
+
+                Ok(vec![])
+            }
+            Feedback::SDPrintComplete => {
+                context.settle_sd_card_print(TaskStatus::TaskFinished);
+
+                Ok(vec![Effect::SendFeedbackProtobuf])
+            }
+            Feedback::NotSDPrinting => {
+                // Not all firmwares report when an SD card print is done so a print that has
+                // made progress and then stops printing without being paused is also finished.
+                let is_finished = context.sd_card_print()
+                    .map(|print| print.bytes_printed > 0)
+                    .unwrap_or(false)
+                    && !context.is_sd_card_print_paused();
+
+                if is_finished {
+                    context.settle_sd_card_print(TaskStatus::TaskFinished);
+                }
+
+                Ok(vec![])
+            }
             _ => Ok(vec![]),
         }
     }
 
+    fn receive_action(self, action: Action, context: &mut Context) -> Loop {
+        match action {
+            // Pause, resume and cancel requests (eg. from a filament runout sensor or the LCD)
+            // are handled by the server so that the print's pause and resume hooks are run
+            Action::Pause => context.push_host_action(HostAction::Pause),
+            Action::Resume => context.push_host_action(HostAction::Resume),
+            Action::Cancel => context.push_host_action(HostAction::Cancel),
+            Action::PromptBegin(message) => {
+                context.begin_prompt(message);
+                return self.and_no_effects()
+            }
+            Action::PromptButton(label) => {
+                context.add_prompt_button(label);
+                return self.and_no_effects()
+            }
+            // The prompt is sent to the server once all of its buttons have been received
+            Action::PromptShow => (),
+            Action::PromptEnd => context.end_prompt(),
+            | Action::Paused
+            | Action::Resumed
+            | Action::Notification(_)
+            | Action::Other(_) => {
+                info!("Firmware action: {:?}", action);
+                return self.and_no_effects()
+            }
+        };
+
+        Loop::new(Ready(self), vec![Effect::SendFeedbackProtobuf])
+    }
+
     fn receive_ok(&mut self, effects: &mut Vec<Effect>, context: &mut Context) -> eyre::Result<()> {
         for heater in context.feedback.heaters.iter_mut() {
             heater.blocking = false;
@@ -539,24 +850,10 @@ impl ReadyState {
                 warn!("Warning: Received Unexpected OK");
             }
             OnOK::Resend => {
-                let gcode = self.last_gcode_sent
-                    .clone()
-                    .expect("Cannot resend GCode if none was sent");
-
-                warn!("Resending GCode: {:?}", gcode);
-
-                send_serial(
-                    effects,
-                    GCodeLine {
-                        gcode,
-                        line_number: Some(self.next_serial_line_number - 1),
-                        checksum: true,
-                    },
-                    context,
-                    false,
-                );
-
-                self.on_ok = OnOK::IgnoreOK;
+                // The OK sent after a resend request does not acknowledge a line. Resend the
+                // rewound lines.
+                self.on_ok = OnOK::Despool;
+                self.despool(effects, context)?;
             }
             OnOK::IgnoreOK => {
                 // Do not cancel the trickle here because it will already have been overwritten by the resend request
@@ -573,6 +870,7 @@ impl ReadyState {
                 self.receive_ok(effects, context)?;
             }
             OnOK::Despool => {
+                self.acknowledge_line(effects, context);
                 self.despool(effects, context)?;
             }
         }
@@ -580,43 +878,177 @@ impl ReadyState {
         Ok(())
     }
 
+    fn can_despool(&self) -> bool {
+        match self.on_ok {
+            | OnOK::NotAwaitingOk
+            | OnOK::Despool => true,
+            _ => false,
+        }
+    }
+
+    fn acknowledge_line(&mut self, effects: &mut Vec<Effect>, context: &mut Context) {
+        // The firmware is responding so reset the response timeout
+        self.tickles_attempted = 0;
+
+        let mut line = if let Some(line) = self.sent_lines.pop_front() {
+            line
+        } else {
+            return
+        };
+
+        if let Some(task_line) = line.task_line.take() {
+            let task = self.tasks
+                .iter_mut()
+                .find(|task| task.id == task_line.task_id);
+
+            // Lines are acknowledged in order but a scheduled pause can count the lines before it
+            // as despooled first so the despooled line number never moves backwards
+            if let Some(task) = task {
+                task.despooled_line_number = task.despooled_line_number
+                    .max(Some(task_line.line_number));
+            }
+
+            context.push_task_line_acknowledged(&task_line.task_id, task_line.line_number);
+            record_print_recovery(effects, context, &task_line, &line.gcode);
+        }
+
+        // Once a resent line has been acknowledged any further resend requests are for new errors
+        if let Some((rewound_to, _)) = self.ignored_resends {
+            if line.line_number >= rewound_to {
+                self.ignored_resends = None;
+            }
+        }
+
+        self.acknowledged_lines.push_back(line);
+
+        if self.acknowledged_lines.len() > RESEND_HISTORY_SIZE {
+            self.acknowledged_lines.pop_front();
+        }
+    }
+
+    /// The lines of a paused task that are still in flight or waiting to be resent are executed
+    /// by the firmware after the pause so they are counted as despooled. This way the task resumes
+    /// after them instead of repeating them.
+    fn pause_in_flight_lines(&self, task: &mut Task) {
+        let in_flight_line_number = self.sent_lines
+            .iter()
+            .chain(self.resend_lines.iter())
+            .filter_map(|line| line.task_line.as_ref())
+            .filter(|task_line| task_line.task_id == task.id)
+            .map(|task_line| task_line.line_number)
+            .max();
+
+        task.despooled_line_number = task.despooled_line_number.max(in_flight_line_number);
+    }
+
+    fn send_window_has_space(&self, context: &Context) -> bool {
+        if self.sent_lines.iter().any(|line| line.is_long_running) {
+            return false
+        }
+
+        let mut window_size = std::cmp::max(context.controller.model.send_window_size, 1);
+
+        // Do not overfill the firmware's command buffer. At least one line is always sent so that
+        // the firmware continues to respond.
+        if let Some(command_buffer_available) = self.command_buffer_available {
+            window_size = std::cmp::min(
+                window_size,
+                std::cmp::max(command_buffer_available, 1),
+            );
+        }
+
+        self.sent_lines.len() < window_size as usize
+    }
+
+    /// Sends lines until the send window is full or there is nothing left to send
     fn despool(&mut self, effects: &mut Vec<Effect>, context: &mut Context) -> eyre::Result<()> {
-        if let Some(Mark::WaitingToSendFallbackGCode) = self.mark {
-            debug!("Sending Mark Start & Stop Fallback GCode (M400)");
-            send_serial(
+        while self.send_window_has_space(context) {
+            if !self.despool_line(effects, context)? {
+                break;
+            }
+        }
+
+        if self.sent_lines.is_empty() {
+            self.on_ok = OnOK::NotAwaitingOk;
+            // Cancel the tickle if there's nothing else to send_serial
+            effects.push(
+                Effect::CancelDelay { key: "tickle_delay".to_string() }
+            );
+
+            if context.reset_when_idle && self.tasks.is_empty() {
+                effects.push(Effect::ExitProcessAfterDelay)
+            };
+        } else {
+            self.on_ok = OnOK::Despool;
+        }
+
+        Ok(())
+    }
+
+    /// Despools a single line. Returns false if there was nothing left to despool.
+    fn despool_line(
+        &mut self,
+        effects: &mut Vec<Effect>,
+        context: &mut Context,
+    ) -> eyre::Result<bool> {
+        if let Some(line) = self.resend_lines.pop_front() {
+            warn!("Resending GCode: {:?}", line.gcode);
+
+            self.send_numbered_line(
                 effects,
-                GCodeLine {
-                    gcode: "M400".to_string(),
-                    line_number: Some(self.next_serial_line_number),
-                    checksum: true,
-                },
                 context,
-                true,
+                line.line_number,
+                line.gcode,
+                line.is_polling,
             );
+        } else if let Some(Mark::WaitingToSendFallbackGCode) = self.mark {
+            debug!("Sending Mark Start & Stop Fallback GCode (M400)");
 
+            self.send_line(effects, context, "M400".to_string(), true);
             self.mark = None;
-            self.on_ok = OnOK::Despool;
-            self.next_serial_line_number += 1;
-        } else if let Some(poll_for) = self.poll_for {
+        } else if let Some(poll_for) = self.poll_for.filter(|_| !self.writing_to_sd_card) {
             self.poll_feedback(effects, context, poll_for);
         } else {
-            self.despool_task(effects, context)?;
+            return self.despool_task(effects, context);
         };
-        Ok(())
-    }
 
-    fn despool_task(&mut self, effects: &mut Vec<Effect>, context: &mut Context) -> eyre::Result<()> {
-        self.awaiting_polling_feedback = false;
+        Ok(true)
+    }
 
+    fn despool_task(
+        &mut self,
+        effects: &mut Vec<Effect>,
+        context: &mut Context,
+    ) -> eyre::Result<bool> {
         if let Some(Mark::WaitingToReachMark(_)) = self.mark {
-            self.on_ok = OnOK::NotAwaitingOk;
-            return Ok(());
+            return Ok(false);
         };
 
         if let Some(task) = self.tasks.front_mut() {
-            let gcode = task.next_gcode();
+            // Wait for the server to pause the task after reaching a scheduled pause
+            if task.line_actions.waiting_to_pause {
+                return Ok(false);
+            }
+
+            let line = task.next_gcode()?;
+
+            if let Some(TaskLine::Pause(line_number)) = line {
+                info!("Despool: Task #{} reached a scheduled pause", task.id);
+
+                // Only the lines before the pause have been despooled
+                task.despooled_line_number = line_number.checked_sub(1);
+
+                context.push_start_task(&task);
+                context.push_host_action(HostAction::Pause);
+
+                effects.push(
+                    Effect::SendFeedbackProtobuf,
+                );
+
+                return Ok(false);
+            }
 
-            if let Some(gcode) = gcode {
+            if let Some(TaskLine::GCode(line_number, gcode)) = line {
                 if !task.started {
                     trace!("Despool: Starting Task #{}", task.id);
                     task.started = true;
@@ -628,30 +1060,31 @@ impl ReadyState {
 
                 trace!("Despool: Task GCode");
 
-                let despooled_line_number = task.despooled_line_number
-                    .map(|n| n + 1)
-                    .unwrap_or(0);
+                context.push_start_task(&task);
 
-                task.despooled_line_number = Some(despooled_line_number);
+                let task_line = PrintRecoveryLine::new(&task, line_number);
 
-                context.push_start_task(&task);
+                match gcode.split_whitespace().next() {
+                    Some("M28") => self.writing_to_sd_card = true,
+                    Some("M29") => self.writing_to_sd_card = false,
+                    _ => (),
+                };
 
                 if gcode.starts_with('!') {
+                    // Host GCodes are executed immediately so there is no acknowledgement to wait on
+                    task.despooled_line_number = Some(line_number);
+                    context.push_task_line_acknowledged(&task_line.task_id, line_number);
+                    record_print_recovery(effects, context, &task_line, &gcode);
+
                     self.execute_host_gcode(effects, context, &gcode)?;
                 } else {
-                    send_serial(
-                        effects,
-                        GCodeLine {
-                            gcode,
-                            line_number: Some(self.next_serial_line_number),
-                            checksum: true,
-                        },
-                        context,
-                        false,
-                    );
+                    self.send_line(effects, context, gcode, false);
 
-                    self.on_ok = OnOK::Despool;
-                    self.next_serial_line_number += 1;
+                    // The line is counted as despooled once it is acknowledged so that a resumed
+                    // or recovered print does not skip the lines still in the send window
+                    if let Some(sent_line) = self.sent_lines.back_mut() {
+                        sent_line.task_line = Some(task_line);
+                    }
                 };
             } else {
                 trace!("Despool: Completed Task #{}", task.id);
@@ -664,25 +1097,55 @@ impl ReadyState {
                 );
 
                 let _ = self.tasks.pop_front();
-
-                // use recursion to despool the next line
-                self.despool(effects, context)?;
             };
+
+            Ok(true)
         } else {
             trace!("Despool: Nothing to send");
 
-            self.on_ok = OnOK::NotAwaitingOk;
-            // Cancel the tickle if there's nothing else to send_serial
-            effects.push(
-                Effect::CancelDelay { key: "tickle_delay".to_string() }
-            );
+            Ok(false)
+        }
+    }
 
-            if context.reset_when_idle {
-                effects.push(Effect::ExitProcessAfterDelay)
-            };
-        };
+    fn send_line(
+        &mut self,
+        effects: &mut Vec<Effect>,
+        context: &mut Context,
+        gcode: String,
+        is_polling: bool,
+    ) {
+        let line_number = self.next_serial_line_number;
+        self.next_serial_line_number += 1;
 
-        Ok(())
+        self.send_numbered_line(effects, context, line_number, gcode, is_polling);
+    }
+
+    fn send_numbered_line(
+        &mut self,
+        effects: &mut Vec<Effect>,
+        context: &mut Context,
+        line_number: u32,
+        gcode: String,
+        is_polling: bool,
+    ) {
+        let is_long_running = send_serial(
+            effects,
+            GCodeLine {
+                gcode: gcode.clone(),
+                line_number: Some(line_number),
+                checksum: true,
+            },
+            context,
+            is_polling,
+        );
+
+        self.sent_lines.push_back(SentLine {
+            line_number,
+            gcode,
+            is_polling,
+            is_long_running,
+            task_line: None,
+        });
     }
 
     fn execute_host_gcode(
@@ -737,7 +1200,7 @@ impl ReadyState {
             },
         };
 
-        self.despool(effects, context)
+        Ok(())
     }
 
     fn poll_feedback(
@@ -757,29 +1220,20 @@ impl ReadyState {
         let gcode = match poll_for {
             Polling::PollTemperature => "M105",
             Polling::PollPosition => "M114",
+            Polling::PollSDCardPrint => "M27",
         };
 
         trace!("Despool: Polling ({:})", gcode);
 
-        self.awaiting_polling_feedback = true;
-
-        send_serial(
-            effects,
-            GCodeLine {
-                gcode: gcode.to_string(),
-                line_number: Some(self.next_serial_line_number),
-                checksum: true,
-            },
-            context,
-            true,
-        );
-
-        self.on_ok = OnOK::Despool;
-        self.next_serial_line_number += 1;
+        self.send_line(effects, context, gcode.to_string(), true);
 
         self.poll_for = match poll_for {
             Polling::PollTemperature => Some(Polling::PollPosition),
-            Polling::PollPosition => None,
+            // Poll the SD card print's progress while an SD card print is running
+            Polling::PollPosition if context.sd_card_print().is_some() => {
+                Some(Polling::PollSDCardPrint)
+            }
+            Polling::PollPosition | Polling::PollSDCardPrint => None,
         };
     }
 
@@ -821,26 +1275,395 @@ impl ReadyState {
     }
 
     fn receive_resend_request(mut self, line_number: u32, context: &mut Context) -> Loop {
-        let sent_line_number = self.next_serial_line_number - 1;
-
         /*
-        * Teg only sends one line at a time. If a resend is requested for a
-        * different line number then this is likely an issue of the printer's
-        * firmware.
+        * Marlin requests a resend for each of the lines that were still in flight when the send
+        * window was rewound. These repeated requests and the OKs that follow them are ignored.
         */
-        if line_number != sent_line_number {
-            let message = format!(
-                "resend line number {:?} does not match sent line number {:?}",
+        if let Some((rewound_to, ignored_count)) = self.ignored_resends.as_mut() {
+            if *rewound_to == line_number && *ignored_count > 0 {
+                *ignored_count -= 1;
+                self.on_ok = OnOK::IgnoreOK;
+
+                return Ready(self).and_no_effects()
+            }
+        }
+
+        let position = self.sent_lines
+            .iter()
+            .position(|line| line.line_number == line_number);
+
+        let acknowledged_position = self.acknowledged_lines
+            .iter()
+            .position(|line| line.line_number == line_number);
+
+        // Rewind the send window to the requested line. Each of the rewound lines that are still
+        // in flight will also be rejected by the firmware.
+        let rewound = if let Some(position) = position {
+            let rewound_lines = self.sent_lines.split_off(position);
+            let in_flight = rewound_lines.len();
+
+            Some((rewound_lines, in_flight))
+        } else if let Some(position) = acknowledged_position {
+            let mut rewound_lines = self.acknowledged_lines.split_off(position);
+            let in_flight = self.sent_lines.len();
+            rewound_lines.append(&mut self.sent_lines);
+
+            Some((rewound_lines, in_flight))
+        } else {
+            None
+        };
+
+        if let Some((mut rewound_lines, in_flight)) = rewound {
+            warn!(
+                "Resend requested for line {:?}. Rewinding {:?} lines.",
                 line_number,
-                sent_line_number,
+                rewound_lines.len(),
             );
 
-            errored(message, &Ready(self), context)
-        } else {
+            self.ignored_resends = Some((line_number, in_flight.saturating_sub(1)));
+
+            rewound_lines.append(&mut self.resend_lines);
+            self.resend_lines = rewound_lines;
+
             // wait for the ok sent after the resend (see marlinFixture.js)
             self.on_ok = OnOK::Resend;
 
             Ready(self).and_no_effects()
+        } else {
+            let message = format!(
+                "resend line number {:?} does not match sent line numbers {:?}",
+                line_number,
+                self.sent_lines
+                    .iter()
+                    .map(|line| line.line_number)
+                    .collect::<Vec<_>>(),
+            );
+
+            errored(message, &Ready(self), context)
         }
     }
 }
+
+fn record_print_recovery(
+    effects: &mut Vec<Effect>,
+    context: &mut Context,
+    line: &PrintRecoveryLine,
+    gcode: &str,
+) {
+    if context.record_print_recovery(line, gcode) {
+        effects.push(Effect::Delay {
+            key: "save_print_recovery".to_string(),
+            duration: PRINT_RECOVERY_SAVE_INTERVAL,
+            event: SavePrintRecoveryState,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom_reprap_response::Resend;
+    use crate::MachineConfig;
+    use super::*;
+
+    /// A connected machine with nothing to poll or send
+    fn ready_state(send_window_size: u32) -> (ReadyState, Context) {
+        let config: MachineConfig = toml::from_str(
+            include_str!("../../../../machine.default.toml"),
+        )
+            .expect("Invalid default machine config");
+
+        let mut context = Context::new(config);
+        context.controller.model.send_window_size = send_window_size;
+
+        let state = ReadyState {
+            on_ok: OnOK::NotAwaitingOk,
+            poll_for: None,
+            tasks: VecDeque::new(),
+            ..ReadyState::default()
+        };
+
+        (state, context)
+    }
+
+    /// Consumes the event and returns the next state and the numbered lines that were sent
+    fn consume(
+        state: ReadyState,
+        event: Event,
+        context: &mut Context,
+    ) -> (ReadyState, Vec<String>) {
+        let Loop { next_state, effects } = state.consume(event, context);
+
+        let state = match next_state {
+            Ready(state) => state,
+            state => panic!("Expected Ready, got: {:?}", state),
+        };
+
+        let sent = effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::SendSerial(GCodeLine { gcode, line_number: Some(n), .. }) => {
+                    Some(format!("N{} {}", n, gcode))
+                }
+                _ => None,
+            })
+            .collect();
+
+        (state, sent)
+    }
+
+    fn print(gcodes: &[&str]) -> Event {
+        GCodeLoaded(Task {
+            id: "TEST_PRINT".into(),
+            client_id: "TEST".into(),
+            gcode_lines: gcodes
+                .iter()
+                .map(|gcode| gcode.to_string())
+                .collect::<Vec<_>>()
+                .into(),
+            next_line_number: 0,
+            despooled_line_number: None,
+            machine_override: false,
+            started: false,
+            line_actions: Default::default(),
+            skipped_lines: Default::default(),
+        })
+    }
+
+    fn ok() -> Event {
+        SerialRec(("ok".to_string(), Response::Ok(None)))
+    }
+
+    fn advanced_ok(line_number: u32, command_buffer_available: u32) -> Event {
+        let feedback = Feedback::AdvancedOk(AdvancedOk {
+            line_number: Some(line_number),
+            planner_buffer_available: 15,
+            command_buffer_available,
+        });
+
+        SerialRec((
+            format!("ok N{} P15 B{}", line_number, command_buffer_available),
+            Response::Ok(Some(feedback)),
+        ))
+    }
+
+    fn resend(line_number: u32) -> Event {
+        SerialRec((
+            format!("Resend: {}", line_number),
+            Response::Resend(Resend { line_number }),
+        ))
+    }
+
+    fn sent_line_numbers(state: &ReadyState) -> Vec<u32> {
+        state.sent_lines
+            .iter()
+            .map(|line| line.line_number)
+            .collect()
+    }
+
+    #[test]
+    fn fills_and_drains_the_send_window() {
+        let (state, mut context) = ready_state(3);
+
+        let (state, sent) = consume(
+            state,
+            print(&["G1 X1", "G1 X2", "G1 X3", "G1 X4", "G1 X5"]),
+            &mut context,
+        );
+
+        assert_eq!(sent, vec!["N1 G1 X1", "N2 G1 X2", "N3 G1 X3"]);
+        assert_eq!(state.on_ok, OnOK::Despool);
+        // Lines are counted as despooled when they are acknowledged, not when they are sent
+        assert_eq!(state.tasks[0].despooled_line_number, None);
+
+        // Each ok frees a slot in the window for the next line
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N4 G1 X4"]);
+        assert_eq!(state.tasks[0].despooled_line_number, Some(0));
+
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N5 G1 X5"]);
+        assert_eq!(sent_line_numbers(&state), vec![3, 4, 5]);
+
+        // The window drains once the task is complete
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert!(sent.is_empty());
+        assert!(state.tasks.is_empty());
+
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+
+        assert!(state.sent_lines.is_empty());
+        assert_eq!(state.on_ok, OnOK::NotAwaitingOk);
+    }
+
+    #[test]
+    fn counts_in_flight_lines_as_despooled_when_paused() {
+        let (state, mut context) = ready_state(3);
+
+        let (state, _) = consume(
+            state,
+            print(&["G1 X1", "G1 X2", "G1 X3", "G1 X4", "G1 X5", "G1 X6"]),
+            &mut context,
+        );
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+
+        assert_eq!(sent_line_numbers(&state), vec![3, 4, 5]);
+        assert_eq!(state.tasks[0].despooled_line_number, Some(1));
+        assert_eq!(context.feedback.task_progress[0].despooled_line_number, 1);
+
+        // The firmware still executes the lines in flight after the pause so the task resumes
+        // after them
+        let pause = ProtobufRec(ServerMessage {
+            payload: Some(server_message::Payload::PauseTask(server_message::PauseTask {
+                task_id: "TEST_PRINT".into(),
+            })),
+        });
+        let (state, _) = consume(state, pause, &mut context);
+
+        let progress = &context.feedback.task_progress[0];
+        assert_eq!(progress.despooled_line_number, 4);
+        assert_eq!(progress.status, TaskStatus::TaskPaused as i32);
+
+        // Acknowledging the lines in flight does not move the paused task's progress backwards
+        let (state, _) = consume(state, ok(), &mut context);
+        assert_eq!(context.feedback.task_progress[0].despooled_line_number, 4);
+        assert_eq!(sent_line_numbers(&state), vec![4, 5]);
+    }
+
+    #[test]
+    fn resends_from_the_middle_of_the_send_window() {
+        let (state, mut context) = ready_state(4);
+
+        let (state, _) = consume(
+            state,
+            print(&["G1 X1", "G1 X2", "G1 X3", "G1 X4", "G1 X5"]),
+            &mut context,
+        );
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N5 G1 X5"]);
+
+        // N2 is acknowledged and then N3 fails its checksum
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, sent) = consume(state, resend(3), &mut context);
+
+        assert!(sent.is_empty());
+        assert_eq!(state.on_ok, OnOK::Resend);
+        assert_eq!(state.ignored_resends, Some((3, 2)));
+
+        // The rewound lines are resent with their original line numbers after the resend's ok
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N3 G1 X3", "N4 G1 X4", "N5 G1 X5"]);
+        assert!(state.resend_lines.is_empty());
+
+        // N4 and N5 were in flight when N3 was rejected so they are also rejected
+        let (state, _) = consume(state, resend(3), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, resend(3), &mut context);
+        let (state, sent) = consume(state, ok(), &mut context);
+
+        assert!(sent.is_empty());
+        assert_eq!(sent_line_numbers(&state), vec![3, 4, 5]);
+
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+
+        assert!(state.sent_lines.is_empty());
+        assert!(state.tasks.is_empty());
+        assert_eq!(state.ignored_resends, None);
+    }
+
+    #[test]
+    fn ignores_duplicate_resends_until_the_rewound_lines_are_rejected() {
+        let (state, mut context) = ready_state(2);
+
+        let (state, _) = consume(state, print(&["G1 X1", "G1 X2", "G1 X3"]), &mut context);
+
+        let (state, _) = consume(state, resend(1), &mut context);
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N1 G1 X1", "N2 G1 X2"]);
+
+        // The resend for N2 which was in flight with N1 is ignored along with its ok
+        let (state, sent) = consume(state, resend(1), &mut context);
+        assert!(sent.is_empty());
+        assert_eq!(state.on_ok, OnOK::IgnoreOK);
+        assert_eq!(state.ignored_resends, Some((1, 0)));
+
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert!(sent.is_empty());
+        assert_eq!(sent_line_numbers(&state), vec![1, 2]);
+
+        // Once the duplicates run out a resend of the same line is a new error and is honoured
+        let (state, _) = consume(state, resend(1), &mut context);
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N1 G1 X1", "N2 G1 X2"]);
+
+        let (state, _) = consume(state, resend(1), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+
+        // Acknowledging the resent line clears the ignored resends
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N3 G1 X3"]);
+        assert_eq!(state.ignored_resends, None);
+
+        let (state, _) = consume(state, ok(), &mut context);
+        let (state, _) = consume(state, ok(), &mut context);
+
+        assert!(state.sent_lines.is_empty());
+        assert!(state.tasks.is_empty());
+    }
+
+    #[test]
+    fn waits_for_space_in_the_firmwares_command_buffer() {
+        let (state, mut context) = ready_state(4);
+
+        let gcodes = ["G1 X1", "G1 X2", "G1 X3", "G1 X4", "G1 X5", "G1 X6", "G1 X7"];
+        let (state, sent) = consume(state, print(&gcodes), &mut context);
+        assert_eq!(sent.len(), 4);
+
+        // A full command buffer blocks sends while lines are in flight
+        let (state, sent) = consume(state, advanced_ok(1, 0), &mut context);
+        assert!(sent.is_empty());
+        assert_eq!(state.command_buffer_available, Some(0));
+
+        let (state, sent) = consume(state, advanced_ok(2, 0), &mut context);
+        assert!(sent.is_empty());
+        let (state, sent) = consume(state, advanced_ok(3, 0), &mut context);
+        assert!(sent.is_empty());
+
+        // A single line is still sent once the window is empty so that the firmware keeps
+        // responding
+        let (state, sent) = consume(state, advanced_ok(4, 0), &mut context);
+        assert_eq!(sent, vec!["N5 G1 X5"]);
+
+        // Freed buffer slots re-open the window
+        let (state, sent) = consume(state, advanced_ok(5, 2), &mut context);
+        assert_eq!(sent, vec!["N6 G1 X6", "N7 G1 X7"]);
+        assert_eq!(sent_line_numbers(&state), vec![6, 7]);
+    }
+
+    #[test]
+    fn blocking_and_long_running_gcodes_hold_the_send_window() {
+        let (state, mut context) = ready_state(4);
+
+        let (state, sent) = consume(
+            state,
+            print(&["G28", "G1 X1", "M109 S200", "G1 X2"]),
+            &mut context,
+        );
+        assert_eq!(sent, vec!["N1 G28"]);
+
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N2 G1 X1", "N3 M109 S200"]);
+
+        // Nothing is sent after the M109 until it is acknowledged
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert!(sent.is_empty());
+        assert_eq!(sent_line_numbers(&state), vec![3]);
+
+        let (state, sent) = consume(state, ok(), &mut context);
+        assert_eq!(sent, vec!["N4 G1 X2"]);
+        assert_eq!(sent_line_numbers(&state), vec![4]);
+    }
+}
@@ -3,12 +3,14 @@ use teg_machine::components::ControllerConfig;
 use super::*;
 use crate::gcode_parser::parse_gcode;
 
+/// Sends the GCode line and resets the response timeout. Returns true if the GCode is a long
+/// running or blocking code.
 pub fn send_serial(
     effects: &mut Vec<Effect>,
     gcode_line: GCodeLine,
     context: &mut Context,
     is_polling: bool,
-) {
+) -> bool {
     // Allow for a byte of spacing between receiving and sending over the serial port
     // The choice of 1 byte was arbitrary but sending without a spin lock seems to
     // loose GCodes.
@@ -32,13 +34,15 @@ pub fn send_serial(
 
 
     let mut duration = fast_code_timeout;
+    let mut is_long_running = false;
     let mut is_blocking = false;
 
     if let Ok(Some((mnemonic, major_number))) = parser_result {
         let gcode_macro = format!("{}{}", mnemonic, major_number);
 
         if long_running_codes.contains(&gcode_macro) {
-            duration = long_running_code_timeout
+            duration = long_running_code_timeout;
+            is_long_running = true;
         }
 
         is_blocking = blocking_codes.contains(&gcode_macro)
@@ -59,4 +63,6 @@ pub fn send_serial(
             },
         );
     }
+
+    is_long_running || is_blocking
 }
@@ -0,0 +1,582 @@
+use std::collections::{
+    HashMap,
+    VecDeque,
+};
+use std::time::Duration;
+use bytes::BytesMut;
+use tokio::io::{
+    AsyncReadExt,
+    AsyncWriteExt,
+    DuplexStream,
+    ReadHalf,
+    WriteHalf,
+};
+use tokio_util::codec::{Decoder, Encoder};
+use teg_machine::components::{
+    SimulatorFaults,
+    SimulatorPersonality,
+    SimulatorPhysics,
+};
+
+use crate::gcode_codec::GCodeCodec;
+use crate::serial_simulator::{
+    SerialLink,
+    SerialSimulator,
+    Fault,
+    FaultScript,
+    Personality,
+};
+use crate::MachineConfig;
+use super::*;
+
+const MAX_STEPS: usize = 1_000;
+
+/// How long the serial port has to be quiet before the response timeout fires
+const QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+const PRINT: [&str; 5] = [
+    "G1 X1",
+    "G1 X2",
+    "G1 X3",
+    "G1 X4",
+    "G1 X5",
+];
+
+/// Runs the state machine against the serial simulator over an in-memory serial port. The
+/// response timeout fires whenever the serial port goes quiet.
+struct Harness {
+    state: State,
+    context: Context,
+    serial_reader: ReadHalf<DuplexStream>,
+    serial_writer: WriteHalf<DuplexStream>,
+    /// Lines sent by the driver that have not yet been written to the serial port
+    serial_tx: VecDeque<GCodeLine>,
+    /// Responses sent by the simulator that have not yet been parsed by the driver
+    serial_rx: BytesMut,
+    /// Delayed events by key
+    delays: HashMap<String, Event>,
+    /// Faults injected into the simulator and the GCodes it executed
+    script: FaultScript,
+}
+
+impl Harness {
+    fn new(personality: SimulatorPersonality, send_window_size: u32) -> Self {
+        let config: MachineConfig = toml::from_str(
+            include_str!("../../../../machine.default.toml"),
+        )
+            .expect("Invalid default machine config");
+
+        let mut context = Context::new(config);
+        context.controller.model.send_window_size = send_window_size;
+
+        let personality = Personality::load(personality)
+            .expect("Invalid simulator personality");
+
+        let script = FaultScript::default();
+        let link = SerialLink::new(personality.clone(), SimulatorFaults::default())
+            .with_script(script.clone());
+
+        let (serial, simulator_serial) = tokio::io::duplex(64 * 1024);
+
+        tokio::spawn(async move {
+            SerialSimulator::run_with_link(
+                simulator_serial,
+                personality,
+                SimulatorPhysics::default(),
+                link,
+            )
+                .await
+                .expect("Serial simulator error");
+        });
+
+        let (serial_reader, serial_writer) = tokio::io::split(serial);
+
+        Self {
+            state: State::new_connection(vec![115_200]),
+            context,
+            serial_reader,
+            serial_writer,
+            serial_tx: VecDeque::new(),
+            serial_rx: BytesMut::new(),
+            delays: HashMap::new(),
+            script,
+        }
+    }
+
+    async fn connect(send_window_size: u32) -> Self {
+        Self::connect_to(SimulatorPersonality::Marlin, send_window_size).await
+    }
+
+    async fn connect_to(personality: SimulatorPersonality, send_window_size: u32) -> Self {
+        let mut harness = Self::new(personality, send_window_size);
+
+        // The simulator sends the personality's greeting once the port is opened
+        harness.consume(SerialPortOpened);
+        harness.run().await;
+
+        if let Ready(_) = harness.state {
+        } else {
+            panic!("Expected Ready, got: {:?}", harness.state)
+        };
+
+        harness
+    }
+
+    fn inject(&mut self, gcode: &str, fault: Fault) {
+        self.script.inject(gcode, fault);
+    }
+
+    /// The GCodes executed by the simulator in the order they were executed
+    fn executed(&self) -> Vec<String> {
+        self.script.executed()
+    }
+
+    fn consume(&mut self, event: Event) {
+        let state = std::mem::replace(&mut self.state, Disconnected);
+        let Loop { next_state, effects } = state.consume(event, &mut self.context);
+
+        self.state = next_state;
+
+        for effect in effects {
+            match effect {
+                Effect::SendSerial(gcode_line) => {
+                    self.serial_tx.push_back(gcode_line);
+                }
+                Effect::Delay { key, event, .. } => {
+                    self.delays.insert(key, event);
+                }
+                Effect::CancelDelay { key } => {
+                    self.delays.remove(&key);
+                }
+                Effect::CancelAllDelays => {
+                    self.delays.clear();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Writes the driver's lines to the simulator and delivers its responses to the driver. If
+    /// the serial port goes quiet the response timeout fires instead. Returns false once there
+    /// is nothing left to do.
+    async fn step(&mut self) -> bool {
+        if !self.serial_tx.is_empty() {
+            let mut tx = BytesMut::new();
+
+            for gcode_line in self.serial_tx.drain(..) {
+                GCodeCodec.encode(gcode_line, &mut tx).unwrap();
+            }
+
+            self.serial_writer.write_all(&tx).await
+                .expect("Unable to write to the serial simulator");
+        }
+
+        let read = tokio::time::timeout(
+            QUIET_PERIOD,
+            self.serial_reader.read_buf(&mut self.serial_rx),
+        ).await;
+
+        match read {
+            Ok(Ok(0)) | Ok(Err(_)) => {
+                panic!("Serial simulator exited. State: {:?}", self.state)
+            }
+            Ok(Ok(_)) => {
+                self.parse_responses();
+
+                true
+            }
+            Err(_) => {
+                if let Some(event) = self.delays.remove("tickle_delay") {
+                    self.consume(event);
+
+                    true
+                } else {
+                    !self.serial_tx.is_empty()
+                }
+            }
+        }
+    }
+
+    /// Delivers firmware output that the simulator does not produce (eg. SD card progress) to
+    /// the driver
+    fn receive(&mut self, responses: Vec<String>) {
+        for response in responses {
+            self.serial_rx.extend_from_slice(format!("{}\n", response).as_bytes());
+        }
+
+        self.parse_responses();
+    }
+
+    fn parse_responses(&mut self) {
+        while let Some(responses) = GCodeCodec.decode(&mut self.serial_rx).unwrap() {
+            for response in responses {
+                self.consume(SerialRec(response));
+            }
+        }
+    }
+
+    async fn run(&mut self) {
+        for _ in 0..MAX_STEPS {
+            if !self.step().await {
+                return
+            }
+        }
+
+        panic!("Did not settle after {} steps. State: {:?}", MAX_STEPS, self.state);
+    }
+
+    async fn print(&mut self, gcodes: &[&str]) {
+        self.print_with_line_actions(gcodes, vec![]).await;
+    }
+
+    async fn print_with_line_actions(
+        &mut self,
+        gcodes: &[&str],
+        line_actions: Vec<server_message::LineAction>,
+    ) {
+        let task = Task {
+            id: "TEST_PRINT".into(),
+            client_id: "TEST".into(),
+            gcode_lines: gcodes
+                .iter()
+                .map(|gcode| gcode.to_string())
+                .collect::<Vec<_>>()
+                .into(),
+            next_line_number: 0,
+            despooled_line_number: None,
+            machine_override: false,
+            started: false,
+            line_actions: LineActions::new(line_actions),
+            skipped_lines: Default::default(),
+        };
+
+        self.consume(GCodeLoaded(task));
+        self.run().await;
+    }
+
+    /// Asserts that each line of the print was executed exactly once and in order
+    fn assert_printed(&self, gcodes: &[&str]) {
+        match &self.state {
+            Ready(ready) => assert!(
+                ready.tasks.is_empty(),
+                "Expected the print to finish, got: {:?}",
+                ready.tasks,
+            ),
+            state => panic!("Expected Ready, got: {:?}", state),
+        };
+
+        let executed = self.executed();
+        let printed = executed
+            .iter()
+            .map(|gcode| gcode.as_str())
+            .filter(|gcode| gcode.starts_with("G1"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(printed, gcodes.to_vec());
+    }
+}
+
+#[tokio::test]
+async fn prints_without_faults() {
+    let mut harness = Harness::connect(1).await;
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn resends_corrupted_lines() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::CorruptByte);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn resends_corrupted_lines_from_a_full_send_window() {
+    let mut harness = Harness::connect(4).await;
+    harness.inject("G1 X2", Fault::CorruptByte);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn resends_lines_on_request() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::RequestResend);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn recovers_dropped_lines_after_a_tickle() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::DropLine);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn continues_after_a_missing_ok() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::MissingOk);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn continues_after_busy_processing() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::Busy);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn errors_after_the_tickle_attempts_run_out() {
+    let mut harness = Harness::connect(1).await;
+    harness.inject("G1 X3", Fault::DropLine);
+    // Tickles are sent as M105 without a line number
+    for _ in 0..harness.context.controller.model.response_timeout_tickle_attempts {
+        harness.inject("M105", Fault::DropLine);
+    }
+
+    harness.print(&PRINT).await;
+
+    if let Errored { .. } = harness.state {
+    } else {
+        panic!("Expected Errored, got: {:?}", harness.state)
+    };
+}
+
+#[tokio::test]
+async fn prints_to_reprap_firmware() {
+    let mut harness = Harness::connect_to(SimulatorPersonality::RepRapFirmware, 1).await;
+    harness.inject("G1 X3", Fault::CorruptByte);
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn prints_to_klipper() {
+    let mut harness = Harness::connect_to(SimulatorPersonality::Klipper, 1).await;
+
+    harness.print(&PRINT).await;
+
+    harness.assert_printed(&PRINT);
+}
+
+#[tokio::test]
+async fn parses_each_personalitys_responses() {
+    let personalities = [
+        SimulatorPersonality::Marlin,
+        SimulatorPersonality::RepRapFirmware,
+        SimulatorPersonality::Klipper,
+    ];
+
+    for personality in personalities.iter() {
+        let mut harness = Harness::connect_to(*personality, 1).await;
+
+        // Lines without line numbers are executed without validation
+        harness.serial_writer.write_all(b"M105\nM114\nM115\n").await.unwrap();
+
+        let mut rx = BytesMut::new();
+
+        while let Ok(read) = tokio::time::timeout(
+            QUIET_PERIOD,
+            harness.serial_reader.read_buf(&mut rx),
+        ).await {
+            if read.unwrap() == 0 {
+                break
+            }
+        }
+
+        while let Some(responses) = GCodeCodec.decode(&mut rx).unwrap() {
+            for (src, response) in responses {
+                match response {
+                    Response::Unknown | Response::Error(_) => panic!(
+                        "{:?} response could not be parsed: {:?}",
+                        personality,
+                        src,
+                    ),
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn records_the_print_recovery_state() {
+    let mut harness = Harness::connect(1).await;
+
+    harness.print(&["M104 S200", "G1 X10 Y20 Z0.3", "G1 X15 E1"]).await;
+
+    let state = harness.context.print_recovery
+        .as_ref()
+        .expect("Expected a print recovery state");
+
+    assert_eq!(state.task_id, "TEST_PRINT");
+    assert_eq!(state.despooled_line_number, Some(2));
+    assert_eq!(state.heater_targets.get("e0"), Some(&200.0));
+    assert_eq!(state.positions.get("x"), Some(&15.0));
+    assert_eq!(state.extruder_position, 1.0);
+    assert!(harness.delays.contains_key("save_print_recovery"));
+}
+
+#[tokio::test]
+async fn tracks_sd_card_prints() {
+    let mut harness = Harness::connect(1).await;
+
+    let start_print = server_message::StartSdCardPrint {
+        task_id: "TEST_SD_PRINT".into(),
+        filename: "TEST.GCO".into(),
+    };
+    harness.consume(ProtobufRec(ServerMessage {
+        payload: Some(server_message::Payload::StartSdCardPrint(start_print)),
+    }));
+    harness.run().await;
+
+    assert!(harness.executed().contains(&"M23 TEST.GCO".to_string()));
+    assert!(harness.executed().contains(&"M24".to_string()));
+
+    let task_status = |harness: &Harness| {
+        harness.context.feedback.task_progress
+            .iter()
+            .find(|p| p.task_id == "TEST_SD_PRINT")
+            .map(|p| p.status)
+    };
+
+    assert_eq!(task_status(&harness), Some(TaskStatus::TaskStarted as i32));
+    assert!(harness.context.sd_card_print().is_some());
+
+    harness.receive(vec![
+        "SD printing byte 20/100".to_string(),
+        "Done printing file".to_string(),
+        "echo:0 hours 0 minutes".to_string(),
+        "ok".to_string(),
+    ]);
+
+    let progress = harness.context.feedback.task_progress
+        .iter()
+        .find(|p| p.task_id == "TEST_SD_PRINT")
+        .expect("Expected SD card print progress");
+
+    assert_eq!(progress.status, TaskStatus::TaskFinished as i32);
+    // SD card prints report their progress in bytes
+    assert_eq!(progress.despooled_line_number, 20);
+    assert!(harness.context.sd_card_print().is_none());
+}
+
+#[tokio::test]
+async fn answers_firmware_prompts() {
+    use crate::protos::machine_message::{HostAction, Prompt};
+
+    let mut harness = Harness::connect(1).await;
+
+    harness.receive(vec![
+        "//action:prompt_begin Nozzle Parked".to_string(),
+        "//action:prompt_button Continue".to_string(),
+        "//action:prompt_show".to_string(),
+    ]);
+
+    assert_eq!(harness.context.feedback.prompt, Some(Prompt {
+        message: "Nozzle Parked".into(),
+        buttons: vec!["Continue".into()],
+    }));
+
+    let response = server_message::RespondToPrompt {
+        button_index: 0,
+    };
+    harness.consume(ProtobufRec(ServerMessage {
+        payload: Some(server_message::Payload::RespondToPrompt(response)),
+    }));
+    harness.run().await;
+
+    assert!(harness.executed().contains(&"M876 S0".to_string()));
+    assert_eq!(harness.context.feedback.prompt, None);
+
+    // Pause requests are forwarded to the server
+    harness.receive(vec!["//action:pause filament_runout 0".to_string()]);
+    harness.context.add_host_actions_to_feedback();
+
+    assert_eq!(harness.context.feedback.host_actions, vec![HostAction::Pause as i32]);
+}
+
+#[tokio::test]
+async fn inserts_gcodes_before_scheduled_lines() {
+    let mut harness = Harness::connect(1).await;
+
+    harness.print_with_line_actions(&PRINT, vec![server_message::LineAction {
+        line_number: 2,
+        pause: false,
+        gcodes: vec!["G1 X9".into()],
+    }]).await;
+
+    harness.assert_printed(&["G1 X1", "G1 X2", "G1 X9", "G1 X3", "G1 X4", "G1 X5"]);
+}
+
+#[tokio::test]
+async fn pauses_before_scheduled_lines() {
+    use crate::protos::machine_message::HostAction;
+
+    let mut harness = Harness::connect(1).await;
+
+    harness.print_with_line_actions(&PRINT, vec![server_message::LineAction {
+        line_number: 2,
+        pause: true,
+        gcodes: vec![],
+    }]).await;
+
+    let task = match &harness.state {
+        Ready(ready) => ready.tasks.front().expect("Expected the print to be paused"),
+        state => panic!("Expected Ready, got: {:?}", state),
+    };
+
+    // Only the lines before the pause are sent and the server is asked to pause the print
+    assert!(task.line_actions.waiting_to_pause);
+    assert_eq!(task.despooled_line_number, Some(1));
+    assert!(!harness.executed().contains(&"G1 X3".to_string()));
+
+    harness.context.add_host_actions_to_feedback();
+    assert_eq!(harness.context.feedback.host_actions, vec![HostAction::Pause as i32]);
+
+    // Resuming the print does not pause it again
+    let mut resumed = Task {
+        id: "TEST_PRINT".into(),
+        client_id: "TEST".into(),
+        gcode_lines: PRINT
+            .iter()
+            .map(|gcode| gcode.to_string())
+            .collect::<Vec<_>>()
+            .into(),
+        next_line_number: 0,
+        despooled_line_number: Some(1),
+        machine_override: false,
+        started: false,
+        line_actions: LineActions::new(vec![server_message::LineAction {
+            line_number: 2,
+            pause: true,
+            gcodes: vec![],
+        }]),
+        skipped_lines: Default::default(),
+    };
+    resumed.skip_despooled_lines().unwrap();
+
+    assert_eq!(resumed.next_gcode().unwrap(), Some(TaskLine::GCode(2, "G1 X3".into())));
+}
@@ -0,0 +1,305 @@
+use nom_gcode::{
+    GCode,
+    Mnemonic::{
+        General as G,
+        Miscellaneous as M,
+    },
+};
+
+use crate::protos::server_message;
+
+/// What to do with a line of a task
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkipResult {
+    /// Send the line unchanged
+    Send,
+    /// Send a replacement for the line (eg. a move without it's extrusion)
+    Replace(String),
+    /// Drop the line
+    Skip,
+    /// Send these GCodes before the line to finish the skipped moves
+    Resync(Vec<String>),
+}
+
+/// The ranges of a task's lines to skip (eg. the lines of a cancelled object).
+///
+/// Skipped moves are not sent to the machine. Instead a single travel move to the end of the
+/// skipped moves is sent after each range. The extruder position is reset to the task's
+/// extruder position after each range so that absolute extrusions continue correctly.
+#[derive(Clone, Debug, Default)]
+pub struct SkippedLines {
+    /// The start and (exclusive) end of each range sorted by their start
+    ranges: Vec<(u32, u32)>,
+    /// True after skipping a move until the end of the range
+    skipping: bool,
+    /// The XYZ target of the moves skipped since the last line that was sent
+    skipped_position: [Option<f32>; 3],
+    relative_positioning: bool,
+    relative_extrusion: bool,
+    /// The absolute extruder position according to the task's GCode
+    extruder_position: Option<f32>,
+}
+
+impl SkippedLines {
+    pub fn new(line_ranges: Vec<server_message::LineRange>) -> Self {
+        let mut skipped_lines = Self::default();
+        skipped_lines.set(line_ranges);
+
+        skipped_lines
+    }
+
+    /// Replaces the skipped ranges
+    pub fn set(&mut self, line_ranges: Vec<server_message::LineRange>) {
+        let mut ranges = line_ranges
+            .into_iter()
+            .filter(|range| range.start < range.end)
+            .map(|range| (range.start as u32, range.end as u32))
+            .collect::<Vec<_>>();
+
+        ranges.sort_unstable();
+
+        // Merge overlapping ranges
+        self.ranges = ranges
+            .into_iter()
+            .fold(vec![], |mut merged: Vec<(u32, u32)>, (start, end)| {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+                merged
+            });
+    }
+
+    fn is_skipped(&self, line_number: u32) -> bool {
+        let index = match self.ranges.binary_search_by_key(&line_number, |(start, _)| *start) {
+            Ok(index) => index,
+            Err(0) => return false,
+            Err(index) => index - 1,
+        };
+
+        line_number < self.ranges[index].1
+    }
+
+    /// Tracks the positioning modes of the task and decides what to send for a GCode line.
+    ///
+    /// `gcode` is the line parsed by nom_gcode or None if it could not be parsed. Unparsed lines
+    /// in a skipped range are dropped since they may be moves.
+    pub fn filter(&mut self, line_number: u32, gcode: Option<&GCode>) -> SkipResult {
+        let is_skipped = self.is_skipped(line_number);
+
+        // Finish the previous range before the line changes the positioning modes
+        if self.skipping && !is_skipped {
+            self.skipping = false;
+            return SkipResult::Resync(self.resync_gcodes());
+        }
+
+        let gcode = match gcode {
+            Some(gcode) => gcode,
+            None if is_skipped => return SkipResult::Skip,
+            None => return SkipResult::Send,
+        };
+
+        let arg = |key: char| {
+            gcode.arguments()
+                .find(|(k, _)| k.to_ascii_uppercase() == key)
+                .and_then(|(_, value)| *value)
+        };
+
+        let is_move = matches!((&gcode.mnemonic, gcode.major), (G, 0..=3));
+        let is_set_position = matches!((&gcode.mnemonic, gcode.major), (G, 92));
+
+        match (&gcode.mnemonic, gcode.major) {
+            (G, 90) => {
+                self.relative_positioning = false;
+                self.relative_extrusion = false;
+            }
+            (G, 91) => {
+                self.relative_positioning = true;
+                self.relative_extrusion = true;
+            }
+            (M, 82) => self.relative_extrusion = false,
+            (M, 83) => self.relative_extrusion = true,
+            _ => (),
+        }
+
+        if is_move || is_set_position {
+            if let Some(e) = arg('E') {
+                if is_set_position || !self.relative_extrusion {
+                    self.extruder_position = Some(e);
+                }
+            }
+        }
+
+        if !is_skipped {
+            return SkipResult::Send;
+        }
+
+        if is_move {
+            self.skipping = true;
+
+            if self.relative_positioning {
+                // Relative moves are sent without their extrusion to keep the position correct
+                let travel = gcode.arguments()
+                    .map(|(key, value)| (key.to_ascii_uppercase(), value))
+                    .filter(|(key, _)| *key != 'E')
+                    .map(|(key, value)| match value {
+                        Some(value) => format!("{}{}", key, value),
+                        None => key.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+
+                let is_travel = gcode.arguments()
+                    .any(|(key, _)| ['X', 'Y', 'Z'].contains(&key.to_ascii_uppercase()));
+
+                // Retractions have nothing left to send
+                return if is_travel {
+                    SkipResult::Replace(format!("G{} {}", gcode.major, travel.join(" ")))
+                } else {
+                    SkipResult::Skip
+                };
+            }
+
+            for (axis, key) in ['X', 'Y', 'Z'].iter().enumerate() {
+                if let Some(position) = arg(*key) {
+                    self.skipped_position[axis] = Some(position);
+                }
+            }
+
+            return SkipResult::Skip;
+        }
+
+        match (&gcode.mnemonic, gcode.major) {
+            // Firmware retractions
+            (G, 10) | (G, 11) => SkipResult::Skip,
+            _ => SkipResult::Send,
+        }
+    }
+
+    fn resync_gcodes(&mut self) -> Vec<String> {
+        let mut gcodes = vec![];
+
+        let travel = ["X", "Y", "Z"]
+            .iter()
+            .zip(self.skipped_position.iter_mut())
+            .filter_map(|(axis, position)| {
+                position.take().map(|position| format!("{}{}", axis, position))
+            })
+            .collect::<Vec<_>>();
+
+        if !travel.is_empty() {
+            gcodes.push(format!("G0 {}", travel.join(" ")));
+        }
+
+        if !self.relative_extrusion {
+            if let Some(e) = self.extruder_position {
+                gcodes.push(format!("G92 E{}", e));
+            }
+        }
+
+        gcodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom_gcode::GCodeLine;
+    use super::*;
+    use SkipResult::*;
+
+    fn filter(skipped_lines: &mut SkippedLines, line_number: usize, gcode: &str) -> SkipResult {
+        match nom_gcode::parse_gcode(gcode) {
+            Ok((_, Some(GCodeLine::GCode(gcode)))) => {
+                skipped_lines.filter(line_number as u32, Some(&gcode))
+            }
+            _ => skipped_lines.filter(line_number as u32, None),
+        }
+    }
+
+    #[test]
+    fn skips_extrusions_and_travels_to_the_end_of_the_range() {
+        let mut skipped_lines = SkippedLines::new(vec![server_message::LineRange {
+            start: 2,
+            end: 5,
+        }]);
+
+        let results = [
+            "G1 X1 Y1 E1",
+            "G1 X2 Y1 E2",
+            // Skipped
+            "G1 X3 Y3 E3",
+            "M106 S255",
+            "G1 X4 Y4 Z0.4 E4",
+            // Not skipped
+            "G1 X5 Y5 E5",
+        ]
+            .iter()
+            .enumerate()
+            .map(|(line_number, gcode)| filter(&mut skipped_lines, line_number, gcode))
+            .collect::<Vec<_>>();
+
+        assert_eq!(results, vec![
+            Send,
+            Send,
+            Skip,
+            Send,
+            Skip,
+            Resync(vec![
+                "G0 X4 Y4 Z0.4".to_string(),
+                "G92 E4".to_string(),
+            ]),
+        ]);
+
+        // The line is sent after the resync
+        assert_eq!(filter(&mut skipped_lines, 5, "G1 X5 Y5 E5"), Send);
+    }
+
+    #[test]
+    fn skips_moves_written_without_spaces_or_in_lower_case() {
+        let mut skipped_lines = SkippedLines::new(vec![server_message::LineRange {
+            start: 1,
+            end: 5,
+        }]);
+
+        let results = [
+            "G1 X1 Y1 E1",
+            // Skipped
+            "G01 X2 Y2 E2",
+            "G1X3Y3E3",
+            "g1 x4 y4 e4",
+            "G1 X6 Y6 Z0.4 E6",
+            // Not skipped
+            "G1 X7 Y7 E7",
+        ]
+            .iter()
+            .enumerate()
+            .map(|(line_number, gcode)| filter(&mut skipped_lines, line_number, gcode))
+            .collect::<Vec<_>>();
+
+        assert_eq!(results, vec![
+            Send,
+            Skip,
+            Skip,
+            Skip,
+            Skip,
+            Resync(vec![
+                "G0 X6 Y6 Z0.4".to_string(),
+                "G92 E6".to_string(),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn sends_relative_moves_without_their_extrusion() {
+        let mut skipped_lines = SkippedLines::new(vec![server_message::LineRange {
+            start: 1,
+            end: 3,
+        }]);
+
+        assert_eq!(filter(&mut skipped_lines, 0, "G91"), Send);
+        assert_eq!(
+            filter(&mut skipped_lines, 1, "G1 X1 Y2 E0.5"),
+            Replace("G1 X1 Y2".to_string()),
+        );
+        assert_eq!(filter(&mut skipped_lines, 2, "G1 E-1 F2400"), Skip);
+    }
+}
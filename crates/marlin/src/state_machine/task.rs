@@ -1,40 +1,217 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+};
 use nom_gcode::{
     GCodeLine,
 };
+use teg_machine::task::GCodeFile;
+
+use crate::protos::server_message;
+
+use super::{SkippedLines, SkipResult};
+
+/// The lines of a task's GCode. Task files are read from disk as they are despooled so that
+/// the driver's memory use does not grow with the size of the print.
+#[derive(Clone, Debug)]
+pub enum GCodeLines {
+    Inline(std::vec::IntoIter<String>),
+    File(GCodeFile),
+}
+
+impl From<Vec<String>> for GCodeLines {
+    fn from(gcodes: Vec<String>) -> Self {
+        GCodeLines::Inline(gcodes.into_iter())
+    }
+}
+
+impl Iterator for GCodeLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            GCodeLines::Inline(gcodes) => gcodes.next().map(Ok),
+            GCodeLines::File(gcode_file) => gcode_file.next(),
+        }
+    }
+}
+
+/// A line despooled from a task
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskLine {
+    /// A GCode and the file line number it was despooled at
+    GCode(u32, String),
+    /// A scheduled pause was reached before the line at this file line number
+    Pause(u32),
+}
+
+#[derive(Clone, Debug, Default)]
+struct LineAction {
+    pause: bool,
+    gcodes: Vec<String>,
+}
+
+/// Pauses and GCodes scheduled to run before specific lines of a task (eg. a colour change at a
+/// layer).
+#[derive(Clone, Debug, Default)]
+pub struct LineActions {
+    /// The actions that have not yet run indexed by the file line number they run before
+    actions: BTreeMap<u32, LineAction>,
+    /// The GCodes inserted by the actions of the pending line
+    inserted_gcodes: VecDeque<String>,
+    /// A line that was read from the task but is waiting for it's actions to run first
+    pending_line: Option<(u32, String)>,
+    /// True once a scheduled pause has been reached. Nothing more is despooled from the task
+    /// until the server pauses it.
+    pub waiting_to_pause: bool,
+}
+
+impl LineActions {
+    pub fn new(line_actions: Vec<server_message::LineAction>) -> Self {
+        let mut actions: BTreeMap<u32, LineAction> = BTreeMap::new();
+
+        // Actions scheduled for the same line are merged
+        for line_action in line_actions {
+            let action = actions
+                .entry(line_action.line_number as u32)
+                .or_default();
+
+            action.pause |= line_action.pause;
+            action.gcodes.extend(line_action.gcodes);
+        }
+
+        Self {
+            actions,
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the actions that have not yet run
+    pub fn set(&mut self, line_actions: Vec<server_message::LineAction>) {
+        self.actions = Self::new(line_actions).actions;
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Task
 {
     pub id: crate::DbId,
     pub client_id: crate::DbId,
-    // TODO: gcode_lines iterator. Does 'self lifetime do what I need?
-    // gcode_lines: Option<Box<dyn std::slice::Iter<T: str>>>,
-    pub gcode_lines: std::vec::IntoIter<String>,
+    pub gcode_lines: GCodeLines,
+    /// The file line number of the next line in gcode_lines
+    pub next_line_number: u32,
+    /// The file line number of the last line acknowledged by the firmware. Lines still in the
+    /// send window are not counted until their OK is received.
     pub despooled_line_number: Option<u32>,
     pub machine_override: bool,
     pub started: bool,
+    pub line_actions: LineActions,
+    pub skipped_lines: SkippedLines,
 }
 
 impl Task {
-    /// Get the next gcode skipping any empty lines or comments
-    pub fn next_gcode(&mut self) -> Option<String> {
-        self.gcode_lines.find_map(|mut gcode| {
+    /// Skips through the lines that were despooled before the print was paused. Task files are
+    /// opened after the despooled lines so this only skips inline GCodes.
+    pub fn skip_despooled_lines(&mut self) -> io::Result<()> {
+        if let Some(despooled_line_number) = self.despooled_line_number {
+            while self.next_line_number <= despooled_line_number {
+                if self.gcode_lines.next().transpose()?.is_none() {
+                    break
+                }
+                self.next_line_number += 1;
+            }
+            self.next_line_number = despooled_line_number + 1;
+
+            // Actions before the resumed line have already run and a pause at the resumed line
+            // is the pause that the task is being resumed from
+            let actions = &mut self.line_actions.actions;
+            *actions = actions.split_off(&self.next_line_number);
+
+            if let Some(action) = actions.get_mut(&self.next_line_number) {
+                action.pause = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the next gcode and it's file line number skipping any empty lines or comments.
+    ///
+    /// Scheduled line actions run before their line is returned. GCodes inserted by an action are
+    /// reported at the previous line number so that they are not skipped if the print is
+    /// paused and resumed before the line.
+    ///
+    /// Lines in skipped ranges are filtered after the line actions run.
+    pub fn next_gcode(&mut self) -> io::Result<Option<TaskLine>> {
+        loop {
+            let line_actions = &mut self.line_actions;
+
+            let (line_number, mut gcode, is_inserted) = if let Some(gcode) = line_actions.inserted_gcodes.pop_front() {
+                let line_number = line_actions.pending_line
+                    .as_ref()
+                    .map(|(line_number, _)| line_number.saturating_sub(1))
+                    .unwrap_or(0);
+
+                (line_number, gcode, true)
+            } else if let Some((line_number, gcode)) = line_actions.pending_line.take() {
+                (line_number, gcode, false)
+            } else if let Some(gcode) = self.gcode_lines.next().transpose()? {
+                let line_number = self.next_line_number;
+                self.next_line_number += 1;
+
+                // Run the actions scheduled before this line
+                if let Some(action) = line_actions.actions.remove(&line_number) {
+                    line_actions.inserted_gcodes.extend(action.gcodes);
+                    line_actions.pending_line = Some((line_number, gcode));
+
+                    if action.pause {
+                        line_actions.waiting_to_pause = true;
+                        return Ok(Some(TaskLine::Pause(line_number)));
+                    }
+
+                    continue;
+                }
+
+                (line_number, gcode, false)
+            } else {
+                return Ok(None);
+            };
+
             // return driver macros
             if gcode.starts_with('!') {
-                return Some(gcode);
+                return Ok(Some(TaskLine::GCode(line_number, gcode)));
             };
 
             if let Some(semicolon) = gcode.find(';') {
                 gcode = gcode[0..semicolon].to_string();
             }
 
-            match nom_gcode::parse_gcode(&gcode) {
+            let skip_result = match nom_gcode::parse_gcode(&gcode) {
                 // skip comments and empty lines
                 | Ok((_, Some(GCodeLine::Comment(_))))
                 | Ok((_, None)) => None,
-                // return gcodes
-                _ => Some(gcode),
+                // return inserted gcodes
+                _ if is_inserted => Some(SkipResult::Send),
+                Ok((_, Some(GCodeLine::GCode(parsed)))) => {
+                    Some(self.skipped_lines.filter(line_number, Some(&parsed)))
+                }
+                _ => Some(self.skipped_lines.filter(line_number, None)),
+            };
+
+            // return gcodes that are not skipped
+            match skip_result {
+                None | Some(SkipResult::Skip) => (),
+                Some(SkipResult::Send) => return Ok(Some(TaskLine::GCode(line_number, gcode))),
+                Some(SkipResult::Replace(gcode)) => {
+                    return Ok(Some(TaskLine::GCode(line_number, gcode)))
+                }
+                Some(SkipResult::Resync(gcodes)) => {
+                    let line_actions = &mut self.line_actions;
+
+                    line_actions.inserted_gcodes.extend(gcodes);
+                    line_actions.pending_line = Some((line_number, gcode));
+                }
             }
-        })
+        }
     }
 }
@@ -26,6 +26,7 @@ impl teg_config_form::Model for Box<FdmFilament>
             "name",
             "targetExtruderTemperature",
             "targetBedTemperature",
+            "slicingProfileID",
         ]
             .into_iter()
             .map(Into::into)
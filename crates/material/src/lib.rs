@@ -6,9 +6,15 @@ use std::sync::Arc;
 
 pub use material::*;
 
+mod slicing_profile;
+pub use slicing_profile::*;
+
 pub mod resolvers;
 pub use resolvers::material_mutation_resolvers::MaterialMutation;
 pub use resolvers::material_query_resolvers::MaterialQuery;
+pub use resolvers::material_subscription_resolvers::MaterialSubscription;
+pub use resolvers::slicing_profile_mutation_resolvers::SlicingProfileMutation;
+pub use resolvers::slicing_profile_query_resolvers::SlicingProfileQuery;
 
 mod configurable_material;
 
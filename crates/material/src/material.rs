@@ -43,6 +43,19 @@ pub struct FdmFilament {
     pub target_extruder_temperature: f32,
     /// # Target Bed Temperature
     pub target_bed_temperature: f32,
+    /// # Slicing Profile
+    /// The slicing profile to use when slicing for a machine loaded with this material.
+    #[serde(default)]
+    #[serde(rename = "slicingProfileID")]
+    pub slicing_profile_id: Option<crate::DbId>,
+}
+
+impl Material {
+    pub fn slicing_profile_id(&self) -> Option<&crate::DbId> {
+        match &self.config {
+            MaterialConfigEnum::FdmFilament(fdm) => fdm.slicing_profile_id.as_ref(),
+        }
+    }
 }
 
 impl MaterialConfig for FdmFilament {
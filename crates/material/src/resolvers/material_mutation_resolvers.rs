@@ -11,8 +11,9 @@ use eyre::{
 };
 // use teg_json_store::Record as _;
 
-use teg_auth::AuthContext;
+use teg_auth::{AuthContext, Permission};
 use teg_json_store::Record;
+use teg_common::into_field_error;
 use crate::{FdmFilament, MaterialTypeGQL, material::{
         Material,
         MaterialConfigEnum,
@@ -57,7 +58,7 @@ impl MaterialMutation {
         let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        auth.authorize_admins_only()?;
+        auth.authorize(Permission::ConfigureMachine)?;
 
         let config = match input.material_type {
             MaterialTypeGQL::FdmFilament => {
@@ -89,7 +90,7 @@ impl MaterialMutation {
         let auth: &AuthContext = ctx.data()?;
         let material_hooks: &crate::MaterialHooksList = ctx.data()?;
 
-        auth.authorize_admins_only()?;
+        auth.authorize(Permission::ConfigureMachine)?;
 
         async move {
             let mut material = Material::get_with_version(
@@ -120,7 +121,7 @@ impl MaterialMutation {
         .await
         .map_err(|err: eyre::Error| {
             warn!("{:?}", err);
-            err.into()
+            into_field_error(err)
         })
     }
 
@@ -132,7 +133,7 @@ impl MaterialMutation {
         let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        auth.authorize_admins_only()?;
+        auth.authorize(Permission::ConfigureMachine)?;
 
         let DeleteMaterialInput { material_id } = input;
 
@@ -140,7 +141,8 @@ impl MaterialMutation {
             .await?
             .remove(db, false)
             .await
-            .wrap_err_with(|| "Error deleting material")?;
+            .wrap_err_with(|| "Error deleting material")
+            .map_err(into_field_error)?;
 
         Ok(None)
     }
@@ -14,7 +14,7 @@ use crate::{
     Material,
 };
 
-#[derive(async_graphql::InputObject, Default)]
+#[derive(async_graphql::InputObject, Default, Clone)]
 pub struct MaterialsInput {
     #[graphql(name = "materialID")]
     material_id: Option<ID>,
@@ -25,7 +25,7 @@ pub struct MaterialQuery;
 
 #[async_graphql::Object]
 impl MaterialQuery {
-    async fn materials<'ctx>(
+    pub async fn materials<'ctx>(
         &self,
         ctx: &'ctx Context<'_>,
         #[graphql(default)]
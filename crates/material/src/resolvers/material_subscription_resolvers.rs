@@ -0,0 +1,60 @@
+use async_graphql::{
+    Context,
+    FieldResult,
+};
+use futures::stream::Stream;
+use teg_json_store::ChangeFeed;
+
+use crate::{
+    Material,
+    SlicingProfile,
+};
+use super::{
+    material_query_resolvers::{
+        MaterialQuery,
+        MaterialsInput,
+    },
+    slicing_profile_query_resolvers::{
+        SlicingProfileQuery,
+        SlicingProfilesInput,
+    },
+};
+
+#[derive(Default)]
+pub struct MaterialSubscription;
+
+#[async_graphql::Subscription]
+impl MaterialSubscription {
+    /// Live materials. Sends the current materials and then again whenever they change.
+    async fn materials<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: MaterialsInput,
+    ) -> FieldResult<impl Stream<Item = FieldResult<Vec<Material>>> + 'ctx> {
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        let stream = change_feed.live_query(&["materials"], move || {
+            (&MaterialQuery).materials(ctx, input.clone())
+        });
+
+        Ok(stream)
+    }
+
+    /// Live slicing profiles. Sends the current slicing profiles and then again whenever they
+    /// change.
+    async fn slicing_profiles<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: SlicingProfilesInput,
+    ) -> FieldResult<impl Stream<Item = FieldResult<Vec<SlicingProfile>>> + 'ctx> {
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        let stream = change_feed.live_query(&["slicing_profiles"], move || {
+            (&SlicingProfileQuery).slicing_profiles(ctx, input.clone())
+        });
+
+        Ok(stream)
+    }
+}
@@ -1,3 +1,7 @@
 pub mod material_mutation_resolvers;
 pub mod material_query_resolvers;
+pub mod material_subscription_resolvers;
 mod material_resolvers;
+pub mod slicing_profile_mutation_resolvers;
+pub mod slicing_profile_query_resolvers;
+mod slicing_profile_resolvers;
@@ -0,0 +1,183 @@
+use std::io::Read;
+
+use async_graphql::{
+    FieldResult,
+    ID,
+    Context,
+};
+use eyre::{
+    Context as _,
+    // eyre,
+    // Result
+};
+
+use teg_auth::{AuthContext, Permission};
+use teg_json_store::Record;
+use teg_common::into_field_error;
+use crate::{
+    SlicingProfile,
+    SlicingProfileFormat,
+};
+
+// Input Types
+// ---------------------------------------------
+
+#[derive(async_graphql::InputObject, Debug)]
+pub struct CreateSlicingProfileInput {
+    pub name: String,
+    pub format: SlicingProfileFormat,
+    pub content: String,
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct ImportSlicingProfileInput {
+    /// Defaults to the profile's file name
+    pub name: Option<String>,
+    /// A PrusaSlicer / SuperSlicer config bundle (.ini) or a Cura printer definition (.def.json)
+    pub file: async_graphql::Upload,
+}
+
+#[derive(async_graphql::InputObject, Debug)]
+pub struct UpdateSlicingProfileInput {
+    #[graphql(name="slicingProfileID")]
+    pub slicing_profile_id: ID,
+    pub model_version: i32,
+    pub name: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct DeleteSlicingProfileInput {
+    #[graphql(name="slicingProfileID")]
+    pub slicing_profile_id: ID,
+}
+
+// Resolvers
+// ---------------------------------------------
+
+#[derive(Default)]
+pub struct SlicingProfileMutation;
+
+#[async_graphql::Object]
+impl SlicingProfileMutation {
+    async fn create_slicing_profile<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: CreateSlicingProfileInput,
+    ) -> FieldResult<SlicingProfile> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize(Permission::ConfigureMachine)?;
+
+        let slicing_profile = SlicingProfile::new(
+            input.name,
+            input.format,
+            input.content,
+        )?;
+
+        slicing_profile.insert(db).await?;
+
+        Ok(slicing_profile)
+    }
+
+    /// Imports a PrusaSlicer or Cura profile file as a new slicing profile
+    #[instrument(skip(self, ctx, input))]
+    async fn import_slicing_profile<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: ImportSlicingProfileInput,
+    ) -> FieldResult<SlicingProfile> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize(Permission::ConfigureMachine)?;
+
+        async move {
+            let mut upload = input.file.value(&ctx)?;
+
+            let mut content = String::new();
+            upload.content
+                .read_to_string(&mut content)
+                .wrap_err("Slicing profiles must be UTF-8 text files")?;
+
+            let slicing_profile = SlicingProfile::import(
+                input.name,
+                &upload.filename,
+                content,
+            )?;
+
+            slicing_profile.insert(db).await?;
+
+            Ok(slicing_profile)
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err: eyre::Error| {
+            warn!("{:?}", err);
+            into_field_error(err)
+        })
+    }
+
+    #[instrument(skip(self, ctx))]
+    async fn update_slicing_profile<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: UpdateSlicingProfileInput,
+    ) -> FieldResult<SlicingProfile> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize(Permission::ConfigureMachine)?;
+
+        async move {
+            let mut slicing_profile = SlicingProfile::get_with_version(
+                db,
+                &input.slicing_profile_id,
+                input.model_version,
+                false,
+            ).await?;
+
+            if let Some(name) = input.name {
+                slicing_profile.name = name;
+            }
+
+            if let Some(content) = input.content {
+                slicing_profile.format.validate(&content)?;
+                slicing_profile.content = content;
+            }
+
+            slicing_profile.update(db).await?;
+
+            Ok(slicing_profile)
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err: eyre::Error| {
+            warn!("{:?}", err);
+            into_field_error(err)
+        })
+    }
+
+    async fn delete_slicing_profile<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: DeleteSlicingProfileInput
+    ) -> FieldResult<Option<teg_common::Void>> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize(Permission::ConfigureMachine)?;
+
+        let DeleteSlicingProfileInput { slicing_profile_id } = input;
+
+        SlicingProfile::get(db, &slicing_profile_id.0, true)
+            .await?
+            .remove(db, false)
+            .await
+            .wrap_err_with(|| "Error deleting slicing profile")
+            .map_err(into_field_error)?;
+
+        Ok(None)
+    }
+}
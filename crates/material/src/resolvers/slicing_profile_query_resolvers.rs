@@ -0,0 +1,47 @@
+// use eyre::{
+//     // eyre,
+//     Result,
+//     // Context as _,
+// };
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use teg_json_store::Record as _;
+
+use crate::{
+    SlicingProfile,
+};
+
+#[derive(async_graphql::InputObject, Default, Clone)]
+pub struct SlicingProfilesInput {
+    #[graphql(name = "slicingProfileID")]
+    slicing_profile_id: Option<ID>,
+}
+
+#[derive(Default)]
+pub struct SlicingProfileQuery;
+
+#[async_graphql::Object]
+impl SlicingProfileQuery {
+    pub async fn slicing_profiles<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: SlicingProfilesInput,
+
+    ) -> FieldResult<Vec<SlicingProfile>> {
+        let db: &crate::Db = ctx.data()?;
+
+        let slicing_profiles = if let Some(id) = input.slicing_profile_id {
+            let slicing_profile = SlicingProfile::get(db, &id, false).await?;
+
+            vec![slicing_profile]
+        } else {
+            SlicingProfile::get_all(db, false).await?
+        };
+
+        Ok(slicing_profiles)
+    }
+}
@@ -0,0 +1,37 @@
+use async_graphql::{
+    ID,
+};
+
+use crate::{
+    SlicingProfile,
+    SlicingProfileFormat,
+};
+
+#[async_graphql::Object]
+impl SlicingProfile {
+    async fn id(&self) -> ID {
+        (&self.id).into()
+    }
+
+    async fn model_version(&self) -> i32 {
+        self.version
+    }
+
+    async fn name(&self) -> &String {
+        &self.name
+    }
+
+    async fn format(&self) -> SlicingProfileFormat {
+        self.format
+    }
+
+    /// The file name to export the profile as (eg. "PLA.ini")
+    async fn file_name(&self) -> String {
+        self.export_file_name()
+    }
+
+    /// The contents of the profile file. Save this as the fileName to export the profile.
+    async fn content(&self) -> &String {
+        &self.content
+    }
+}
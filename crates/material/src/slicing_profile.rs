@@ -0,0 +1,177 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+use teg_json_store::Record;
+
+/// A slicer settings file stored in the database so that it can be linked to materials and
+/// machines instead of being dropped into the Teg config directory by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlicingProfile {
+    pub id: crate::DbId,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    // Props
+    pub name: String,
+    pub format: SlicingProfileFormat,
+    /// The contents of the slicer's profile file
+    pub content: String,
+}
+
+#[derive(async_graphql::Enum, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SlicingProfileFormat {
+    /// PrusaSlicer / SuperSlicer config bundles (.ini). Also used by belt-engine.
+    #[graphql(name = "PRUSA_SLICER_INI")]
+    PrusaSlicerIni,
+    /// CuraEngine printer definitions (.def.json)
+    #[graphql(name = "CURA_DEF_JSON")]
+    CuraDefJson,
+}
+
+impl SlicingProfileFormat {
+    /// Detects the profile format from a profile's file name (eg. "my-printer.ini")
+    pub fn from_file_name(file_name: &str) -> Result<Self> {
+        let lowercase_file_name = file_name.to_lowercase();
+
+        if lowercase_file_name.ends_with(".ini") {
+            Ok(Self::PrusaSlicerIni)
+        } else if lowercase_file_name.ends_with(".json") {
+            Ok(Self::CuraDefJson)
+        } else {
+            Err(eyre!(
+                "Unsupported slicing profile: {:?}. Expected a PrusaSlicer .ini or Cura .def.json file",
+                file_name,
+            ))
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::PrusaSlicerIni => "ini",
+            Self::CuraDefJson => "def.json",
+        }
+    }
+
+    /// Checks that the content can be parsed as this profile format
+    pub fn validate(&self, content: &str) -> Result<()> {
+        match self {
+            Self::PrusaSlicerIni => {
+                let invalid_line = content
+                    .lines()
+                    .map(str::trim)
+                    .enumerate()
+                    .find(|(_, line)| {
+                        !(
+                            line.is_empty()
+                            || line.starts_with('#')
+                            || line.starts_with(';')
+                            || (line.starts_with('[') && line.ends_with(']'))
+                            || line.contains('=')
+                        )
+                    });
+
+                if let Some((index, line)) = invalid_line {
+                    Err(eyre!(
+                        "Invalid PrusaSlicer profile (line {}): {:?}",
+                        index + 1,
+                        line,
+                    ))?;
+                }
+            }
+            Self::CuraDefJson => {
+                let json: serde_json::Value = serde_json::from_str(content)
+                    .map_err(|err| eyre!("Invalid Cura profile: {}", err))?;
+
+                if !json.is_object() {
+                    Err(eyre!("Invalid Cura profile: expected a JSON object"))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SlicingProfile {
+    pub fn new(
+        name: String,
+        format: SlicingProfileFormat,
+        content: String,
+    ) -> Result<Self> {
+        format.validate(&content)?;
+
+        Ok(Self {
+            id: nanoid!(11),
+            version: 0,
+            created_at: Utc::now(),
+            deleted_at: None,
+            name,
+            format,
+            content,
+        })
+    }
+
+    /// Creates a slicing profile from the contents of a PrusaSlicer or Cura profile file
+    pub fn import(
+        name: Option<String>,
+        file_name: &str,
+        content: String,
+    ) -> Result<Self> {
+        let format = SlicingProfileFormat::from_file_name(file_name)?;
+
+        let name = name.unwrap_or_else(|| {
+            // Default to the file name without its extension
+            let lowercase_file_name = file_name.to_lowercase();
+            let name_len = [".def.json", ".json", ".ini"]
+                .iter()
+                .find(|ext| lowercase_file_name.ends_with(*ext))
+                .map(|ext| file_name.len().saturating_sub(ext.len()))
+                .unwrap_or(file_name.len());
+
+            file_name
+                .get(..name_len)
+                .filter(|name| !name.is_empty())
+                .unwrap_or(file_name)
+                .to_string()
+        });
+
+        Self::new(name, format, content)
+    }
+
+    /// The file name to use when exporting the profile to the slicer (eg. "PLA.ini")
+    pub fn export_file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.file_extension())
+    }
+}
+
+impl Record for SlicingProfile {
+    const TABLE: &'static str = "slicing_profiles";
+
+    fn id(&self) -> &crate::DbId {
+        &self.id
+    }
+
+    fn version(&self) -> teg_json_store::Version {
+        self.version
+    }
+
+    fn version_mut(&mut self) -> &mut teg_json_store::Version {
+        &mut self.version
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+
+    fn deleted_at_mut(&mut self) -> &mut Option<DateTime<Utc>> {
+        &mut self.deleted_at
+    }
+}
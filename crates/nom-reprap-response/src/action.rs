@@ -0,0 +1,120 @@
+use nom::{
+    IResult,
+    character::streaming::*,
+    bytes::streaming::*,
+};
+use nom::branch::*;
+use nom::combinator::*;
+use nom::sequence::*;
+// use nom::multi::*;
+
+use super::Response;
+
+/// Host action commands sent by the firmware (eg. "//action:pause"). These are sent on
+/// filament runout, when a print is paused or cancelled from the LCD and to prompt the user
+/// (eg. during an M600 filament change).
+///
+/// See: https://reprap.org/wiki/G-code#Action_commands
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Requests that the host pause the print
+    Pause,
+    /// The firmware has paused the print itself
+    Paused,
+    /// Requests that the host resume the print
+    Resume,
+    /// The firmware has resumed the print itself
+    Resumed,
+    /// Requests that the host cancel the print
+    Cancel,
+    Notification(String),
+    /// Starts a new prompt. The prompt's buttons follow in PromptButton actions.
+    PromptBegin(String),
+    PromptButton(String),
+    PromptShow,
+    PromptEnd,
+    Other(String),
+}
+
+pub fn action<'r>(input: &'r str) ->  IResult<&'r str, Response> {
+    map(
+        preceded(
+            tuple((
+                tag("//"),
+                space0,
+                tag_no_case("action:"),
+            )),
+            alt((
+                prompt_action,
+                notification,
+                simple_action,
+            )),
+        ),
+        |action| Response::Action(action),
+    )(input)
+}
+
+fn action_text<'r>(input: &'r str) ->  IResult<&'r str, String> {
+    map(
+        preceded(space0, not_line_ending),
+        |s: &str| s.trim_end().to_string(),
+    )(input)
+}
+
+fn prompt_action<'r>(input: &'r str) ->  IResult<&'r str, Action> {
+    preceded(
+        tag_no_case("prompt_"),
+        alt((
+            map(
+                preceded(tag_no_case("begin"), action_text),
+                |message| Action::PromptBegin(message),
+            ),
+            // Older versions of Marlin send prompt_choice instead of prompt_button
+            map(
+                preceded(
+                    alt((
+                        tag_no_case("button"),
+                        tag_no_case("choice"),
+                    )),
+                    action_text,
+                ),
+                |label| Action::PromptButton(label),
+            ),
+            value(
+                Action::PromptShow,
+                pair(tag_no_case("show"), not_line_ending),
+            ),
+            value(
+                Action::PromptEnd,
+                pair(tag_no_case("end"), not_line_ending),
+            ),
+        )),
+    )(input)
+}
+
+fn notification<'r>(input: &'r str) ->  IResult<&'r str, Action> {
+    map(
+        preceded(tag_no_case("notification"), action_text),
+        |message| Action::Notification(message),
+    )(input)
+}
+
+fn simple_action<'r>(input: &'r str) ->  IResult<&'r str, Action> {
+    map(
+        pair(
+            take_till(|c: char| c.is_whitespace()),
+            // Any arguments (eg. "//action:pause filament_runout") are ignored
+            not_line_ending,
+        ),
+        |(name, _): (&str, &str)| {
+            match &name.to_ascii_lowercase()[..] {
+                "pause" => Action::Pause,
+                "paused" => Action::Paused,
+                "resume" => Action::Resume,
+                "resumed" => Action::Resumed,
+                "cancel" => Action::Cancel,
+                _ => Action::Other(name.to_string()),
+            }
+        },
+    )(input)
+}
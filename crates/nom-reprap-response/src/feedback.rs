@@ -11,6 +11,7 @@ use nom::multi::*;
 use super::{
     Response,
     f32_str,
+    u32_str,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -23,6 +24,9 @@ pub enum Busy {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Feedback {
     SDCard(SDCard),
+    SDFileList(Vec<SDFile>),
+    SDPrintProgress(SDPrintProgress),
+    NotSDPrinting,
     ActualTemperatures(Vec<(String, f32)>),
     Positions(Positions),
     StartSDWrite(StartSDWrite),
@@ -30,6 +34,18 @@ pub enum Feedback {
     SDPrintComplete,
     Busy(Busy),
     PausedForUser,
+    AdvancedOk(AdvancedOk),
+}
+
+/// Marlin's ADVANCED_OK fields (eg. "ok N10 P15 B3")
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdvancedOk {
+    /// The line number of the acknowledged GCode (only sent if the GCode had a line number)
+    pub line_number: Option<u32>,
+    /// Free slots in the firmware's planner buffer
+    pub planner_buffer_available: u32,
+    /// Free slots in the firmware's command buffer
+    pub command_buffer_available: u32,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -49,6 +65,21 @@ pub struct SDCard {
     pub size: Option<u32>,
 }
 
+/// A file listed by M20
+#[derive(Clone, Debug, PartialEq)]
+pub struct SDFile {
+    pub filename: String,
+    /// The file size in bytes (only sent by newer firmwares)
+    pub size: Option<u64>,
+}
+
+/// The byte position of the current SD card print reported by M27
+#[derive(Clone, Debug, PartialEq)]
+pub struct SDPrintProgress {
+    pub bytes_printed: u64,
+    pub total_bytes: u64,
+}
+
 pub fn feedback_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
     map(
         feedback,
@@ -66,6 +97,28 @@ pub fn feedback<'r>(input: &'r str) ->  IResult<&'r str, Feedback> {
     )(input)
 }
 
+pub fn advanced_ok_feedback<'r>(input: &'r str) ->  IResult<&'r str, Feedback> {
+    // ok N10 P15 B3
+    // ok P15 B4
+    map(
+        tuple((
+            opt(terminated(
+                preceded(char('N'), u32_str()),
+                space1,
+            )),
+            preceded(char('P'), u32_str()),
+            preceded(pair(space1, char('B')), u32_str()),
+        )),
+        |(line_number, planner_buffer_available, command_buffer_available)| {
+            Feedback::AdvancedOk(AdvancedOk {
+                line_number,
+                planner_buffer_available,
+                command_buffer_available,
+            })
+        },
+    )(input)
+}
+
 pub fn key_value<'r>(input: &'r str) -> IResult<&'r str, (String, Option<f32>)> {
     // T:25.0 /0.0 B:25.0 /0.0 T0:25.0 /0.0 @:0 B@:0
     // X:0.00 Y:191.00 Z:159.00 E:0.00 Count X: 0 Y:19196 Z:254400
@@ -91,19 +144,26 @@ pub fn key_value<'r>(input: &'r str) -> IResult<&'r str, (String, Option<f32>)>
 pub fn temperature_feedback<'r>(input: &'r str) ->  IResult<&'r str, Feedback> {
     // ok T:25.0 /0.0 B:25.0 /0.0 T0:25.0 /0.0 @:0 B@:0
     // T:${extruder} /0.0 B:${bed} /0.0 B@:0 @:0
+    // Klipper:
+    // ok B:25.0 /0.0 T0:25.0 /0.0
     map(
         preceded(
-            peek(tag("T:")),
+            peek(alt((
+                tag("T:"),
+                tag("T0:"),
+                tag("B:"),
+            ))),
             separated_list1(
-                pair(
-                    space1,
+                space1,
+                // Skip the target temperatures (eg. "/0.0")
+                terminated(
+                    key_value,
                     opt(tuple((
+                        space1,
                         char('/'),
                         f32_str(),
-                        space1,
                     ))),
                 ),
-                key_value,
             ),
         ),
         |temperatures| {
@@ -137,6 +197,8 @@ pub fn position_feedback<'r>(input: &'r str) ->  IResult<&'r str, Feedback> {
     // |-------------------------------|     |---------------------|
     // 'X:0.00 Y:191.00 Z:159.00 E:0.00 Count X: 0 Y:19196 Z:254400',
     // `X:${position()} Y:${position()} Z:${position()} E:0.00 Count X: 0.00Y:0.00Z:0.00`,
+    // RepRapFirmware's step counts are not labeled so they are skipped:
+    // 'X:0.000 Y:0.000 Z:0.000 E:0.000 E0:-0.0 Count 0 0 0 Machine 0.000 0.000 0.000 Bed comp 0.000'
     map(
         preceded(
             peek(tag("X:")),
@@ -151,15 +213,18 @@ pub fn position_feedback<'r>(input: &'r str) ->  IResult<&'r str, Feedback> {
                         tag_no_case("Count"),
                         space1,
                     )),
-                    many0(terminated(
-                        key_value,
-                        space0,
+                    alt((
+                        many1(terminated(
+                            key_value,
+                            space0,
+                        )),
+                        value(vec![], not_line_ending),
                     )),
                 )),
             ),
         ),
         |(p1, p2)| {
-            if let Some(p2) = p2 {
+            if let Some(p2) = p2.filter(|p2| !p2.is_empty()) {
                 Feedback::Positions(Positions {
                     target_positions: Some(normalize_positions(p1)),
                     actual_positions: normalize_positions(p2),
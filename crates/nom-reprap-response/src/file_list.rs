@@ -10,6 +10,8 @@ use nom::sequence::*;
 
 use super::{
     Response,
+    Feedback,
+    SDFile,
 };
 
 // TX "M20"
@@ -17,17 +19,39 @@ use super::{
 // RX "/47ACB~1.MOD/TEST/TEST-D~1.GCO\n"
 // RX "TEST-D~1.GCO\n"
 // RX "End file list\n"
+//
+// Newer firmwares also send the size of each file in bytes:
+// RX "TEST-D~1.GCO 1234\n"
 pub fn file_list<'r>(input: &'r str) ->  IResult<&'r str, Response> {
-    let end_tag = "\nEnd file list";
+    let end_tag = "End file list";
 
-    value(
-        Response::Ok(None),
-        tuple((
-            tag("Begin file list"),
-            space0,
-            line_ending,
+    map(
+        delimited(
+            tuple((
+                tag("Begin file list"),
+                space0,
+                line_ending,
+            )),
             take_until(end_tag),
             tag(end_tag),
-        )),
+        ),
+        |files: &str| {
+            let files = files
+                .lines()
+                .filter_map(|line| {
+                    let mut words = line.split_whitespace();
+
+                    let filename = words.next()?.to_string();
+                    let size = words.next().and_then(|size| size.parse().ok());
+
+                    Some(SDFile {
+                        filename,
+                        size,
+                    })
+                })
+                .collect();
+
+            Response::Ok(Some(Feedback::SDFileList(files)))
+        },
     )(input)
 }
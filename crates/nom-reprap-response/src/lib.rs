@@ -12,6 +12,9 @@ use nom::combinator::*;
 use nom::sequence::*;
 use nom::multi::*;
 
+mod action;
+pub use action::{action, Action};
+
 mod delete_file;
 pub use delete_file::delete_file_resp;
 
@@ -37,6 +40,7 @@ pub enum Response {
     Greeting,
     Ok(Option<Feedback>),
     Feedback(Feedback),
+    Action(Action),
     Debug(String),
     Echo(String),
     Error(String),
@@ -75,6 +79,7 @@ pub fn response<'r>() -> impl FnMut(&'r str) -> IResult<&'r str, Response> {
     terminated(
         alt((
             greeting,
+            action,
             debug,
             echo,
             ok_resp,
@@ -85,6 +90,7 @@ pub fn response<'r>() -> impl FnMut(&'r str) -> IResult<&'r str, Response> {
             sd_responses::done_print_resp,
             sd_responses::done_sd_write_resp,
             sd_responses::file_deleted_resp,
+            sd_responses::sd_print_progress_resp,
             file_list,
             delete_file_resp,
             firmware_version,
@@ -179,6 +185,7 @@ pub fn ok_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
             opt(preceded(
                 space1,
                 alt((
+                    map(advanced_ok_feedback, |feedback| Some(feedback)),
                     map(feedback, |feedback| Some(feedback)),
                     map(not_line_ending, |unrecognized: &str| {
                         if unrecognized.len() > 0 {
@@ -194,6 +201,13 @@ pub fn ok_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
     )(input)
 }
 
+const RESEND_ERRORS: [&str; 4] = [
+    "checksum mismatch",
+    "Line Number is not Last Line Number+1",
+    "No Checksum with line number",
+    "No Line Number with checksum",
+];
+
 pub fn err_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
     map(
         preceded(
@@ -209,7 +223,13 @@ pub fn err_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
             not_line_ending,
         ),
         |s: &str| {
-            if s == "checksum mismatch" {
+            // Line number and checksum errors are followed by a resend request so they are not
+            // fatal.
+            let is_resend_error = RESEND_ERRORS
+                .iter()
+                .any(|resend_error| s.starts_with(resend_error));
+
+            if is_resend_error {
                 Response::Warning(s.to_string())
             } else {
                 Response::Error(s.to_string())
@@ -312,3 +332,10 @@ pub fn u32_str<'r>() -> impl FnMut (&'r str) ->  IResult<&'r str, u32> {
         |s: &str| s.parse(),
     )
 }
+
+pub fn u64_str<'r>() -> impl FnMut (&'r str) ->  IResult<&'r str, u64> {
+    map_res(
+        digit1,
+        |s: &str| s.parse(),
+    )
+}
@@ -3,14 +3,16 @@ use nom::{
     character::streaming::*,
     bytes::streaming::*,
 };
-// use nom::branch::*;
+use nom::branch::*;
 use nom::combinator::*;
 use nom::sequence::*;
 // use nom::multi::*;
 
 use super::{
     Response,
-    Feedback
+    Feedback,
+    SDPrintProgress,
+    u64_str,
 };
 
 
@@ -52,3 +54,36 @@ pub fn file_deleted_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
         |s: &str| Response::Debug(s.to_string())
     )(input)
 }
+
+// TX: "N48 M27*36\n"
+//
+// SD printing byte 1234/56789\n
+// OR
+// Not SD printing\n
+pub fn sd_print_progress_resp<'r>(input: &'r str) ->  IResult<&'r str, Response> {
+    alt((
+        map(
+            preceded(
+                pair(
+                    tag_no_case("SD printing byte"),
+                    space0,
+                ),
+                separated_pair(
+                    u64_str(),
+                    char('/'),
+                    u64_str(),
+                ),
+            ),
+            |(bytes_printed, total_bytes)| {
+                Response::Feedback(Feedback::SDPrintProgress(SDPrintProgress {
+                    bytes_printed,
+                    total_bytes,
+                }))
+            },
+        ),
+        value(
+            Response::Feedback(Feedback::NotSDPrinting),
+            tag_no_case("Not SD printing"),
+        ),
+    ))(input)
+}
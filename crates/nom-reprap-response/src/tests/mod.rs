@@ -98,3 +98,21 @@ fn ultimaker2_marlin_dbg_2019_firmware() -> eyre::Result<()> {
     let data = include_str!("data/ultimaker2_marlin_dbg_2019_firmware.toml");
     snapshot_test_responses(data)
 }
+
+#[test]
+fn marlin_2_advanced_ok_firmware() -> eyre::Result<()> {
+    let data = include_str!("data/marlin_2_advanced_ok_firmware.toml");
+    snapshot_test_responses(data)
+}
+
+#[test]
+fn reprap_firmware_3_firmware() -> eyre::Result<()> {
+    let data = include_str!("data/reprap_firmware_3_firmware.toml");
+    snapshot_test_responses(data)
+}
+
+#[test]
+fn klipper_firmware() -> eyre::Result<()> {
+    let data = include_str!("data/klipper_firmware.toml");
+    snapshot_test_responses(data)
+}
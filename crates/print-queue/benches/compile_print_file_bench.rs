@@ -1,6 +1,6 @@
 use criterion::BenchmarkId;
 use criterion::{criterion_group, criterion_main, Criterion};
-use teg_print_queue::compile_print_file;
+use teg_print_queue::{compile_print_file, PrintTimeEstimator};
 
 // This is a struct that tells Criterion.rs to use the "futures" crate's current-thread executor
 use criterion::async_executor::AsyncStdExecutor;
@@ -181,6 +181,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                     task_file_path.to_str().unwrap(),
                     "",
                     "",
+                    PrintTimeEstimator::default(),
                     compile_internal_macro,
                     read_buffer_size,
                     write_buffer_size,
@@ -0,0 +1,84 @@
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_json_store::Record as _;
+use teg_machine::task::Task;
+
+/// Authorizes the permission for the print queues that the packages belong to
+pub async fn authorize_packages<'e, 'c, E>(
+    db: E,
+    auth: &AuthContext,
+    package_ids: &[crate::DbId],
+    permission: Permission,
+) -> Result<()>
+where
+    E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    // Check the role and scope even if the packages do not exist
+    auth.authorize(permission)?;
+
+    let print_queue_ids = sqlx::query!(
+        r#"
+            SELECT DISTINCT print_queue_id FROM packages
+            WHERE id = ANY($1)
+        "#,
+        package_ids,
+    )
+        .fetch_all(db)
+        .await?;
+
+    for row in print_queue_ids {
+        auth.authorize_print_queue(&row.print_queue_id, permission)?;
+    }
+
+    Ok(())
+}
+
+/// Authorizes the permission for the print queues that the parts belong to
+pub async fn authorize_parts<'e, 'c, E>(
+    db: E,
+    auth: &AuthContext,
+    part_ids: &[crate::DbId],
+    permission: Permission,
+) -> Result<()>
+where
+    E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    auth.authorize(permission)?;
+
+    let print_queue_ids = sqlx::query!(
+        r#"
+            SELECT DISTINCT packages.print_queue_id FROM parts
+            INNER JOIN packages ON packages.id = parts.package_id
+            WHERE parts.id = ANY($1)
+        "#,
+        part_ids,
+    )
+        .fetch_all(db)
+        .await?;
+
+    for row in print_queue_ids {
+        auth.authorize_print_queue(&row.print_queue_id, permission)?;
+    }
+
+    Ok(())
+}
+
+/// Authorizes the permission for the machine that the task belongs to
+pub async fn authorize_task<'e, 'c, E>(
+    db: E,
+    auth: &AuthContext,
+    task_id: &crate::DbId,
+    permission: Permission,
+) -> Result<Task>
+where
+    E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    let task = Task::get(db, task_id, false).await?;
+    auth.authorize_machine(&task.machine_id, permission)?;
+
+    Ok(task)
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+use futures::FutureExt;
+use xactor::Actor;
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+use teg_machine::{
+    MachineHooksList,
+    MachineMap,
+    machine::{
+        MachineStatus,
+        events::MachineReady,
+        messages::GetData,
+    },
+    task::Task,
+};
+
+use crate::{insert_print, part::Part};
+
+/// How often to check idle machines for parts that were queued since they became ready.
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Actor that starts the next eligible part in a machine's print queues whenever an automatic
+/// printing machine is ready and it's bed is clear. Each machine is sent the first part in queue
+/// order that matches the machine's loaded materials, build volume and tags so that a shared print
+/// queue can feed any number of machines.
+pub struct FarmScheduler {
+    pub db: crate::Db,
+    pub machines: MachineMap,
+    pub machine_hooks: MachineHooksList,
+}
+
+#[xactor::message(result = "()")]
+#[derive(Clone, Debug)]
+struct DispatchParts;
+
+#[async_trait::async_trait]
+impl Actor for FarmScheduler {
+    #[instrument(skip(self, ctx))]
+    async fn started(&mut self, ctx: &mut xactor::Context<Self>) -> Result<()> {
+        ctx.subscribe::<MachineReady>().await?;
+        ctx.send_interval(DispatchParts, DISPATCH_INTERVAL);
+
+        Ok(())
+    }
+}
+
+impl FarmScheduler {
+    pub async fn start(
+        db: crate::Db,
+        machines: MachineMap,
+        machine_hooks: MachineHooksList,
+    ) -> Result<xactor::Addr<FarmScheduler>> {
+        let farm_scheduler = xactor::Supervisor::start(move ||
+            FarmScheduler {
+                db: db.clone(),
+                machines: machines.clone(),
+                machine_hooks: machine_hooks.clone(),
+            }
+        ).await?;
+        Ok(farm_scheduler)
+    }
+
+    /// Starts the next eligible part on the machine if it has automatic printing enabled and it is
+    /// ready and it's bed is clear
+    async fn dispatch(&self, machine_id: &crate::DbId) -> Result<()> {
+        let machines = self.machines.load();
+        let machine = machines.get(&machine_id.into())
+            .ok_or_else(|| eyre!("Machine #{} not found", machine_id))?;
+
+        let machine_data = match machine.call(GetData).await? {
+            Ok(machine_data) => machine_data,
+            // Machines that have not loaded their config (eg. deleted machines) cannot print
+            Err(_) => return Ok(()),
+        };
+
+        // Only machines with automation hardware (eg. an auto-scraper) start prints unattended
+        let automatic_printing = machine_data.config.core_plugin()?.model.automatic_printing;
+
+        if
+            !automatic_printing
+            || machine_data.status != MachineStatus::Ready
+            || !machine_data.bed_clear
+        {
+            return Ok(())
+        }
+
+        let mut tx = self.db.begin().await?;
+
+        // Skip machines that are already spooling a print
+        let is_busy = !Task::tasks_running_on_machine(&mut tx, machine_id)
+            .await?
+            .is_empty();
+
+        if is_busy {
+            return Ok(())
+        }
+
+        let part = if let Some(part) = Part::fetch_next_part(
+            &mut tx,
+            &machine_data.config,
+        ).await? {
+            part
+        } else {
+            return Ok(())
+        };
+
+        info!("Farm Scheduler: Starting {} on Machine #{}", part.name, machine_id);
+
+        let (task_id, parse_and_spool) = insert_print(
+            self.db.clone(),
+            &mut tx,
+            &self.machine_hooks,
+            machine_id,
+            machine.clone(),
+            part,
+            false,
+        ).await?;
+
+        tx.commit().await?;
+
+        // Parse and spool the print in the background so that large prints do not hold up the
+        // other machines
+        async_std::task::spawn(parse_and_spool.map(move |res| {
+            if let Err(err) = res {
+                error!(
+                    "Error parsing and spooling farm scheduled print (ID: {:?}): {:?}",
+                    task_id,
+                    err,
+                );
+            }
+        }));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<MachineReady> for FarmScheduler {
+    async fn handle(&mut self, _ctx: &mut xactor::Context<Self>, msg: MachineReady) -> () {
+        if let Err(err) = self.dispatch(&msg.machine_id).await {
+            warn!("Error scheduling a print on Machine #{}: {:?}", msg.machine_id, err);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl xactor::Handler<DispatchParts> for FarmScheduler {
+    async fn handle(&mut self, _ctx: &mut xactor::Context<Self>, _msg: DispatchParts) -> () {
+        let machine_ids = self.machines
+            .load()
+            .keys()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+
+        for machine_id in machine_ids {
+            if let Err(err) = self.dispatch(&machine_id).await {
+                warn!("Error scheduling a print on Machine #{}: {:?}", machine_id, err);
+            }
+        }
+    }
+}
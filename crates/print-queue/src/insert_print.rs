@@ -17,10 +17,13 @@ use eyre::{
 };
 use teg_json_store::Record;
 use teg_macros::{AnnotatedGCode, GCodeAnnotation, InternalMacro, compile_macros};
-use teg_machine::{MachineHooksList, machine::{Errored, Machine, MachineStatus, Printing, messages::GetData}, task::{Task, TaskContent, TaskStatus}};
+use teg_machine::{MachineHooksList, machine::{Errored, Machine, MachineStatus, Printing, messages::GetData}, task::{GCodeIndexWriter, PrintTimeTable, Task, TaskContent, TaskStatus}};
 
 use crate::{
+    layer_detector::LayerDetector,
+    object_detector::ObjectDetector,
     part::Part,
+    print_time_estimator::PrintTimeEstimator,
     resolvers::print_resolvers::Print,
 };
 
@@ -107,10 +110,14 @@ pub async fn insert_print<'c>(
         despooled_line_number: None,
         machine_override: false,
         estimated_print_time: Default::default(),
+        print_time_table: Default::default(),
         time_blocked: Default::default(),
         time_paused: Default::default(),
         estimated_filament_meters: Default::default(),
+        material_ids: Default::default(),
         status: Default::default(),
+        scheduled_actions: vec![],
+        cancelled_objects: vec![],
     };
 
     task.insert_no_rollback(tx).await?;
@@ -119,6 +126,11 @@ pub async fn insert_print<'c>(
     let parse_and_spool = async move {
         let config = machine.call(GetData).await??.config;
 
+        let material_ids = config.toolheads
+            .iter()
+            .filter_map(|toolhead| toolhead.model.material_id.clone())
+            .collect();
+
         /*
         * Preprocess GCodes (part file => task file)
         * =========================================================================================
@@ -145,6 +157,7 @@ pub async fn insert_print<'c>(
             total_lines,
             estimated_print_time,
             estimated_filament_meters,
+            print_time_table,
         } = async_std::task::spawn_blocking(move || {
             let core_plugin = config.core_plugin()?;
 
@@ -153,6 +166,7 @@ pub async fn insert_print<'c>(
                 (*task_file_path_clone).clone(),
                 &core_plugin.model.before_print_hook,
                 &core_plugin.model.after_print_hook,
+                PrintTimeEstimator::new(&config),
                 compile_internal_macro,
                 read_buffer_size,
                 write_buffer_size,
@@ -167,8 +181,11 @@ pub async fn insert_print<'c>(
             total_lines,
             despooled_line_number: None,
             machine_override: false,
-            estimated_print_time,
+            // Fallback to the simulated print time if the slicer did not provide an estimate
+            estimated_print_time: estimated_print_time.or_else(|| print_time_table.total()),
+            print_time_table,
             estimated_filament_meters,
+            material_ids,
             ..task
         };
 
@@ -182,7 +199,7 @@ pub async fn insert_print<'c>(
         Result::<_>::Ok(Print {
             id: task.id.clone().into(),
             task,
-            part,
+            part: Some(part),
         })
     };
 
@@ -233,6 +250,7 @@ pub struct PrintMetaData {
     pub total_lines: u64,
     pub estimated_print_time: Option<std::time::Duration>,
     pub estimated_filament_meters: Option<f64>,
+    pub print_time_table: PrintTimeTable,
 }
 
 fn hook<'a>(hook_gcodes: &'a str) -> Lines<Cursor<&'a str>> {
@@ -245,6 +263,7 @@ pub fn compile_print_file<C, F, P1, P2>(
     task_file_path: P2,
     before_print_hook: &str,
     after_print_hook: &str,
+    mut print_time_estimator: PrintTimeEstimator,
     compile_internal_macro: C,
     read_buffer_size: usize,
     write_buffer_size: usize,
@@ -291,11 +310,15 @@ where
         write_buffer_size,
         task_file,
     );
+    // Allows the driver to start reading the task file from any line when resuming a print
+    let mut index_writer = GCodeIndexWriter::create(&task_file_path)?;
 
     let mut total_lines = 0u64;
     let mut annotations = vec![];
     let mut estimated_print_time = None;
     let mut estimated_filament_meters= None;
+    let mut layer_detector = LayerDetector::default();
+    let mut object_detector = ObjectDetector::default();
 
     for item in annotated_gcodes {
         let item = item?;
@@ -324,9 +347,14 @@ where
                     };
                 }
 
+                layer_detector.push_line(total_lines, &gcode);
+                object_detector.push_line(total_lines, &gcode);
+                print_time_estimator.push_line(total_lines, &gcode);
+
                 // Add the gcode
                 total_lines += 1;
                 gcode.push('\n');
+                index_writer.push_line(gcode.len() as u64)?;
                 gcodes_writer.write_all(&gcode.into_bytes())?;
             }
             AnnotatedGCode::Annotation(annotation) => {
@@ -336,6 +364,14 @@ where
     };
 
     gcodes_writer.flush()?;
+    index_writer.finish()?;
+
+    // Merge the layers and objects into the macro annotations keeping them in line number order
+    annotations.extend(layer_detector.finish());
+    annotations.extend(object_detector.finish(total_lines));
+    annotations.sort_by_key(|(line_number, _)| *line_number);
+
+    let print_time_table = print_time_estimator.finish(total_lines);
 
     info!("Parsed {} lines of GCode in: {:?}", total_lines, start.elapsed());
 
@@ -344,6 +380,7 @@ where
         total_lines,
         estimated_print_time,
         estimated_filament_meters,
+        print_time_table,
     })
 }
 
@@ -0,0 +1,192 @@
+use teg_macros::GCodeAnnotation;
+
+/// Detects the layer changes in a print's GCode.
+///
+/// Layer change comments (`;LAYER:` and `;LAYER_CHANGE`) are used if the slicer adds them.
+/// Otherwise each increase in Z height that is followed by an extruding move is treated as a new
+/// layer.
+///
+/// Lines are checked with simple string matching rather than the GCode parser as every line of
+/// the print passes through the detector.
+#[derive(Debug, Default)]
+pub struct LayerDetector {
+    /// Layers found from layer change comments
+    comment_layers: Vec<(u64, GCodeAnnotation)>,
+    /// True after a layer change comment until the Z height of the layer is found
+    awaiting_comment_z: bool,
+    /// Layers found from increases in Z height
+    z_layers: Vec<(u64, GCodeAnnotation)>,
+    /// The line number and height of a Z increase that becomes a layer once material is
+    /// extruded at it
+    pending_z_layer: Option<(u64, f32)>,
+    /// The height of the latest layer found from Z increases
+    layer_z: Option<f32>,
+    /// True after a G91. Relative Z moves are ignored.
+    relative_positioning: bool,
+}
+
+impl LayerDetector {
+    /// Checks a GCode line for layer changes. line_number is the line's index in the task file.
+    pub fn push_line(&mut self, line_number: u64, gcode: &str) {
+        let gcode = gcode.trim_start();
+
+        if let Some(comment) = gcode.strip_prefix(';') {
+            self.push_comment(line_number, comment.trim());
+            return;
+        }
+
+        let gcode = gcode.split(';').next().unwrap_or("");
+        let mut words = gcode.split_whitespace();
+
+        match words.next() {
+            Some("G0") | Some("G1") => (),
+            Some("G90") => {
+                self.relative_positioning = false;
+                return;
+            }
+            Some("G91") => {
+                self.relative_positioning = true;
+                return;
+            }
+            _ => return,
+        }
+
+        let mut z = None;
+        let mut extrudes = false;
+        let mut moves_xy = false;
+
+        for word in words {
+            let mut chars = word.chars();
+            let axis = chars.next().map(|c| c.to_ascii_uppercase());
+
+            match axis {
+                Some('Z') => z = chars.as_str().parse::<f32>().ok(),
+                Some('E') => extrudes = true,
+                Some('X') | Some('Y') => moves_xy = true,
+                _ => (),
+            }
+        }
+
+        if let Some(z) = z.filter(|_| !self.relative_positioning) {
+            self.set_comment_z(z);
+
+            let is_above_layer = self.layer_z
+                .map(|layer_z| z > layer_z)
+                .unwrap_or(true);
+
+            // Z hops return to the layer's height before extruding so they are not counted
+            self.pending_z_layer = if is_above_layer {
+                Some((line_number, z))
+            } else {
+                None
+            };
+        }
+
+        // Retractions extrude without moving
+        if extrudes && moves_xy {
+            if let Some((line_number, z)) = self.pending_z_layer.take() {
+                let layer = self.z_layers.len() as u32 + 1;
+
+                self.z_layers.push((line_number, GCodeAnnotation::Layer { layer, z: Some(z) }));
+                self.layer_z = Some(z);
+            }
+        }
+    }
+
+    fn push_comment(&mut self, line_number: u64, comment: &str) {
+        if comment.starts_with("LAYER:") || comment.starts_with("LAYER_CHANGE") {
+            let layer = self.comment_layers.len() as u32 + 1;
+
+            self.comment_layers.push((line_number, GCodeAnnotation::Layer { layer, z: None }));
+            self.awaiting_comment_z = true;
+        } else if let Some(z) = comment.strip_prefix("Z:") {
+            // PrusaSlicer adds the layer height after the layer change comment
+            if let Ok(z) = z.trim().parse() {
+                self.set_comment_z(z);
+            }
+        }
+    }
+
+    fn set_comment_z(&mut self, z: f32) {
+        if !self.awaiting_comment_z {
+            return;
+        }
+
+        if let Some((_, GCodeAnnotation::Layer { z: layer_z, .. })) = self.comment_layers.last_mut() {
+            *layer_z = Some(z);
+        }
+
+        self.awaiting_comment_z = false;
+    }
+
+    /// Returns the detected layer annotations
+    pub fn finish(self) -> Vec<(u64, GCodeAnnotation)> {
+        if self.comment_layers.is_empty() {
+            self.z_layers
+        } else {
+            self.comment_layers
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_layers(gcodes: &[&str]) -> Vec<(u64, u32, Option<f32>)> {
+        let mut detector = LayerDetector::default();
+
+        for (line_number, gcode) in gcodes.iter().enumerate() {
+            detector.push_line(line_number as u64, gcode);
+        }
+
+        detector.finish()
+            .into_iter()
+            .map(|(line_number, annotation)| match annotation {
+                GCodeAnnotation::Layer { layer, z } => (line_number, layer, z),
+                annotation => panic!("Unexpected annotation: {:?}", annotation),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_layer_change_comments() {
+        let layers = detect_layers(&[
+            ";FLAVOR:Marlin",
+            ";LAYER:0",
+            "G0 F3600 X10 Y10 Z0.2",
+            "G1 X20 Y10 E1",
+            ";LAYER_CHANGE",
+            ";Z:0.4",
+            "G1 Z0.4",
+            "G1 X10 Y10 E2",
+        ]);
+
+        assert_eq!(layers, vec![
+            (1, 1, Some(0.2)),
+            (4, 2, Some(0.4)),
+        ]);
+    }
+
+    #[test]
+    fn detects_z_increases_without_layer_comments() {
+        let layers = detect_layers(&[
+            "G28",
+            "G1 Z5 F600",
+            "G1 Z0.2",
+            "G1 X20 Y10 E1",
+            // A Z hop is not a new layer
+            "G1 Z0.6",
+            "G1 X30 Y30",
+            "G1 Z0.2",
+            "G1 X20 Y20 E2",
+            "G1 Z0.4 ; next layer",
+            "G1 X10 Y10 E3",
+        ]);
+
+        assert_eq!(layers, vec![
+            (2, 1, Some(0.2)),
+            (8, 2, Some(0.4)),
+        ]);
+    }
+}
@@ -2,8 +2,14 @@
 #[macro_use] extern crate nanoid;
 #[macro_use] extern crate derive_new;
 
+mod authorize;
+
 pub mod mutations;
 pub use mutations::PrintQueueMutation;
+pub use mutations::add_parts_to_print_queue_mutation::{
+    add_parts_to_print_queue,
+    NewPart,
+};
 
 pub mod package;
 
@@ -13,12 +19,21 @@ pub use part::part_query_resolvers::PartQuery;
 pub mod print;
 pub use teg_common::paths;
 
+mod farm_scheduler;
+pub use farm_scheduler::FarmScheduler;
+
+mod layer_detector;
+mod object_detector;
+
 mod insert_print;
 pub use insert_print::{
     insert_print,
     compile_print_file,
 };
 
+mod print_time_estimator;
+pub use print_time_estimator::PrintTimeEstimator;
+
 pub mod machine_print_queue;
 
 mod print_queue;
@@ -28,7 +43,10 @@ pub mod print_queue_machine_hooks;
 
 mod resolvers;
 pub use resolvers::print_queue_query_resolvers::PrintQueueQuery;
+pub use resolvers::print_queue_subscription_resolvers::PrintQueueSubscription;
+pub use resolvers::print_history_query_resolvers::PrintHistoryQuery;
 
+pub mod slicer;
 
 mod task_from_gcodes;
 pub use task_from_gcodes::{
@@ -81,7 +81,7 @@ impl Record for MachinePrintQueue {
     {
         let (json, previous_version) = self.prep_for_update()?;
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE machine_print_queues
                 SET
@@ -104,9 +104,9 @@ impl Record for MachinePrintQueue {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
@@ -17,9 +17,11 @@ use eyre::{
 };
 use teg_json_store::Record;
 
+use teg_auth::{AuthContext, Permission};
 use crate::{
     PrintQueue,
-    part::{ Part, PartTemplate },
+    authorize::authorize_packages,
+    part::{ Part, PartRequirements, PartTemplate, detect_file_max_position },
     package::Package,
 };
 
@@ -40,6 +42,12 @@ struct AddPartsToPrintQueuePartInput {
     name: String,
     // content: String,
     file: async_graphql::Upload,
+    /// Only print the part on machines with all of these tags
+    #[graphql(default)]
+    required_tags: Vec<String>,
+    /// Only print the part on machines with all of these materials loaded
+    #[graphql(name="requiredMaterialIDs", default)]
+    required_material_ids: Vec<ID>,
 }
 
 #[derive(async_graphql::InputObject)]
@@ -49,7 +57,7 @@ struct AddStarredPackagesToPrintQueueInput {
     package_ids: Vec<ID>,
 }
 
-async fn add_to_print_queue(
+pub(crate) async fn add_to_print_queue(
     db: &crate::Db,
     start: std::time::Instant,
     package: Package,
@@ -89,6 +97,97 @@ async fn add_to_print_queue(
     Ok(package)
 }
 
+/// A GCode file saved to the parts directory that is to be added to a print queue
+pub struct NewPart {
+    pub id: crate::DbId,
+    pub name: String,
+    pub file_path: String,
+    pub required_tags: Vec<String>,
+    pub required_material_ids: Vec<crate::DbId>,
+}
+
+impl NewPart {
+    /// Creates a NewPart and the parts directory. The part's GCode should be saved to file_path
+    /// before it is added to the print queue.
+    pub async fn create(name: String) -> Result<Self> {
+        let part_dir = crate::paths::var().join("parts");
+        fs::create_dir_all(&part_dir).await?;
+
+        let id = nanoid!(11);
+        let file_path = part_dir.join(format!(
+            "part_{}.gcode",
+            id.to_string(),
+        )).into_os_string().into_string().unwrap();
+
+        Ok(Self {
+            id,
+            name,
+            file_path,
+            required_tags: vec![],
+            required_material_ids: vec![],
+        })
+    }
+}
+
+/// Adds a new package to the print queue containing the given part files
+pub async fn add_parts_to_print_queue(
+    db: &crate::Db,
+    print_queue_id: &crate::DbId,
+    name: String,
+    new_parts: Vec<NewPart>,
+) -> Result<Package> {
+    let start = std::time::Instant::now();
+
+    let print_queue = PrintQueue::get(
+        db,
+        print_queue_id,
+        false,
+    ).await?;
+
+    let package = Package::new(
+        print_queue.id.clone(),
+        None,
+        name,
+        1,
+    );
+    let package_id = package.id.clone();
+
+    let parts = new_parts
+        .into_iter()
+        .enumerate()
+        .map(move |(index, new_part)| {
+            let package_id = package_id.clone();
+
+            async move {
+                let requirements = PartRequirements {
+                    material_ids: new_part.required_material_ids,
+                    tags: new_part.required_tags,
+                    max_position: detect_file_max_position(new_part.file_path.clone()).await?,
+                };
+
+                let part = Part {
+                    id: new_part.id,
+                    version: 0,
+                    created_at: Utc::now(),
+                    deleted_at: None,
+                    package_id,
+                    name: new_part.name,
+                    position: index as i64,
+                    quantity: 1,
+                    file_path: new_part.file_path,
+                    based_on: None,
+                    requirements,
+                };
+
+                Ok(part) as eyre::Result<Part>
+            }
+        });
+
+    let parts = try_join_all(parts).await?;
+
+    add_to_print_queue(db, start, package, parts).await
+}
+
 #[async_graphql::Object]
 impl AddPartsToPrintQueueMutation {
     /// create a Package of parts for printing from the content and fileName of a file upload.
@@ -97,42 +196,18 @@ impl AddPartsToPrintQueueMutation {
         ctx: &'ctx Context<'_>,
         input: AddPartsToPrintQueueInput,
     ) -> FieldResult<Package> {
-        let start = std::time::Instant::now();
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_print_queue(&input.print_queue_id, Permission::ManageQueue)?;
 
         let db: &crate::Db = ctx.data()?;
 
         async move {
-            let part_dir = crate::paths::var().join("parts");
-            fs::create_dir_all(&part_dir).await?;
-
-            let print_queue = PrintQueue::get(
-                db,
-                &input.print_queue_id,
-                false,
-            ).await?;
-
-            let package = Package::new(
-                print_queue.id.clone(),
-                None,
-                input.name,
-                1,
-            );
-            let package_id = package.id.clone();
-
-            // Create parts from the uploaded files
-            let parts = input.parts
+            // Save the uploaded files to the parts directory
+            let new_parts = input.parts
                 .into_iter()
-                .enumerate()
-                .map(move |(index, part_input)| {
-                    let package_id = package_id.clone();
-                    let part_dir = part_dir.clone();
-
+                .map(move |part_input| {
                     async move {
-                        let part_id = nanoid!(11);
-                        let file_path = part_dir.clone().join(format!(
-                            "part_{}.gcode",
-                            part_id.to_string(),
-                        )).into_os_string().into_string().unwrap();
+                        let mut new_part = NewPart::create(part_input.name).await?;
 
                         let tmp_file = part_input.file.value(&ctx)?.content;
 
@@ -147,30 +222,28 @@ impl AddPartsToPrintQueueMutation {
                             None,
                             &tmp_file_path[..],
                             None,
-                            &file_path[..],
+                            &new_part.file_path[..],
                             nix::unistd::LinkatFlags::SymlinkFollow,
                         )?;
 
-                        let part = Part {
-                            id: part_id,
-                            version: 0,
-                            created_at: Utc::now(),
-                            deleted_at: None,
-                            package_id,
-                            name: part_input.name,
-                            position: index as i64,
-                            quantity: 1,
-                            file_path,
-                            based_on: None,
-                        };
-
-                        Ok(part) as eyre::Result<Part>
+                        new_part.required_tags = part_input.required_tags;
+                        new_part.required_material_ids = part_input.required_material_ids
+                            .into_iter()
+                            .map(|id| id.0)
+                            .collect();
+
+                        Ok(new_part) as eyre::Result<NewPart>
                     }
                 });
 
-            let parts = try_join_all(parts).await?;
+            let new_parts = try_join_all(new_parts).await?;
 
-            add_to_print_queue(db, start, package, parts).await
+            add_parts_to_print_queue(
+                db,
+                &input.print_queue_id.0,
+                input.name,
+                new_parts,
+            ).await
         }
         // log the backtrace which is otherwise lost by FieldResult
         .await
@@ -188,6 +261,9 @@ impl AddPartsToPrintQueueMutation {
     ) -> FieldResult<Vec<Package>> {
         let start = std::time::Instant::now();
 
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
 
         async move {
@@ -199,6 +275,9 @@ impl AddPartsToPrintQueueMutation {
                 .map(|id| id.0)
                 .collect::<Vec<_>>();
 
+            // Starred packages are copied into their own print queues
+            authorize_packages(db, auth, &pkg_template_ids, Permission::ManageQueue).await?;
+
             let pkg_templates = Package::get_by_ids(
                 db,
                 &pkg_template_ids,
@@ -251,6 +330,7 @@ impl AddPartsToPrintQueueMutation {
                                     part_id: part_template.id,
                                     package_id: pkg_template.id.clone(),
                                 }),
+                                requirements: part_template.requirements,
                             }
                         })
                         .collect::<Vec<_>>();
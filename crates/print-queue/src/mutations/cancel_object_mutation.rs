@@ -0,0 +1,76 @@
+use eyre::{
+    eyre,
+    // Context as _,
+};
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    MachineMap,
+    machine::messages::CancelObject,
+    task::Task,
+};
+use crate::authorize::authorize_task;
+
+#[derive(async_graphql::InputObject, Debug)]
+struct CancelObjectInput {
+    #[graphql(name="taskID")]
+    task_id: ID,
+    /// The ID of one of the print's objects
+    #[graphql(name="objectID")]
+    object_id: ID,
+}
+
+#[derive(Default)]
+pub struct CancelObjectMutation;
+
+#[async_graphql::Object]
+impl CancelObjectMutation {
+    /// Stops printing an object on a multi-part plate while the rest of the plate continues
+    /// printing.
+    async fn cancel_object<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: CancelObjectInput,
+    ) -> FieldResult<Task> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &input.task_id,
+                Permission::ControlMachine,
+            ).await?;
+
+            let object = input.object_id.parse::<u32>()
+                .map_err(|_| eyre!("Invalid object ID: {:?}", input.object_id.0))?;
+
+            let machine = machines.get(&(&task.machine_id).into())
+                .ok_or_else(||
+                    eyre!("machine (ID: {}) not found for object cancellation", task.machine_id)
+                )?;
+
+            let msg = CancelObject {
+                task_id: task.id.clone(),
+                object,
+            };
+
+            machine.call(msg).await?
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+}
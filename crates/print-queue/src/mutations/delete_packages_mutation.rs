@@ -12,6 +12,8 @@ use teg_json_store::{
     Record,
     JsonRow,
 };
+use teg_common::into_field_error;
+use teg_auth::{AuthContext, Permission};
 use teg_machine::{MachineHooksList, MachineMap, machine::messages::{GetData, StopMachine}, task::{
         Task,
         TaskStatus,
@@ -19,6 +21,7 @@ use teg_machine::{MachineHooksList, MachineMap, machine::messages::{GetData, Sto
     }};
 
 use crate::{
+    authorize::authorize_packages,
     package::Package,
 };
 
@@ -47,6 +50,9 @@ impl DeletePackagesMutation {
         ctx: &'ctx async_graphql::Context<'_>,
         input: DeletePackagesInput,
     ) -> FieldResult<DeletedPackages> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
         let machine_hooks: &MachineHooksList = ctx.data()?;
 
@@ -59,11 +65,18 @@ impl DeletePackagesMutation {
             let mut all_packages_parts = vec![];
             let mut tx = db.begin().await?;
 
+            let package_ids = input.package_ids
+                .into_iter()
+                .map(|id| id.0)
+                .collect::<Vec<_>>();
+
+            authorize_packages(&mut tx, auth, &package_ids, Permission::ManageQueue).await?;
+
             // Verify the package exists
             let mut packages = Package::get_by_ids(
                 &mut tx,
-                &input.package_ids.into_iter().map(|id| id.0).collect(),
-            false,
+                &package_ids,
+                false,
             ).await?;
 
             for package in packages.iter_mut() {
@@ -138,7 +151,7 @@ impl DeletePackagesMutation {
         .await
         .map_err(|err| {
             warn!("{:?}", err);
-            err.into()
+            into_field_error(err)
         })
     }
 }
@@ -12,6 +12,8 @@ use teg_json_store::{
     Record,
     JsonRow,
 };
+use teg_common::into_field_error;
+use teg_auth::{AuthContext, Permission};
 use teg_machine::{MachineHooksList, MachineMap, machine::messages::{GetData, StopMachine}, task::{
         Task,
         TaskStatus,
@@ -19,6 +21,7 @@ use teg_machine::{MachineHooksList, MachineMap, machine::messages::{GetData, Sto
     }};
 
 use crate::{
+    authorize::authorize_parts,
     part::Part,
 };
 
@@ -45,6 +48,9 @@ impl DeletePartsMutation {
         ctx: &'ctx async_graphql::Context<'_>,
         input: DeletePartsInput,
     ) -> FieldResult<DeletedParts> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
         let machine_hooks: &MachineHooksList = ctx.data()?;
 
@@ -59,6 +65,8 @@ impl DeletePartsMutation {
                 .map(|id| id.0.clone())
                 .collect::<Vec<_>>();
 
+            authorize_parts(&mut tx, auth, &part_ids, Permission::ManageQueue).await?;
+
             // Verify the parts exist
             let parts = Part::get_by_ids(
                 &mut tx,
@@ -135,7 +143,7 @@ impl DeletePartsMutation {
         .await
         .map_err(|err| {
             warn!("{:?}", err);
-            err.into()
+            into_field_error(err)
         })
     }
 }
@@ -8,7 +8,7 @@ use async_graphql::{ID, Context, FieldResult};
 use xactor::Actor as _;
 use teg_json_store::Record;
 
-use teg_auth::AuthContext;
+use teg_auth::{AuthContext, Permission};
 use teg_machine::{MachineMap, machine::{events::TaskSettled, messages::SpoolTask}, task::{Task, TaskStatus}};
 use teg_macros::AnyMacro;
 
@@ -73,7 +73,7 @@ impl ExecGCodesMutation {
         let db: &crate::Db = ctx.data()?;
         let auth: &AuthContext = ctx.data()?;
 
-        let _ = auth.require_authorized_user()?;
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
 
         let machines: &MachineMap = ctx.data()?;
         let machines = machines.load();
@@ -1,6 +1,9 @@
 pub mod add_parts_to_print_queue_mutation;
 use add_parts_to_print_queue_mutation::AddPartsToPrintQueueMutation;
 
+pub mod cancel_object_mutation;
+use cancel_object_mutation::CancelObjectMutation;
+
 pub mod delete_packages_mutation;
 use delete_packages_mutation::DeletePackagesMutation;
 
@@ -16,6 +19,15 @@ use pause_print_mutation::PausePrintMutation;
 pub mod resume_print_mutation;
 use resume_print_mutation::ResumePrintMutation;
 
+pub mod recover_print_mutation;
+use recover_print_mutation::RecoverPrintMutation;
+
+pub mod scheduled_action_mutations;
+use scheduled_action_mutations::ScheduledActionMutation;
+
+pub mod sd_card_mutations;
+use sd_card_mutations::SDCardMutation;
+
 pub mod set_part_positions_mutation;
 use set_part_positions_mutation::SetPartPositionsMutation;
 
@@ -34,11 +46,15 @@ use slice_mutation::SliceMutation;
 #[derive(async_graphql::MergedObject, Default)]
 pub struct PrintQueueMutation(
     AddPartsToPrintQueueMutation,
+    CancelObjectMutation,
     DeletePackagesMutation,
     DeletePartsMutation,
     ExecGCodesMutation,
     PausePrintMutation,
     ResumePrintMutation,
+    RecoverPrintMutation,
+    ScheduledActionMutation,
+    SDCardMutation,
     SetPartPositionsMutation,
     SetPartQuantityMutation,
     PrintMutation,
@@ -10,9 +10,15 @@ use async_graphql::{
 };
 use machine::messages::{GetData, PauseTask};
 use teg_json_store::Record;
-use teg_machine::{MachineMap, machine, task::Task};
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{MachineMap, machine::{self, Machine}, task::Task};
 
-use crate::{part::Part, resolvers::print_resolvers::Print, task_from_hook};
+use crate::{
+    authorize::authorize_task,
+    part::Part,
+    resolvers::print_resolvers::Print,
+    task_from_hook,
+};
 
 #[derive(Default)]
 pub struct PausePrintMutation;
@@ -26,62 +32,28 @@ impl PausePrintMutation {
         #[graphql(name="taskID")]
         task_id: ID,
     ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
         let db: &crate::Db = ctx.data()?;
 
         let machines: &MachineMap = ctx.data()?;
         let machines = machines.load();
 
         async move {
-            let task = Task::get(db, &task_id, false).await?;
-            let part_id = task.part_id
-                .as_ref()
-                .ok_or_else(|| eyre!("Task is not a print"))?
-                .into();
+            let task = authorize_task(
+                db,
+                auth,
+                &task_id,
+                Permission::ControlMachine,
+            ).await?;
 
             let machine = machines.get(&(&task.machine_id).into())
                 .ok_or_else(||
                     eyre!("machine (ID: {}) not found for print pause", task.machine_id)
                 )?;
 
-            let config = machine.call(GetData).await??.config;
-            let core_plugin = config.core_plugin()?;
-
-            let gcodes = config.toolheads
-                .iter()
-                .map(|toolhead| {
-                    serde_json::json!({
-                        "moveBy": {
-                            "distances": {"e0": -toolhead.model.pause_retraction_distance},
-                            "feedrate": toolhead.model.retraction_speed,
-                        },
-                    }).to_string()
-                })
-                .chain(vec![
-                    core_plugin.model.pause_hook.clone()
-                ])
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            let pause_hook = task_from_hook(
-                &task.machine_id,
-                machine.clone(),
-                &gcodes,
-            ).await?;
-
-            // Pause the task and spool the pause hook
-            let msg = PauseTask {
-                task_id: task.id.clone(),
-                pause_hook,
-            };
-            let task = machine.call(msg).await??;
-
-            let part = Part::get(db, &part_id, true).await?;
-
-            Result::<_>::Ok(Print {
-                id: (&task.id).into(),
-                task,
-                part,
-            })
+            pause_print(db, machine, task).await
         }
         // log the backtrace which is otherwise lost by FieldResult
         .await
@@ -91,3 +63,56 @@ impl PausePrintMutation {
         })
     }
 }
+
+/// Pauses the print and spools the pause hook. Used both by the pausePrint mutation and to pause
+/// prints at the firmware's request (eg. when the filament runs out).
+pub async fn pause_print(
+    db: &crate::Db,
+    machine: &xactor::Addr<Machine>,
+    task: Task,
+) -> Result<Print> {
+    let part_id = task.part_id
+        .as_ref()
+        .ok_or_else(|| eyre!("Task is not a print"))?
+        .into();
+
+    let config = machine.call(GetData).await??.config;
+    let core_plugin = config.core_plugin()?;
+
+    let gcodes = config.toolheads
+        .iter()
+        .map(|toolhead| {
+            serde_json::json!({
+                "moveBy": {
+                    "distances": {"e0": -toolhead.model.pause_retraction_distance},
+                    "feedrate": toolhead.model.retraction_speed,
+                },
+            }).to_string()
+        })
+        .chain(vec![
+            core_plugin.model.pause_hook.clone()
+        ])
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let pause_hook = task_from_hook(
+        &task.machine_id,
+        machine.clone(),
+        &gcodes,
+    ).await?;
+
+    // Pause the task and spool the pause hook
+    let msg = PauseTask {
+        task_id: task.id.clone(),
+        pause_hook,
+    };
+    let task = machine.call(msg).await??;
+
+    let part = Part::get(db, &part_id, true).await?;
+
+    Ok(Print {
+        id: (&task.id).into(),
+        task,
+        part: Some(part),
+    })
+}
@@ -8,10 +8,12 @@ use async_graphql::{
     FieldResult,
 };
 use teg_json_store::Record as _;
+use teg_auth::{AuthContext, Permission};
 use teg_machine::{MachineHooksList, MachineMap};
 use crate::{part::Part, resolvers::print_resolvers::Print};
 
 use crate::insert_print;
+use crate::authorize::authorize_parts;
 
 #[derive(Default)]
 pub struct PrintMutation;
@@ -36,6 +38,9 @@ impl PrintMutation {
         ctx: &'ctx async_graphql::Context<'_>,
         input: PrintInput,
     ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
+
         let db: &crate::Db = ctx.data()?;
 
         let machines: &MachineMap = ctx.data()?;
@@ -48,6 +53,8 @@ impl PrintMutation {
                     eyre!("machine ({:?}) not found for spool job file", input.machine_id)
                 )?;
 
+            authorize_parts(db, auth, &[input.part_id.to_string()], Permission::View).await?;
+
             let mut tx = db.begin().await?;
 
             let part = Part::get(
@@ -0,0 +1,187 @@
+use chrono::prelude::*;
+use eyre::{
+    Result,
+    eyre,
+    // Context as _,
+};
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use machine::messages::{GetData, RecoverTask};
+use teg_json_store::Record;
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    MachineMap,
+    machine::{self, MachineStatus},
+    task::{
+        GCodeFile,
+        PrintRecoveryState,
+        Task,
+        TaskContent,
+        TaskStatus,
+    },
+};
+
+use crate::{
+    authorize::authorize_task,
+    part::Part,
+    resolvers::print_resolvers::Print,
+    task_from_hook,
+};
+
+#[derive(Default)]
+pub struct RecoverPrintMutation;
+
+#[async_graphql::Object]
+impl RecoverPrintMutation {
+    /// Resumes an errored print from where it stopped after a driver crash, host crash or power
+    /// loss. The machine's heaters and fans are restored, X and Y are re-homed and the print
+    /// continues from the last line acknowledged by the machine.
+    ///
+    /// Only a machine's most recent print can be recovered. Prints cannot be recovered on machines
+    /// configured with "No motion before homing" (eg. Marlin's NO_MOTION_BEFORE_HOMING) because Z
+    /// is not re-homed.
+    async fn recover_print<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(name="taskID")]
+        task_id: ID,
+    ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &task_id,
+                Permission::ControlMachine,
+            ).await?;
+            let part_id = task.part_id
+                .as_ref()
+                .ok_or_else(|| eyre!("Task is not a print"))?
+                .into();
+
+            if !matches!(task.status, TaskStatus::Errored(_)) {
+                Err(eyre!("Cannot recover a print that is {}", task.status.to_db_str()))?;
+            }
+
+            let file_path = match &task.content {
+                TaskContent::FilePath(file_path) => file_path.clone(),
+                _ => Err(eyre!(
+                    "Cannot recover print. Only a machine's most recent print can be recovered."
+                ))?,
+            };
+
+            let machine = machines.get(&(&task.machine_id).into())
+                .ok_or_else(||
+                    eyre!("machine (ID: {}) not found for print recovery", task.machine_id)
+                )?;
+
+            let machine_data = machine.call(GetData).await??;
+
+            if !matches!(machine_data.status, MachineStatus::Ready) {
+                Err(eyre!("Cannot recover print while machine is: {:?}", machine_data.status))?;
+            }
+
+            if machine_data.config.get_controller().model.no_motion_before_homing {
+                Err(eyre!(
+                    "Cannot recover print. The machine's firmware does not allow moves before \
+                    homing and Z cannot be re-homed without hitting the print."
+                ))?;
+            }
+
+            // The driver and the server both record the file line number of the last line
+            // acknowledged by the machine. The driver only saves it's state every few seconds so
+            // the lines acknowledged since it was saved are replayed from the GCode file.
+            let saved_state = PrintRecoveryState::load(
+                &PrintRecoveryState::path(&task.machine_id),
+            )?
+                .filter(|state| state.task_id == task.id);
+
+            let recovery_state = match (saved_state, task.despooled_line_number) {
+                // The saved state can be ahead of the server if the server did not receive the
+                // driver's last feedback (eg. after a host crash)
+                (Some(saved_state), despooled_line_number)
+                    if saved_state.despooled_line_number >= despooled_line_number =>
+                {
+                    saved_state
+                }
+                (saved_state, Some(despooled_line_number)) => {
+                    let mut state = saved_state
+                        .unwrap_or_else(|| PrintRecoveryState::new(task.id.clone()));
+
+                    // Replaying the GCode is a slow and IO intensive task so we run it in a
+                    // blocking thread to prevent it from blocking other async tasks.
+                    async_std::task::spawn_blocking(move || {
+                        let gcode_file = GCodeFile::open_at_line(
+                            file_path,
+                            state.next_line_number(),
+                        )?;
+
+                        state.replay(gcode_file, despooled_line_number)?;
+
+                        Result::<_>::Ok(state)
+                    }).await?
+                }
+                (_, None) => {
+                    Err(eyre!("Cannot recover print. The print stopped before it started."))?
+                }
+            };
+
+            let recovery_hook = task_from_hook(
+                &task.machine_id,
+                machine.clone(),
+                &recovery_state.recovery_gcodes().join("\n"),
+            ).await?;
+
+            let mut tx = db.begin().await?;
+            // Re-fetch the task within the transaction
+            let mut task = Task::get(&mut tx, &task_id, false).await?;
+
+            let errored_at = if let TaskStatus::Errored(errored) = &task.status {
+                errored.errored_at
+            } else {
+                Err(eyre!("Cannot recover a print that is {}", task.status.to_db_str()))?
+            };
+
+            // Time spent stopped is excluded from the print's duration the same way as a pause
+            task.time_paused += (Utc::now() - errored_at).to_std()?;
+            task.despooled_line_number = recovery_state.despooled_line_number;
+            task.status = TaskStatus::Created(Default::default());
+
+            task.update(&mut tx).await?;
+            recovery_hook.insert_no_rollback(&mut tx).await?;
+
+            tx.commit().await?;
+
+            // Spool the recovery hook and then the task
+            let msg = RecoverTask {
+                task,
+                recovery_hook,
+            };
+            let task = machine.call(msg).await??;
+
+            info!("Recovering Print #{} from line {:?}", task.id, task.despooled_line_number);
+
+            let part = Part::get(db, &part_id, true).await?;
+
+            Result::<_>::Ok(Print {
+                id: (&task.id).into(),
+                task,
+                part: Some(part),
+            })
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+}
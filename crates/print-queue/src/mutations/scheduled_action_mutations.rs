@@ -0,0 +1,183 @@
+use eyre::{
+    Result,
+    eyre,
+    // Context as _,
+};
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    MachineMap,
+    MachineMapLocal,
+    machine::messages::SetScheduledActions,
+    task::{ActionTrigger, ScheduledAction, Task},
+};
+use crate::authorize::authorize_task;
+
+#[derive(async_graphql::InputObject, Debug)]
+struct ScheduleTaskActionInput {
+    #[graphql(name="taskID")]
+    task_id: ID,
+    /// Run the action at the start of this layer. Layers are numbered from 1.
+    layer: Option<u32>,
+    /// Run the action at the start of the first layer at or above this Z height (in mm)
+    height: Option<f32>,
+    /// Pause the print before running the GCodes
+    #[graphql(default)]
+    pause: bool,
+    /// GCodes to run at the layer (eg. a colour change)
+    #[graphql(default)]
+    gcodes: Vec<String>,
+}
+
+#[derive(async_graphql::InputObject, Debug)]
+struct RemoveTaskActionInput {
+    #[graphql(name="taskID")]
+    task_id: ID,
+    #[graphql(name="actionID")]
+    action_id: ID,
+}
+
+#[derive(Default)]
+pub struct ScheduledActionMutation;
+
+#[async_graphql::Object]
+impl ScheduledActionMutation {
+    /// Schedules a pause and/or GCodes to run when a print reaches a layer or height.
+    async fn schedule_task_action<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: ScheduleTaskActionInput,
+    ) -> FieldResult<Task> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &input.task_id,
+                Permission::ControlMachine,
+            ).await?;
+
+            let trigger = match (input.layer, input.height) {
+                (Some(layer), None) => ActionTrigger::Layer(layer),
+                (None, Some(height)) => ActionTrigger::Height(height),
+                _ => Err(eyre!("Either a layer or a height is required (but not both)"))?,
+            };
+
+            // Macros are compiled before the task is spooled so they cannot be inserted by the
+            // driver
+            let gcodes = input.gcodes
+                .iter()
+                .flat_map(|gcodes| gcodes.lines())
+                .map(|gcode| gcode.trim().to_string())
+                .filter(|gcode| !gcode.is_empty())
+                .collect::<Vec<_>>();
+
+            if let Some(gcode) = gcodes.iter().find(|gcode| {
+                gcode.starts_with('{') || gcode.starts_with('!')
+            }) {
+                Err(eyre!("Macros cannot be scheduled, only GCodes: {:?}", gcode))?;
+            }
+
+            if !input.pause && gcodes.is_empty() {
+                Err(eyre!("Scheduled actions must pause the print or run GCodes"))?;
+            }
+
+            let line_number = task.trigger_line_number(&trigger)
+                .ok_or_else(|| eyre!("{:?} was not found in the print", trigger))?;
+
+            let despooled = task.despooled_line_number
+                .map(|n| n >= line_number)
+                .unwrap_or(false);
+
+            if despooled {
+                Err(eyre!("{:?} has already been printed", trigger))?;
+            }
+
+            let mut scheduled_actions = task.scheduled_actions.clone();
+            scheduled_actions.push(ScheduledAction {
+                id: nanoid!(11),
+                trigger,
+                line_number,
+                pause: input.pause,
+                gcodes,
+            });
+
+            set_scheduled_actions(&machines, task, scheduled_actions).await
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Removes a scheduled pause or GCodes from a print.
+    async fn remove_task_action<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: RemoveTaskActionInput,
+    ) -> FieldResult<Task> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &input.task_id,
+                Permission::ControlMachine,
+            ).await?;
+
+            let scheduled_actions = task.scheduled_actions
+                .iter()
+                .filter(|action| action.id != input.action_id.0)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if scheduled_actions.len() == task.scheduled_actions.len() {
+                Err(eyre!("Scheduled action (ID: {}) not found", input.action_id.0))?;
+            }
+
+            set_scheduled_actions(&machines, task, scheduled_actions).await
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+}
+
+async fn set_scheduled_actions(
+    machines: &MachineMapLocal,
+    task: Task,
+    scheduled_actions: Vec<ScheduledAction>,
+) -> Result<Task> {
+    let machine = machines.get(&(&task.machine_id).into())
+        .ok_or_else(||
+            eyre!("machine (ID: {}) not found for task #{}", task.machine_id, task.id)
+        )?;
+
+    let msg = SetScheduledActions {
+        task_id: task.id.clone(),
+        scheduled_actions,
+    };
+
+    machine.call(msg).await?
+}
@@ -0,0 +1,415 @@
+use chrono::prelude::*;
+use async_std::fs;
+use eyre::{
+    Result,
+    eyre,
+    // Context as _,
+};
+use async_graphql::{
+    ID,
+    Context,
+    FieldResult,
+};
+use teg_json_store::Record;
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    MachineMap,
+    machine::messages::{
+        DeleteSDCardFile,
+        GetData,
+        ListSDCardFiles,
+        PauseSDCardPrint,
+        SpoolTask,
+        StartSDCardPrint,
+    },
+    task::{Task, TaskContent},
+};
+
+use crate::{
+    authorize::{authorize_parts, authorize_task},
+    compile_print_file,
+    insert_print::PrintMetaData,
+    part::Part,
+    print_time_estimator::PrintTimeEstimator,
+    resolvers::print_resolvers::Print,
+};
+
+#[derive(async_graphql::InputObject, Debug)]
+struct ListSDCardFilesInput {
+    #[graphql(name="machineID")]
+    machine_id: ID,
+}
+
+#[derive(async_graphql::InputObject, Debug)]
+struct DeleteSDCardFileInput {
+    #[graphql(name="machineID")]
+    machine_id: ID,
+    filename: String,
+}
+
+#[derive(async_graphql::InputObject, Debug)]
+struct UploadPartToSDCardInput {
+    #[graphql(name="machineID")]
+    machine_id: ID,
+    #[graphql(name="partID")]
+    part_id: ID,
+    /// The name of the file on the SD card. Most firmwares require 8.3 filenames (eg. PART.GCO).
+    filename: String,
+}
+
+#[derive(async_graphql::InputObject, Debug)]
+struct StartSDCardPrintInput {
+    #[graphql(name="machineID")]
+    machine_id: ID,
+    filename: String,
+}
+
+fn validate_filename(filename: &str) -> Result<()> {
+    if filename.is_empty() || filename.contains(char::is_whitespace) {
+        Err(eyre!("Invalid SD card filename: {:?}", filename))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct SDCardMutation;
+
+#[async_graphql::Object]
+impl SDCardMutation {
+    /// Requests the list of files on the machine's SD card. Once the machine responds the files
+    /// are available through the machine's sdCardFiles field.
+    #[graphql(name = "listSDCardFiles")]
+    async fn list_sd_card_files<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: ListSDCardFilesInput,
+    ) -> FieldResult<Option<teg_common::Void>> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::View)?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let machine = machines.get(&input.machine_id)
+                .ok_or_else(|| eyre!("machine ({:?}) not found", input.machine_id))?;
+
+            machine.call(ListSDCardFiles).await??;
+
+            Result::<_>::Ok(None)
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Deletes a file from the machine's SD card
+    #[graphql(name = "deleteSDCardFile")]
+    async fn delete_sd_card_file<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: DeleteSDCardFileInput,
+    ) -> FieldResult<Option<teg_common::Void>> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            validate_filename(&input.filename)?;
+
+            let machine = machines.get(&input.machine_id)
+                .ok_or_else(|| eyre!("machine ({:?}) not found", input.machine_id))?;
+
+            machine.call(DeleteSDCardFile {
+                filename: input.filename,
+            }).await??;
+
+            Result::<_>::Ok(None)
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Writes the part's GCode to a file on the machine's SD card (M28/M29). The upload runs as a
+    /// task so the machine must be idle.
+    #[graphql(name = "uploadPartToSDCard")]
+    async fn upload_part_to_sd_card<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: UploadPartToSDCardInput,
+    ) -> FieldResult<Task> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            validate_filename(&input.filename)?;
+
+            let machine = machines.get(&input.machine_id)
+                .ok_or_else(|| eyre!("machine ({:?}) not found", input.machine_id))?;
+
+            authorize_parts(db, auth, &[input.part_id.to_string()], Permission::View).await?;
+
+            let part = Part::get(db, &input.part_id.0, false).await?;
+
+            let task_id = nanoid!(11);
+            let task_dir = crate::paths::var().join("tasks");
+            let task_file_path = task_dir.join(format!("task_{}.gcode", task_id));
+            fs::create_dir_all(task_dir).await?;
+
+            use teg_macros::CompileInternalMacro;
+
+            let machine_clone = machine.clone();
+            let compile_internal_macro = move |internal_macro| {
+                let machine = machine_clone.clone();
+                async move {
+                    machine.call(CompileInternalMacro(internal_macro)).await?
+                }
+            };
+
+            let read_buffer_size = 1024 * 1024; // 1 MB
+            let write_buffer_size = read_buffer_size;
+
+            // Wrap the part's GCodes in the commands to start and stop writing to the SD card.
+            // Compiling the file is a slow and CPU intensive task so we run it in a blocking thread
+            // to prevent it from blocking other async tasks.
+            let part_file_path = part.file_path.clone();
+            let task_file_path_clone = task_file_path.clone();
+            let filename = input.filename.clone();
+            let PrintMetaData {
+                annotations,
+                total_lines,
+                ..
+            } = async_std::task::spawn_blocking(move || {
+                compile_print_file(
+                    &part_file_path,
+                    task_file_path_clone,
+                    &format!("M28 {}", filename),
+                    &format!("M29 {}", filename),
+                    PrintTimeEstimator::default(),
+                    compile_internal_macro,
+                    read_buffer_size,
+                    write_buffer_size,
+                )
+            }).await?;
+
+            // The upload is not a print so it is not associated with the part
+            let task = Task {
+                id: task_id,
+                version: 0,
+                created_at: Utc::now(),
+                deleted_at: None,
+                machine_id: input.machine_id.0.clone(),
+                part_id: None,
+                despooled_line_number: None,
+                machine_override: false,
+                content: TaskContent::FilePath(task_file_path),
+                annotations,
+                total_lines,
+                estimated_filament_meters: None,
+                estimated_print_time: None,
+                print_time_table: Default::default(),
+                material_ids: vec![],
+                time_blocked: Default::default(),
+                time_paused: Default::default(),
+                status: Default::default(),
+                scheduled_actions: vec![],
+                cancelled_objects: vec![],
+            };
+
+            task.insert(db).await?;
+
+            let task = machine.call(SpoolTask { task }).await??;
+
+            info!("Uploading Part #{} to the SD card as {}", part.id, input.filename);
+
+            Result::<_>::Ok(task)
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Prints a file from the machine's SD card. The print is run by the machine's firmware and
+    /// its progress is tracked in bytes.
+    #[graphql(name = "startSDCardPrint")]
+    async fn start_sd_card_print<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: StartSDCardPrintInput,
+    ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            validate_filename(&input.filename)?;
+
+            let machine = machines.get(&input.machine_id)
+                .ok_or_else(|| eyre!("machine ({:?}) not found", input.machine_id))?;
+
+            let task = Task {
+                id: nanoid!(11),
+                version: 0,
+                created_at: Utc::now(),
+                deleted_at: None,
+                machine_id: input.machine_id.0.clone(),
+                part_id: None,
+                despooled_line_number: None,
+                machine_override: false,
+                content: TaskContent::SDCardFile(input.filename),
+                annotations: vec![],
+                total_lines: 0,
+                estimated_filament_meters: None,
+                estimated_print_time: None,
+                print_time_table: Default::default(),
+                material_ids: vec![],
+                time_blocked: Default::default(),
+                time_paused: Default::default(),
+                status: Default::default(),
+                scheduled_actions: vec![],
+                cancelled_objects: vec![],
+            };
+
+            // Verify the machine is ready before the task is added to the print history
+            machine.call(GetData).await??.status.verify_can_start(&task, false)?;
+
+            task.insert(db).await?;
+
+            let task = machine.call(StartSDCardPrint {
+                task_id: task.id,
+            }).await??;
+
+            Result::<_>::Ok(Print {
+                id: (&task.id).into(),
+                task,
+                part: None,
+            })
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Pauses an SD card print
+    #[graphql(name = "pauseSDCardPrint")]
+    async fn pause_sd_card_print<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(name="taskID")]
+        task_id: ID,
+    ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &task_id,
+                Permission::ControlMachine,
+            ).await?;
+
+            let machine = machines.get(&(&task.machine_id).into())
+                .ok_or_else(||
+                    eyre!("machine (ID: {}) not found for print pause", task.machine_id)
+                )?;
+
+            let task = machine.call(PauseSDCardPrint {
+                task_id: task.id,
+            }).await??;
+
+            Result::<_>::Ok(Print {
+                id: (&task.id).into(),
+                task,
+                part: None,
+            })
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+
+    /// Resumes a paused SD card print
+    #[graphql(name = "resumeSDCardPrint")]
+    async fn resume_sd_card_print<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(name="taskID")]
+        task_id: ID,
+    ) -> FieldResult<Print> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ControlMachine)?;
+
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
+
+        async move {
+            let task = authorize_task(
+                db,
+                auth,
+                &task_id,
+                Permission::ControlMachine,
+            ).await?;
+
+            if !task.status.is_paused() {
+                Err(eyre!("Cannot resume. This print is not paused."))?;
+            }
+
+            let machine = machines.get(&(&task.machine_id).into())
+                .ok_or_else(||
+                    eyre!("machine (ID: {}) not found for print resume", task.machine_id)
+                )?;
+
+            let task = machine.call(StartSDCardPrint {
+                task_id: task.id,
+            }).await??;
+
+            Result::<_>::Ok(Print {
+                id: (&task.id).into(),
+                task,
+                part: None,
+            })
+        }
+        // log the backtrace which is otherwise lost by FieldResult
+        .await
+        .map_err(|err| {
+            warn!("{:?}", err);
+            err.into()
+        })
+    }
+}
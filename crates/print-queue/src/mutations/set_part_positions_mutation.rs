@@ -12,8 +12,11 @@ use teg_json_store::{
     Record,
     JsonRow,
 };
+use teg_common::into_field_error;
 
+use teg_auth::{AuthContext, Permission};
 use crate::{
+    authorize::authorize_parts,
     part::Part,
 };
 
@@ -41,9 +44,19 @@ impl SetPartPositionsMutation {
         ctx: &'ctx Context<'_>,
         input: SetPartPositionsInput,
     ) -> FieldResult<Vec<Part>> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
         let mut tx = db.begin().await?;
 
+        let moved_part_ids = input.parts
+            .iter()
+            .map(|part| part.part_id.to_string())
+            .collect::<Vec<_>>();
+
+        authorize_parts(&mut tx, auth, &moved_part_ids, Permission::ManageQueue).await?;
+
         let parts = sqlx::query_as!(
             JsonRow,
             r#"
@@ -107,7 +120,7 @@ impl SetPartPositionsMutation {
         for (mut part, original_position) in parts.into_iter().zip(original_positions) {
             // Update the database
             if part.position != original_position {
-                part.update(&mut tx).await?;
+                part.update(&mut tx).await.map_err(into_field_error)?;
                 moved_parts.push(part);
             }
         }
@@ -9,11 +9,12 @@ use async_graphql::validators::{IntGreaterThan};
 //     // Result,
 //     // Context as _,
 // };
-use teg_json_store::{
-    Record,
-};
+use teg_json_store::Record;
+use teg_common::into_field_error;
 
+use teg_auth::{AuthContext, Permission};
 use crate::{
+    authorize::authorize_parts,
     part::Part,
 };
 
@@ -38,9 +39,15 @@ impl SetPartQuantityMutation {
         ctx: &'ctx Context<'_>,
         input: SetPartQuantityInput,
     ) -> FieldResult<Part> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
         let mut tx = db.begin().await?;
 
+        authorize_parts(&mut tx, auth, &[input.part_id.to_string()], Permission::ManageQueue)
+            .await?;
+
         let mut part = Part::get(
             &mut tx,
             &input.part_id.0,
@@ -50,7 +57,7 @@ impl SetPartQuantityMutation {
 
         part.quantity = input.quantity;
 
-        part.update(&mut tx).await?;
+        part.update(&mut tx).await.map_err(into_field_error)?;
 
         tx.commit().await?;
 
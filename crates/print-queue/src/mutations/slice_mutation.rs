@@ -1,54 +1,91 @@
 use std::os::unix::prelude::AsRawFd;
 
+use chrono::prelude::*;
 use async_std::fs;
 use eyre::{
     Result,
     eyre,
-    Context as _,
+    // Context as _,
 };
 use async_graphql::{
-    // ID,
+    ID,
     FieldResult,
 };
-// use teg_json_store::Record as _;
-// use teg_machine::{MachineHooksList, MachineMap};
+use teg_json_store::Record as _;
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    MachineMap,
+    machine::messages::GetData,
+};
+
+use crate::{
+    PrintQueue,
+    package::Package,
+    part::{ Part, PartRequirements, detect_file_max_position },
+    slicer::{
+        export_slicing_profile,
+        slice,
+        slicer_for,
+    },
+    mutations::add_parts_to_print_queue_mutation::add_to_print_queue,
+};
 
 #[derive(Default)]
 pub struct SliceMutation;
 
 #[derive(async_graphql::InputObject)]
 struct SliceInput {
-    // #[graphql(name="machineID")]
-    // machine_id: ID,
-    // // TODO: update graphql names to match latest struct fields
-    // // #[field(name="partID")]
-    // #[graphql(name="partID")]
-    // part_id: ID,
+    /// The machine to slice for. The machine's slicer and slicing profile are used to slice the model.
+    #[graphql(name="machineID")]
+    machine_id: ID,
     name: String,
     file: async_graphql::Upload,
+    /// Adds the GCode to the print queue as a new part instead of returning it
+    #[graphql(name="printQueueID")]
+    print_queue_id: Option<ID>,
 }
 
+#[derive(async_graphql::SimpleObject)]
+struct SliceResult {
+    /// The sliced GCode. Null if the GCode was added to the print queue.
+    gcode: Option<String>,
+    /// The package containing the sliced part if the GCode was added to the print queue.
+    package: Option<Package>,
+}
 
 #[async_graphql::Object]
 impl SliceMutation {
-    /// Slices a 3D model on the print server and returns GCode
+    /// Slices a 3D model on the print server and returns GCode or adds it to the print queue
     #[instrument(skip(self, input, ctx))]
     async fn slice<'ctx>(
         &self,
         ctx: &'ctx async_graphql::Context<'_>,
         input: SliceInput,
-    ) -> FieldResult<String> {
-        // let db: &crate::Db = ctx.data()?;
+    ) -> FieldResult<SliceResult> {
+        let start = std::time::Instant::now();
 
-        // let machines: &MachineMap = ctx.data()?;
-        // let machines = machines.load();
-        // let machine_hooks: &MachineHooksList = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize_machine(&input.machine_id, Permission::ManageQueue)?;
+
+        if let Some(print_queue_id) = &input.print_queue_id {
+            auth.authorize_print_queue(print_queue_id, Permission::ManageQueue)?;
+        }
+
+        let db: &crate::Db = ctx.data()?;
+
+        let machines: &MachineMap = ctx.data()?;
+        let machines = machines.load();
 
         async move {
-            // let machine = machines.get(&input.machine_id)
-            //     .ok_or_else(||
-            //         eyre!("machine ({:?}) not found for spool job file", input.machine_id)
-            //     )?;
+            let machine = machines.get(&input.machine_id)
+                .ok_or_else(||
+                    eyre!("machine ({:?}) not found for slicing", input.machine_id)
+                )?;
+
+            let config = machine.call(GetData).await??.config;
+            let core_plugin = config.core_plugin()?;
+
+            let slicer = slicer_for(core_plugin.model.slicer);
 
             // Rename the model file to add the appropriate file extension (eg. ".stl")
             let tmp_model = input.file.value(&ctx)?.content;
@@ -61,58 +98,104 @@ impl SliceMutation {
 
             let tmp_path = crate::paths::var().join("tmp");
 
-            let model_dir = tempfile::tempdir_in(tmp_path)?;
+            let model_dir = tempfile::tempdir_in(&tmp_path)?;
             let model_tmp_name = format!("model.{}", file_ext);
 
-            let model_path = model_dir.path().join(model_tmp_name)
-                .to_str()
-                .map(|s| s.to_string())
-                .ok_or_else(|| eyre!("Non-utf8 file path"))?;
+            let model_path = model_dir.path().join(model_tmp_name);
 
             nix::unistd::linkat(
                 None,
                 &tmp_model_path[..],
                 None,
-                &model_path[..],
+                &model_path,
                 nix::unistd::LinkatFlags::SymlinkFollow,
             )?;
 
-            // Create a temporary directory for the gcode output
-            let gcode_dir = tempfile::TempDir::new()?;
+            let slicing_profile_path = export_slicing_profile(
+                db,
+                &config,
+                &*slicer,
+                model_dir.path(),
+            ).await?;
+
+            // Create a temporary directory for the gcode output. This is on the same file system
+            // as the parts directory so that the GCode can be moved into the print queue.
+            let gcode_dir = tempfile::tempdir_in(&tmp_path)?;
             let gcode_path = gcode_dir.path().join("output.gcode");
 
-            // TODO: Load the slicing profile path
-            let slicing_profile_path = crate::paths::etc().join("CR30.cfg.ini");
-
-            // Run the script
-            let script = format!(
-                "\
-                    taskset --cpu-list 1-1024 belt-engine \
-                        -o {} \
-                        {} \
-                        -c {} \
-                        -s support_enable=True \
-                ",
-                &gcode_path.to_str().ok_or_else(|| eyre!("Non-utf8 file path"))?,
+            slice(
+                &*slicer,
                 &model_path,
-                &slicing_profile_path.to_str().ok_or_else(|| eyre!("Non-utf8 file path"))?,
+                &gcode_path,
+                slicing_profile_path.as_deref(),
+            ).await?;
+
+            let print_queue_id = if let Some(print_queue_id) = input.print_queue_id {
+                print_queue_id
+            } else {
+                let gcode = fs::read_to_string(&gcode_path).await?;
+
+                return Ok(SliceResult {
+                    gcode: Some(gcode),
+                    package: None,
+                })
+            };
+
+            // Add the GCode to the print queue
+            let part_dir = crate::paths::var().join("parts");
+            fs::create_dir_all(&part_dir).await?;
+
+            let print_queue = PrintQueue::get(
+                db,
+                &print_queue_id,
+                false,
+            ).await?;
+
+            let package = Package::new(
+                print_queue.id.clone(),
+                None,
+                input.name.clone(),
+                1,
             );
 
-            info!("Slicing...");
-            info!("Running slicing command: {:?}", script);
-
-            let _output = async_std::process::Command::new("sh")
-                .arg("-c")
-                .arg(script)
-                .output()
-                .await
-                .wrap_err("Slicer error")?;
-
-            let gcode = fs::read_to_string(&gcode_path).await?;
-
-            info!("Slicing... [DONE]");
-
-            Result::<_>::Ok(gcode)
+            let part_id = nanoid!(11);
+            let file_path = part_dir.join(format!(
+                "part_{}.gcode",
+                part_id.to_string(),
+            ));
+
+            fs::rename(&gcode_path, &file_path).await?;
+
+            let file_path = file_path
+                .into_os_string()
+                .into_string()
+                .map_err(|_| eyre!("Non-utf8 file path"))?;
+
+            let max_position = detect_file_max_position(file_path.clone()).await?;
+
+            let part = Part {
+                id: part_id,
+                version: 0,
+                created_at: Utc::now(),
+                deleted_at: None,
+                package_id: package.id.clone(),
+                name: input.name,
+                position: 0,
+                quantity: 1,
+                file_path,
+                based_on: None,
+                requirements: PartRequirements {
+                    max_position,
+                    ..Default::default()
+                },
+            };
+
+            let package = add_to_print_queue(db, start, package, vec![part]).await?;
+
+            Result::<_>::Ok(SliceResult {
+                gcode: None,
+                package: Some(package),
+            })
         }
         // log the backtrace which is otherwise lost by FieldResult
         .await
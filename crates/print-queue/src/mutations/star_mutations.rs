@@ -9,11 +9,11 @@ use eyre::{
     Result,
     // Context as _,
 };
-use teg_json_store::{
-    Record as _,
-};
+use teg_json_store::Record as _;
+use teg_common::into_field_error;
 
-use crate::{package::Package, part::{Part, PartTemplate}};
+use teg_auth::{AuthContext, Permission};
+use crate::{authorize::authorize_packages, package::Package, part::{Part, PartTemplate}};
 
 #[derive(Default)]
 pub struct StarMutations;
@@ -33,10 +33,20 @@ impl StarMutations {
         ctx: &'ctx Context<'_>,
         input: SetStarredInput,
     ) -> FieldResult<Package> {
+        let auth: &AuthContext = ctx.data()?;
+        auth.authorize(Permission::ManageQueue)?;
+
         let db: &crate::Db = ctx.data()?;
         async move {
             let mut tx = db.begin().await?;
 
+            authorize_packages(
+                &mut tx,
+                auth,
+                &[input.package_id.to_string()],
+                Permission::ManageQueue,
+            ).await?;
+
             let mut original_pkg = Package::get(
                 &mut tx,
                 &input.package_id.0,
@@ -111,6 +121,7 @@ impl StarMutations {
                             quantity: original_part.quantity,
                             file_path: original_part.file_path.clone(),
                             based_on: None,
+                            requirements: original_part.requirements.clone(),
                         };
 
                         starred_part.insert_no_rollback(&mut tx).await?;
@@ -135,7 +146,7 @@ impl StarMutations {
             .await
             .map_err(|err| {
                 warn!("{:?}", err);
-                err.into()
+                into_field_error(err)
             })
     }
 }
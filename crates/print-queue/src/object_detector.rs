@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use teg_macros::GCodeAnnotation;
+
+/// Detects the line ranges of each object in a print's GCode from the slicer's object labels:
+///
+/// * `; printing object NAME` and `; stop printing object NAME` (PrusaSlicer, SuperSlicer)
+/// * `;MESH:NAME` (Cura). Each range ends at the next `;MESH:` or `;LAYER:` comment.
+/// * `M486 S<index>` and `M486 A<name>` (Marlin's object cancellation labels). `M486 S-1` ends
+///   the range.
+#[derive(Debug, Default)]
+pub struct ObjectDetector {
+    /// The object index of each name in the order they were found
+    objects: HashMap<String, u32>,
+    /// The names of M486 objects by their M486 index
+    m486_names: HashMap<i64, String>,
+    /// The M486 index of the object range that is currently open
+    m486_index: Option<i64>,
+    /// The name and first line of the object range that is currently open
+    open_range: Option<(String, u64)>,
+    annotations: Vec<(u64, GCodeAnnotation)>,
+}
+
+impl ObjectDetector {
+    /// Checks a GCode line for object labels. line_number is the line's index in the task file.
+    pub fn push_line(&mut self, line_number: u64, gcode: &str) {
+        let gcode = gcode.trim();
+
+        if let Some(comment) = gcode.strip_prefix(';') {
+            let comment = comment.trim();
+
+            if let Some(name) = comment.strip_prefix("printing object ") {
+                self.start_range(line_number, name.trim().to_string());
+            } else if comment.starts_with("stop printing object") {
+                self.end_range(line_number);
+            } else if let Some(name) = comment.strip_prefix("MESH:") {
+                if name == "NONMESH" {
+                    self.end_range(line_number);
+                } else {
+                    self.start_range(line_number, name.to_string());
+                }
+            } else if comment.starts_with("LAYER:") {
+                self.end_range(line_number);
+            }
+        } else if gcode.starts_with("M486") {
+            self.push_m486(line_number, gcode);
+        }
+    }
+
+    fn push_m486(&mut self, line_number: u64, gcode: &str) {
+        let gcode = gcode.split(';').next().unwrap_or("");
+
+        let mut index = None;
+        let mut name = None;
+
+        for word in gcode.split_whitespace().skip(1) {
+            if let Some(value) = word.strip_prefix('S') {
+                index = value.parse::<i64>().ok();
+            } else if let Some(value) = word.strip_prefix('A') {
+                name = Some(value.trim_matches('"').to_string());
+            }
+        }
+
+        match (index, name) {
+            (Some(index), name) if index >= 0 => {
+                let name = name
+                    .or_else(|| self.m486_names.get(&index).cloned())
+                    .unwrap_or_else(|| format!("Object {}", index));
+
+                self.m486_names.insert(index, name.clone());
+                self.m486_index = Some(index);
+                self.start_range(line_number, name);
+            }
+            (Some(_), _) => {
+                self.m486_index = None;
+                self.end_range(line_number);
+            }
+            // M486 A names the current object
+            (None, Some(name)) => {
+                if let Some(index) = self.m486_index {
+                    self.m486_names.insert(index, name.clone());
+
+                    if let Some((open_name, _)) = self.open_range.as_mut() {
+                        *open_name = name;
+                    }
+                }
+            }
+            (None, None) => (),
+        }
+    }
+
+    fn start_range(&mut self, line_number: u64, name: String) {
+        self.end_range(line_number);
+        self.open_range = Some((name, line_number));
+    }
+
+    fn end_range(&mut self, line_number: u64) {
+        if let Some((name, start)) = self.open_range.take() {
+            let next_index = self.objects.len() as u32;
+            let object = *self.objects
+                .entry(name.clone())
+                .or_insert(next_index);
+
+            self.annotations.push((start, GCodeAnnotation::Object {
+                object,
+                name,
+                end_line_number: line_number,
+            }));
+        }
+    }
+
+    /// Returns the object annotations. total_lines ends any range that is still open.
+    pub fn finish(mut self, total_lines: u64) -> Vec<(u64, GCodeAnnotation)> {
+        self.end_range(total_lines);
+
+        // Plates with a single object have nothing to cancel
+        if self.objects.len() < 2 {
+            return vec![];
+        }
+
+        self.annotations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_objects(gcodes: &[&str]) -> Vec<(u64, u32, String, u64)> {
+        let mut detector = ObjectDetector::default();
+
+        for (line_number, gcode) in gcodes.iter().enumerate() {
+            detector.push_line(line_number as u64, gcode);
+        }
+
+        detector.finish(gcodes.len() as u64)
+            .into_iter()
+            .map(|(line_number, annotation)| match annotation {
+                GCodeAnnotation::Object { object, name, end_line_number } => {
+                    (line_number, object, name, end_line_number)
+                }
+                annotation => panic!("Unexpected annotation: {:?}", annotation),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_slicer_object_labels() {
+        let objects = detect_objects(&[
+            ";LAYER:0",
+            ";MESH:cube.stl",
+            "G1 X1 Y1 E1",
+            ";MESH:cone.stl",
+            "G1 X2 Y2 E2",
+            ";MESH:NONMESH",
+            "G0 X0 Y0",
+            ";LAYER:1",
+            ";MESH:cube.stl",
+            "G1 X1 Y1 E3",
+        ]);
+
+        assert_eq!(objects, vec![
+            (1, 0, "cube.stl".into(), 3),
+            (3, 1, "cone.stl".into(), 5),
+            (8, 0, "cube.stl".into(), 10),
+        ]);
+
+        let objects = detect_objects(&[
+            "; printing object cube id:0 copy 0",
+            "G1 X1 Y1 E1",
+            "; stop printing object cube id:0 copy 0",
+            "M486 S1 A\"cone\"",
+            "G1 X2 Y2 E2",
+            "M486 S-1",
+        ]);
+
+        assert_eq!(objects, vec![
+            (0, 0, "cube id:0 copy 0".into(), 2),
+            (3, 1, "cone".into(), 5),
+        ]);
+    }
+}
@@ -252,7 +252,7 @@ impl Record for Package {
     {
         let (json, previous_version) = self.prep_for_update()?;
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE packages
                 SET
@@ -277,9 +277,9 @@ impl Record for Package {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
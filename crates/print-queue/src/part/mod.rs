@@ -1,5 +1,12 @@
 mod part;
 pub use part::{ Part, PartTemplate };
 
+mod part_requirements;
+pub use part_requirements::{
+    PartRequirements,
+    detect_file_max_position,
+    next_printable_part,
+};
+
 mod part_resolvers;
 pub mod part_query_resolvers;
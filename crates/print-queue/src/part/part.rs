@@ -6,7 +6,9 @@ use eyre::{
     // Context as _,
 };
 use teg_json_store::{JsonRow, Record};
+use teg_machine::config::MachineConfig;
 
+use super::{PartRequirements, next_printable_part};
 // use crate::package::Package;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,6 +34,8 @@ pub struct Part {
     pub quantity: i32,
     pub position: i64,
     pub file_path: String,
+    #[serde(default)]
+    pub requirements: PartRequirements,
 }
 
 impl Part {
@@ -169,14 +173,15 @@ impl Part {
         Ok(done)
     }
 
+    /// The first part in the machine's print queues that the machine is able to print
     pub async fn fetch_next_part<'e, 'c, E>(
         db: E,
-        machine_id: &crate::DbId,
+        machine_config: &MachineConfig,
     ) -> Result<Option<Part>>
     where
         E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
     {
-        let part = sqlx::query_as!(
+        let parts = sqlx::query_as!(
             JsonRow,
             r#"
                 SELECT
@@ -199,14 +204,13 @@ impl Part {
                     AND parts.deleted_at IS NULL
                 ORDER BY
                     parts.position
-                LIMIT 1
             "#,
-            machine_id,
+            machine_config.id,
         )
-            .fetch_optional(db)
-            .await?
-            .map(|row| Part::from_row(row))
-            .transpose()?;
+            .fetch_all(db)
+            .await?;
+
+        let part = next_printable_part(Part::from_rows(parts)?, machine_config);
 
         Ok(part)
     }
@@ -305,7 +309,7 @@ impl Record for Part {
             .as_ref()
             .map(|based_on| &based_on.part_id);
 
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
                 UPDATE parts
                 SET
@@ -332,9 +336,9 @@ impl Record for Part {
             self.id,
             previous_version,
         )
-            .fetch_optional(db)
+            .execute(db)
             .await?;
 
-        Ok(())
+        self.check_version_conflict(result.rows_affected(), previous_version)
     }
 }
@@ -10,11 +10,19 @@ use eyre::{
 };
 
 use teg_json_store::{ Record as _, JsonRow };
+use teg_auth::{
+    AuthContext,
+    Permission,
+};
 
-use crate::part::Part;
+use crate::{
+    PrintQueue,
+    authorize::authorize_parts,
+    part::Part,
+};
 
-#[derive(async_graphql::InputObject, Debug, Default)]
-struct PartsInput {
+#[derive(async_graphql::InputObject, Debug, Default, Clone)]
+pub(crate) struct PartsInput {
     /// Optional filter: Return only the one part or error if it doesn't exist
     #[graphql(name="partID")]
     part_id: Option<ID>,
@@ -26,16 +34,19 @@ pub struct PartQuery;
 #[async_graphql::Object]
 impl PartQuery {
     #[instrument(skip(self, ctx))]
-    async fn parts<'ctx>(
+    pub(crate) async fn parts<'ctx>(
         &self,
         ctx: &'ctx Context<'_>,
         #[graphql(default)]
         input: PartsInput,
     ) -> FieldResult<Vec<Part>> {
         let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
 
         async move {
             let mut parts = if let Some(part_id) = input.part_id {
+                authorize_parts(db, auth, &[part_id.to_string()], Permission::View).await?;
+
                 let part = sqlx::query_as!(
                     JsonRow,
                     r#"
@@ -50,13 +61,25 @@ impl PartQuery {
 
                 vec![Part::from_row(part)?]
             } else {
+                auth.authorize(Permission::View)?;
+
+                let print_queue_ids = PrintQueue::get_all(db, false)
+                    .await?
+                    .into_iter()
+                    .filter(|q| auth.can_view_print_queue(&q.id))
+                    .map(|q| q.id)
+                    .collect::<Vec<_>>();
+
                 let parts = sqlx::query_as!(
                     JsonRow,
                     r#"
                         SELECT parts.props FROM parts
+                        INNER JOIN packages ON packages.id = parts.package_id
                         WHERE
                             parts.deleted_at IS NULL
+                            AND packages.print_queue_id = ANY($1)
                     "#,
+                    &print_queue_ids,
                 )
                     .fetch_all(db)
                     .await?;
@@ -0,0 +1,311 @@
+use std::{
+    fs::File,
+    io::{ BufRead, BufReader },
+};
+use serde::{Deserialize, Serialize};
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_machine::config::MachineConfig;
+
+use super::Part;
+
+/// The machines a part can be printed on. Used by the farm scheduler to match queued parts to
+/// machines.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PartRequirements {
+    /// Materials that must be loaded in the machine's toolheads
+    #[serde(default)]
+    pub material_ids: Vec<crate::DbId>,
+    /// Tags that the machine must have
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The largest X, Y and Z positions that the part's GCode moves to
+    #[serde(default)]
+    pub max_position: Option<[f32; 3]>,
+}
+
+impl PartRequirements {
+    pub fn is_satisfied_by(&self, config: &MachineConfig) -> bool {
+        let machine_tags = match config.core_plugin() {
+            Ok(core_plugin) => &core_plugin.model.tags,
+            Err(_) => return false,
+        };
+
+        let has_tags = self.tags
+            .iter()
+            .all(|tag| machine_tags.contains(tag));
+
+        let has_materials = self.material_ids
+            .iter()
+            .all(|material_id| {
+                config.toolheads
+                    .iter()
+                    .any(|toolhead| toolhead.model.material_id.as_ref() == Some(material_id))
+            });
+
+        let fits_build_volume = self.max_position
+            .map(|max_position| {
+                ["x", "y", "z"]
+                    .iter()
+                    .zip(max_position.iter())
+                    .all(|(address, position)| {
+                        config.axes
+                            .iter()
+                            .find(|axis| &axis.model.address == address)
+                            .and_then(|axis| axis.model.length)
+                            // Axes without a length are assumed to fit every part
+                            .map(|length| *position <= length)
+                            .unwrap_or(true)
+                    })
+            })
+            .unwrap_or(true);
+
+        has_tags && has_materials && fits_build_volume
+    }
+}
+
+/// The first part in print queue order that the machine is able to print. Parts that the machine
+/// cannot print are skipped so that they do not hold up the parts queued after them.
+pub fn next_printable_part<I>(parts: I, config: &MachineConfig) -> Option<Part>
+where
+    I: IntoIterator<Item = Part>,
+{
+    parts
+        .into_iter()
+        .filter(|part| part.requirements.is_satisfied_by(config))
+        .min_by_key(|part| part.position)
+}
+
+/// Detects the max position of a GCode file without blocking the async executor
+pub async fn detect_file_max_position(file_path: String) -> Result<Option<[f32; 3]>> {
+    async_std::task::spawn_blocking(move || {
+        let file = File::open(file_path)?;
+        let mut detector = MaxPositionDetector::default();
+
+        for gcode in BufReader::new(file).lines() {
+            detector.push_line(&gcode?);
+        }
+
+        Ok(detector.finish())
+    }).await
+}
+
+/// Finds the largest X, Y and Z positions that a part's GCode moves to
+#[derive(Debug, Default)]
+pub struct MaxPositionDetector {
+    relative: bool,
+    position: [f32; 3],
+    max_position: [Option<f32>; 3],
+}
+
+impl MaxPositionDetector {
+    pub fn push_line(&mut self, gcode: &str) {
+        let gcode = gcode.split(';').next().unwrap_or("");
+
+        let mut words = gcode.split_whitespace();
+        let command = words.next().unwrap_or("");
+
+        let is_move = match command {
+            "G90" => {
+                self.relative = false;
+                return
+            }
+            "G91" => {
+                self.relative = true;
+                return
+            }
+            "G0" | "G1" | "G2" | "G3" => true,
+            // Position resets
+            "G28" | "G92" => false,
+            _ => return,
+        };
+
+        let mut axes_set = false;
+
+        for word in words {
+            let axis = match word.chars().next() {
+                Some('X') => 0,
+                Some('Y') => 1,
+                Some('Z') => 2,
+                _ => continue,
+            };
+
+            let value = match word[1..].parse::<f32>() {
+                Ok(value) => value,
+                // G28 axes are given without a value
+                Err(_) if command == "G28" => 0.0,
+                Err(_) => continue,
+            };
+
+            axes_set = true;
+
+            if is_move && self.relative {
+                self.position[axis] += value;
+            } else {
+                self.position[axis] = value;
+            }
+
+            if is_move {
+                let position = self.position[axis];
+                let max = self.max_position[axis].get_or_insert(position);
+                *max = max.max(position);
+            }
+        }
+
+        // G28 without any axes homes all of them
+        if command == "G28" && !axes_set {
+            self.position = [0.0; 3];
+        }
+    }
+
+    /// Returns None if the GCode does not move along all three axes
+    pub fn finish(self) -> Option<[f32; 3]> {
+        let [x, y, z] = self.max_position;
+
+        Some([x?, y?, z?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn machine_config() -> MachineConfig {
+        let mut config: MachineConfig = toml::from_str(
+            include_str!("../../../../machine.default.toml"),
+        )
+            .expect("Invalid default machine config");
+
+        config.core_plugin_mut().unwrap().model.tags = vec!["enclosed".to_string()];
+        config.toolheads[0].model.material_id = Some("pla".to_string());
+
+        for axis in config.axes.iter_mut() {
+            axis.model.length = Some(200.0);
+        }
+
+        config
+    }
+
+    fn part(id: &str, position: i64, requirements: PartRequirements) -> Part {
+        Part {
+            id: id.to_string(),
+            version: 0,
+            created_at: Utc::now(),
+            deleted_at: None,
+            package_id: "package".to_string(),
+            based_on: None,
+            name: id.to_string(),
+            quantity: 1,
+            position,
+            file_path: format!("{}.gcode", id),
+            requirements,
+        }
+    }
+
+    #[test]
+    fn matches_machines_with_the_required_tags_and_materials() {
+        let config = machine_config();
+
+        let requirements = PartRequirements {
+            material_ids: vec!["pla".to_string()],
+            tags: vec!["enclosed".to_string()],
+            max_position: None,
+        };
+        assert!(requirements.is_satisfied_by(&config));
+        assert!(PartRequirements::default().is_satisfied_by(&config));
+
+        let missing_tag = PartRequirements {
+            tags: vec!["enclosed".to_string(), "nozzle-0.6mm".to_string()],
+            ..PartRequirements::default()
+        };
+        assert!(!missing_tag.is_satisfied_by(&config));
+
+        let missing_material = PartRequirements {
+            material_ids: vec!["petg".to_string()],
+            ..PartRequirements::default()
+        };
+        assert!(!missing_material.is_satisfied_by(&config));
+    }
+
+    #[test]
+    fn matches_machines_that_fit_the_part() {
+        let mut config = machine_config();
+
+        let fits = PartRequirements {
+            max_position: Some([200.0, 150.0, 10.0]),
+            ..PartRequirements::default()
+        };
+        assert!(fits.is_satisfied_by(&config));
+
+        let too_tall = PartRequirements {
+            max_position: Some([100.0, 100.0, 250.0]),
+            ..PartRequirements::default()
+        };
+        assert!(!too_tall.is_satisfied_by(&config));
+
+        // Axes without a length fit every part
+        for axis in config.axes.iter_mut() {
+            axis.model.length = None;
+        }
+        assert!(too_tall.is_satisfied_by(&config));
+    }
+
+    #[test]
+    fn dispatches_the_first_printable_part_in_queue_order() {
+        let config = machine_config();
+
+        let needs_petg = PartRequirements {
+            material_ids: vec!["petg".to_string()],
+            ..PartRequirements::default()
+        };
+
+        let parts = vec![
+            part("third", 3, PartRequirements::default()),
+            part("first", 1, needs_petg.clone()),
+            part("second", 2, PartRequirements::default()),
+        ];
+
+        // The first part is skipped because the machine does not have its material loaded
+        let next_part = next_printable_part(parts.clone(), &config);
+        assert_eq!(next_part.map(|part| part.id), Some("second".to_string()));
+
+        let next_part = next_printable_part(
+            parts.into_iter().filter(|part| part.id == "first"),
+            &config,
+        );
+        assert!(next_part.is_none());
+    }
+
+    fn detect_max_position(gcodes: &[&str]) -> Option<[f32; 3]> {
+        let mut detector = MaxPositionDetector::default();
+
+        for gcode in gcodes {
+            detector.push_line(gcode);
+        }
+
+        detector.finish()
+    }
+
+    #[test]
+    fn detects_the_max_position_of_moves() {
+        let max_position = detect_max_position(&[
+            "G28",
+            "G1 Z0.2 F300 ; first layer",
+            "G1 X20 Y10 E1",
+            "G91",
+            "G1 X15 Y-5 E1",
+            "G1 Z10",
+            "G90",
+            "G92 X0",
+            "G1 X5 Y30",
+        ]);
+
+        assert_eq!(max_position, Some([35.0, 30.0, 10.2]));
+
+        assert_eq!(detect_max_position(&["G1 X10 Y10"]), None);
+    }
+}
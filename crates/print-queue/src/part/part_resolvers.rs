@@ -47,6 +47,18 @@ impl Part {
     async fn position(&self) -> i64 { self.position }
     async fn created_at(&self) -> DateTime<Utc> { self.created_at }
 
+    /// The part is only printed on machines with all of these tags
+    async fn required_tags(&self) -> &Vec<String> { &self.requirements.tags }
+
+    /// The part is only printed on machines with all of these materials loaded
+    #[graphql(name="requiredMaterialIDs")]
+    async fn required_material_ids(&self) -> Vec<ID> {
+        self.requirements.material_ids
+            .iter()
+            .map(|id| id.into())
+            .collect()
+    }
+
     async fn starred<'ctx>(&self, ctx: &'ctx Context<'_>) -> FieldResult<bool> {
         let db: &crate::Db = ctx.data()?;
 
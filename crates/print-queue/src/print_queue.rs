@@ -21,6 +21,37 @@ pub struct PrintQueue {
 }
 
 impl PrintQueue {
+    /// The machine's first print queue. Parts that are sent directly to a machine (eg. from a
+    /// slicer) are added to this queue.
+    pub async fn get_machine_default<'e, 'c, E>(
+        db: E,
+        machine_id: &crate::DbId,
+    ) -> Result<Option<Self>>
+    where
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        let print_queue = sqlx::query_as!(
+            JsonRow,
+            r#"
+                SELECT print_queues.props FROM print_queues
+                INNER JOIN machine_print_queues
+                    ON machine_print_queues.print_queue_id = print_queues.id
+                WHERE
+                    machine_print_queues.machine_id = $1
+                    AND print_queues.deleted_at IS NULL
+                ORDER BY machine_print_queues.created_at ASC
+                LIMIT 1
+            "#,
+            machine_id,
+        )
+            .fetch_optional(db)
+            .await?
+            .map(Self::from_row)
+            .transpose()?;
+
+        Ok(print_queue)
+    }
+
     pub async fn get_parts<'e, 'c, E>(
         db: E,
         print_queue_id: &crate::DbId,
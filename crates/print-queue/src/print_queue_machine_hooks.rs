@@ -10,8 +10,20 @@ use eyre::{
 use futures::{Future, FutureExt};
 use teg_json_store::Record;
 use teg_machine::{MachineHooks, MachineHooksList, config::MachineConfig, machine::Machine, machine::MachineData, plugins::Plugin, task::Task};
+use teg_machine::{
+    machine::{
+        MachineStatus,
+        messages::{GetData, PauseSDCardPrint, StartSDCardPrint, StopMachine},
+    },
+    task::{Cancelled, TaskStatus},
+};
+use teg_protobufs::machine_message::HostAction;
 
 use crate::{PrintQueue, insert_print, machine_print_queue::MachinePrintQueue, part::Part};
+use crate::mutations::{
+    pause_print_mutation::pause_print,
+    resume_print_mutation::resume_print,
+};
 
 pub struct PrintQueueMachineHooks {
     pub db: crate::Db,
@@ -116,7 +128,7 @@ impl MachineHooks for PrintQueueMachineHooks {
         {
             let next_part = Part::fetch_next_part(
                 &mut *tx,
-                &task.machine_id,
+                &machine_data.config,
             ).await?;
 
             if let Some(next_part) = next_part {
@@ -156,4 +168,73 @@ impl MachineHooks for PrintQueueMachineHooks {
 
         Ok(None)
     }
+
+    async fn after_host_action(
+        &self,
+        machine_hooks: &MachineHooksList,
+        machine_data: &MachineData,
+        machine_addr: xactor::Addr<Machine>,
+        action: HostAction,
+    ) -> Result<()> {
+        let task_id = if let MachineStatus::Printing(printing) = &machine_data.status {
+            printing.task_id.clone()
+        } else {
+            warn!("Ignoring firmware {:?} request. The machine is not printing.", action);
+            return Ok(())
+        };
+
+        info!("Firmware requested {:?} for Print #{}", action, task_id);
+
+        let db = self.db.clone();
+        let machine_hooks = machine_hooks.clone();
+        let machine = machine_addr;
+
+        // Handle the request in a seperate task so that it does not deadlock with the Machine
+        // actor that called this hook.
+        async_std::task::spawn(async move {
+            let result = async move {
+                let mut task = Task::get(&db, &task_id, false).await?;
+
+                match action {
+                    HostAction::Pause if task.is_sd_card_print() => {
+                        machine.call(PauseSDCardPrint { task_id }).await??;
+                    }
+                    HostAction::Pause => {
+                        pause_print(&db, &machine, task).await?;
+                    }
+                    HostAction::Resume if task.is_sd_card_print() => {
+                        machine.call(StartSDCardPrint { task_id }).await??;
+                    }
+                    HostAction::Resume => {
+                        resume_print(&db, &machine, task).await?;
+                    }
+                    HostAction::Cancel => {
+                        task.status = TaskStatus::Cancelled(Cancelled {
+                            cancelled_at: Utc::now(),
+                        });
+
+                        let tx = db.begin().await?;
+                        let machine_data = machine.call(GetData).await??;
+
+                        task.settle_task(
+                            tx,
+                            &machine_hooks,
+                            &machine_data,
+                            &machine,
+                        ).await?;
+
+                        machine.call(StopMachine).await?;
+                    }
+                }
+
+                Result::<_>::Ok(())
+            }.await;
+
+            if let Err(err) = result {
+                warn!("Error handling firmware {:?} request: {:?}", action, err);
+            }
+        });
+
+        Ok(())
+    }
 }
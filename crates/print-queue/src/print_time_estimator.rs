@@ -0,0 +1,472 @@
+use teg_machine::{
+    config::MachineConfig,
+    task::PrintTimeTable,
+};
+
+/// Used for axes that do not have an acceleration configured (mm/s²)
+const DEFAULT_ACCELERATION: f32 = 1000.0;
+/// Used for the Z axis if it does not have an acceleration configured (mm/s²)
+const DEFAULT_Z_ACCELERATION: f32 = 100.0;
+/// Used for retractions and other extruder only moves (mm/s²)
+const EXTRUDER_ACCELERATION: f32 = 3000.0;
+/// Used until the GCode sets a feedrate (mm/s)
+const DEFAULT_FEEDRATE: f32 = 25.0;
+
+/// The temperature heaters are assumed to start at (°C)
+const AMBIENT_TEMPERATURE: f32 = 25.0;
+/// How quickly hotends are assumed to heat up (°C/s)
+const EXTRUDER_HEATING_RATE: f32 = 2.0;
+/// How quickly build platforms are assumed to heat up (°C/s)
+const BED_HEATING_RATE: f32 = 0.7;
+
+/// The minimum estimated time between samples in the print time table (s)
+const SAMPLE_INTERVAL: f32 = 1.0;
+/// The maximum number of samples kept in the print time table. Tasks are saved on each progress
+/// update so the table is kept small.
+const MAX_SAMPLES: usize = 500;
+
+/// A move waiting for the next move so that the speed at the junction between them is known
+#[derive(Debug, Clone)]
+struct Move {
+    line_number: u64,
+    /// The unit vector of the move in XYZ
+    direction: [f32; 3],
+    distance: f32,
+    feedrate: f32,
+    acceleration: f32,
+    entry_speed: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Heater {
+    heating_rate: f32,
+    /// The temperature when the target was set
+    start_temperature: f32,
+    target: f32,
+    /// The estimated time the target was set at
+    target_set_at: f32,
+}
+
+impl Heater {
+    fn new(heating_rate: f32) -> Self {
+        Self {
+            heating_rate,
+            start_temperature: AMBIENT_TEMPERATURE,
+            target: AMBIENT_TEMPERATURE,
+            target_set_at: 0.0,
+        }
+    }
+
+    fn temperature_at(&self, time: f32) -> f32 {
+        if self.target <= self.start_temperature {
+            // Cooling is not waited on
+            self.target
+        } else {
+            let heated = self.start_temperature
+                + self.heating_rate * (time - self.target_set_at);
+
+            heated.min(self.target)
+        }
+    }
+
+    fn set_target(&mut self, target: f32, time: f32) {
+        self.start_temperature = self.temperature_at(time);
+        self.target = target;
+        self.target_set_at = time;
+    }
+
+    /// Sets the target and returns the estimated time until it is reached
+    fn wait_for(&mut self, target: f32, time: f32) -> f32 {
+        self.set_target(target, time);
+
+        (target - self.start_temperature).max(0.0) / self.heating_rate
+    }
+}
+
+/// Estimates the time to print each line of a task by simulating it's moves, dwells and heating.
+///
+/// Moves are simulated with trapezoidal velocity profiles. The speed at the junction between two
+/// moves is limited by the angle between them. This ignores firmware specific planner details
+/// (eg. jerk and junction deviation settings) but is far closer to the actual print time than
+/// dividing the move distances by their feedrates.
+#[derive(Debug, Clone)]
+pub struct PrintTimeEstimator {
+    /// XYZ accelerations (mm/s²)
+    accelerations: [f32; 3],
+    /// XYZE positions (mm)
+    position: [f32; 4],
+    relative_positioning: bool,
+    relative_extrusion: bool,
+    /// mm/s
+    feedrate: f32,
+    extruder: Heater,
+    bed: Heater,
+    pending_move: Option<Move>,
+    /// The estimated time at the end of the last line (s)
+    time: f32,
+    table: Vec<(u64, f32)>,
+}
+
+impl Default for PrintTimeEstimator {
+    fn default() -> Self {
+        Self {
+            accelerations: [
+                DEFAULT_ACCELERATION,
+                DEFAULT_ACCELERATION,
+                DEFAULT_Z_ACCELERATION,
+            ],
+            position: [0.0; 4],
+            relative_positioning: false,
+            relative_extrusion: false,
+            feedrate: DEFAULT_FEEDRATE,
+            extruder: Heater::new(EXTRUDER_HEATING_RATE),
+            bed: Heater::new(BED_HEATING_RATE),
+            pending_move: None,
+            time: 0.0,
+            table: vec![],
+        }
+    }
+}
+
+impl PrintTimeEstimator {
+    pub fn new(config: &MachineConfig) -> Self {
+        let mut estimator = Self::default();
+
+        for axis in config.axes.iter() {
+            let index = match axis.model.address.as_str() {
+                "x" => 0,
+                "y" => 1,
+                "z" => 2,
+                _ => continue,
+            };
+
+            if let Some(acceleration) = axis.model.acceleration.filter(|a| *a > 0.0) {
+                estimator.accelerations[index] = acceleration;
+            }
+        }
+
+        estimator
+    }
+
+    /// Adds the estimated time to run a GCode line. line_number is the line's index in the task
+    /// file.
+    pub fn push_line(&mut self, line_number: u64, gcode: &str) {
+        let gcode = gcode.split(';').next().unwrap_or("");
+        let mut words = gcode.split_whitespace();
+
+        let command = if let Some(command) = words.next() {
+            command
+        } else {
+            return
+        };
+
+        // Parses the GCode's arguments. Returns None for missing or invalid arguments.
+        let mut args: [Option<f32>; 7] = [None; 7];
+        for word in words {
+            let mut chars = word.chars();
+            let index = match chars.next().map(|c| c.to_ascii_uppercase()) {
+                Some('X') => 0,
+                Some('Y') => 1,
+                Some('Z') => 2,
+                Some('E') => 3,
+                Some('F') => 4,
+                Some('S') => 5,
+                Some('P') | Some('R') => 6,
+                _ => continue,
+            };
+            args[index] = chars.as_str().parse().ok();
+        }
+
+        match command {
+            "G0" | "G1" => {
+                self.push_move(line_number, args);
+                return
+            }
+            "G4" => {
+                // P is in milliseconds and S is in seconds
+                let dwell = args[6].map(|ms| ms / 1000.0)
+                    .or(args[5])
+                    .unwrap_or(0.0);
+
+                self.flush_move();
+                self.time += dwell.max(0.0);
+            }
+            "G90" => self.relative_positioning = false,
+            "G91" => self.relative_positioning = true,
+            "M82" => self.relative_extrusion = false,
+            "M83" => self.relative_extrusion = true,
+            "G92" => {
+                for (axis, value) in args[..4].iter().enumerate() {
+                    if let Some(value) = value {
+                        self.position[axis] = *value;
+                    }
+                }
+            }
+            "M104" => {
+                if let Some(target) = args[5] {
+                    self.flush_move();
+                    self.extruder.set_target(target, self.time);
+                }
+            }
+            "M140" => {
+                if let Some(target) = args[5] {
+                    self.flush_move();
+                    self.bed.set_target(target, self.time);
+                }
+            }
+            "M109" => {
+                if let Some(target) = args[5].or(args[6]) {
+                    self.flush_move();
+                    self.time += self.extruder.wait_for(target, self.time);
+                }
+            }
+            "M190" => {
+                if let Some(target) = args[5].or(args[6]) {
+                    self.flush_move();
+                    self.time += self.bed.wait_for(target, self.time);
+                }
+            }
+            _ => return,
+        }
+
+        self.sample(line_number);
+    }
+
+    fn push_move(&mut self, line_number: u64, args: [Option<f32>; 7]) {
+        if let Some(feedrate) = args[4].filter(|f| *f > 0.0) {
+            // mm/min to mm/s
+            self.feedrate = feedrate / 60.0;
+        }
+
+        let mut deltas = [0.0f32; 4];
+        for axis in 0..4 {
+            if let Some(value) = args[axis] {
+                let relative = if axis == 3 {
+                    self.relative_extrusion || self.relative_positioning
+                } else {
+                    self.relative_positioning
+                };
+
+                deltas[axis] = if relative {
+                    value
+                } else {
+                    value - self.position[axis]
+                };
+                self.position[axis] += deltas[axis];
+            }
+        }
+
+        let xyz_distance = deltas[..3]
+            .iter()
+            .map(|d| d * d)
+            .sum::<f32>()
+            .sqrt();
+
+        let (distance, direction, acceleration) = if xyz_distance > 0.0 {
+            let direction = [
+                deltas[0] / xyz_distance,
+                deltas[1] / xyz_distance,
+                deltas[2] / xyz_distance,
+            ];
+
+            // Limit the acceleration so that no axis exceeds it's own acceleration
+            let acceleration = (0..3)
+                .filter(|axis| direction[*axis] != 0.0)
+                .map(|axis| self.accelerations[axis] / direction[axis].abs())
+                .fold(f32::INFINITY, f32::min);
+
+            (xyz_distance, direction, acceleration)
+        } else if deltas[3] != 0.0 {
+            (deltas[3].abs(), [0.0; 3], EXTRUDER_ACCELERATION)
+        } else {
+            return
+        };
+
+        let next_move = Move {
+            line_number,
+            direction,
+            distance,
+            feedrate: self.feedrate,
+            acceleration,
+            entry_speed: 0.0,
+        };
+
+        self.plan_move(next_move);
+    }
+
+    /// Completes the pending move with a junction speed based on the next move
+    fn plan_move(&mut self, mut next_move: Move) {
+        if let Some(previous) = self.pending_move.take() {
+            let cos_theta = previous.direction
+                .iter()
+                .zip(next_move.direction.iter())
+                .map(|(a, b)| a * b)
+                .sum::<f32>();
+
+            // Moves in the same direction keep their speed through the junction and the speed
+            // falls to zero as the angle between the moves approaches 90°
+            let junction_speed = previous.feedrate
+                .min(next_move.feedrate)
+                * cos_theta.max(0.0);
+
+            self.complete_move(previous, junction_speed);
+
+            next_move.entry_speed = junction_speed;
+        }
+
+        self.pending_move = Some(next_move);
+    }
+
+    /// Completes the pending move coming to a stop at the end of it
+    fn flush_move(&mut self) {
+        if let Some(previous) = self.pending_move.take() {
+            self.complete_move(previous, 0.0);
+        }
+    }
+
+    fn complete_move(&mut self, m: Move, exit_speed: f32) {
+        self.time += trapezoid_time(
+            m.distance,
+            m.entry_speed,
+            m.feedrate,
+            exit_speed,
+            m.acceleration,
+        );
+
+        self.sample(m.line_number);
+    }
+
+    fn sample(&mut self, line_number: u64) {
+        let last_sample = self.table
+            .last()
+            .map(|(_, time)| *time)
+            .unwrap_or(0.0);
+
+        if self.time - last_sample >= SAMPLE_INTERVAL {
+            self.table.push((line_number, self.time));
+        }
+    }
+
+    /// Returns the estimated time at lines of the task
+    pub fn finish(mut self, total_lines: u64) -> PrintTimeTable {
+        self.flush_move();
+
+        let last_line = total_lines.saturating_sub(1);
+        if self.table.last().map(|(line, _)| *line) != Some(last_line) && self.time > 0.0 {
+            self.table.push((last_line, self.time));
+        }
+
+        let mut table = self.table;
+
+        // Keep evenly spaced samples including the last one
+        if table.len() > MAX_SAMPLES {
+            let step = table.len() as f32 / MAX_SAMPLES as f32;
+            let last = table[table.len() - 1];
+
+            table = (1..MAX_SAMPLES)
+                .map(|i| table[(i as f32 * step) as usize - 1])
+                .chain(std::iter::once(last))
+                .collect();
+        }
+
+        PrintTimeTable(table)
+    }
+}
+
+/// The time to move a distance accelerating from entry_speed to at most max_speed and then
+/// decelerating to exit_speed
+fn trapezoid_time(
+    distance: f32,
+    entry_speed: f32,
+    max_speed: f32,
+    exit_speed: f32,
+    acceleration: f32,
+) -> f32 {
+    if distance <= 0.0 || max_speed <= 0.0 {
+        return 0.0
+    }
+
+    let acceleration = acceleration.max(1.0);
+    let entry_speed = entry_speed.min(max_speed);
+    let exit_speed = exit_speed.min(max_speed);
+
+    let accel_distance = (max_speed.powi(2) - entry_speed.powi(2)) / (2.0 * acceleration);
+    let decel_distance = (max_speed.powi(2) - exit_speed.powi(2)) / (2.0 * acceleration);
+
+    if accel_distance + decel_distance <= distance {
+        // Accelerate, cruise at max_speed and decelerate
+        let cruise_distance = distance - accel_distance - decel_distance;
+
+        (max_speed - entry_speed) / acceleration
+            + cruise_distance / max_speed
+            + (max_speed - exit_speed) / acceleration
+    } else {
+        // The move is too short to reach max_speed
+        let peak_speed = (
+            (2.0 * acceleration * distance + entry_speed.powi(2) + exit_speed.powi(2)) / 2.0
+        ).sqrt();
+
+        if peak_speed < entry_speed.max(exit_speed) {
+            // The junction speeds cannot be met within the move so average them
+            2.0 * distance / (entry_speed + exit_speed)
+        } else {
+            (peak_speed - entry_speed) / acceleration + (peak_speed - exit_speed) / acceleration
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate(gcodes: &[&str]) -> PrintTimeTable {
+        let mut estimator = PrintTimeEstimator::default();
+
+        for (line_number, gcode) in gcodes.iter().enumerate() {
+            estimator.push_line(line_number as u64, gcode);
+        }
+
+        estimator.finish(gcodes.len() as u64)
+    }
+
+    fn seconds(table: &PrintTimeTable) -> f32 {
+        table.total().unwrap().as_secs_f32()
+    }
+
+    #[test]
+    fn estimates_moves_with_acceleration() {
+        // 100mm at 50mm/s with 0.05s accelerating and 0.05s decelerating
+        let table = estimate(&["G1 X100 F3000"]);
+        assert!((seconds(&table) - 2.05).abs() < 0.01, "{:?}", table);
+
+        // Continuing in the same direction does not slow down at the junction
+        let straight = estimate(&["G1 X50 F3000", "G1 X100"]);
+        assert!((seconds(&straight) - 2.05).abs() < 0.01, "{:?}", straight);
+
+        // Turning 90° stops at the junction
+        let corner = estimate(&["G1 X50 F3000", "G1 X50 Y50"]);
+        assert!((seconds(&corner) - 2.1).abs() < 0.01, "{:?}", corner);
+    }
+
+    #[test]
+    fn estimates_dwells_and_heating() {
+        let table = estimate(&[
+            "M140 S60",
+            "M104 S200",
+            "G4 P500",
+            "G4 S2",
+            // The bed heats from 25°C at 0.7°C/s
+            "M190 S60",
+            // The hotend heated while waiting on the bed
+            "M109 S200",
+        ]);
+
+        let bed = 35.0 / BED_HEATING_RATE;
+        let hotend = 175.0 / EXTRUDER_HEATING_RATE;
+
+        assert!((seconds(&table) - hotend).abs() < 0.01, "{:?}", table);
+
+        let at_bed = table.time_at(4).unwrap().as_secs_f32();
+        assert!((at_bed - bed).abs() < 0.01, "{:?}", table);
+    }
+}
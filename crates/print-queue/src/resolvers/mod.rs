@@ -1,4 +1,7 @@
 pub mod print_queue_resolvers;
 pub mod print_queue_query_resolvers;
+pub mod print_queue_subscription_resolvers;
+pub mod print_history_query_resolvers;
 
 pub mod print_resolvers;
+pub mod print_statistics;
@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use chrono::prelude::*;
+use async_graphql::{
+    Context,
+    // ID,
+    FieldResult,
+};
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_json_store::{ Record as _, JsonRow };
+use teg_auth::{
+    AuthContext,
+    Permission,
+    user::UserRole,
+};
+use teg_machine::task::{
+    Task,
+    TaskStatusGQL,
+};
+
+use crate::part::Part;
+use super::print_resolvers::Print;
+use super::print_statistics::{
+    PrintStatistics,
+    PrintStatisticsReport,
+    MachinePrintStatistics,
+    MaterialPrintStatistics,
+};
+
+const SETTLED_STATUSES: [TaskStatusGQL; 3] = [
+    TaskStatusGQL::Finished,
+    TaskStatusGQL::Cancelled,
+    TaskStatusGQL::Errored,
+];
+
+/// The number of tasks loaded at a time while aggregating the print statistics
+const STATISTICS_PAGE_SIZE: i64 = 500;
+
+#[derive(async_graphql::InputObject, Debug, Default, Clone)]
+struct PrintHistoryFilter {
+    /// Optional filter: Return only the prints that are associated with the given machine ids
+    #[graphql(name="machineIDs", default)]
+    machine_ids: Option<Vec<async_graphql::ID>>,
+    /// Optional filter: Return only the prints of the given parts
+    #[graphql(name="partIDs", default)]
+    part_ids: Option<Vec<async_graphql::ID>>,
+    /// Optional filter: Return only the prints with the given statuses
+    #[graphql(default)]
+    statuses: Option<Vec<TaskStatusGQL>>,
+    /// Optional filter: Return only the prints started at or after this time
+    #[graphql(default)]
+    started_after: Option<DateTime<Utc>>,
+    /// Optional filter: Return only the prints started before this time
+    #[graphql(default)]
+    started_before: Option<DateTime<Utc>>,
+}
+
+#[derive(async_graphql::InputObject, Debug, Default)]
+struct PrintHistoryInput {
+    #[graphql(default)]
+    filter: PrintHistoryFilter,
+    /// The number of prints to skip
+    #[graphql(default)]
+    offset: u32,
+    /// The maximum number of prints to return
+    #[graphql(default = 50)]
+    limit: u32,
+}
+
+#[derive(async_graphql::InputObject, Debug, Default)]
+struct PrintStatisticsInput {
+    #[graphql(default)]
+    filter: PrintHistoryFilter,
+}
+
+#[derive(async_graphql::SimpleObject)]
+struct PrintHistory {
+    /// The matching prints, most recent first
+    prints: Vec<Print>,
+    /// The total number of matching prints, ignoring the offset and limit
+    total_count: u64,
+}
+
+/// SQL conditions for the PrintHistoryFilter. The filter's values are bound in the order they are
+/// listed here by PrintHistoryFilter::bind.
+const PRINT_HISTORY_SQL_WHERE_CLAUSE: &str = r#"
+    (tasks.part_id IS NOT NULL OR tasks.props->'content' ? 'SDCardFile')
+    AND ($1::TEXT[] IS NULL OR tasks.machine_id = ANY($1))
+    AND ($2::TEXT[] IS NULL OR tasks.part_id = ANY($2))
+    AND ($3::TEXT[] IS NULL OR tasks.status = ANY($3))
+    AND ($4::TIMESTAMPTZ IS NULL OR tasks.created_at >= $4)
+    AND ($5::TIMESTAMPTZ IS NULL OR tasks.created_at < $5)
+"#;
+
+impl PrintHistoryFilter {
+    /// Restricts the filter to the machines that the user has been granted
+    fn authorize(&mut self, auth: &AuthContext) -> Result<()> {
+        let user = auth.authorize(Permission::View)?;
+
+        let granted_machine_ids = match &user.config.machine_ids {
+            Some(machine_ids) if user.config.role() != UserRole::Admin => machine_ids,
+            // Users without machine grants can view the print history of every machine
+            _ => return Ok(()),
+        };
+
+        let machine_ids = match self.machine_ids.take() {
+            Some(machine_ids) => machine_ids
+                .into_iter()
+                .filter(|id| granted_machine_ids.contains(&id.0))
+                .collect(),
+            None => granted_machine_ids
+                .iter()
+                .map(|id| id.clone().into())
+                .collect(),
+        };
+
+        self.machine_ids = Some(machine_ids);
+
+        Ok(())
+    }
+
+    fn bind<'q, O>(
+        self,
+        query: sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments> {
+        let ids = |ids: Option<Vec<async_graphql::ID>>| {
+            ids.map(|ids| ids.into_iter().map(|id| id.0).collect::<Vec<_>>())
+        };
+
+        let statuses = self.statuses.map(|statuses| {
+            statuses
+                .iter()
+                .map(|status| status.to_db_str().to_string())
+                .collect::<Vec<_>>()
+        });
+
+        query
+            .bind(ids(self.machine_ids))
+            .bind(ids(self.part_ids))
+            .bind(statuses)
+            .bind(self.started_after)
+            .bind(self.started_before)
+    }
+}
+
+#[derive(Default)]
+pub struct PrintHistoryQuery;
+
+#[async_graphql::Object]
+impl PrintHistoryQuery {
+    /// Returns the prints matching the filter, most recent first.
+    #[instrument(skip(self, ctx))]
+    async fn print_history<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: PrintHistoryInput,
+    ) -> FieldResult<PrintHistory> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        async move {
+            let mut filter = input.filter;
+            filter.authorize(auth)?;
+
+            let count_sql = format!(
+                r#"
+                    SELECT COUNT(*) FROM tasks
+                    WHERE {}
+                "#,
+                PRINT_HISTORY_SQL_WHERE_CLAUSE,
+            );
+
+            let (total_count,): (i64,) = filter
+                .clone()
+                .bind(sqlx::query_as(&count_sql))
+                .fetch_one(db)
+                .await?;
+
+            let sql = format!(
+                r#"
+                    SELECT tasks.props FROM tasks
+                    WHERE {}
+                    ORDER BY
+                        tasks.created_at DESC,
+                        tasks.id
+                    LIMIT $6
+                    OFFSET $7
+                "#,
+                PRINT_HISTORY_SQL_WHERE_CLAUSE,
+            );
+
+            let tasks: Vec<JsonRow> = filter
+                .bind(sqlx::query_as(&sql))
+                .bind(input.limit as i64)
+                .bind(input.offset as i64)
+                .fetch_all(db)
+                .await?;
+
+            let tasks = Task::from_rows(tasks)?;
+
+            let mut part_ids = tasks.iter()
+                .filter_map(|task| task.part_id.clone())
+                .collect::<Vec<String>>();
+            part_ids.sort();
+            part_ids.dedup();
+
+            let parts = sqlx::query_as!(
+                JsonRow,
+                r#"
+                    SELECT props FROM parts
+                    WHERE
+                        parts.id = ANY($1)
+                "#,
+                &part_ids,
+            )
+                .fetch_all(db)
+                .await?;
+
+            let parts = Part::from_rows(parts)?;
+
+            let prints = tasks
+                .into_iter()
+                .map(|task| {
+                    // SD card prints are not associated with a part and the parts of older prints
+                    // may have been hard deleted
+                    let part = task.part_id
+                        .as_ref()
+                        .and_then(|part_id| {
+                            let part = parts
+                                .iter()
+                                .find(|part| &part.id == part_id);
+
+                            if part.is_none() {
+                                warn!(
+                                    "Part ({:?}) missing for print history task ({:?})",
+                                    part_id,
+                                    task.id,
+                                );
+                            }
+
+                            // Part must be cloned because multiple tasks could reference the same part
+                            part.cloned()
+                        });
+
+                    Print {
+                        id: task.id.clone().into(),
+                        part,
+                        task,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            Result::<_>::Ok(PrintHistory {
+                prints,
+                total_count: total_count as u64,
+            })
+        }
+            // log the backtrace which is otherwise lost by FieldResult
+            .await
+            .map_err(|err| {
+                warn!("{:?}", err);
+                err.into()
+            })
+    }
+
+    /// Aggregates the settled (finished, cancelled and errored) prints matching the filter in
+    /// total, per machine and per material.
+    #[instrument(skip(self, ctx))]
+    async fn print_statistics<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: PrintStatisticsInput,
+    ) -> FieldResult<PrintStatisticsReport> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        async move {
+            let mut filter = input.filter;
+            filter.authorize(auth)?;
+
+            // Statistics are only meaningful for prints that have settled
+            let statuses = filter.statuses
+                .unwrap_or_else(|| SETTLED_STATUSES.to_vec())
+                .into_iter()
+                .filter(|status| SETTLED_STATUSES.contains(status))
+                .collect();
+            filter.statuses = Some(statuses);
+
+            // Page through the tasks so that the print history is never loaded into memory at once
+            let sql = format!(
+                r#"
+                    SELECT tasks.props FROM tasks
+                    WHERE
+                        {}
+                        AND tasks.id > $6
+                    ORDER BY tasks.id
+                    LIMIT $7
+                "#,
+                PRINT_HISTORY_SQL_WHERE_CLAUSE,
+            );
+
+            let mut total = PrintStatistics::default();
+            let mut machines = BTreeMap::<String, PrintStatistics>::new();
+            let mut materials = BTreeMap::<String, PrintStatistics>::new();
+
+            let mut last_task_id = String::new();
+
+            loop {
+                let tasks: Vec<JsonRow> = filter
+                    .clone()
+                    .bind(sqlx::query_as(&sql))
+                    .bind(&last_task_id)
+                    .bind(STATISTICS_PAGE_SIZE)
+                    .fetch_all(db)
+                    .await?;
+
+                let tasks = Task::from_rows(tasks)?;
+
+                for task in tasks.iter() {
+                    total.add(task);
+
+                    machines
+                        .entry(task.machine_id.clone())
+                        .or_default()
+                        .add(task);
+
+                    for material_id in task.material_ids.iter() {
+                        materials
+                            .entry(material_id.clone())
+                            .or_default()
+                            .add(task);
+                    }
+                }
+
+                match tasks.last() {
+                    Some(task) if tasks.len() as i64 == STATISTICS_PAGE_SIZE => {
+                        last_task_id = task.id.clone();
+                    }
+                    _ => break,
+                }
+            }
+
+            Result::<_>::Ok(PrintStatisticsReport {
+                total,
+                machines: machines
+                    .into_iter()
+                    .map(|(machine_id, statistics)| MachinePrintStatistics {
+                        machine_id: machine_id.into(),
+                        statistics,
+                    })
+                    .collect(),
+                materials: materials
+                    .into_iter()
+                    .map(|(material_id, statistics)| MaterialPrintStatistics {
+                        material_id: material_id.into(),
+                        statistics,
+                    })
+                    .collect(),
+            })
+        }
+            // log the backtrace which is otherwise lost by FieldResult
+            .await
+            .map_err(|err| {
+                warn!("{:?}", err);
+                err.into()
+            })
+    }
+}
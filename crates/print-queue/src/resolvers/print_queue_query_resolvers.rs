@@ -9,14 +9,18 @@ use eyre::{
     // Context as _,
 };
 use teg_json_store::{ Record as _, JsonRow };
+use teg_auth::{
+    AuthContext,
+    Permission,
+};
 use teg_machine::task::Task;
 
 use crate::PrintQueue;
 use crate::part::Part;
 use super::print_resolvers::Print;
 
-#[derive(async_graphql::InputObject, Debug, Default)]
-struct PrintQueuesInput {
+#[derive(async_graphql::InputObject, Debug, Default, Clone)]
+pub(crate) struct PrintQueuesInput {
     /// Optional filter: Return only the print queues that are associated with the given machine id
     #[graphql(name="machineID", default)]
     machine_id: Option<async_graphql::ID>,
@@ -38,15 +42,18 @@ pub struct PrintQueueQuery;
 #[async_graphql::Object]
 impl PrintQueueQuery {
     #[instrument(skip(self, ctx))]
-    async fn print_queues<'ctx>(
+    pub(crate) async fn print_queues<'ctx>(
         &self,
         ctx: &'ctx Context<'_>,
         #[graphql(default)]
         input: PrintQueuesInput,
     ) -> FieldResult<Vec<PrintQueue>> {
         let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
 
         async move {
+            auth.authorize(Permission::View)?;
+
             let mut print_queues = if let Some(machine_id) = input.machine_id {
                 let machine_id = machine_id.to_string();
 
@@ -69,6 +76,8 @@ impl PrintQueueQuery {
                 PrintQueue::get_all(db, false).await?
             };
 
+            print_queues.retain(|q| auth.can_view_print_queue(&q.id));
+
             // Alphabetical and consistent ordering
             print_queues.sort_by_cached_key(|q| (q.name.clone(), q.id.clone()));
 
@@ -91,8 +100,11 @@ impl PrintQueueQuery {
         input: LatestPrintsInput,
     ) -> FieldResult<Vec<Print>> {
         let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
 
         async move {
+            auth.authorize(Permission::View)?;
+
             let mut args = vec![];
             let mut print_queues_sql_join = "".to_string();
             let mut machine_sql_where_clause = "".to_string();
@@ -160,7 +172,10 @@ impl PrintQueueQuery {
                 .fetch_all(db)
                 .await?;
 
-            let tasks = Task::from_rows(tasks)?;
+            let tasks = Task::from_rows(tasks)?
+                .into_iter()
+                .filter(|task| auth.can_view_machine(&task.machine_id))
+                .collect::<Vec<_>>();
 
             let mut part_ids = tasks.iter()
                 .filter_map(|task| task.part_id.clone())
@@ -203,7 +218,7 @@ impl PrintQueueQuery {
 
                     Ok(Print {
                         id: task.id.clone().into(),
-                        part,
+                        part: Some(part),
                         task,
                     })
                 })
@@ -0,0 +1,67 @@
+use async_graphql::{
+    Context,
+    FieldResult,
+};
+use futures::stream::Stream;
+use teg_json_store::ChangeFeed;
+
+use crate::PrintQueue;
+use crate::part::{
+    Part,
+    part_query_resolvers::{
+        PartQuery,
+        PartsInput,
+    },
+};
+use super::print_queue_query_resolvers::{
+    PrintQueueQuery,
+    PrintQueuesInput,
+};
+
+#[derive(Default)]
+pub struct PrintQueueSubscription;
+
+#[async_graphql::Subscription]
+impl PrintQueueSubscription {
+    /// Live print queues. Sends the current print queues and then again whenever the print
+    /// queues, their packages, parts or prints change.
+    async fn print_queues<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: PrintQueuesInput,
+    ) -> FieldResult<impl Stream<Item = FieldResult<Vec<PrintQueue>>> + 'ctx> {
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        let tables = &[
+            "print_queues",
+            "machine_print_queues",
+            "packages",
+            "parts",
+            "tasks",
+        ];
+
+        let stream = change_feed.live_query(tables, move || {
+            (&PrintQueueQuery).print_queues(ctx, input.clone())
+        });
+
+        Ok(stream)
+    }
+
+    /// Live parts. Sends the current parts and then again whenever the parts or their prints
+    /// change.
+    async fn parts<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        #[graphql(default)]
+        input: PartsInput,
+    ) -> FieldResult<impl Stream<Item = FieldResult<Vec<Part>>> + 'ctx> {
+        let change_feed: &ChangeFeed = ctx.data()?;
+
+        let stream = change_feed.live_query(&["parts", "tasks"], move || {
+            (&PartQuery).parts(ctx, input.clone())
+        });
+
+        Ok(stream)
+    }
+}
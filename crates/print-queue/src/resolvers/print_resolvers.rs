@@ -11,6 +11,7 @@ use crate::{
 #[derive(async_graphql::SimpleObject)]
 pub struct Print {
     pub id: async_graphql::ID,
-    pub part: Part,
+    /// The part being printed. Null for prints of files on a machine's SD card.
+    pub part: Option<Part>,
     pub task: Task,
 }
@@ -0,0 +1,237 @@
+use teg_machine::task::{
+    Task,
+    TaskStatus,
+    Finished,
+    Cancelled,
+    Errored,
+};
+
+const SECONDS_PER_HOUR: f64 = 60.0 * 60.0;
+
+/// Aggregated statistics for a set of settled prints
+#[derive(Debug, Default, Clone)]
+pub struct PrintStatistics {
+    pub finished_prints: u64,
+    pub cancelled_prints: u64,
+    pub errored_prints: u64,
+    /// Seconds spent printing excluding the time paused
+    pub print_seconds: f64,
+    pub blocked_seconds: f64,
+    pub paused_seconds: f64,
+    pub filament_meters: f64,
+}
+
+impl PrintStatistics {
+    /// Adds a settled print to the statistics. Prints that have not settled are ignored.
+    pub fn add(&mut self, task: &Task) {
+        let stopped_at = match &task.status {
+            TaskStatus::Finished(Finished { finished_at: t }) => {
+                self.finished_prints += 1;
+                t
+            }
+            TaskStatus::Cancelled(Cancelled { cancelled_at: t }) => {
+                self.cancelled_prints += 1;
+                t
+            }
+            TaskStatus::Errored(Errored { errored_at: t, .. }) => {
+                self.errored_prints += 1;
+                t
+            }
+            _ => return,
+        };
+
+        let paused_seconds = task.time_paused.as_secs_f64();
+        let duration_seconds = (*stopped_at - task.created_at).num_milliseconds() as f64 / 1000.0;
+
+        self.print_seconds += (duration_seconds - paused_seconds).max(0.0);
+        self.blocked_seconds += task.time_blocked.as_secs_f64();
+        self.paused_seconds += paused_seconds;
+
+        // Cancelled and errored prints only consumed the filament for the lines they printed
+        let progress = if task.status.was_successful() {
+            1.0
+        } else {
+            let printed_lines = task.despooled_line_number
+                .map(|n| n + 1)
+                .unwrap_or(0) as f64;
+
+            printed_lines / std::cmp::max(task.total_lines, 1) as f64
+        };
+
+        self.filament_meters += task.estimated_filament_meters.unwrap_or(0.0) * progress;
+    }
+
+    fn total_prints(&self) -> u64 {
+        self.finished_prints + self.cancelled_prints + self.errored_prints
+    }
+
+    fn rate(&self, prints: u64) -> Option<f64> {
+        let total_prints = self.total_prints();
+
+        if total_prints == 0 {
+            None
+        } else {
+            Some(prints as f64 / total_prints as f64)
+        }
+    }
+}
+
+#[async_graphql::Object]
+impl PrintStatistics {
+    /// The number of settled prints (finished, cancelled or errored)
+    async fn total_prints(&self) -> u64 { self.total_prints() }
+
+    async fn finished_prints(&self) -> u64 { self.finished_prints }
+
+    async fn cancelled_prints(&self) -> u64 { self.cancelled_prints }
+
+    async fn errored_prints(&self) -> u64 { self.errored_prints }
+
+    /// The fraction (0 to 1) of settled prints that finished. Null if there are no settled
+    /// prints.
+    async fn success_rate(&self) -> Option<f64> { self.rate(self.finished_prints) }
+
+    /// The fraction (0 to 1) of settled prints that errored. Null if there are no settled
+    /// prints.
+    async fn failure_rate(&self) -> Option<f64> { self.rate(self.errored_prints) }
+
+    /// The time spent printing in hours, excluding the time the prints were paused
+    async fn print_hours(&self) -> f64 { self.print_seconds / SECONDS_PER_HOUR }
+
+    /// The time the prints spent waiting on blocking GCodes (eg. heating up) in hours
+    async fn blocked_hours(&self) -> f64 { self.blocked_seconds / SECONDS_PER_HOUR }
+
+    /// The time the prints were paused for in hours
+    async fn paused_hours(&self) -> f64 { self.paused_seconds / SECONDS_PER_HOUR }
+
+    /// The estimated filament consumed in meters. Cancelled and errored prints only count the
+    /// filament of the lines they printed.
+    async fn filament_meters(&self) -> f64 { self.filament_meters }
+}
+
+#[derive(async_graphql::SimpleObject, Debug, Clone)]
+pub struct MachinePrintStatistics {
+    #[graphql(name="machineID")]
+    pub machine_id: async_graphql::ID,
+    pub statistics: PrintStatistics,
+}
+
+#[derive(async_graphql::SimpleObject, Debug, Clone)]
+pub struct MaterialPrintStatistics {
+    #[graphql(name="materialID")]
+    pub material_id: async_graphql::ID,
+    pub statistics: PrintStatistics,
+}
+
+#[derive(async_graphql::SimpleObject, Debug, Default, Clone)]
+pub struct PrintStatisticsReport {
+    /// Statistics for all the prints matching the filter
+    pub total: PrintStatistics,
+    pub machines: Vec<MachinePrintStatistics>,
+    /// Statistics for each material. Prints using more than one material are counted towards
+    /// each of them.
+    pub materials: Vec<MaterialPrintStatistics>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use chrono::prelude::*;
+    use teg_machine::task::{
+        Task,
+        TaskContent,
+        TaskStatus,
+        Finished,
+        Cancelled,
+        Errored,
+    };
+
+    use super::PrintStatistics;
+
+    /// A print that ran for an hour with the given status
+    fn print(status: fn(DateTime<Utc>) -> TaskStatus) -> Task {
+        let created_at = Utc.ymd(2021, 1, 1).and_hms(12, 0, 0);
+
+        Task {
+            id: "TASK".into(),
+            version: 0,
+            created_at,
+            deleted_at: None,
+            machine_id: "MACHINE".into(),
+            part_id: Some("PART".into()),
+            content: TaskContent::GCodes(vec![]),
+            annotations: vec![],
+            total_lines: 100,
+            despooled_line_number: Some(49),
+            machine_override: false,
+            estimated_print_time: None,
+            print_time_table: Default::default(),
+            time_blocked: Duration::from_secs(60),
+            time_paused: Duration::from_secs(600),
+            estimated_filament_meters: Some(10.0),
+            material_ids: vec![],
+            status: status(created_at + chrono::Duration::hours(1)),
+            scheduled_actions: vec![],
+            cancelled_objects: vec![],
+        }
+    }
+
+    fn finished(t: DateTime<Utc>) -> TaskStatus {
+        TaskStatus::Finished(Finished { finished_at: t })
+    }
+
+    fn cancelled(t: DateTime<Utc>) -> TaskStatus {
+        TaskStatus::Cancelled(Cancelled { cancelled_at: t })
+    }
+
+    fn errored(t: DateTime<Utc>) -> TaskStatus {
+        TaskStatus::Errored(Errored { errored_at: t, message: "Error".into() })
+    }
+
+    #[test]
+    fn counts_the_prints_by_status() {
+        let mut statistics = PrintStatistics::default();
+
+        statistics.add(&print(finished));
+        statistics.add(&print(finished));
+        statistics.add(&print(cancelled));
+        statistics.add(&print(errored));
+        // Prints that have not settled are ignored
+        statistics.add(&print(|_| TaskStatus::default()));
+
+        assert_eq!(statistics.finished_prints, 2);
+        assert_eq!(statistics.cancelled_prints, 1);
+        assert_eq!(statistics.errored_prints, 1);
+        assert_eq!(statistics.rate(statistics.finished_prints), Some(0.5));
+        assert_eq!(statistics.rate(statistics.errored_prints), Some(0.25));
+    }
+
+    #[test]
+    fn has_no_success_rate_without_prints() {
+        assert_eq!(PrintStatistics::default().rate(0), None);
+    }
+
+    #[test]
+    fn totals_the_print_time_excluding_pauses() {
+        let mut statistics = PrintStatistics::default();
+
+        statistics.add(&print(finished));
+        statistics.add(&print(errored));
+
+        assert_eq!(statistics.print_seconds, 2.0 * (3600.0 - 600.0));
+        assert_eq!(statistics.paused_seconds, 2.0 * 600.0);
+        assert_eq!(statistics.blocked_seconds, 2.0 * 60.0);
+    }
+
+    #[test]
+    fn totals_the_filament_of_the_printed_lines() {
+        let mut statistics = PrintStatistics::default();
+
+        statistics.add(&print(finished));
+        assert_eq!(statistics.filament_meters, 10.0);
+
+        // Half of the lines of the cancelled print were printed
+        statistics.add(&print(cancelled));
+        assert_eq!(statistics.filament_meters, 15.0);
+    }
+}
@@ -0,0 +1,95 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+
+use super::Slicer;
+
+/// The settings file that belt-engine sliced with before slicing profiles were added
+const LEGACY_PROFILE: &str = "CR30.cfg.ini";
+
+/// belt-engine slices for belt printers (eg. the Creality CR-30). Its slicer profiles are
+/// settings files (eg. "CR30.cfg.ini").
+pub struct BeltEngine;
+
+impl Slicer for BeltEngine {
+    fn executable(&self) -> &str {
+        "belt-engine"
+    }
+
+    fn args(
+        &self,
+        model_path: &Path,
+        gcode_path: &Path,
+        profile_path: Option<&Path>,
+    ) -> Result<Vec<OsString>> {
+        let mut args: Vec<OsString> = vec![
+            "-o".into(),
+            gcode_path.into(),
+            model_path.into(),
+        ];
+
+        if let Some(profile_path) = profile_path {
+            args.push("-c".into());
+            args.push(profile_path.into());
+        }
+
+        args.extend(vec![
+            "-s".into(),
+            "support_enable=True".into(),
+        ]);
+
+        Ok(args)
+    }
+
+    /// Print servers set up before slicing profiles were added keep slicing with the CR30.cfg.ini
+    /// in the config directory
+    fn default_profile(&self) -> Option<PathBuf> {
+        Some(crate::paths::etc().join(LEGACY_PROFILE))
+            .filter(|path| path.exists())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_with_a_settings_file() {
+        let args = BeltEngine.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            Some(Path::new("CR30.cfg.ini")),
+        ).unwrap();
+
+        assert_eq!(args, vec![
+            "-o",
+            "output.gcode",
+            "model.stl",
+            "-c",
+            "CR30.cfg.ini",
+            "-s",
+            "support_enable=True",
+        ]);
+    }
+
+    #[test]
+    fn slices_without_a_settings_file() {
+        let args = BeltEngine.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            None,
+        ).unwrap();
+
+        assert_eq!(args, vec![
+            "-o",
+            "output.gcode",
+            "model.stl",
+            "-s",
+            "support_enable=True",
+        ]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::ffi::OsString;
+use std::path::Path;
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use super::Slicer;
+
+/// CuraEngine's slicer profiles are printer definitions (eg. "my-printer.def.json")
+pub struct CuraEngine;
+
+impl Slicer for CuraEngine {
+    fn executable(&self) -> &str {
+        "CuraEngine"
+    }
+
+    fn args(
+        &self,
+        model_path: &Path,
+        gcode_path: &Path,
+        profile_path: Option<&Path>,
+    ) -> Result<Vec<OsString>> {
+        let profile_path = profile_path
+            .ok_or_else(|| eyre!("CuraEngine requires a printer definition slicer profile"))?;
+
+        Ok(vec![
+            "slice".into(),
+            "-j".into(),
+            profile_path.into(),
+            "-o".into(),
+            gcode_path.into(),
+            "-l".into(),
+            model_path.into(),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_with_a_printer_definition() {
+        let args = CuraEngine.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            Some(Path::new("profile.def.json")),
+        ).unwrap();
+
+        assert_eq!(args, vec![
+            "slice",
+            "-j",
+            "profile.def.json",
+            "-o",
+            "output.gcode",
+            "-l",
+            "model.stl",
+        ]);
+    }
+
+    #[test]
+    fn requires_a_printer_definition() {
+        let result = CuraEngine.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            None,
+        );
+
+        assert!(result.is_err());
+    }
+}
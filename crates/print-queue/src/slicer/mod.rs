@@ -0,0 +1,172 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use eyre::{
+    eyre,
+    Result,
+    Context as _,
+};
+use async_std::fs;
+use teg_json_store::Record as _;
+use teg_machine::{
+    config::MachineConfig,
+    plugins::core::SlicerEngine,
+};
+use teg_material::{
+    Material,
+    SlicingProfile,
+};
+
+mod belt_engine;
+pub use belt_engine::BeltEngine;
+
+mod cura_engine;
+pub use cura_engine::CuraEngine;
+
+mod prusa_slicer;
+pub use prusa_slicer::PrusaSlicer;
+
+/// A command line slicer backend
+pub trait Slicer {
+    /// The slicer's executable
+    fn executable(&self) -> &str;
+
+    /// The arguments to slice the model at model_path into GCode at gcode_path
+    fn args(
+        &self,
+        model_path: &Path,
+        gcode_path: &Path,
+        profile_path: Option<&Path>,
+    ) -> Result<Vec<OsString>>;
+
+    /// The profile to slice with when neither the material nor the machine has a slicing profile
+    fn default_profile(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub fn slicer_for(engine: SlicerEngine) -> Box<dyn Slicer + Send + Sync> {
+    match engine {
+        SlicerEngine::CuraEngine => Box::new(CuraEngine),
+        SlicerEngine::PrusaSlicer => Box::new(PrusaSlicer::new("prusa-slicer")),
+        SlicerEngine::SuperSlicer => Box::new(PrusaSlicer::new("superslicer")),
+        SlicerEngine::BeltEngine => Box::new(BeltEngine),
+    }
+}
+
+/// Writes the machine's slicing profile to profile_dir for the slicer and returns its path. The
+/// profile is, in order of precedence:
+///
+/// 1. The slicing profile of the first loaded material that has one
+/// 2. The machine's default slicing profile
+/// 3. The slicer's default profile (eg. belt-engine's CR30.cfg.ini)
+pub async fn export_slicing_profile(
+    db: &crate::Db,
+    config: &MachineConfig,
+    slicer: &(dyn Slicer + Send + Sync),
+    profile_dir: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut slicing_profile_id = None;
+
+    for toolhead in config.toolheads.iter() {
+        if let Some(material_id) = &toolhead.model.material_id {
+            let material = Material::get(db, material_id, true).await?;
+
+            if let Some(id) = material.slicing_profile_id() {
+                slicing_profile_id = Some(id.clone());
+                break;
+            }
+        }
+    }
+
+    let slicing_profile_id = match slicing_profile_id {
+        Some(id) => id,
+        None => match &config.core_plugin()?.model.slicing_profile_id {
+            Some(id) => id.clone(),
+            None => return Ok(slicer.default_profile()),
+        },
+    };
+
+    let slicing_profile = SlicingProfile::get_optional(db, &slicing_profile_id, false)
+        .await?
+        .ok_or_else(|| eyre!("Slicing profile ({:?}) not found", slicing_profile_id))?;
+
+    let profile_path = profile_dir.join(format!(
+        "profile.{}",
+        slicing_profile.format.file_extension(),
+    ));
+
+    fs::write(&profile_path, slicing_profile.content).await?;
+
+    Ok(Some(profile_path))
+}
+
+/// Slices the model at model_path into GCode at gcode_path
+pub async fn slice(
+    slicer: &(dyn Slicer + Send + Sync),
+    model_path: &Path,
+    gcode_path: &Path,
+    profile_path: Option<&Path>,
+) -> Result<()> {
+    let executable = slicer.executable();
+    let args = slicer.args(model_path, gcode_path, profile_path)?;
+
+    info!("Slicing...");
+    info!("Running slicing command: {} {:?}", executable, args);
+
+    // Restrict the slicer to any logical CPU except for the one that is reserved for
+    // the printer driver processes (eg. teg-marlin).
+    let output = async_std::process::Command::new("taskset")
+        .arg("--cpu-list")
+        .arg("1-1024")
+        .arg(executable)
+        .args(args)
+        .output()
+        .await
+        .wrap_err_with(|| format!("Unable to run slicer: {}", executable))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let message = if stderr.trim().is_empty() {
+            stdout.trim()
+        } else {
+            stderr.trim()
+        };
+
+        Err(eyre!("{} failed ({}): {}", executable, output.status, message))?;
+    }
+
+    if !gcode_path.exists() {
+        Err(eyre!("{} did not output any GCode", executable))?;
+    }
+
+    info!("Slicing... [DONE]");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_belt_engine() {
+        assert_eq!(SlicerEngine::default(), SlicerEngine::BeltEngine);
+        assert_eq!(slicer_for(SlicerEngine::default()).executable(), "belt-engine");
+    }
+
+    #[test]
+    fn selects_each_engines_executable() {
+        let executables = [
+            (SlicerEngine::CuraEngine, "CuraEngine"),
+            (SlicerEngine::PrusaSlicer, "prusa-slicer"),
+            (SlicerEngine::SuperSlicer, "superslicer"),
+            (SlicerEngine::BeltEngine, "belt-engine"),
+        ];
+
+        for (engine, executable) in executables.iter() {
+            assert_eq!(slicer_for(*engine).executable(), *executable);
+        }
+    }
+}
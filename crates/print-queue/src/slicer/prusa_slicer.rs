@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::path::Path;
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+
+use super::Slicer;
+
+/// PrusaSlicer and its SuperSlicer fork share the same command line interface. Their slicer
+/// profiles are exported config bundles (eg. "my-printer.ini").
+#[derive(new)]
+pub struct PrusaSlicer {
+    executable: &'static str,
+}
+
+impl Slicer for PrusaSlicer {
+    fn executable(&self) -> &str {
+        self.executable
+    }
+
+    fn args(
+        &self,
+        model_path: &Path,
+        gcode_path: &Path,
+        profile_path: Option<&Path>,
+    ) -> Result<Vec<OsString>> {
+        let mut args: Vec<OsString> = vec![
+            "--export-gcode".into(),
+        ];
+
+        if let Some(profile_path) = profile_path {
+            args.push("--load".into());
+            args.push(profile_path.into());
+        }
+
+        args.extend(vec![
+            "--output".into(),
+            gcode_path.into(),
+            model_path.into(),
+        ]);
+
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_with_a_config_bundle() {
+        let slicer = PrusaSlicer::new("prusa-slicer");
+
+        let args = slicer.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            Some(Path::new("profile.ini")),
+        ).unwrap();
+
+        assert_eq!(args, vec![
+            "--export-gcode",
+            "--load",
+            "profile.ini",
+            "--output",
+            "output.gcode",
+            "model.stl",
+        ]);
+    }
+
+    #[test]
+    fn slices_without_a_config_bundle() {
+        let slicer = PrusaSlicer::new("superslicer");
+
+        let args = slicer.args(
+            Path::new("model.stl"),
+            Path::new("output.gcode"),
+            None,
+        ).unwrap();
+
+        assert_eq!(slicer.executable(), "superslicer");
+        assert_eq!(args, vec![
+            "--export-gcode",
+            "--output",
+            "output.gcode",
+            "model.stl",
+        ]);
+    }
+}
@@ -99,9 +99,13 @@ pub async fn task_from_gcodes(
         total_lines,
         estimated_filament_meters: None,
         estimated_print_time: None,
+        print_time_table: Default::default(),
+        material_ids: vec![],
         time_blocked: Default::default(),
         time_paused: Default::default(),
         status: Default::default(),
+        scheduled_actions: vec![],
+        cancelled_objects: vec![],
     };
 
     Ok(task)
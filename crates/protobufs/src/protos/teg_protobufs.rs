@@ -4,7 +4,7 @@
 /// uint32 message_id = 2;
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ServerMessage {
-    #[prost(oneof="server_message::Payload", tags="9, 10, 11, 15, 16, 17, 100, 110, 111")]
+    #[prost(oneof="server_message::Payload", tags="9, 10, 11, 12, 13, 15, 16, 17, 20, 21, 22, 23, 30, 100, 110, 111")]
     pub payload: ::core::option::Option<server_message::Payload>,
 }
 /// Nested message and enum types in `ServerMessage`.
@@ -32,6 +32,13 @@ pub mod server_message {
         /// override during another task.
         #[prost(bool, tag="9")]
         pub machine_override: bool,
+        /// Actions to run as the task's lines are despooled (eg. a pause at a layer change)
+        #[prost(message, repeated, tag="10")]
+        pub line_actions: ::prost::alloc::vec::Vec<LineAction>,
+        /// Ranges of lines to skip the extrusions of (eg. the lines of a cancelled object). Travel
+        /// moves to the end of each range are still sent.
+        #[prost(message, repeated, tag="11")]
+        pub skipped_lines: ::prost::alloc::vec::Vec<LineRange>,
         /// 4-7: task file is sent as either a file path or array of GCode commands
         #[prost(oneof="spool_task::Content", tags="4, 5")]
         pub content: ::core::option::Option<spool_task::Content>,
@@ -47,6 +54,40 @@ pub mod server_message {
             Inline(super::InlineContent),
         }
     }
+    /// An action that runs before the task's line at line_number is despooled
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct LineAction {
+        #[prost(uint64, tag="1")]
+        pub line_number: u64,
+        /// Pauses the task before the line by requesting a pause from the server
+        #[prost(bool, tag="2")]
+        pub pause: bool,
+        /// GCodes to run before the line (eg. a filament change)
+        #[prost(string, repeated, tag="3")]
+        pub gcodes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SetLineActions {
+        #[prost(string, tag="1")]
+        pub task_id: ::prost::alloc::string::String,
+        #[prost(message, repeated, tag="2")]
+        pub line_actions: ::prost::alloc::vec::Vec<LineAction>,
+    }
+    /// The lines from start up to but not including end
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct LineRange {
+        #[prost(uint64, tag="1")]
+        pub start: u64,
+        #[prost(uint64, tag="2")]
+        pub end: u64,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SetSkippedLines {
+        #[prost(string, tag="1")]
+        pub task_id: ::prost::alloc::string::String,
+        #[prost(message, repeated, tag="2")]
+        pub skipped_lines: ::prost::alloc::vec::Vec<LineRange>,
+    }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct InlineContent {
         #[prost(string, repeated, tag="3")]
@@ -68,6 +109,31 @@ pub mod server_message {
         pub device_path: ::prost::alloc::string::String,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ListSdCardFiles {
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct DeleteSdCardFile {
+        #[prost(string, tag="1")]
+        pub filename: ::prost::alloc::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StartSdCardPrint {
+        #[prost(string, tag="1")]
+        pub task_id: ::prost::alloc::string::String,
+        #[prost(string, tag="2")]
+        pub filename: ::prost::alloc::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PauseSdCardPrint {
+        #[prost(string, tag="1")]
+        pub task_id: ::prost::alloc::string::String,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct RespondToPrompt {
+        #[prost(uint32, tag="1")]
+        pub button_index: u32,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct EStop {
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
@@ -86,6 +152,13 @@ pub mod server_message {
         SpoolTask(SpoolTask),
         #[prost(message, tag="11")]
         PauseTask(PauseTask),
+        /// Replaces the line actions of a task that has already been spooled
+        #[prost(message, tag="12")]
+        SetLineActions(SetLineActions),
+        /// Replaces the skipped lines of a task that has already been spooled (eg. to cancel an
+        /// object mid-print)
+        #[prost(message, tag="13")]
+        SetSkippedLines(SetSkippedLines),
         /// immediately stop the machine
         #[prost(message, tag="15")]
         Estop(EStop),
@@ -95,6 +168,20 @@ pub mod server_message {
         /// reset the machine once the current task completes
         #[prost(message, tag="17")]
         ResetWhenIdle(Reset),
+        /// 20-29: SD card file management and printing
+        #[prost(message, tag="20")]
+        ListSdCardFiles(ListSdCardFiles),
+        #[prost(message, tag="21")]
+        DeleteSdCardFile(DeleteSdCardFile),
+        /// Starts printing a file from the SD card or resumes the SD card print if it is paused
+        #[prost(message, tag="22")]
+        StartSdCardPrint(StartSdCardPrint),
+        #[prost(message, tag="23")]
+        PauseSdCardPrint(PauseSdCardPrint),
+        /// 30-39: Firmware host actions
+        /// Answers the firmware's prompt by the index of the chosen button
+        #[prost(message, tag="30")]
+        RespondToPrompt(RespondToPrompt),
         /// TODO: delete task history at the end of a task
         #[prost(message, tag="100")]
         DeleteTaskHistory(DeleteTaskHistory),
@@ -156,12 +243,25 @@ pub mod machine_message {
         /// client.
         #[prost(message, repeated, tag="10")]
         pub gcode_history: ::prost::alloc::vec::Vec<GCodeHistoryEntry>,
+        /// Host action commands received from the firmware (eg. a request to pause the print when
+        /// the filament runs out).
+        ///
+        /// Host actions will not be duplicated and will be sent at most once to each client.
+        #[prost(enumeration="HostAction", repeated, tag="11")]
+        pub host_actions: ::prost::alloc::vec::Vec<i32>,
         // 16-99:  [Reserved for Future Use]
 
         /// Note: field numbers 16 through 2047 take 2 bytes
         /// 100-999 Less frequently set sub-messages
         #[prost(message, optional, tag="100")]
         pub error: ::core::option::Option<Error>,
+        /// Not set until the SD card's files are listed or an SD card print is started
+        #[prost(message, optional, tag="101")]
+        pub sd_card: ::core::option::Option<SdCard>,
+        /// The prompt the firmware is waiting for the user to respond to (eg. during an M600 filament
+        /// change). Not set when there is no prompt.
+        #[prost(message, optional, tag="102")]
+        pub prompt: ::core::option::Option<Prompt>,
     }
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct Error {
@@ -223,6 +323,44 @@ pub mod machine_message {
         #[prost(bool, tag="4")]
         pub enabled: bool,
     }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SdCard {
+        /// True once the files on the SD card have been listed
+        #[prost(bool, tag="1")]
+        pub files_listed: bool,
+        #[prost(message, repeated, tag="2")]
+        pub files: ::prost::alloc::vec::Vec<SdCardFile>,
+        #[prost(message, optional, tag="3")]
+        pub print: ::core::option::Option<SdCardPrint>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SdCardFile {
+        #[prost(string, tag="1")]
+        pub filename: ::prost::alloc::string::String,
+        /// File sizes are in bytes. Zero if the firmware did not send the size.
+        #[prost(uint64, tag="2")]
+        pub size: u64,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SdCardPrint {
+        #[prost(string, tag="1")]
+        pub task_id: ::prost::alloc::string::String,
+        #[prost(string, tag="2")]
+        pub filename: ::prost::alloc::string::String,
+        /// Progress through the file as reported by M27
+        #[prost(uint64, tag="3")]
+        pub bytes_printed: u64,
+        #[prost(uint64, tag="4")]
+        pub total_bytes: u64,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Prompt {
+        #[prost(string, tag="1")]
+        pub message: ::prost::alloc::string::String,
+        /// Buttons are answered by their index with M876
+        #[prost(string, repeated, tag="2")]
+        pub buttons: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    }
     /// Raw response strings from the device correlated to the task + line number
     /// that preceeded them.
     #[derive(Clone, PartialEq, ::prost::Message)]
@@ -261,6 +399,13 @@ pub mod machine_message {
     }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
+    pub enum HostAction {
+        Pause = 0,
+        Resume = 1,
+        Cancel = 2,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
     pub enum GCodeHistoryDirection {
         Rx = 0,
         Tx = 1,
@@ -0,0 +1,10 @@
+CREATE TABLE slicing_profiles(
+  id TEXT PRIMARY KEY NOT NULL,
+  version INT NOT NULL DEFAULT 0,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  deleted_at TIMESTAMP WITH TIME ZONE,
+
+  props JSONB NOT NULL
+);
+
+CREATE INDEX slicing_profiles_deleted ON slicing_profiles((deleted_at IS NULL));
@@ -0,0 +1,61 @@
+-- Publishes a notification on the record_changes channel whenever a record is inserted, updated
+-- or deleted. Only the table, operation and id are sent to stay well under the 8000 byte NOTIFY
+-- payload limit - listeners load the latest copy of the record themselves.
+CREATE FUNCTION notify_record_change() RETURNS TRIGGER AS $$
+DECLARE
+  record_id TEXT;
+BEGIN
+  IF (TG_OP = 'DELETE') THEN
+    record_id = OLD.id;
+  ELSE
+    record_id = NEW.id;
+  END IF;
+
+  PERFORM pg_notify(
+    'record_changes',
+    json_build_object(
+      'table', TG_TABLE_NAME,
+      'operation', TG_OP,
+      'id', record_id
+    )::text
+  );
+
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER materials_changed AFTER INSERT OR UPDATE OR DELETE ON materials
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER slicing_profiles_changed AFTER INSERT OR UPDATE OR DELETE ON slicing_profiles
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER users_changed AFTER INSERT OR UPDATE OR DELETE ON users
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER invites_changed AFTER INSERT OR UPDATE OR DELETE ON invites
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER machine_viewers_changed AFTER INSERT OR UPDATE OR DELETE ON machine_viewers
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER tasks_changed AFTER INSERT OR UPDATE OR DELETE ON tasks
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER print_queues_changed AFTER INSERT OR UPDATE OR DELETE ON print_queues
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER machine_print_queues_changed AFTER INSERT OR UPDATE OR DELETE ON machine_print_queues
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER packages_changed AFTER INSERT OR UPDATE OR DELETE ON packages
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER parts_changed AFTER INSERT OR UPDATE OR DELETE ON parts
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER servers_changed AFTER INSERT OR UPDATE OR DELETE ON servers
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
+
+CREATE TRIGGER machine_signalling_updates_changed AFTER INSERT OR UPDATE OR DELETE ON machine_signalling_updates
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
@@ -0,0 +1,18 @@
+CREATE TABLE api_tokens(
+  id TEXT PRIMARY KEY NOT NULL,
+  version INT NOT NULL DEFAULT 0,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  deleted_at TIMESTAMP WITH TIME ZONE,
+
+  user_id TEXT NOT NULL,
+  secret_hash TEXT NOT NULL,
+
+  props JSONB NOT NULL
+);
+
+CREATE INDEX api_tokens_deleted            ON api_tokens((deleted_at IS NULL));
+CREATE UNIQUE INDEX api_tokens_secret_hash ON api_tokens((deleted_at IS NULL), secret_hash);
+CREATE INDEX api_tokens_user_id            ON api_tokens((deleted_at IS NULL), user_id);
+
+CREATE TRIGGER api_tokens_changed AFTER INSERT OR UPDATE OR DELETE ON api_tokens
+  FOR EACH ROW EXECUTE PROCEDURE notify_record_change();
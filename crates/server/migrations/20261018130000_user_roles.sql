@@ -0,0 +1,18 @@
+-- Roles replace the is_admin flag. Existing non-admin users keep the ability to configure machines.
+UPDATE users SET props = jsonb_set(
+  props,
+  '{config,role}',
+  CASE
+    WHEN COALESCE((props->'config'->>'is_admin')::boolean, FALSE) THEN '"Admin"'::jsonb
+    ELSE '"Maintainer"'::jsonb
+  END
+);
+
+UPDATE invites SET props = jsonb_set(
+  props,
+  '{config,role}',
+  CASE
+    WHEN COALESCE((props->'config'->>'is_admin')::boolean, FALSE) THEN '"Admin"'::jsonb
+    ELSE '"Maintainer"'::jsonb
+  END
+);
@@ -0,0 +1,59 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+/// Incremented whenever the layout of the backup archive changes
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// The first entry of every backup archive. Describes what the archive contains and the server
+/// that created it.
+///
+/// The rest of the archive is laid out as:
+///
+/// * `db/<table>.jsonl` - each row of the table as a JSON object per line
+/// * `etc/` - machine config files and the server's keys from `paths::etc()`
+/// * `var/` - task, print recovery and (optionally) part files from `paths::var()`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// The version of teg-server that created the backup
+    pub server_version: String,
+    /// The latest database migration applied when the backup was created
+    pub migration_version: i64,
+    /// The database tables in the archive
+    pub tables: Vec<String>,
+    pub includes_part_files: bool,
+    /// The paths::etc() and paths::var() directories of the backed up server. File paths in the
+    /// database are rewritten from these directories to the restoring server's directories.
+    pub etc_dir: String,
+    pub var_dir: String,
+}
+
+impl BackupManifest {
+    pub const FILE_NAME: &'static str = "manifest.json";
+
+    pub fn validate(&self, latest_migration_version: i64) -> Result<()> {
+        if self.format_version != BACKUP_FORMAT_VERSION {
+            Err(eyre!(
+                "Unsupported backup format version: {} (expected {})",
+                self.format_version,
+                BACKUP_FORMAT_VERSION,
+            ))?;
+        }
+
+        if self.migration_version > latest_migration_version {
+            Err(eyre!(
+                "Backup was created by a newer version of teg-server ({}). Please upgrade before \
+                restoring it.",
+                self.server_version,
+            ))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use async_graphql::{
+    FieldResult,
+    Context,
+};
+use chrono::prelude::*;
+use eyre::{
+    // eyre,
+    // Result,
+    Context as _,
+};
+use teg_auth::AuthContext;
+
+// Input Types
+// ---------------------------------------------
+
+#[derive(async_graphql::InputObject)]
+pub struct CreateBackupInput {
+    /// Part files are often the bulk of a backup. Set this to false to exclude them.
+    #[graphql(default = true)]
+    pub include_part_files: bool,
+}
+
+#[derive(async_graphql::SimpleObject)]
+pub struct Backup {
+    /// The location of the backup archive on the print server
+    pub file_path: String,
+    pub byte_size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+// Resolvers
+// ---------------------------------------------
+
+#[derive(Default)]
+pub struct BackupMutation;
+
+#[async_graphql::Object]
+impl BackupMutation {
+    /// Writes an archive of the print server's database, machine configs, keys and print files to
+    /// the backups directory. Restore it with `teg-backup restore`.
+    async fn create_backup<'ctx>(
+        &self,
+        ctx: &'ctx Context<'_>,
+        input: CreateBackupInput,
+    ) -> FieldResult<Backup> {
+        let db: &crate::Db = ctx.data()?;
+        let auth: &AuthContext = ctx.data()?;
+
+        auth.authorize_admins_only()?;
+
+        async move {
+            let backups_dir = crate::paths::var().join("backups");
+            async_std::fs::create_dir_all(&backups_dir).await?;
+
+            let file_path = backups_dir.join(format!(
+                "teg-backup-{}.tar.gz",
+                Utc::now().format("%Y-%m-%dT%H-%M-%S"),
+            ));
+
+            let manifest = super::create_backup(
+                db,
+                &file_path,
+                input.include_part_files,
+            ).await?;
+
+            let byte_size = async_std::fs::metadata(&file_path)
+                .await
+                .wrap_err("Unable to read backup file")?
+                .len();
+
+            eyre::Result::<_>::Ok(Backup {
+                file_path: file_path.to_string_lossy().to_string(),
+                byte_size,
+                created_at: manifest.created_at,
+            })
+        }
+            // log the backtrace which is otherwise lost by FieldResult
+            .await
+            .map_err(|err| {
+                warn!("{:?}", err);
+                err.into()
+            })
+    }
+}
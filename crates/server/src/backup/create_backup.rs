@@ -0,0 +1,211 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use chrono::prelude::*;
+use flate2::{Compression, write::GzEncoder};
+use eyre::{
+    eyre,
+    Result,
+    Context as _,
+};
+
+use super::{BACKUP_FORMAT_VERSION, BackupManifest};
+
+/// The paths::var() directories that are included in backups
+pub(super) const VAR_DIRS: [&str; 3] = ["tasks", "print-recovery", "bed-clear"];
+/// Part files are optional since they are often the bulk of a backup
+pub(super) const PART_FILES_DIR: &str = "parts";
+
+/// Writes a gzipped tar archive of the database, machine configs, server keys and print files to
+/// output_path.
+pub async fn create_backup(
+    db: &crate::Db,
+    output_path: &Path,
+    include_part_files: bool,
+) -> Result<BackupManifest> {
+    // Read every table from a single snapshot of the database
+    let mut tx = db.begin().await?;
+
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut tx)
+        .await?;
+
+    let migration_version = sqlx::query!(
+        r#"
+            SELECT MAX(version) AS version FROM _sqlx_migrations
+            WHERE success IS TRUE
+        "#,
+    )
+        .fetch_one(&mut tx)
+        .await?
+        .version
+        .ok_or_else(|| eyre!("Cannot backup a database that has not been migrated"))?;
+
+    let tables = sqlx::query!(
+        r#"
+            SELECT table_name AS "table_name!" FROM information_schema.tables
+            WHERE
+                table_schema = 'public'
+                AND table_type = 'BASE TABLE'
+                AND table_name <> '_sqlx_migrations'
+            ORDER BY table_name
+        "#,
+    )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| row.table_name)
+        .collect::<Vec<_>>();
+
+    let mut table_rows = vec![];
+
+    for table in tables.iter() {
+        let rows: Vec<String> = sqlx::query_scalar(&format!(
+            r#"SELECT row_to_json(t)::text FROM "{}" t"#,
+            table,
+        ))
+            .fetch_all(&mut tx)
+            .await
+            .wrap_err_with(|| format!("Error reading {} for backup", table))?;
+
+        table_rows.push((table.clone(), rows));
+    }
+
+    tx.commit().await?;
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        created_at: Utc::now(),
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        migration_version,
+        tables,
+        includes_part_files: include_part_files,
+        etc_dir: crate::paths::etc().to_string_lossy().to_string(),
+        var_dir: crate::paths::var().to_string_lossy().to_string(),
+    };
+
+    let manifest_clone = manifest.clone();
+    let output_path = output_path.to_owned();
+
+    // Compress the archive on a blocking thread to keep the server responsive
+    async_std::task::spawn_blocking(move || {
+        write_archive(&output_path, &manifest_clone, table_rows)
+    }).await?;
+
+    Ok(manifest)
+}
+
+fn write_archive(
+    output_path: &Path,
+    manifest: &BackupManifest,
+    table_rows: Vec<(String, Vec<String>)>,
+) -> Result<()> {
+    // Write to a temporary file first so that a failed backup never leaves a partial archive
+    let tmp_path = output_path.with_extension("partial");
+    let file = File::create(&tmp_path)
+        .wrap_err_with(|| format!("Unable to create backup file: {:?}", tmp_path))?;
+
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    append_bytes(&mut archive, BackupManifest::FILE_NAME, &manifest_json)?;
+
+    for (table, rows) in table_rows {
+        let mut jsonl = rows.join("\n");
+        jsonl.push('\n');
+
+        append_bytes(&mut archive, &format!("db/{}.jsonl", table), jsonl.as_bytes())?;
+    }
+
+    for (archive_path, path) in config_files()? {
+        archive.append_path_with_name(&path, &archive_path)
+            .wrap_err_with(|| format!("Error adding {:?} to backup", path))?;
+    }
+
+    let mut var_dirs = VAR_DIRS.to_vec();
+    if manifest.includes_part_files {
+        var_dirs.push(PART_FILES_DIR);
+    }
+
+    for dir in var_dirs {
+        for (archive_path, path) in files_in(&crate::paths::var().join(dir), &format!("var/{}", dir))? {
+            match File::open(&path) {
+                Ok(mut file) => {
+                    archive.append_file(&archive_path, &mut file)
+                        .wrap_err_with(|| format!("Error adding {:?} to backup", path))?;
+                }
+                // Task files are deleted when tasks settle so they may disappear mid-backup
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => Err(err)?,
+            }
+        }
+    }
+
+    archive.into_inner()?.finish()?;
+
+    fs::rename(&tmp_path, output_path)?;
+
+    Ok(())
+}
+
+fn append_bytes<W: io::Write>(
+    archive: &mut tar::Builder<W>,
+    archive_path: &str,
+    bytes: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+
+    archive.append_data(&mut header, archive_path, bytes)?;
+
+    Ok(())
+}
+
+/// The machine config files and server keys in paths::etc()
+fn config_files() -> Result<Vec<(String, PathBuf)>> {
+    let files = files_in(&crate::paths::etc(), "etc")?
+        .into_iter()
+        .filter(|(_, path)| {
+            let file_name = path.file_name()
+                .and_then(|file_name| file_name.to_str())
+                .unwrap_or("");
+
+            (file_name.starts_with("machine-") && file_name.ends_with(".toml"))
+                || file_name == "id_ecdsa"
+                || file_name == "id_ecdsa.pub"
+        })
+        .collect();
+
+    Ok(files)
+}
+
+/// The archive path and file system path of each file in a directory and its sub-directories
+fn files_in(dir: &Path, archive_dir: &str) -> Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => Err(err)?,
+    };
+
+    let mut files = vec![];
+
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let archive_path = format!("{}/{}", archive_dir, file_name);
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            files.append(&mut files_in(&entry.path(), &archive_path)?);
+        } else if file_type.is_file() {
+            files.push((archive_path, entry.path()));
+        }
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,11 @@
+mod backup_manifest;
+pub use backup_manifest::*;
+
+mod create_backup;
+pub use create_backup::create_backup;
+
+mod restore_backup;
+pub use restore_backup::restore_backup;
+
+mod backup_mutation;
+pub use backup_mutation::BackupMutation;
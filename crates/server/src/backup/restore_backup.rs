@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Component, Path, PathBuf},
+};
+use flate2::read::GzDecoder;
+use eyre::{
+    eyre,
+    Result,
+    Context as _,
+};
+
+use super::{
+    BackupManifest,
+    create_backup::{PART_FILES_DIR, VAR_DIRS},
+};
+
+/// Restores a backup created by create_backup into the database at $DATABASE_URL and the
+/// paths::etc() and paths::var() directories.
+///
+/// The server must be stopped before restoring. Restoring into a database that already contains
+/// teg data will fail unless force is set in which case the existing data is dropped.
+pub async fn restore_backup(archive_path: &Path, force: bool) -> Result<BackupManifest> {
+    let migrator = crate::migrator().await?;
+
+    let latest_migration_version = migrator.migrations
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0);
+
+    // Validate the whole archive before making any changes
+    let archive_path = archive_path.to_owned();
+    let (manifest, table_rows) = async_std::task::spawn_blocking({
+        let archive_path = archive_path.clone();
+        move || read_archive(&archive_path, latest_migration_version)
+    }).await?;
+
+    info!(
+        "Restoring backup of teg-server {} from {}",
+        manifest.server_version,
+        manifest.created_at,
+    );
+
+    let db = crate::connect_db().await?;
+
+    let is_initialized = sqlx::query_scalar::<_, bool>(
+        r#"
+            SELECT EXISTS (
+                SELECT FROM information_schema.tables
+                WHERE table_schema = 'public' AND table_name = '_sqlx_migrations'
+            )
+        "#,
+    )
+        .fetch_one(&db)
+        .await?;
+
+    if is_initialized && !force {
+        Err(eyre!(
+            "The database already contains teg data. Use --force to replace it with the backup."
+        ))?;
+    }
+
+    let mut tx = db.begin().await?;
+
+    if is_initialized {
+        warn!("Dropping the existing teg database");
+
+        sqlx::query("DROP SCHEMA public CASCADE").execute(&mut tx).await?;
+        sqlx::query("CREATE SCHEMA public").execute(&mut tx).await?;
+    }
+
+    // Recreate the schema as it was when the backup was created so that the rows can be inserted
+    // as-is. Later migrations are run once the data is restored.
+    let mut backup_migrator = crate::migrator().await?;
+    backup_migrator.migrations = migrator.migrations
+        .iter()
+        .filter(|migration| migration.version <= manifest.migration_version)
+        .cloned()
+        .collect::<Vec<_>>()
+        .into();
+
+    backup_migrator.run(&mut tx).await?;
+
+    let etc_dir = crate::paths::etc().to_string_lossy().to_string();
+    let var_dir = crate::paths::var().to_string_lossy().to_string();
+
+    for (table, rows) in table_rows {
+        info!("Restoring {} ({} rows)", table, rows.len());
+
+        for mut row in rows {
+            rewrite_paths(&mut row, &manifest.var_dir, &var_dir);
+            rewrite_paths(&mut row, &manifest.etc_dir, &etc_dir);
+
+            let columns = row
+                .as_object()
+                .ok_or_else(|| eyre!("Invalid row in {} backup: {:?}", table, row))?
+                .keys()
+                .map(|column| format!(r#""{}""#, column))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // Columns missing from the backup are set to their default values
+            sqlx::query(&format!(
+                r#"
+                    INSERT INTO "{table}" ({columns})
+                    SELECT {columns} FROM json_populate_record(NULL::"{table}", $1::json)
+                "#,
+                table = table,
+                columns = columns,
+            ))
+                .bind(row)
+                .execute(&mut tx)
+                .await
+                .wrap_err_with(|| format!("Error restoring {}", table))?;
+        }
+    }
+
+    tx.commit().await?;
+
+    migrator.run(&db).await?;
+
+    async_std::task::spawn_blocking(move || unpack_files(&archive_path)).await?;
+
+    Ok(manifest)
+}
+
+fn open_archive(archive_path: &Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    let file = File::open(archive_path)
+        .wrap_err_with(|| format!("Unable to open backup: {:?}", archive_path))?;
+
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// Reads and validates the manifest and database rows of a backup
+fn read_archive(
+    archive_path: &Path,
+    latest_migration_version: i64,
+) -> Result<(BackupManifest, Vec<(String, Vec<serde_json::Value>)>)> {
+    let mut archive = open_archive(archive_path)?;
+    let mut entries = archive.entries()?;
+
+    let manifest: BackupManifest = match entries.next() {
+        Some(entry) => {
+            let entry = entry?;
+
+            if entry.path()?.to_str() != Some(BackupManifest::FILE_NAME) {
+                Err(eyre!("Invalid backup: {} not found", BackupManifest::FILE_NAME))?;
+            }
+
+            serde_json::from_reader(entry)
+                .wrap_err("Invalid backup manifest")?
+        }
+        None => Err(eyre!("Invalid backup: archive is empty"))?,
+    };
+
+    manifest.validate(latest_migration_version)?;
+
+    let mut table_rows = HashMap::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let path = safe_path(&entry.path()?)?;
+
+        let table = match (path.parent(), path.extension()) {
+            (Some(parent), Some(extension)) if parent == Path::new("db") && extension == "jsonl" => {
+                path.file_stem()
+                    .and_then(|table| table.to_str())
+                    .ok_or_else(|| eyre!("Invalid backup table: {:?}", path))?
+                    .to_string()
+            }
+            _ => continue,
+        };
+
+        if !manifest.tables.contains(&table) {
+            Err(eyre!("Invalid backup: {} is not listed in the manifest", table))?;
+        }
+
+        let rows = BufReader::new(entry)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<Vec<serde_json::Value>>>()
+            .wrap_err_with(|| format!("Invalid backup of {}", table))?;
+
+        table_rows.insert(table, rows);
+    }
+
+    let table_rows = manifest.tables
+        .iter()
+        .map(|table| {
+            let rows = table_rows
+                .remove(table)
+                .ok_or_else(|| eyre!("Invalid backup: {} table is missing", table))?;
+
+            Ok((table.clone(), rows))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((manifest, table_rows))
+}
+
+/// Extracts the config and var files of a backup into paths::etc() and paths::var(). Only the var
+/// directories that create_backup writes are restored.
+fn unpack_files(archive_path: &Path) -> Result<()> {
+    let mut archive = open_archive(archive_path)?;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = safe_path(&entry.path()?)?;
+
+        let mut components = path.components();
+        let root_dir = match components.next() {
+            Some(Component::Normal(dir)) if dir == "etc" => crate::paths::etc(),
+            Some(Component::Normal(dir)) if dir == "var" => {
+                let is_backed_up = match components.clone().next() {
+                    Some(Component::Normal(var_dir)) => {
+                        VAR_DIRS.iter().any(|dir| var_dir == *dir) || var_dir == PART_FILES_DIR
+                    }
+                    _ => false,
+                };
+
+                if !is_backed_up {
+                    warn!("Skipping unexpected file in backup: {:?}", path);
+                    continue
+                }
+
+                crate::paths::var()
+            }
+            _ => continue,
+        };
+
+        let dest = root_dir.join(components.as_path());
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        entry.unpack(&dest)
+            .wrap_err_with(|| format!("Error restoring {:?}", dest))?;
+    }
+
+    Ok(())
+}
+
+/// Rejects absolute paths and parent directories so that a backup cannot write outside of the
+/// teg directories
+fn safe_path(path: &Path) -> Result<PathBuf> {
+    let is_safe = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !is_safe {
+        Err(eyre!("Invalid backup: unsafe file path {:?}", path))?;
+    }
+
+    Ok(path.to_owned())
+}
+
+/// Replaces the from_dir prefix of every file path in a JSON value with to_dir
+fn rewrite_paths(value: &mut serde_json::Value, from_dir: &str, to_dir: &str) {
+    use serde_json::Value;
+
+    if from_dir == to_dir {
+        return
+    }
+
+    match value {
+        Value::String(s) => {
+            let from_prefix = format!("{}/", from_dir.trim_end_matches('/'));
+
+            if s.starts_with(&from_prefix) {
+                *s = format!(
+                    "{}/{}",
+                    to_dir.trim_end_matches('/'),
+                    &s[from_prefix.len()..],
+                );
+            }
+        }
+        Value::Array(values) => {
+            values
+                .iter_mut()
+                .for_each(|value| rewrite_paths(value, from_dir, to_dir));
+        }
+        Value::Object(map) => {
+            map
+                .values_mut()
+                .for_each(|value| rewrite_paths(value, from_dir, to_dir));
+        }
+        _ => (),
+    }
+}
+
@@ -0,0 +1,93 @@
+use chrono::prelude::*;
+use eyre::{
+    eyre,
+    Result,
+    // Context as _,
+};
+
+use teg_server::paths;
+use teg_server::backup::{create_backup, restore_backup};
+
+const USAGE: &str = r#"Backs up and restores a teg print server
+
+USAGE:
+    teg-backup create [--exclude-part-files] [ARCHIVE]
+    teg-backup restore [--force] ARCHIVE
+
+The server should be stopped before restoring a backup. Restoring into an existing teg database
+requires --force and replaces all of its data."#;
+
+fn main() -> Result<()> {
+    async_std::task::block_on(backup())
+}
+
+async fn backup() -> Result<()> {
+    // Resolve relative archive paths before changing directories
+    let cwd = std::env::current_dir()?;
+
+    // Load dot env from teg's etc directory in production
+    if
+        // Default to development when running inside a cargo command
+        std::env::var("CARGO_MANIFEST_DIR").is_err()
+        // Default to production when running as a distributed binary (ie. outside of cargo)
+        && std::env::var("RUST_ENV") != Ok("development".into())
+    {
+        let etc = crate::paths::etc();
+        std::env::set_current_dir(&etc)
+            .expect(&format!("Set current directory to {:?}", &etc));
+    }
+
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+    color_eyre::install()?;
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (flags, positional): (Vec<_>, Vec<_>) = args
+        .iter()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+
+    let archive_path = match positional.as_slice() {
+        [] => None,
+        [archive_path] => Some(cwd.join(archive_path)),
+        _ => Err(eyre!("Too many arguments\n\n{}", USAGE))?,
+    };
+
+    let has_flag = |flag: &str| flags.iter().any(|arg| *arg == flag);
+
+    match args.first().map(|command| command.as_str()) {
+        Some("create") => {
+            let archive_path = archive_path.unwrap_or_else(|| {
+                cwd.join(format!(
+                    "teg-backup-{}.tar.gz",
+                    Utc::now().format("%Y-%m-%dT%H-%M-%S"),
+                ))
+            });
+
+            let db = teg_server::connect_db()
+                .await
+                .expect("Connect to database");
+
+            create_backup(&db, &archive_path, !has_flag("--exclude-part-files")).await?;
+
+            println!("Backup created: {}", archive_path.display());
+        }
+        Some("restore") => {
+            let archive_path = archive_path
+                .ok_or_else(|| eyre!("No backup archive given\n\n{}", USAGE))?;
+
+            let manifest = restore_backup(&archive_path, has_flag("--force")).await?;
+
+            println!(
+                "Restored backup of teg-server {} from {}",
+                manifest.server_version,
+                manifest.created_at,
+            );
+        }
+        _ => {
+            println!("{}", USAGE);
+        }
+    }
+
+    Ok(())
+}
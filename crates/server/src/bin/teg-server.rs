@@ -11,7 +11,9 @@ pub use teg_machine::paths;
 
 use teg_server::mutation;
 use teg_server::query;
+use teg_server::subscription;
 use teg_server::local_http_server;
+use teg_server::octoprint::OctoPrintApi;
 
 use teg_server::health_check_socket;
 
@@ -33,9 +35,10 @@ use teg_server::teg_auth::AuthContext;
 use teg_server::teg_device::DeviceManager;
 use teg_server::teg_machine::{MachineHooksList, MachineMap, MachineMapLocal, MachineMaterialHooks, machine::Machine, signalling_updater::{SignallingUpdater, SignallingUpdaterMachineHooks}};
 use teg_server::teg_material::{MaterialHooksList};
-use teg_server::teg_print_queue::print_queue_machine_hooks::PrintQueueMachineHooks;
+use teg_server::teg_print_queue::{FarmScheduler, print_queue_machine_hooks::PrintQueueMachineHooks};
 
 use teg_server::DbId;
+use teg_json_store::ChangeFeed;
 
 #[derive(Deserialize)]
 struct IdFromConfig {
@@ -160,6 +163,8 @@ async fn app() -> Result<()> {
 
     let (_pg_embed, db) = create_db(true).await?;
 
+    let change_feed = ChangeFeed::start(&db).await?;
+
     let machine_ids: Vec<crate::DbId> = std::fs::read_dir(crate::paths::etc())?
         .map(|entry| {
             let entry = entry?;
@@ -221,10 +226,12 @@ async fn app() -> Result<()> {
         .map(|machine_id| {
             let db = db.clone();
             let hooks = machine_hooks.clone();
+            let change_feed = change_feed.clone();
             async move {
                 let machine = Machine::start(
                     db,
                     hooks,
+                    change_feed,
                     &machine_id,
                 ).await?;
                 let id: async_graphql::ID = machine_id.into();
@@ -240,6 +247,13 @@ async fn app() -> Result<()> {
 
     let machines: MachineMap = Arc::new(ArcSwap::new(Arc::new(machines)));
 
+    // Start queued prints on machines as they become ready
+    let _farm_scheduler = FarmScheduler::start(
+        db.clone(),
+        machines.clone(),
+        machine_hooks.clone(),
+    ).await?;
+
     let material_hooks: MaterialHooksList = Arc::new(vec![
         Box::new(MachineMaterialHooks { machines: machines.clone() }),
     ]);
@@ -253,7 +267,7 @@ async fn app() -> Result<()> {
             async_graphql::Schema::build(
             query::Query::default(),
             mutation::Mutation::default(),
-            async_graphql::EmptySubscription,
+            subscription::Subscription::default(),
         )
             .extension(async_graphql::extensions::Tracing)
             .extension(async_graphql::extensions::ApolloTracing)
@@ -264,6 +278,7 @@ async fn app() -> Result<()> {
             .data(machine_hooks.clone())
             .data(material_hooks.clone())
             .data(device_manager.clone())
+            .data(change_feed.clone())
     };
 
     let schema = schema_builder().finish();
@@ -380,9 +395,16 @@ async fn app() -> Result<()> {
         },
     );
 
+    let octoprint_api = OctoPrintApi::new(
+        db.clone(),
+        machines.clone(),
+        machine_hooks.clone(),
+    );
+
     let http_server = local_http_server::start(
         &db,
         schema_builder(),
+        octoprint_api,
     );
 
     let res = select! {
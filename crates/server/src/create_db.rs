@@ -1,7 +1,7 @@
 use std::{env, path::PathBuf, str::FromStr};
 // use async_std::{fs::{self, File}, io::prelude::WriteExt, path::Path};
 // use nix::unistd::setuid;
-use sqlx::{PgPool, migrate::MigrateDatabase, postgres::{PgConnectOptions, PgPoolOptions}};
+use sqlx::{PgPool, migrate::{MigrateDatabase, Migrator}, postgres::{PgConnectOptions, PgPoolOptions}};
 use eyre::{Context, Result};
 // // use pg_embed::postgres::{PgEmbed, PgSettings};
 // // use pg_embed::pg_enums::PgAuthMethod;
@@ -137,11 +137,24 @@ pub async fn create_db(_start_pg: bool) -> Result<(Option<i32>, PgPool)> {
     // } else {
         // Connect to an external PG Database
         debug!("Using external postgres database");
-        let db_url = env::var("DATABASE_URL")
-            // .map(|db_url| (None, db_url))
-            .wrap_err("DATABASE_URL not set")?;
     // };
 
+    let db = connect_db().await?;
+
+    // Migrate the database
+    migrator().await?
+        .run(&db)
+        .await?;
+
+    Ok((pg_embed, db))
+}
+
+/// Connects to the database at $DATABASE_URL, creating it if it does not exist. Migrations are
+/// not run.
+pub async fn connect_db() -> Result<PgPool> {
+    let db_url = env::var("DATABASE_URL")
+        .wrap_err("DATABASE_URL not set")?;
+
     if !sqlx::Postgres::database_exists(&db_url).await
         .wrap_err(format!("Failed to connect to teg postgres server: {:?}", db_url))?
     {
@@ -161,7 +174,11 @@ pub async fn create_db(_start_pg: bool) -> Result<(Option<i32>, PgPool)> {
         .await
         .wrap_err("Failed to connect to initialized teg database")?;
 
-    // Migrate the database
+    Ok(db)
+}
+
+/// The server's database migrations
+pub async fn migrator() -> Result<Migrator> {
     let migrations = if env::var("RUST_ENV") == Ok("production".to_string()) {
         // Productions migrations dir
         crate::paths::etc().join("migrations")
@@ -171,12 +188,9 @@ pub async fn create_db(_start_pg: bool) -> Result<(Option<i32>, PgPool)> {
         PathBuf::from_str(&crate_dir)?.join("migrations")
     };
 
-    info!("Running migrations: {:?}", migrations);
+    info!("Loading migrations: {:?}", migrations);
 
-    sqlx::migrate::Migrator::new(migrations)
-        .await?
-        .run(&db)
-        .await?;
+    let migrator = Migrator::new(migrations).await?;
 
-    Ok((pg_embed, db))
+    Ok(migrator)
 }
@@ -20,12 +20,19 @@ pub use teg_machine::paths;
 
 pub mod mutation;
 pub mod query;
+pub mod subscription;
 pub mod server_query;
 pub mod local_http_server;
+pub mod octoprint;
 pub mod server;
+pub mod backup;
 
 mod create_db;
-pub use create_db::create_db;
+pub use create_db::{
+    create_db,
+    connect_db,
+    migrator,
+};
 
 pub mod health_check_socket;
 pub use health_check_socket::health_check_socket;
@@ -42,11 +49,11 @@ pub type DbId = teg_json_store::DbId;
 pub type AppSchemaBuilder = async_graphql::SchemaBuilder<
     query::Query,
     mutation::Mutation,
-    async_graphql::EmptySubscription
+    subscription::Subscription,
 >;
 
 pub type AppSchema = async_graphql::Schema<
     query::Query,
     mutation::Mutation,
-    async_graphql::EmptySubscription
+    subscription::Subscription,
 >;
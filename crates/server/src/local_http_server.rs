@@ -1,53 +1,117 @@
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
 use teg_auth::AuthContext;
 use tracing::Instrument;
-use warp::{Filter, http::Response as HttpResponse, hyper::Method};
+use warp::{Filter, Rejection, Reply, http::{Response as HttpResponse, StatusCode}, hyper::Method};
 use eyre::{
-    // eyre,
+    eyre,
     Result,
     // Error,
     // Context as _,
 };
 
+use crate::octoprint::OctoPrintApi;
+
 #[derive(Debug)]
 pub struct InternalServerError;
 impl warp::reject::Reject for InternalServerError {}
 
+#[derive(Debug)]
+pub struct Unauthorized;
+impl warp::reject::Reject for Unauthorized {}
+
 pub async fn start(
     db: &crate::Db,
     schema_builder: crate::AppSchemaBuilder,
+    octoprint_api: OctoPrintApi,
 ) -> Result<()> {
-    if &std::env::var("INSECURE_LOCAL_CONNECTION").unwrap_or("0".to_string()) == "0" {
-        debug!("Secure Connections Only: Insecure local connections are disabled.");
-        futures_util::future::pending::<()>().await;
-        return Ok(())
-    }
-
-    debug!("Insecure local connections are enabled");
+    let insecure_local_connection =
+        &std::env::var("INSECURE_LOCAL_CONNECTION").unwrap_or("0".to_string()) != "0";
 
     let port = std::env::var("LOCAL_HTTP_PORT")
         .unwrap_or("20807".to_string())
         .parse()
         .expect("Invalid $LOCAL_HTTP_PORT");
 
-    info!("Playground: http://localhost:{}/playground", port);
+    info!("OctoPrint API: http://localhost:{}/api", port);
 
-    let auth = AuthContext::local_http_auth(db).await?;
+    let schema_builder = if insecure_local_connection {
+        debug!("Insecure local connections are enabled");
+        info!("Playground: http://localhost:{}/playground", port);
 
-    let schema = schema_builder
-        .data(auth)
-        .finish();
+        let auth = AuthContext::local_http_auth(db).await?;
+        schema_builder.data(auth)
+    } else {
+        debug!("Secure Connections Only: Insecure local connections are disabled.");
+        info!("GraphQL API (API tokens only): http://localhost:{}/graphql", port);
+        schema_builder
+    };
 
-    let graphql_post = async_graphql_warp::graphql(schema.clone())
+    let schema = schema_builder.finish();
+
+    // The playground is only served when insecure local connections are enabled
+    let insecure_only = warp::any()
+        .and_then(move || async move {
+            if insecure_local_connection {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one();
+
+    // Authenticates HTTP requests by their API token. Shared by the GraphQL and OctoPrint APIs so
+    // that both accept the same tokens.
+    let auth = warp::header::optional::<String>("authorization")
+        .and(warp::header::optional::<String>("x-api-key"))
+        .and_then({
+            let db = db.clone();
+
+            move |
+                authorization: Option<String>,
+                api_key: Option<String>,
+            | {
+                let db = db.clone();
+
+                async move {
+                    authenticate(
+                        &db,
+                        authorization.as_deref(),
+                        api_key.as_deref(),
+                        insecure_local_connection,
+                    )
+                        .await
+                        .map_err(|err| {
+                            debug!("Unauthorized local HTTP request: {:?}", err);
+                            warp::reject::custom(Unauthorized)
+                        })
+                }
+            }
+        });
+
+    let graphql_post = auth.clone()
+        .and(async_graphql_warp::graphql(schema.clone()))
         .and(warp::body::content_length_limit(1024 * 1024 * 1024))
-        .and_then(move |
+        .map(|
+            auth: Option<AuthContext>,
             graphql_tuple,
         | {
-            let (schema, request): (
+            let (schema, mut request): (
                 crate::AppSchema,
                 async_graphql::Request,
             ) = graphql_tuple;
 
+            // Token sessions override the local HTTP user in the schema's data
+            if let Some(auth) = auth {
+                request = request.data(auth);
+            }
+
+            (schema, request)
+        })
+        .untuple_one()
+        .and_then(|
+            schema: crate::AppSchema,
+            request: async_graphql::Request,
+        | {
             async move {
                 let root_span = span!(
                     parent: None,
@@ -63,29 +127,61 @@ pub async fn start(
                     async_graphql_warp::Response::from(result)
                 )
             }
-        });
+        })
+        .recover(recover_unauthorized);
+
+    let graphql_subscription = async_graphql_warp::graphql_subscription_with_data(
+        schema,
+        {
+            let db = db.clone();
+
+            move |payload: serde_json::Value| async move {
+                let mut data = async_graphql::Data::default();
 
-    let graphql_subscription =
-        async_graphql_warp::graphql_subscription_with_data(schema, |_| async {
-            let data = async_graphql::Data::default();
+                let authorization = payload
+                    .get("Authorization")
+                    .or_else(|| payload.get("authorization"))
+                    .and_then(|authorization| authorization.as_str());
+
+                let auth = authenticate(
+                    &db,
+                    authorization,
+                    None,
+                    insecure_local_connection,
+                )
+                    .await
+                    .map_err(|err| async_graphql::Error::new(err.to_string()))?;
 
-            // let root_span = span!(
-            //     parent: None,
-            //     tracing::Level::INFO,
-            //     "span root"
-            // );
+                if let Some(auth) = auth {
+                    data.insert(auth);
+                }
 
-            Ok(data)
+                // let root_span = span!(
+                //     parent: None,
+                //     tracing::Level::INFO,
+                //     "span root"
+                // );
+
+                Ok(data)
+            }
+        },
+    );
+
+    let graphql_playground = insecure_only
+        .and(warp::path("playground"))
+        .and(warp::get())
+        .map(|| {
+            HttpResponse::builder()
+                .header("content-type", "text/html")
+                .body(playground_source(
+                    GraphQLPlaygroundConfig::new("/graphql")
+                        .subscription_endpoint("/"),
+                ))
         });
 
-    let graphql_playground = warp::path("playground").and(warp::get()).map(|| {
-        HttpResponse::builder()
-            .header("content-type", "text/html")
-            .body(playground_source(
-                GraphQLPlaygroundConfig::new("/graphql")
-                    .subscription_endpoint("/"),
-            ))
-    });
+    let octoprint_routes = octoprint_api
+        .routes(auth)
+        .recover(recover_unauthorized);
 
     let is_dev = std::env::var("RUST_ENV")
         .ok()
@@ -96,12 +192,13 @@ pub async fn start(
         let cors = warp::cors()
             .allow_any_origin()
             .allow_methods(&[Method::GET, Method::POST, Method::DELETE])
-            .allow_headers(vec!["authorization", "content-type"]);
+            .allow_headers(vec!["authorization", "x-api-key", "content-type"]);
 
         let cors_route = warp::options()
             .map(warp::reply);
 
-        let routes = graphql_playground
+        let routes = octoprint_routes
+            .or(graphql_playground)
             .or(graphql_post)
             .or(graphql_subscription)
             .or(cors_route)
@@ -110,7 +207,8 @@ pub async fn start(
         warp::serve(routes).run(([0, 0, 0, 0], port)).await;
     } else {
         // Disable CORS in production to prevent unauthorized 3D printer access from random websites
-        let routes = graphql_playground
+        let routes = octoprint_routes
+            .or(graphql_playground)
             .or(graphql_post)
             .or(graphql_subscription);
 
@@ -119,3 +217,47 @@ pub async fn start(
 
     Ok(())
 }
+
+/// Authenticates local HTTP requests by their "Bearer <api token>" authorization header or, for
+/// OctoPrint clients, their X-Api-Key header.
+///
+/// Returns None for requests without an API token when insecure local connections are enabled so
+/// that they fall back to the local HTTP user.
+async fn authenticate(
+    db: &crate::Db,
+    authorization: Option<&str>,
+    api_key: Option<&str>,
+    insecure_local_connection: bool,
+) -> Result<Option<AuthContext>> {
+    let token = match (authorization, api_key) {
+        (Some(authorization), _) => {
+            authorization
+                .strip_prefix("Bearer ")
+                .ok_or_else(|| eyre!("Expected a Bearer authorization header"))?
+        }
+        (None, Some(api_key)) => api_key,
+        (None, None) if insecure_local_connection => return Ok(None),
+        (None, None) => {
+            Err(eyre!("An API token is required. Insecure local connections are disabled."))?
+        }
+    };
+
+    let auth = AuthContext::api_token_auth(db, token).await?;
+
+    Ok(Some(auth))
+}
+
+async fn recover_unauthorized(rejection: Rejection) -> Result<warp::reply::Response, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let reply = warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "errors": [{ "message": "Unauthorized" }],
+            })),
+            StatusCode::UNAUTHORIZED,
+        );
+
+        Ok(reply.into_response())
+    } else {
+        Err(rejection)
+    }
+}
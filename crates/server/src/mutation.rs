@@ -1,4 +1,5 @@
 use teg_auth::{
+    ApiTokenMutation,
     InviteMutation,
     UserMutation,
 };
@@ -10,12 +11,18 @@ use teg_machine::{
     VideoMutation,
 };
 
-use teg_material::MaterialMutation;
+use teg_material::{
+    MaterialMutation,
+    SlicingProfileMutation,
+};
 use teg_print_queue::PrintQueueMutation;
 
+use crate::backup::BackupMutation;
+
 #[derive(async_graphql::MergedObject, Default)]
 pub struct Mutation(
     // auth
+    ApiTokenMutation,
     InviteMutation,
     UserMutation,
     // machine
@@ -25,6 +32,9 @@ pub struct Mutation(
     VideoMutation,
     // material
     MaterialMutation,
+    SlicingProfileMutation,
     // print queue
     PrintQueueMutation,
+    // server
+    BackupMutation,
 );
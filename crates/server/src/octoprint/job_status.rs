@@ -0,0 +1,79 @@
+use chrono::prelude::*;
+use serde_json::json;
+use warp::Reply;
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_json_store::Record as _;
+use teg_machine::{
+    machine::{MachineStatus, messages::GetData},
+    task::Task,
+};
+use teg_print_queue::part::Part;
+
+use super::{OctoPrintApi, printer_status::state_text};
+
+/// GET /api/job
+pub async fn job_status(
+    api: OctoPrintApi,
+    auth: AuthContext,
+    machine_id: Option<String>,
+) -> Result<warp::reply::Response> {
+    let (_, machine) = api.machine(&auth, machine_id, Permission::View)?;
+    let machine_data = machine.call(GetData).await??;
+
+    let task = if let MachineStatus::Printing(printing) = &machine_data.status {
+        Some(Task::get(&api.db, &printing.task_id, true).await?)
+    } else {
+        None
+    };
+
+    let part = if let Some(part_id) = task.as_ref().and_then(|task| task.part_id.as_ref()) {
+        Some(Part::get(&api.db, part_id, true).await?)
+    } else {
+        None
+    };
+
+    let job = json!({
+        "file": {
+            "name": part.as_ref().map(|part| &part.name),
+            "origin": "local",
+        },
+        "estimatedPrintTime": task.as_ref().and_then(|task| {
+            let elapsed = task.estimated_elapsed_time()?;
+            let remaining = task.estimated_remaining_time()?;
+
+            Some((elapsed + remaining).as_secs())
+        }),
+    });
+
+    let progress = json!({
+        "completion": task.as_ref().map(|task| {
+            let printed_lines = task.despooled_line_number
+                .map(|n| n + 1)
+                .unwrap_or(0) as f32;
+            // Empty tasks need to not divide by zero
+            let total_lines = std::cmp::max(task.total_lines, 1) as f32;
+
+            100.0 * printed_lines / total_lines
+        }),
+        "printTime": task.as_ref().map(|task| {
+            (Utc::now() - task.created_at).num_seconds()
+        }),
+        "printTimeLeft": task.as_ref().and_then(|task| {
+            Some(task.estimated_remaining_time()?.as_secs())
+        }),
+        "printTimeLeftOrigin": task.as_ref().map(|_| "estimate"),
+    });
+
+    let reply = warp::reply::json(&json!({
+        "job": job,
+        "progress": progress,
+        "state": state_text(&machine_data.status),
+    }));
+
+    Ok(reply.into_response())
+}
@@ -0,0 +1,253 @@
+//! A subset of the OctoPrint REST API so that slicers (eg. PrusaSlicer, SuperSlicer and Cura) can
+//! upload GCode straight to a machine's print queue.
+//!
+//! Each machine's API is served at `/machines/<machine_id>/api/`. Servers with a single machine
+//! can also be reached at `/api/`.
+//!
+//! Requests are authenticated by the local HTTP server's API token check (eg. the `X-Api-Key`
+//! header) and tokens must have the QUEUE_MANAGEMENT or MACHINE_CONTROL scope. Each request is
+//! then authorized for the machine and print queue that it acts on.
+use std::fmt;
+use serde_json::json;
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+use xactor::Addr;
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_auth::{
+    ApiTokenScope,
+    AuthContext,
+    Permission,
+};
+use teg_machine::{
+    MachineHooksList,
+    MachineMap,
+    machine::Machine,
+};
+
+mod job_status;
+mod printer_status;
+mod upload_file;
+
+/// The OctoPrint API version that this API emulates. Slicers check the version response to detect
+/// OctoPrint servers.
+const OCTOPRINT_API_VERSION: &str = "0.1";
+const OCTOPRINT_SERVER_VERSION: &str = "1.7.0";
+
+/// The largest GCode file that can be uploaded
+const MAX_UPLOAD_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// An error response in the format returned by OctoPrint
+#[derive(Debug)]
+pub struct OctoPrintError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl OctoPrintError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for OctoPrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.status)
+    }
+}
+
+impl std::error::Error for OctoPrintError {}
+impl warp::reject::Reject for OctoPrintError {}
+
+/// Responds to requests that fail authorization with a 403
+pub fn forbidden(err: eyre::Report) -> OctoPrintError {
+    OctoPrintError::new(StatusCode::FORBIDDEN, err.to_string())
+}
+
+#[derive(Clone)]
+pub struct OctoPrintApi {
+    pub db: crate::Db,
+    pub machines: MachineMap,
+    pub machine_hooks: MachineHooksList,
+}
+
+impl OctoPrintApi {
+    pub fn new(
+        db: crate::Db,
+        machines: MachineMap,
+        machine_hooks: MachineHooksList,
+    ) -> Self {
+        Self {
+            db,
+            machines,
+            machine_hooks,
+        }
+    }
+
+    /// Serves the API to requests authenticated by the `auth` filter. `auth` extracts None for
+    /// unauthenticated requests when insecure local connections are enabled.
+    pub fn routes<A>(
+        self,
+        auth: A,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
+    where
+        A: Filter<Extract = (Option<AuthContext>,), Error = Rejection>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let api = warp::any().map(move || self.clone());
+
+        // Extracts the optional machine ID, the OctoPrintApi and the AuthContext for authenticated
+        // requests
+        let base = warp::path("machines")
+            .and(warp::path::param::<String>())
+            .map(Some)
+            .or(warp::any().map(|| None))
+            .unify()
+            .and(warp::path("api"))
+            .and(api)
+            .and(auth)
+            .and_then(authorize)
+            .untuple_one();
+
+        let version = base.clone()
+            .and(warp::path!("version"))
+            .and(warp::get())
+            .map(|_machine_id: Option<String>, _api: OctoPrintApi, _auth: AuthContext| {
+                warp::reply::json(&json!({
+                    "api": OCTOPRINT_API_VERSION,
+                    "server": OCTOPRINT_SERVER_VERSION,
+                    "text": format!("OctoPrint {} (Print Spool)", OCTOPRINT_SERVER_VERSION),
+                }))
+                    .into_response()
+            });
+
+        let job = base.clone()
+            .and(warp::path!("job"))
+            .and(warp::get())
+            .and_then(|machine_id, api, auth| async move {
+                job_status::job_status(api, auth, machine_id).await.map_err(reject)
+            });
+
+        let printer = base.clone()
+            .and(warp::path!("printer"))
+            .and(warp::get())
+            .and_then(|machine_id, api, auth| async move {
+                printer_status::printer_status(api, auth, machine_id).await.map_err(reject)
+            });
+
+        let upload = base
+            .and(warp::path!("files" / "local"))
+            .and(warp::post())
+            .and(warp::multipart::form().max_length(MAX_UPLOAD_BYTES))
+            .and_then(|machine_id, api, auth, form| async move {
+                upload_file::upload_file(api, auth, machine_id, form).await.map_err(reject)
+            });
+
+        version
+            .or(job)
+            .unify()
+            .or(printer)
+            .unify()
+            .or(upload)
+            .unify()
+            .recover(recover)
+    }
+
+    /// Returns the machine in the URL or, for URLs without a machine ID, the server's only machine
+    /// if the user has the permission for it
+    pub fn machine(
+        &self,
+        auth: &AuthContext,
+        machine_id: Option<String>,
+        permission: Permission,
+    ) -> Result<(crate::DbId, Addr<Machine>)> {
+        let machines = self.machines.load();
+
+        let (machine_id, machine) = match machine_id {
+            Some(machine_id) => {
+                machines
+                    .get_key_value(&async_graphql::ID::from(machine_id))
+                    .ok_or_else(|| OctoPrintError::new(
+                        StatusCode::NOT_FOUND,
+                        "Machine not found",
+                    ))?
+            }
+            None if machines.len() == 1 => {
+                machines.iter().next().unwrap()
+            }
+            None => {
+                Err(OctoPrintError::new(
+                    StatusCode::NOT_FOUND,
+                    "This server has more than one machine. Add /machines/<machine id> to the \
+                    OctoPrint URL to select one.",
+                ))?
+            }
+        };
+
+        auth.authorize_machine(&machine_id.to_string(), permission)
+            .map_err(forbidden)?;
+
+        Ok((machine_id.to_string(), machine.clone()))
+    }
+}
+
+async fn authorize(
+    machine_id: Option<String>,
+    api: OctoPrintApi,
+    auth: Option<AuthContext>,
+) -> Result<(Option<String>, OctoPrintApi, AuthContext), Rejection> {
+    // Requests without a token fall back to the local HTTP user like the GraphQL API
+    let auth = match auth {
+        Some(auth) => auth,
+        None => {
+            AuthContext::local_http_auth(&api.db)
+                .await
+                .map_err(reject)?
+        }
+    };
+
+    // Slicers upload to print queues and optionally start prints so read only tokens are rejected
+    let has_scope = auth.has_scope(ApiTokenScope::QueueManagement)
+        || auth.has_scope(ApiTokenScope::MachineControl);
+
+    if !has_scope {
+        return Err(warp::reject::custom(OctoPrintError::new(
+            StatusCode::FORBIDDEN,
+            "The API token requires the QUEUE_MANAGEMENT or MACHINE_CONTROL scope",
+        )))
+    }
+
+    Ok((machine_id, api, auth))
+}
+
+fn reject(err: eyre::Report) -> Rejection {
+    let err = err
+        .downcast::<OctoPrintError>()
+        .unwrap_or_else(|err| {
+            warn!("{:?}", err);
+            OctoPrintError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+        });
+
+    warp::reject::custom(err)
+}
+
+async fn recover(rejection: Rejection) -> Result<warp::reply::Response, Rejection> {
+    if let Some(err) = rejection.find::<OctoPrintError>() {
+        let reply = warp::reply::with_status(
+            warp::reply::json(&json!({ "error": err.message })),
+            err.status,
+        );
+
+        Ok(reply.into_response())
+    } else {
+        Err(rejection)
+    }
+}
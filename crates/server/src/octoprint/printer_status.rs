@@ -0,0 +1,91 @@
+use serde_json::json;
+use warp::{Reply, http::StatusCode};
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_machine::{
+    components::HeaterEphemeral,
+    machine::{MachineStatus, messages::GetData},
+};
+
+use super::{OctoPrintApi, OctoPrintError};
+
+/// GET /api/printer
+pub async fn printer_status(
+    api: OctoPrintApi,
+    auth: AuthContext,
+    machine_id: Option<String>,
+) -> Result<warp::reply::Response> {
+    let (_, machine) = api.machine(&auth, machine_id, Permission::View)?;
+    let machine_data = machine.call(GetData).await??;
+    let status = &machine_data.status;
+
+    // OctoPrint responds with a conflict when the printer is not connected
+    if let MachineStatus::Disconnected | MachineStatus::Connecting = status {
+        Err(OctoPrintError::new(StatusCode::CONFLICT, "Printer is not operational"))?;
+    }
+
+    let mut temperature = serde_json::Map::new();
+
+    let toolheads = machine_data.config.toolheads
+        .iter()
+        .filter(|toolhead| toolhead.model.heater);
+
+    for toolhead in toolheads {
+        // Toolhead addresses are the letter 'e' followed by the extruder number (eg. e0)
+        let tool = format!("tool{}", toolhead.model.address.trim_start_matches('e'));
+        temperature.insert(tool, heater_temperature(&toolhead.ephemeral.heater));
+    }
+
+    let bed = machine_data.config.build_platforms
+        .iter()
+        .find(|build_platform| build_platform.model.heater);
+
+    if let Some(bed) = bed {
+        temperature.insert("bed".to_string(), heater_temperature(&bed.ephemeral));
+    }
+
+    let reply = warp::reply::json(&json!({
+        "state": {
+            "text": state_text(status),
+            "flags": {
+                "operational": status.is_driver_ready(),
+                "printing": status.is_printing() && !status.is_paused(),
+                "paused": status.is_paused(),
+                "pausing": false,
+                "cancelling": false,
+                "sdReady": false,
+                "error": matches!(status, MachineStatus::Errored(_)),
+                "ready": status == &MachineStatus::Ready,
+                "closedOrError": matches!(status, MachineStatus::Errored(_) | MachineStatus::Stopped),
+            },
+        },
+        "temperature": temperature,
+    }));
+
+    Ok(reply.into_response())
+}
+
+/// The OctoPrint state text of the machine's status
+pub fn state_text(status: &MachineStatus) -> String {
+    match status {
+        MachineStatus::Disconnected => "Offline".to_string(),
+        MachineStatus::Connecting => "Connecting".to_string(),
+        MachineStatus::Ready => "Operational".to_string(),
+        MachineStatus::Printing(printing) if printing.paused => "Paused".to_string(),
+        MachineStatus::Printing(_) => "Printing".to_string(),
+        MachineStatus::Errored(errored) => format!("Error: {}", errored.message),
+        MachineStatus::Stopped => "Offline".to_string(),
+    }
+}
+
+fn heater_temperature(heater: &HeaterEphemeral) -> serde_json::Value {
+    json!({
+        "actual": heater.actual_temperature,
+        "target": heater.target_temperature,
+        "offset": 0,
+    })
+}
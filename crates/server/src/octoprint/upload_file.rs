@@ -0,0 +1,237 @@
+use std::path::Path;
+use async_std::{fs::File, io::prelude::WriteExt as _};
+use bytes::Buf as _;
+use futures_util::{FutureExt as _, TryStreamExt as _, pin_mut};
+use serde_json::json;
+use warp::{Reply, http::StatusCode, multipart::{FormData, Part as FormPart}};
+use eyre::{
+    // eyre,
+    Result,
+    // Context as _,
+};
+use teg_auth::{AuthContext, Permission};
+use teg_machine::machine::{MachineStatus, messages::GetData};
+use teg_print_queue::{
+    NewPart,
+    PrintQueue,
+    add_parts_to_print_queue,
+    insert_print,
+    package::Package,
+};
+
+use super::{OctoPrintApi, OctoPrintError, forbidden};
+
+const GCODE_EXTENSIONS: [&str; 3] = ["gcode", "gco", "g"];
+
+/// POST /api/files/local
+///
+/// Adds the uploaded GCode file to the machine's default print queue as a new package. If the
+/// `print` field is true the part is also printed immediately.
+pub async fn upload_file(
+    api: OctoPrintApi,
+    auth: AuthContext,
+    machine_id: Option<String>,
+    form: FormData,
+) -> Result<warp::reply::Response> {
+    let (machine_id, machine) = api.machine(&auth, machine_id, Permission::View)?;
+
+    let print_queue = PrintQueue::get_machine_default(&api.db, &machine_id)
+        .await?
+        .ok_or_else(|| OctoPrintError::new(
+            StatusCode::CONFLICT,
+            "The machine is not in a print queue",
+        ))?;
+
+    auth.authorize_print_queue(&print_queue.id, Permission::ManageQueue)
+        .map_err(forbidden)?;
+
+    let mut new_part: Option<(NewPart, UploadedFile)> = None;
+    let mut print = false;
+
+    pin_mut!(form);
+
+    while let Some(form_part) = form.try_next().await? {
+        let field_name = form_part.name().to_string();
+
+        match &field_name[..] {
+            "file" => {
+                if new_part.is_some() {
+                    Err(OctoPrintError::new(
+                        StatusCode::BAD_REQUEST,
+                        "Only one file can be uploaded at a time",
+                    ))?;
+                }
+
+                let file_name = form_part.filename()
+                    .unwrap_or("")
+                    .to_string();
+
+                let is_gcode = Path::new(&file_name)
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(|extension| GCODE_EXTENSIONS.contains(&&extension.to_lowercase()[..]))
+                    .unwrap_or(false);
+
+                if !is_gcode {
+                    Err(OctoPrintError::new(
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        "Only GCode files can be uploaded",
+                    ))?;
+                }
+
+                let part = NewPart::create(file_name).await?;
+                // Guard the file before saving it so that partially saved files are also deleted
+                let uploaded_file = UploadedFile {
+                    file_path: part.file_path.clone(),
+                    committed: false,
+                };
+                save_form_part(form_part, &part.file_path).await?;
+
+                new_part = Some((part, uploaded_file));
+            }
+            "print" => {
+                print = read_form_part(form_part).await? == "true";
+            }
+            // Other OctoPrint fields (eg. select and path) do not apply to print queues
+            _ => (),
+        }
+    }
+
+    let (new_part, uploaded_file) = new_part
+        .ok_or_else(|| OctoPrintError::new(StatusCode::BAD_REQUEST, "No file included"))?;
+
+    if print {
+        auth.authorize_machine(&machine_id, Permission::ControlMachine)
+            .map_err(forbidden)?;
+
+        let machine_data = machine.call(GetData).await??;
+
+        if machine_data.status != MachineStatus::Ready {
+            Err(OctoPrintError::new(
+                StatusCode::CONFLICT,
+                "Printer is not operational or already printing",
+            ))?;
+        }
+    }
+
+    let name = new_part.name.clone();
+    info!("OctoPrint API: Adding {} to print queue {}", name, print_queue.name);
+
+    let package = add_parts_to_print_queue(
+        &api.db,
+        &print_queue.id,
+        name.clone(),
+        vec![new_part],
+    ).await?;
+
+    // The file now belongs to the part
+    uploaded_file.commit();
+
+    if print {
+        let part = Package::get_parts(&api.db, &package.id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| OctoPrintError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Part not found",
+            ))?;
+
+        let mut tx = api.db.begin().await?;
+
+        let (task_id, parse_and_spool) = insert_print(
+            api.db.clone(),
+            &mut tx,
+            &api.machine_hooks,
+            &machine_id,
+            machine.clone(),
+            part,
+            false,
+        ).await?;
+
+        tx.commit().await?;
+
+        // Respond to the slicer without waiting for large prints to be parsed and spooled
+        async_std::task::spawn(parse_and_spool.map(move |res| {
+            if let Err(err) = res {
+                error!(
+                    "Error parsing and spooling OctoPrint API print (ID: {:?}): {:?}",
+                    task_id,
+                    err,
+                );
+            }
+        }));
+    }
+
+    let reply = warp::reply::with_status(
+        warp::reply::json(&json!({
+            "done": true,
+            "files": {
+                "local": {
+                    "name": name,
+                    "path": name,
+                    "origin": "local",
+                },
+            },
+        })),
+        StatusCode::CREATED,
+    );
+
+    Ok(reply.into_response())
+}
+
+/// Deletes an uploaded file when it is dropped unless it has been committed to the print queue so
+/// that failed uploads do not leave files behind
+struct UploadedFile {
+    file_path: String,
+    committed: bool,
+}
+
+impl UploadedFile {
+    fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for UploadedFile {
+    fn drop(&mut self) {
+        if self.committed {
+            return
+        }
+
+        if let Err(err) = std::fs::remove_file(&self.file_path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!("Unable to delete uploaded file {:?}: {:?}", self.file_path, err);
+            }
+        }
+    }
+}
+
+/// Streams an uploaded file to disk so that large GCode files are not buffered in memory
+async fn save_form_part(form_part: FormPart, file_path: &str) -> Result<()> {
+    let mut file = File::create(file_path).await?;
+
+    let stream = form_part.stream();
+    pin_mut!(stream);
+
+    while let Some(chunk) = stream.try_next().await? {
+        file.write_all(chunk.chunk()).await?;
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
+
+async fn read_form_part(form_part: FormPart) -> Result<String> {
+    let mut bytes = vec![];
+
+    let stream = form_part.stream();
+    pin_mut!(stream);
+
+    while let Some(chunk) = stream.try_next().await? {
+        bytes.extend_from_slice(chunk.chunk());
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
@@ -1,11 +1,15 @@
 use teg_auth::{
+    ApiTokenQuery,
     InviteQuery,
     UserQuery,
 };
 
 use teg_device::DeviceQuery;
 
-use teg_material::MaterialQuery;
+use teg_material::{
+    MaterialQuery,
+    SlicingProfileQuery,
+};
 
 use teg_machine::{
     ConfigQuery,
@@ -16,6 +20,7 @@ use teg_machine::{
 use teg_print_queue::{
     PartQuery,
     PrintQueueQuery,
+    PrintHistoryQuery,
 };
 
 use crate::server_query::ServerQuery;
@@ -23,6 +28,7 @@ use crate::server_query::ServerQuery;
 #[derive(async_graphql::MergedObject, Default)]
 pub struct Query(
     // auth
+    ApiTokenQuery,
     InviteQuery,
     UserQuery,
     // device
@@ -33,9 +39,11 @@ pub struct Query(
     VideoQuery,
     // material
     MaterialQuery,
+    SlicingProfileQuery,
     // print queue
     PartQuery,
     PrintQueueQuery,
+    PrintHistoryQuery,
     // server
     ServerQuery,
 );
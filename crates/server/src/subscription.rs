@@ -0,0 +1,13 @@
+use teg_machine::MachineSubscription;
+use teg_material::MaterialSubscription;
+use teg_print_queue::PrintQueueSubscription;
+
+#[derive(async_graphql::MergedSubscription, Default)]
+pub struct Subscription(
+    // machine
+    MachineSubscription,
+    // material
+    MaterialSubscription,
+    // print queue
+    PrintQueueSubscription,
+);
@@ -32,7 +32,9 @@ const PrintDialogContent = ({
   const classes = PrintDialogContentStyles()
   const [slice, sliceMutation] = useMutation(gql`
     mutation($input: SliceInput!) {
-      slice(input: $input)
+      slice(input: $input) {
+        gcode
+      }
     }
   `)
 
@@ -68,6 +70,7 @@ const PrintDialogContent = ({
       const { data }: any = await slice({
         variables: {
           input: {
+            machineID: machine.id,
             file: files[0],
             name: files[0].name,
           },
@@ -77,7 +80,7 @@ const PrintDialogContent = ({
 
       // Disable 3D model rendering after slicing
       modelByteArray = new Uint8Array([]);
-      gcodeText = data.slice
+      gcodeText = data.slice.gcode
     } else {
       modelByteArray = new Uint8Array([]);
       gcodeText = await files[0].text()